@@ -6,7 +6,7 @@ use anyhow::{bail, Context, Result};
 use convert_case::{Case, Casing};
 use indexmap::IndexMap;
 use multimap::MultiMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Write;
 use std::fs::File;
@@ -19,6 +19,30 @@ use std::fs::File;
 #[serde(rename_all = "kebab-case")]
 struct Config {
     i2c: I2cConfig,
+    sensor: Option<SensorConfig>,
+}
+
+///
+/// Sensors that aren't on I2C devices (e.g., transceivers, which are read
+/// through an FPGA), as described in `[config.sensor]`.  These are given
+/// `SensorId`s after all I2C sensors:  in the order in which the devices are
+/// listed, and by sensor kind (in alphabetical order) within each device.
+///
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SensorConfig {
+    pub devices: Vec<OtherSensorDevice>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct OtherSensorDevice {
+    pub name: String,
+    pub device: String,
+    pub description: String,
+
+    /// number of sensors of each kind
+    pub sensors: BTreeMap<String, usize>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -101,9 +125,19 @@ struct I2cDevice {
     /// sensor information, if any
     sensors: Option<I2cSensors>,
 
+    /// command table for the generic PMBus driver, if any
+    pmbus: Option<I2cPmbus>,
+
+    /// task to notify when this device asserts SMBus ALERT#, if any
+    alert: Option<I2cAlertOwner>,
+
     /// device is removable
     #[serde(default)]
     removable: bool,
+
+    /// device uses SMBus Packet Error Checking
+    #[serde(default)]
+    pec: bool,
 }
 
 impl I2cDevice {
@@ -138,6 +172,25 @@ struct I2cPort {
     af: u8,
     #[serde(default)]
     muxes: Vec<I2cMux>,
+
+    /// SMBus ALERT# line for this port, if it has one
+    alert: Option<I2cPortAlert>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct I2cPortAlert {
+    /// Notification (of the I2C server) that indicates that ALERT# has been
+    /// asserted.  The line must be routed to this notification by the `sys`
+    /// task's `gpio-irqs`.
+    notification: String,
+
+    /// GPIO port of the line; as with `scl` and `sda`, this defaults to the
+    /// port itself
+    gpio_port: Option<String>,
+
+    /// GPIO pin of the line
+    pin: u8,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -154,6 +207,16 @@ struct I2cGpio {
     pin: u8,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct I2cAlertOwner {
+    /// task to notify
+    name: String,
+
+    /// notification to post to that task
+    notification: String,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 struct I2cMux {
@@ -161,6 +224,56 @@ struct I2cMux {
     address: u8,
     #[serde(alias = "enable")]
     nreset: Option<I2cGpio>,
+
+    /// mux (on the same port) and segment that this mux is behind, if it
+    /// isn't directly on the bus
+    parent: Option<I2cMuxParent>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct I2cMuxParent {
+    mux: u8,
+    segment: u8,
+}
+
+/// Maximum number of muxes on a port, as limited by `drv_i2c_api::Mux`
+const MAX_MUXES: usize = 7;
+
+/// Maximum number of segments on a mux, as limited by `drv_i2c_api::Segment`
+const MAX_SEGMENTS: u8 = 8;
+
+///
+/// Returns the path of (mux, segment) pairs that must be enabled to reach the
+/// given segment of one of a port's muxes, starting from the bus itself.
+/// Muxes are numbered from 1, in the order in which they're listed.
+///
+fn mux_path(
+    muxes: &[I2cMux],
+    segment: Option<(u8, u8)>,
+) -> Result<Vec<(u8, u8)>> {
+    let mut path = vec![];
+    let mut next = segment;
+
+    while let Some((mux, segment)) = next {
+        if mux == 0 || mux as usize > muxes.len() {
+            bail!("there is no mux {mux}");
+        }
+        if segment == 0 || segment > MAX_SEGMENTS {
+            bail!("mux {mux} has no segment {segment}");
+        }
+        if path.len() == muxes.len() {
+            bail!("mux {mux} is its own ancestor");
+        }
+        path.push((mux, segment));
+        next = muxes[mux as usize - 1]
+            .parent
+            .as_ref()
+            .map(|p| (p.mux, p.segment));
+    }
+
+    path.reverse();
+    Ok(path)
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -190,6 +303,98 @@ impl I2cPower {
     }
 }
 
+//
+// Describes a device to be driven by the generic PMBus driver (in
+// `drv_i2c_devices::generic_pmbus`), rather than a dedicated one.
+//
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct I2cPmbus {
+    /// Fixed value for VOUT_MODE; if absent, it is read from the device
+    vout_mode: Option<u8>,
+
+    /// PMBus page for each rail, which must be the same length as `rails`.
+    ///
+    /// When `None`, rails on a multi-rail device are on the page matching
+    /// their index, and single-rail devices don't use PAGE at all.
+    pages: Option<Vec<u8>>,
+
+    /// Power state in which the rails are expected to be up
+    power_state: PmbusPowerState,
+
+    /// Supported commands, and the format of their data
+    commands: I2cPmbusCommands,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+enum PmbusPowerState {
+    A0,
+    A2,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct I2cPmbusCommands {
+    read_vin: Option<I2cPmbusFormat>,
+    read_iin: Option<I2cPmbusFormat>,
+    read_vout: Option<I2cPmbusFormat>,
+    read_iout: Option<I2cPmbusFormat>,
+    #[serde(rename = "read-temperature-1")]
+    read_temperature_1: Option<I2cPmbusFormat>,
+    read_pout: Option<I2cPmbusFormat>,
+}
+
+//
+// As with `I2cDevice`, this should really be an enum, but is flattened
+// (with the DIRECT coefficients as optional fields) to keep the TOML simple.
+//
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct I2cPmbusFormat {
+    format: PmbusEncoding,
+    m: Option<i32>,
+    b: Option<i32>,
+    r: Option<i8>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+enum PmbusEncoding {
+    Linear11,
+    Linear16,
+    Direct,
+}
+
+impl I2cPmbusFormat {
+    /// Returns the `Format` for this command, as Rust source
+    fn to_rust(&self, cmd: &str) -> Result<String> {
+        let vout = cmd == "read-vout";
+        match (self.format, self.m, self.b, self.r) {
+            (PmbusEncoding::Linear11, None, None, None) if !vout => {
+                Ok("Some(Format::Linear11)".to_string())
+            }
+            (PmbusEncoding::Linear16, None, None, None) if vout => {
+                Ok("Some(Format::Linear16)".to_string())
+            }
+            (PmbusEncoding::Linear11 | PmbusEncoding::Linear16, ..) => bail!(
+                "{cmd}: {:?} is not valid here (READ_VOUT must use \
+                 linear16 or direct, and other commands linear11 or direct; \
+                 only direct takes coefficients)",
+                self.format
+            ),
+            (PmbusEncoding::Direct, Some(m), Some(b), Some(r)) => {
+                if m == 0 {
+                    bail!("{cmd}: DIRECT coefficient m must be nonzero");
+                }
+                Ok(format!("Some(Format::Direct {{ m: {m}, b: {b}, r: {r} }})"))
+            }
+            (PmbusEncoding::Direct, ..) => {
+                bail!("{cmd}: DIRECT format requires m, b, and r")
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 #[allow(dead_code)]
@@ -424,7 +629,7 @@ pub enum Disposition {
     Validation,
 }
 
-#[derive(Copy, Clone, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Deserialize, Serialize, Debug, PartialEq, Eq, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum Sensor {
     Temperature,
@@ -495,10 +700,15 @@ impl ConfigGenerator {
             }
         };
 
+        Self::from_config(i2c, disposition)
+    }
+
+    fn from_config(i2c: I2cConfig, disposition: Disposition) -> Self {
         let mut controllers = vec![];
         let mut buses = HashMap::new();
         let mut ports = IndexMap::new();
         let mut singletons = HashMap::new();
+        let all_controllers = i2c.controllers.clone();
 
         for c in i2c.controllers {
             //
@@ -559,7 +769,7 @@ impl ConfigGenerator {
             }
         }
 
-        Self {
+        let g = Self {
             output: String::new(),
             devices: i2c.devices.unwrap_or_default(),
             disposition,
@@ -567,7 +777,114 @@ impl ConfigGenerator {
             buses,
             ports,
             singletons,
+        };
+
+        if let Err(err) = g.check_mux_topology(&all_controllers) {
+            panic!("{err:#}");
+        }
+
+        g
+    }
+
+    ///
+    /// Checks that each port's muxes form a tree, and that no two devices
+    /// (or muxes) can conflict:  two things at the same address conflict if
+    /// they can be on the bus at the same time, which is to say that the mux
+    /// segments leading to one are a prefix of those leading to the other.
+    /// (A device at the same address and on the same segment as a mux is
+    /// taken to be that mux.)
+    ///
+    fn check_mux_topology(&self, controllers: &[I2cController]) -> Result<()> {
+        for c in controllers {
+            for (index, (p, port)) in c.ports.iter().enumerate() {
+                let what = format!("I2C{}, port {p}", c.controller);
+
+                if port.muxes.len() > MAX_MUXES {
+                    bail!("{what}: more than {MAX_MUXES} muxes");
+                }
+
+                // Everything on this port, as (path, address, mux, name)
+                let mut endpoints = vec![];
+
+                for (i, mux) in port.muxes.iter().enumerate() {
+                    let parent =
+                        mux.parent.as_ref().map(|p| (p.mux, p.segment));
+                    let path = mux_path(&port.muxes, parent)
+                        .with_context(|| format!("{what}, mux {}", i + 1))?;
+                    endpoints.push((
+                        path,
+                        mux.address,
+                        true,
+                        format!("mux {} ({})", i + 1, mux.driver),
+                    ));
+                }
+
+                for d in &self.devices {
+                    //
+                    // Devices that don't resolve to a port are caught (with
+                    // a better message) when we generate them.
+                    //
+                    let on_port = match (&d.bus, &d.port) {
+                        (Some(bus), _) => self.buses.get(bus).copied(),
+                        (None, Some(p)) => d.controller.and_then(|c| {
+                            self.ports
+                                .get(&(c, p.clone()))
+                                .map(|&port| (c, port))
+                        }),
+                        (None, None) => d.controller.and_then(|c| {
+                            self.singletons.get(&c).map(|&port| (c, port))
+                        }),
+                    };
+
+                    if on_port != Some((c.controller, index)) {
+                        continue;
+                    }
+
+                    let segment = match (d.mux, d.segment) {
+                        (Some(mux), Some(segment)) => Some((mux, segment)),
+                        _ => None,
+                    };
+                    let path =
+                        mux_path(&port.muxes, segment).with_context(|| {
+                            format!(
+                                "{what}: device {} at address {:#x}",
+                                d.device, d.address
+                            )
+                        })?;
+                    endpoints.push((
+                        path,
+                        d.address,
+                        false,
+                        format!(
+                            "device {}",
+                            d.name.as_ref().unwrap_or(&d.device)
+                        ),
+                    ));
+                }
+
+                for (i, a) in endpoints.iter().enumerate() {
+                    for b in &endpoints[i + 1..] {
+                        if a.1 != b.1 {
+                            continue;
+                        }
+                        if a.2 != b.2 && a.0 == b.0 {
+                            continue;
+                        }
+                        if a.0.starts_with(&b.0) || b.0.starts_with(&a.0) {
+                            bail!(
+                                "{what}: {} and {} can both be on the bus \
+                                 at address {:#x}",
+                                a.3,
+                                b.3,
+                                a.1
+                            );
+                        }
+                    }
+                }
+            }
         }
+
+        Ok(())
     }
 
     pub fn ncontrollers(&self) -> usize {
@@ -745,6 +1062,7 @@ impl ConfigGenerator {
         let mut s = &mut self.output;
         let mut nmuxedbuses = 0;
         let mut len = 0;
+        let mut depth = 1;
 
         for c in &self.controllers {
             for port in c.ports.values() {
@@ -753,6 +1071,12 @@ impl ConfigGenerator {
                 }
 
                 len += port.muxes.len();
+
+                for mux in &port.muxes {
+                    let parent =
+                        mux.parent.as_ref().map(|p| (p.mux, p.segment));
+                    depth = depth.max(mux_path(&port.muxes, parent)?.len() + 1);
+                }
             }
         }
 
@@ -762,6 +1086,10 @@ impl ConfigGenerator {
     #[allow(dead_code)]
     pub const NMUXEDBUSES: usize = {nmuxedbuses};
 
+    /// Greatest number of muxes between a device and its bus
+    #[allow(dead_code)]
+    pub const MAX_MUX_DEPTH: usize = {depth};
+
     use drv_stm32xx_i2c::I2cMux;
 
     pub fn muxes() -> [I2cMux<'static>; {}] {{"##,
@@ -772,7 +1100,8 @@ impl ConfigGenerator {
             writeln!(
                 &mut s,
                 r##"
-        use drv_i2c_api::{{Controller, PortIndex, Mux}};
+        #[allow(unused_imports)]
+        use drv_i2c_api::{{Controller, PortIndex, Mux, Segment}};
 
         #[allow(unused_imports)]
         use drv_stm32xx_sys_api::{{self as gpio_api, Alternate}};"##
@@ -787,7 +1116,33 @@ impl ConfigGenerator {
 
         for c in &self.controllers {
             for (index, port) in c.ports.values().enumerate() {
+                //
+                // We emit each port's muxes with parents before their
+                // children, so that they can be reset in order.
+                //
+                let mut muxes = vec![];
+
                 for (mindex, mux) in port.muxes.iter().enumerate() {
+                    let parent =
+                        mux.parent.as_ref().map(|p| (p.mux, p.segment));
+                    let depth = mux_path(&port.muxes, parent)?.len();
+                    muxes.push((depth, mindex, mux));
+                }
+
+                muxes.sort_by_key(|&(depth, mindex, _)| (depth, mindex));
+
+                for (_, mindex, mux) in muxes {
+                    let parent = mux
+                        .parent
+                        .as_ref()
+                        .map(|p| {
+                            format!(
+                                "Some((Mux::M{}, Segment::S{}))",
+                                p.mux, p.segment
+                            )
+                        })
+                        .unwrap_or_else(|| "None".to_string());
+
                     let nreset = mux
                         .nreset
                         .as_ref()
@@ -816,6 +1171,7 @@ impl ConfigGenerator {
                 port: PortIndex({i2c_port}),
                 id: Mux::M{mindex},
                 driver: &drv_stm32xx_i2c::{driver}::{driver_struct},
+                parent: {parent},
                 nreset: {nreset},
                 address: {address:#x},
             }},"##,
@@ -840,6 +1196,191 @@ impl ConfigGenerator {
         Ok(())
     }
 
+    pub fn generate_alerts(&mut self) -> Result<()> {
+        if self.disposition != Disposition::Initiator {
+            panic!("alerts can only be generated for an initiator");
+        }
+
+        let me = build_util::task_full_config_toml()?;
+        let mask = |note: &str| {
+            format!("{}_MASK", note.to_uppercase().replace('-', "_"))
+        };
+
+        let mut alerts = vec![];
+
+        for c in &self.controllers {
+            for (index, (p, port)) in c.ports.iter().enumerate() {
+                if let Some(alert) = &port.alert {
+                    let note = &alert.notification;
+                    if !me.notifications.contains(note) {
+                        bail!(
+                            "I2C{}, port {p}: alert notification `{note}` \
+                             is not a notification of this task",
+                            c.controller
+                        );
+                    }
+                    let gpio_port = alert.gpio_port.as_ref().unwrap_or(p);
+                    let pin = format!("Port::{gpio_port}.pin({})", alert.pin);
+                    alerts.push((c.controller, index, mask(note), pin));
+                }
+            }
+        }
+
+        let mut owners = vec![];
+
+        for d in &self.devices {
+            let owner = match &d.alert {
+                Some(owner) => owner,
+                None => continue,
+            };
+
+            let (controller, port) = self.lookup_controller_port(d);
+
+            if !alerts.iter().any(|a| a.0 == controller && a.1 == port) {
+                bail!(
+                    "device {} at address {:#x} has an alert owner, but \
+                     its port has no ALERT# line",
+                    d.device,
+                    d.address
+                );
+            }
+
+            let task = build_util::other_task_full_config_toml(&owner.name)
+                .with_context(|| {
+                    format!(
+                        "device {} at address {:#x}: unknown alert owner {}",
+                        d.device, d.address, owner.name
+                    )
+                })?;
+
+            if !task.notifications.contains(&owner.notification) {
+                bail!(
+                    "device {} at address {:#x}: task {} has no \
+                     notification `{}`",
+                    d.device,
+                    d.address,
+                    owner.name,
+                    owner.notification
+                );
+            }
+
+            let segment = match (d.mux, d.segment) {
+                (Some(mux), Some(segment)) => {
+                    format!("Some((Mux::M{}, Segment::S{}))", mux, segment)
+                }
+                _ => "None".to_string(),
+            };
+
+            owners.push((d, controller, port, segment, owner));
+        }
+
+        let mut s = &mut self.output;
+
+        writeln!(
+            &mut s,
+            r##"
+    #[allow(unused_imports)]
+    use drv_stm32xx_i2c::{{I2cAlert, I2cAlertOwner}};
+
+    /// Notifications that indicate ALERT# on one of our ports
+    pub const ALERT_MASK: u32 = 0{};
+
+    pub fn alerts() -> [I2cAlert; {}] {{"##,
+            alerts
+                .iter()
+                .map(|(_, _, mask, _)| {
+                    format!(" | crate::notifications::{mask}")
+                })
+                .collect::<String>(),
+            alerts.len(),
+        )?;
+
+        if !alerts.is_empty() {
+            writeln!(
+                &mut s,
+                r##"
+        use drv_i2c_api::{{Controller, PortIndex}};
+        use drv_stm32xx_sys_api::Port;"##
+            )?;
+        }
+
+        write!(
+            &mut s,
+            r##"
+        ["##
+        )?;
+
+        for (controller, port, mask, pin) in &alerts {
+            write!(
+                &mut s,
+                r##"
+            I2cAlert {{
+                controller: Controller::I2C{controller},
+                port: PortIndex({port}),
+                pin: {pin},
+                notification: crate::notifications::{mask},
+            }},"##
+            )?;
+        }
+
+        writeln!(
+            &mut s,
+            r##"
+        ]
+    }}
+
+    pub fn alert_owners() -> [I2cAlertOwner; {}] {{"##,
+            owners.len()
+        )?;
+
+        if !owners.is_empty() {
+            writeln!(
+                &mut s,
+                r##"
+        use drv_i2c_api::{{Controller, PortIndex, Mux, Segment}};
+        use userlib::{{Generation, TaskId}};"##
+            )?;
+        }
+
+        write!(
+            &mut s,
+            r##"
+        ["##
+        )?;
+
+        for (d, controller, port, segment, owner) in &owners {
+            write!(
+                &mut s,
+                r##"
+            // {description}
+            I2cAlertOwner {{
+                controller: Controller::I2C{controller},
+                port: PortIndex({port}),
+                segment: {segment},
+                address: {address:#x},
+                task: TaskId::for_index_and_gen(
+                    hubris_num_tasks::Task::{task} as usize,
+                    Generation::ZERO,
+                ),
+                notification: crate::notifications::{task}::{mask},
+            }},"##,
+                description = d.description,
+                address = d.address,
+                task = owner.name,
+                mask = mask(&owner.notification),
+            )?;
+        }
+
+        writeln!(
+            &mut s,
+            r##"
+        ]
+    }}"##
+        )?;
+
+        Ok(())
+    }
+
     fn lookup_controller_port(&self, d: &I2cDevice) -> (u8, usize) {
         let controller = match &d.bus {
             Some(bus) => self.buses.get(bus).unwrap().0,
@@ -911,12 +1452,13 @@ impl ConfigGenerator {
 {indent}    PortIndex({port}),
 {indent}    {segment},
 {indent}    {address:#x}
-{indent})"##,
+{indent}){pec}"##,
             description = d.description,
             controller = controller,
             port = port,
             segment = segment,
             address = d.address,
+            pec = if d.pec { ".with_pec()" } else { "" },
             indent = indent,
         )
     }
@@ -1203,7 +1745,16 @@ impl ConfigGenerator {
                 _ => Err(drv_i2c_api::ResponseCode::BadArg)
             }}
         }}
-    }}"##
+
+        pub const NUM_DEVICES: usize = {};
+
+        /// Whether each device is marked `removable`, and so may be
+        /// legitimately absent
+        #[allow(dead_code)]
+        pub const REMOVABLE: [bool; NUM_DEVICES] = {:?};
+    }}"##,
+            self.devices.len(),
+            self.devices.iter().map(|d| d.removable).collect::<Vec<_>>(),
         )?;
 
         Ok(())
@@ -1311,6 +1862,207 @@ impl ConfigGenerator {
         Ok(())
     }
 
+    pub fn generate_generic_pmbus(&mut self) -> Result<()> {
+        write!(
+            &mut self.output,
+            r##"
+pub(crate) mod generic_pmbus {{
+    #[allow(unused_imports)]
+    use drv_i2c_devices::generic_pmbus::{{CommandTable, Format}};
+"##
+        )?;
+
+        for d in &self.devices {
+            let pmbus = match &d.pmbus {
+                Some(pmbus) => pmbus,
+                None => continue,
+            };
+
+            let rails = d
+                .power
+                .as_ref()
+                .filter(|power| power.pmbus)
+                .and_then(|power| power.rails.as_ref())
+                .with_context(|| {
+                    format!(
+                        "{} at {:#x}: PMBus command table requires PMBus \
+                         power rails",
+                        d.device, d.address
+                    )
+                })?;
+
+            if let Some(pages) = &pmbus.pages {
+                if pages.len() != rails.len() {
+                    bail!("rail/page length mismatch on {d:?}");
+                }
+            }
+
+            let vout_mode = match pmbus.vout_mode {
+                Some(mode) => format!("Some({mode:#x})"),
+                None => "None".to_string(),
+            };
+
+            let c = &pmbus.commands;
+            let cmd = |name: &str, f: &Option<I2cPmbusFormat>| {
+                f.as_ref()
+                    .map_or(Ok("None".to_string()), |f| f.to_rust(name))
+                    .with_context(|| format!("bad PMBus command in {d:?}"))
+            };
+
+            for (index, rail) in rails.iter().enumerate() {
+                if rail.is_empty() {
+                    continue;
+                }
+
+                let page = match &pmbus.pages {
+                    Some(pages) => format!("Some({})", pages[index]),
+                    None if rails.len() > 1 => format!("Some({index})"),
+                    None => "None".to_string(),
+                };
+
+                write!(
+                    &mut self.output,
+                    r##"
+    #[allow(dead_code)]
+    pub const {}_{}: CommandTable = CommandTable {{
+        page: {page},
+        vout_mode: {vout_mode},
+        read_vin: {},
+        read_iin: {},
+        read_vout: {},
+        read_iout: {},
+        read_temperature_1: {},
+        read_pout: {},
+    }};
+"##,
+                    d.device.to_uppercase(),
+                    rail.to_uppercase(),
+                    cmd("read-vin", &c.read_vin)?,
+                    cmd("read-iin", &c.read_iin)?,
+                    cmd("read-vout", &c.read_vout)?,
+                    cmd("read-iout", &c.read_iout)?,
+                    cmd("read-temperature-1", &c.read_temperature_1)?,
+                    cmd("read-pout", &c.read_pout)?,
+                )?;
+            }
+        }
+
+        let controllers = self.generic_pmbus_controllers()?;
+
+        //
+        // The power task's BSP hands its own controllers to
+        // `with_generic_pmbus_controllers!`, which appends one for each rail
+        // with a command table; `CONTROLLER_COUNT` is the number appended.
+        //
+        write!(
+            &mut self.output,
+            r##"
+    pub(crate) const CONTROLLER_COUNT: usize = {};
+}}
+
+#[allow(unused_macros)]
+macro_rules! with_generic_pmbus_controllers {{
+    ($($controller:expr),* $(,)?) => {{
+        [
+            $($controller,)*{}
+        ]
+    }};
+}}
+"##,
+            controllers.len(),
+            controllers.join(""),
+        )?;
+
+        Ok(())
+    }
+
+    //
+    // Returns a power controller (as Rust source) for each rail with a
+    // generic PMBus command table.
+    //
+    fn generic_pmbus_controllers(&self) -> Result<Vec<String>> {
+        let mut controllers = vec![];
+
+        for d in &self.devices {
+            let pmbus = match &d.pmbus {
+                Some(pmbus) => pmbus,
+                None => continue,
+            };
+
+            // We have already validated that there are rails in
+            // `generate_generic_pmbus()`
+            let rails = d.power.as_ref().unwrap().rails.as_ref().unwrap();
+            let device = d.device.to_uppercase();
+
+            let sensor = |kind: Sensor, index: usize| {
+                let count = d.sensors.as_ref().map_or(0, |s| match kind {
+                    Sensor::Temperature => s.temperature,
+                    Sensor::Current => s.current,
+                    Sensor::Voltage => s.voltage,
+                    Sensor::InputCurrent => s.input_current,
+                    Sensor::InputVoltage => s.input_voltage,
+                    Sensor::Power | Sensor::Speed => 0,
+                });
+
+                if index < count && d.power_for_kind(kind).is_some() {
+                    Some(format!(
+                        "crate::i2c_config::sensors::{device}_{}_{kind}_SENSOR",
+                        rails[index].to_uppercase()
+                    ))
+                } else {
+                    None
+                }
+            };
+
+            let optional = |s: Option<String>| {
+                s.map_or("None".to_string(), |s| format!("Some({s})"))
+            };
+
+            for (index, rail) in rails.iter().enumerate() {
+                if rail.is_empty() {
+                    continue;
+                }
+
+                let required = |kind: Sensor| {
+                    sensor(kind, index).with_context(|| {
+                        format!(
+                            "{} at {:#x}: rail {rail} needs a {kind} sensor \
+                             to be driven by the generic PMBus driver",
+                            d.device, d.address
+                        )
+                    })
+                };
+
+                controllers.push(format!(
+                    r##"
+            crate::PowerControllerConfig {{
+                state: crate::PowerState::{:?},
+                device: crate::DeviceType::Pmbus(
+                    &crate::generic_pmbus::{device}_{}
+                ),
+                builder: crate::i2c_config::pmbus::{},
+                voltage: {},
+                input_voltage: {},
+                current: {},
+                input_current: {},
+                temperature: {},
+                phases: crate::i2c_config::pmbus::{device}_{rail}_PHASES,
+            }},"##,
+                    pmbus.power_state,
+                    rail.to_uppercase(),
+                    rail.to_lowercase(),
+                    required(Sensor::Voltage)?,
+                    optional(sensor(Sensor::InputVoltage, index)),
+                    required(Sensor::Current)?,
+                    optional(sensor(Sensor::InputCurrent, index)),
+                    optional(sensor(Sensor::Temperature, index)),
+                ));
+            }
+        }
+
+        Ok(controllers)
+    }
+
     fn emit_sensor(
         &mut self,
         device: &str,
@@ -1466,6 +2218,7 @@ pub fn codegen(disposition: Disposition) -> Result<()> {
             g.generate_pins()?;
             g.generate_ports()?;
             g.generate_muxes()?;
+            g.generate_alerts()?;
         }
 
         Disposition::Devices => {
@@ -1491,6 +2244,26 @@ pub fn codegen(disposition: Disposition) -> Result<()> {
     Ok(())
 }
 
+///
+/// Generates command tables for devices that use the generic PMBus driver,
+/// as `generic_pmbus.rs` in `OUT_DIR`.  Unlike the code generated by
+/// [`codegen`], this depends on `drv-i2c-devices`.
+///
+pub fn codegen_generic_pmbus() -> Result<()> {
+    use std::io::Write;
+
+    let out_dir = build_util::out_dir();
+    let dest_path = out_dir.join("generic_pmbus.rs");
+    let mut file = File::create(dest_path)?;
+
+    let mut g = ConfigGenerator::new(Disposition::Devices);
+    g.generate_generic_pmbus()?;
+
+    file.write_all(g.output.as_bytes())?;
+
+    Ok(())
+}
+
 pub struct I2cDeviceDescription {
     pub device: String,
     pub description: String,
@@ -1519,3 +2292,456 @@ pub fn device_descriptions() -> impl Iterator<Item = I2cDeviceDescription> {
         },
     )
 }
+
+///
+/// Version of the inventory returned by [`inventory`], which must be bumped
+/// whenever its format changes incompatibly.
+///
+pub const INVENTORY_VERSION: u32 = 1;
+
+///
+/// A machine-readable description of all I2C devices in an image, along with
+/// their sensors and power rails.
+///
+#[derive(Debug, Serialize)]
+pub struct Inventory {
+    pub version: u32,
+    pub devices: Vec<InventoryDevice>,
+
+    /// devices with sensors that aren't on I2C, from `[config.sensor]`
+    pub other_devices: Vec<InventoryOtherDevice>,
+
+    /// all sensors, indexed by their `SensorId`
+    pub sensors: Vec<InventorySensor>,
+    pub rails: Vec<InventoryRail>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InventoryDevice {
+    /// index of the device, as used by `validate()`
+    pub index: usize,
+    pub device: String,
+    pub name: Option<String>,
+    pub description: String,
+    pub refdes: Option<String>,
+    pub controller: u8,
+
+    /// name of the port (e.g., "F"), and its index as a `PortIndex`
+    pub port: String,
+    pub port_index: usize,
+    pub bus: Option<String>,
+    pub address: u8,
+
+    /// muxes and segments between the bus and the device, starting from the
+    /// bus itself
+    pub mux_path: Vec<InventoryMuxSegment>,
+    pub removable: bool,
+    pub pec: bool,
+
+    /// IDs of this device's sensors
+    pub sensors: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InventoryMuxSegment {
+    pub mux: u8,
+    pub segment: u8,
+    pub driver: String,
+    pub address: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InventoryOtherDevice {
+    /// index of the device in `other_devices`
+    pub index: usize,
+    pub name: String,
+    pub device: String,
+    pub description: String,
+
+    /// IDs of this device's sensors
+    pub sensors: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InventorySensor {
+    pub id: usize,
+    pub kind: Sensor,
+    pub name: Option<String>,
+
+    /// device that has this sensor
+    pub device: InventorySensorDevice,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InventorySensorDevice {
+    /// index of an I2C device in `devices`
+    I2c(usize),
+
+    /// index of a device in `other_devices`
+    Other(usize),
+}
+
+#[derive(Debug, Serialize)]
+pub struct InventoryRail {
+    pub name: String,
+
+    /// index of the device that provides this rail, and of the rail on it
+    pub device: usize,
+    pub index: usize,
+    pub pmbus: bool,
+    pub phases: Option<Vec<u8>>,
+}
+
+///
+/// Returns the inventory of I2C devices described by the given global
+/// configuration (that is, the `[config]` section of an app.toml), for use by
+/// host software.  The sensor IDs, device indices and rail indices all match
+/// the ones that we generate code for.
+///
+pub fn inventory(app_config: &str) -> Result<Inventory> {
+    let config: Config =
+        toml::from_str(app_config).context("malformed config.i2c")?;
+    let g = ConfigGenerator::from_config(config.i2c, Disposition::Validation);
+    let sensors = g.sensors_description();
+
+    let mut devices = vec![];
+    let mut all_sensors = vec![];
+    let mut rails = vec![];
+
+    for (index, (d, ds)) in
+        g.devices.iter().zip(&sensors.device_sensors).enumerate()
+    {
+        let (controller, port_index) = g.lookup_controller_port(d);
+
+        let port = g
+            .ports
+            .iter()
+            .find(|((c, _), p)| *c == controller && **p == port_index)
+            .map(|((_, port), _)| port.clone())
+            .unwrap();
+
+        let muxes = g
+            .controllers
+            .iter()
+            .find(|c| c.controller == controller)
+            .and_then(|c| c.ports.get(&port))
+            .map(|p| &p.muxes[..])
+            .unwrap_or_default();
+
+        let segment = match (d.mux, d.segment) {
+            (Some(mux), Some(segment)) => Some((mux, segment)),
+            _ => None,
+        };
+
+        let mux_path = mux_path(muxes, segment)?
+            .into_iter()
+            .map(|(mux, segment)| {
+                let m = &muxes[mux as usize - 1];
+                InventoryMuxSegment {
+                    mux,
+                    segment,
+                    driver: m.driver.clone(),
+                    address: m.address,
+                }
+            })
+            .collect();
+
+        for s in ds {
+            all_sensors.push(InventorySensor {
+                id: s.id,
+                kind: s.kind,
+                name: s.name.clone(),
+                device: InventorySensorDevice::I2c(index),
+            });
+        }
+
+        if let Some(power) = &d.power {
+            for (i, rail) in power.rails.iter().flatten().enumerate() {
+                if rail.is_empty() {
+                    continue;
+                }
+
+                rails.push(InventoryRail {
+                    name: rail.clone(),
+                    device: index,
+                    index: i,
+                    pmbus: power.pmbus,
+                    phases: power
+                        .phases
+                        .as_ref()
+                        .and_then(|p| p.get(i).cloned()),
+                });
+            }
+        }
+
+        devices.push(InventoryDevice {
+            index,
+            device: d.device.clone(),
+            name: d.name.clone(),
+            description: d.description.clone(),
+            refdes: d.refdes.clone(),
+            controller,
+            port,
+            port_index,
+            bus: d.bus.clone(),
+            address: d.address,
+            mux_path,
+            removable: d.removable,
+            pec: d.pec,
+            sensors: ds.iter().map(|s| s.id).collect(),
+        });
+    }
+
+    all_sensors.sort_by_key(|s| s.id);
+    assert_eq!(all_sensors.len(), sensors.total_sensors);
+
+    //
+    // Sensors in `[config.sensor]` are numbered after the I2C sensors, as
+    // they are by `task/sensor-api/build.rs`.
+    //
+    let mut other_devices = vec![];
+
+    for (index, d) in config.sensor.iter().flat_map(|s| &s.devices).enumerate()
+    {
+        let mut ids = vec![];
+
+        for (kind, &count) in &d.sensors {
+            let kind: Sensor =
+                toml::Value::String(kind.clone()).try_into().with_context(
+                    || format!("sensor {}: unknown sensor kind {kind}", d.name),
+                )?;
+
+            for _ in 0..count {
+                let id = all_sensors.len();
+                all_sensors.push(InventorySensor {
+                    id,
+                    kind,
+                    name: None,
+                    device: InventorySensorDevice::Other(index),
+                });
+                ids.push(id);
+            }
+        }
+
+        other_devices.push(InventoryOtherDevice {
+            index,
+            name: d.name.clone(),
+            device: d.device.clone(),
+            description: d.description.clone(),
+            sensors: ids,
+        });
+    }
+
+    Ok(Inventory {
+        version: INVENTORY_VERSION,
+        devices,
+        other_devices,
+        sensors: all_sensors,
+        rails,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A port with three muxes, at 0x70, 0x71 and 0x72, with the given
+    /// parents (each a `parent = ...` line, or empty), and the given devices.
+    fn config(parents: [&str; 3], devices: &str) -> I2cConfig {
+        let [p1, p2, p3] = parents;
+        let toml = format!(
+            r#"
+            [[controllers]]
+            controller = 2
+
+            [controllers.ports.F]
+            name = "front"
+            scl = {{ gpio_port = "F", pin = 1 }}
+            sda = {{ gpio_port = "F", pin = 0 }}
+            af = 4
+
+            [[controllers.ports.F.muxes]]
+            driver = "pca9548"
+            address = 0x70
+            {p1}
+
+            [[controllers.ports.F.muxes]]
+            driver = "pca9548"
+            address = 0x71
+            {p2}
+
+            [[controllers.ports.F.muxes]]
+            driver = "pca9548"
+            address = 0x72
+            {p3}
+
+            {devices}
+            "#
+        );
+        toml::from_str(&toml).unwrap()
+    }
+
+    /// A device on the given segment of the given mux
+    fn device(name: &str, address: u8, mux: u8, segment: u8) -> String {
+        format!(
+            r#"
+            [[devices]]
+            device = "tmp117"
+            name = "{name}"
+            bus = "front"
+            address = {address}
+            mux = {mux}
+            segment = {segment}
+            description = "{name}"
+            "#
+        )
+    }
+
+    //
+    // Mux 3 is on the bus; mux 1 is behind segment 2 of it, and mux 2 behind
+    // segment 1 of mux 1.
+    //
+    const NESTED: [&str; 3] = [
+        "parent = { mux = 3, segment = 2 }",
+        "parent = { mux = 1, segment = 1 }",
+        "",
+    ];
+
+    #[test]
+    fn nested_muxes() {
+        let devices = [
+            device("a", 0x48, 2, 4),
+            device("b", 0x48, 1, 2),
+            device("c", 0x48, 3, 1),
+        ]
+        .concat();
+        let mut g = ConfigGenerator::from_config(
+            config(NESTED, &devices),
+            Disposition::Initiator,
+        );
+        g.generate_muxes().unwrap();
+
+        assert!(g.output.contains("MAX_MUX_DEPTH: usize = 3;"));
+
+        // Muxes are emitted parents first, with their parents' segments.
+        let parents: Vec<_> = g
+            .output
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("parent: "))
+            .collect();
+        assert_eq!(
+            parents,
+            [
+                "None,",
+                "Some((Mux::M3, Segment::S2)),",
+                "Some((Mux::M1, Segment::S1)),",
+            ]
+        );
+        let ids: Vec<_> = g
+            .output
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("id: "))
+            .collect();
+        assert_eq!(ids, ["Mux::M3,", "Mux::M1,", "Mux::M2,"]);
+
+        let muxes = &g.controllers[0].ports["F"].muxes;
+        assert_eq!(
+            mux_path(muxes, Some((2, 4))).unwrap(),
+            [(3, 2), (1, 1), (2, 4)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "device a and device b can both be on the bus \
+                               at address 0x48")]
+    fn nested_mux_conflict() {
+        //
+        // Device b is on the segment leading to mux 2, so whenever device a
+        // is selected, so is b.
+        //
+        let devices =
+            [device("a", 0x48, 2, 4), device("b", 0x48, 1, 1)].concat();
+        ConfigGenerator::from_config(
+            config(NESTED, &devices),
+            Disposition::Initiator,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "mux 2 (pca9548) and device a can both be on \
+                               the bus at address 0x71")]
+    fn nested_mux_address_conflict() {
+        // A device on the segment leading to mux 2 collides with it.
+        let devices = device("a", 0x71, 3, 2);
+        ConfigGenerator::from_config(
+            config(NESTED, &devices),
+            Disposition::Initiator,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "mux 1 is its own ancestor")]
+    fn mux_cycle() {
+        let parents = [
+            "parent = { mux = 2, segment = 1 }",
+            "parent = { mux = 1, segment = 1 }",
+            "",
+        ];
+        ConfigGenerator::from_config(
+            config(parents, ""),
+            Disposition::Initiator,
+        );
+    }
+
+    #[test]
+    fn inventory_other_sensors() {
+        let app: toml::Value =
+            toml::from_str(include_str!("../../../app/sidecar/base.toml"))
+                .unwrap();
+        let inventory =
+            inventory(&toml::to_string(&app["config"]).unwrap()).unwrap();
+
+        // Sensors are listed by ID, I2C sensors first.
+        for (i, s) in inventory.sensors.iter().enumerate() {
+            assert_eq!(s.id, i);
+        }
+        let ni2c = inventory
+            .sensors
+            .iter()
+            .take_while(|s| matches!(s.device, InventorySensorDevice::I2c(_)))
+            .count();
+        assert!(ni2c > 0);
+
+        // 32 transceivers with a temperature sensor each, then the same 32
+        // with 13 diagnostic sensors each
+        assert_eq!(inventory.other_devices.len(), 64);
+        assert_eq!(inventory.sensors.len(), ni2c + 32 + 32 * 13);
+
+        let xcvr1 = &inventory.other_devices[1];
+        assert_eq!(xcvr1.name, "xcvr1");
+        assert_eq!(xcvr1.sensors, [ni2c + 1]);
+        let s = &inventory.sensors[ni2c + 1];
+        assert_eq!(s.kind, Sensor::Temperature);
+        assert_eq!(s.device, InventorySensorDevice::Other(1));
+
+        let dom = &inventory.other_devices[33];
+        assert_eq!(dom.name, "xcvr1_dom");
+        let start = ni2c + 32 + 13;
+        assert_eq!(dom.sensors, (start..start + 13).collect::<Vec<_>>());
+        let kinds: Vec<_> = dom
+            .sensors
+            .iter()
+            .map(|&id| inventory.sensors[id].kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                [Sensor::Current; 4].as_slice(),
+                &[Sensor::Power; 8],
+                &[Sensor::Voltage]
+            ]
+            .concat()
+        );
+    }
+}
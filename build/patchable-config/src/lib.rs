@@ -0,0 +1,272 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Layout of the config blobs generated by `patchable_task_config!`, shared
+//! by the macro (which builds them) and `cargo xtask patch-config` (which
+//! patches them in a built archive).
+//!
+//! A blob holds packed, little-endian values, with `bool`s stored as a single
+//! byte that is either 0 or 1.  It is described by a schema with one
+//! `name type offset` line per value, where `type` is a scalar type or an
+//! array of them (e.g. `gains [f32;3] 4`).
+
+use std::fmt;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// A value in a patchable config blob
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    /// Scalar type of the value (or of each element, for arrays)
+    pub ty: String,
+    /// Number of elements, if this is an array
+    pub len: Option<usize>,
+    /// Offset of the value within the blob
+    pub offset: usize,
+}
+
+impl Field {
+    pub fn new(
+        name: &str,
+        ty: &str,
+        len: Option<usize>,
+        offset: usize,
+    ) -> Result<Self> {
+        if scalar_size(ty).is_none() {
+            bail!("unknown type {ty} for {name}");
+        }
+        Ok(Self {
+            name: name.to_owned(),
+            ty: ty.to_owned(),
+            len,
+            offset,
+        })
+    }
+
+    /// Parses a line of a schema
+    pub fn parse(line: &str) -> Result<Self> {
+        let mut words = line.split_whitespace();
+        let (Some(name), Some(ty), Some(offset), None) =
+            (words.next(), words.next(), words.next(), words.next())
+        else {
+            bail!("malformed config schema line {line:?}");
+        };
+        let offset = offset
+            .parse()
+            .with_context(|| format!("bad offset in {line:?}"))?;
+
+        let (ty, len) =
+            match ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+                Some(array) => {
+                    let (ty, len) = array
+                        .split_once(';')
+                        .ok_or_else(|| anyhow!("bad array type in {line:?}"))?;
+                    let len = len.parse().with_context(|| {
+                        format!("bad array length in {line:?}")
+                    })?;
+                    (ty, Some(len))
+                }
+                None => (ty, None),
+            };
+
+        Self::new(name, ty, len, offset)
+    }
+
+    /// Returns the size of the value in the blob
+    pub fn size(&self) -> usize {
+        scalar_size(&self.ty).unwrap() * self.len.unwrap_or(1)
+    }
+
+    /// Encodes `value`, which must match our type
+    pub fn encode(&self, value: &toml::Value) -> Result<Vec<u8>> {
+        let Some(len) = self.len else {
+            return encode_scalar(&self.ty, value);
+        };
+        let values = value
+            .as_array()
+            .ok_or_else(|| anyhow!("expected an array of {len} {}", self.ty))?;
+        if values.len() != len {
+            bail!("expected {len} values; got {}", values.len());
+        }
+        let mut out = vec![];
+        for v in values {
+            out.extend(encode_scalar(&self.ty, v)?);
+        }
+        Ok(out)
+    }
+
+    /// Decodes our value from `blob`
+    pub fn decode(&self, blob: &[u8]) -> Result<toml::Value> {
+        let bytes = blob
+            .get(self.offset..self.offset + self.size())
+            .ok_or_else(|| anyhow!("{} is outside of the blob", self.name))?;
+        if self.len.is_none() {
+            return Ok(decode_scalar(&self.ty, bytes));
+        }
+        let size = scalar_size(&self.ty).unwrap();
+        Ok(toml::Value::Array(
+            bytes
+                .chunks(size)
+                .map(|b| decode_scalar(&self.ty, b))
+                .collect(),
+        ))
+    }
+}
+
+/// Formats the field as a line of a schema (without the newline)
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.len {
+            Some(len) => {
+                write!(f, "{} [{};{len}] {}", self.name, self.ty, self.offset)
+            }
+            None => write!(f, "{} {} {}", self.name, self.ty, self.offset),
+        }
+    }
+}
+
+/// Returns the size of a scalar type, or `None` if it can't appear in a
+/// patchable config.
+pub fn scalar_size(ty: &str) -> Option<usize> {
+    match ty {
+        "bool" | "u8" | "i8" => Some(1),
+        "u16" | "i16" => Some(2),
+        "u32" | "i32" | "f32" => Some(4),
+        "u64" | "i64" | "f64" => Some(8),
+        _ => None,
+    }
+}
+
+/// Encodes a TOML value as a scalar of type `ty`
+pub fn encode_scalar(ty: &str, v: &toml::Value) -> Result<Vec<u8>> {
+    fn int<T: TryFrom<i64>>(ty: &str, v: &toml::Value) -> Result<T> {
+        let i = v
+            .as_integer()
+            .ok_or_else(|| anyhow!("expected an integer for {ty}; got {v}"))?;
+        T::try_from(i).map_err(|_| anyhow!("{i} is out of range for {ty}"))
+    }
+    fn float(ty: &str, v: &toml::Value) -> Result<f64> {
+        match v {
+            toml::Value::Float(f) => Ok(*f),
+            toml::Value::Integer(i) => Ok(*i as f64),
+            _ => bail!("expected a number for {ty}; got {v}"),
+        }
+    }
+    Ok(match ty {
+        "bool" => {
+            let b = v
+                .as_bool()
+                .ok_or_else(|| anyhow!("expected a boolean; got {v}"))?;
+            vec![b as u8]
+        }
+        "u8" => int::<u8>(ty, v)?.to_le_bytes().to_vec(),
+        "i8" => int::<i8>(ty, v)?.to_le_bytes().to_vec(),
+        "u16" => int::<u16>(ty, v)?.to_le_bytes().to_vec(),
+        "i16" => int::<i16>(ty, v)?.to_le_bytes().to_vec(),
+        "u32" => int::<u32>(ty, v)?.to_le_bytes().to_vec(),
+        "i32" => int::<i32>(ty, v)?.to_le_bytes().to_vec(),
+        "u64" => int::<u64>(ty, v)?.to_le_bytes().to_vec(),
+        "i64" => int::<i64>(ty, v)?.to_le_bytes().to_vec(),
+        "f32" => (float(ty, v)? as f32).to_le_bytes().to_vec(),
+        "f64" => float(ty, v)?.to_le_bytes().to_vec(),
+        _ => bail!("unknown type {ty}"),
+    })
+}
+
+/// Decodes a scalar of type `ty` from `b`, which must be its size.  (A `u64`
+/// too large for a TOML integer comes back as its two's complement.)
+pub fn decode_scalar(ty: &str, b: &[u8]) -> toml::Value {
+    use toml::Value::{Boolean, Float, Integer};
+    match ty {
+        "bool" => Boolean(b[0] != 0),
+        "u8" => Integer(b[0].into()),
+        "i8" => Integer(i8::from_le_bytes([b[0]]).into()),
+        "u16" => Integer(u16::from_le_bytes(b.try_into().unwrap()).into()),
+        "i16" => Integer(i16::from_le_bytes(b.try_into().unwrap()).into()),
+        "u32" => Integer(u32::from_le_bytes(b.try_into().unwrap()).into()),
+        "i32" => Integer(i32::from_le_bytes(b.try_into().unwrap()).into()),
+        "u64" => Integer(u64::from_le_bytes(b.try_into().unwrap()) as i64),
+        "i64" => Integer(i64::from_le_bytes(b.try_into().unwrap())),
+        "f32" => {
+            // Go by way of the shortest representation of the `f32`, so that
+            // e.g. 0.1 doesn't come back as 0.10000000149011612.
+            let f = f32::from_le_bytes(b.try_into().unwrap());
+            Float(f.to_string().parse().unwrap())
+        }
+        "f64" => Float(f64::from_le_bytes(b.try_into().unwrap())),
+        _ => panic!("unknown type {ty}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(s: &str) -> toml::Value {
+        let mut t: toml::Table = toml::from_str(&format!("v = {s}")).unwrap();
+        t.remove("v").unwrap()
+    }
+
+    #[test]
+    fn parses_schema_lines() {
+        for line in ["count u32 0", "gains [f32;3] 4", "enabled bool 16"] {
+            let f = Field::parse(line).unwrap();
+            assert_eq!(f.to_string(), line);
+        }
+        let f = Field::parse("gains [f32;3] 4").unwrap();
+        assert_eq!(f.ty, "f32");
+        assert_eq!(f.len, Some(3));
+        assert_eq!(f.size(), 12);
+
+        assert!(Field::parse("count u32").is_err());
+        assert!(Field::parse("count usize 0").is_err());
+        assert!(Field::parse("gains [f32;x] 0").is_err());
+        assert!(Field::parse("count u32 0 1").is_err());
+    }
+
+    #[test]
+    fn round_trips_values() {
+        let cases = [
+            ("bool", "true", vec![1]),
+            ("u8", "200", vec![200]),
+            ("i8", "-2", vec![0xfe]),
+            ("u16", "0x1234", vec![0x34, 0x12]),
+            ("i16", "-1", vec![0xff, 0xff]),
+            ("u32", "1000", vec![0xe8, 0x03, 0, 0]),
+            ("i32", "-1000", vec![0x18, 0xfc, 0xff, 0xff]),
+            ("u64", "1", vec![1, 0, 0, 0, 0, 0, 0, 0]),
+            ("i64", "-1", vec![0xff; 8]),
+            ("f32", "1.75", 1.75f32.to_le_bytes().to_vec()),
+            ("f64", "0.0135", 0.0135f64.to_le_bytes().to_vec()),
+        ];
+        for (ty, v, bytes) in cases {
+            let f = Field::new("x", ty, None, 0).unwrap();
+            assert_eq!(f.encode(&value(v)).unwrap(), bytes, "{ty} {v}");
+            assert_eq!(f.decode(&bytes).unwrap(), value(v), "{ty} {v}");
+        }
+
+        // Integers are accepted for floats, and f32s come back as written.
+        let f = Field::new("x", "f32", Some(3), 2).unwrap();
+        let mut blob = vec![0xaa, 0xbb];
+        blob.extend(f.encode(&value("[0.0135, 2, -0.1]")).unwrap());
+        assert_eq!(f.decode(&blob).unwrap(), value("[0.0135, 2.0, -0.1]"));
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let f = Field::new("x", "u8", None, 0).unwrap();
+        assert!(f.encode(&value("256")).is_err());
+        assert!(f.encode(&value("-1")).is_err());
+        assert!(f.encode(&value("1.0")).is_err());
+
+        let f = Field::new("x", "bool", None, 0).unwrap();
+        assert!(f.encode(&value("1")).is_err());
+
+        let f = Field::new("x", "u16", Some(2), 0).unwrap();
+        assert!(f.encode(&value("[1, 2, 3]")).is_err());
+        assert!(f.encode(&value("1")).is_err());
+        assert!(f.decode(&[0; 3]).is_err());
+    }
+}
@@ -29,6 +29,15 @@ SECTIONS
     __erodata = .;
   } > FLASH
 
+  /* ### .hubris_config */
+  /* Config values from `patchable_task_config!`, which can be patched in
+     a built image by `cargo xtask patch-config`. */
+  .hubris_config : ALIGN(4)
+  {
+    KEEP(*(.hubris_config));
+    . = ALIGN(4);
+  } > FLASH
+
   /*
    * Sections in RAM
    *
@@ -86,6 +95,22 @@ SECTIONS
     KEEP(*(.idolatry));
   }
 
+  /* ## .hubris_log_fmt */
+  /* Format strings interned by `sys_log!` with the `log-task` backend. The
+     address of each string identifies it in log records; the strings
+     themselves are only needed on the host, to decode them. */
+  .hubris_log_fmt (INFO) : {
+    . = .;
+    KEEP(*(.hubris_log_fmt));
+  }
+
+  /* ## .hubris_config_schema */
+  /* Layout of the `.hubris_config` section, for `cargo xtask patch-config`. */
+  .hubris_config_schema (INFO) : {
+    . = .;
+    KEEP(*(.hubris_config_schema));
+  }
+
   /* ## Discarded sections */
   /DISCARD/ :
   {
@@ -299,3 +299,61 @@ fn write_task_notifications<W: Write>(out: &mut W, t: &[String]) -> Result<()> {
     }
     Ok(())
 }
+
+/// A task to be notified by the task being built, as listed in its config:
+///
+/// ```toml
+/// subscribers = [{ name = "thermal", notification = "power-fault" }]
+/// ```
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Subscriber {
+    pub name: String,
+    pub notification: String,
+}
+
+/// Generates `subscribers.rs`, which defines `SUBSCRIBERS` as an array of
+/// `(TaskId, notification mask)` pairs for the given tasks.
+///
+/// The task being built must depend on `hubris-num-tasks` (with the
+/// `task-enum` feature) and include the output of `build_notifications`.
+pub fn build_subscribers(subscribers: &[Subscriber]) -> Result<()> {
+    let out_dir = out_dir();
+    let mut out = std::fs::File::create(out_dir.join("subscribers.rs"))?;
+
+    for s in subscribers {
+        let task = other_task_full_config_toml(&s.name)
+            .with_context(|| format!("unknown subscriber task `{}`", s.name))?;
+        if !task.notifications.contains(&s.notification) {
+            bail!(
+                "subscriber task `{}` has no notification `{}`",
+                s.name,
+                s.notification
+            );
+        }
+    }
+
+    writeln!(
+        out,
+        "pub(crate) const SUBSCRIBERS: [(userlib::TaskId, u32); {}] = [",
+        subscribers.len()
+    )?;
+    for s in subscribers {
+        let note =
+            format!("{}_MASK", s.notification.to_uppercase().replace('-', "_"));
+        writeln!(
+            out,
+            "    (
+        userlib::TaskId::for_index_and_gen(
+            hubris_num_tasks::Task::{task} as usize,
+            userlib::Generation::ZERO,
+        ),
+        crate::notifications::{task}::{note},
+    ),",
+            task = s.name,
+        )?;
+    }
+    writeln!(out, "];")?;
+
+    Ok(())
+}
@@ -0,0 +1,587 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Conversion of Hubris dumps into ELF core files.
+//!
+//! The input is a raw dump as read out of one or more dump areas (e.g. via
+//! `read_dump` over Idol or the dump agent's UDP protocol):  each dump area
+//! starts with a `humpty` header and is padded out to a multiple of the read
+//! size.  The output is an ELF core file with a `PT_LOAD` segment for each
+//! dumped region of memory and an `NT_PRSTATUS` note holding the registers,
+//! suitable for loading into GDB along with the task ELFs in the archive.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use byteorder::{LittleEndian, WriteBytesExt};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use zerocopy::FromBytes;
+
+/// Size of each read from a dump area; every area in a raw dump is padded to
+/// a multiple of this.
+const DUMP_READ_SIZE: usize = 256;
+
+/// Magic (and header) for `gnarle`-compressed segments written by jefe; see
+/// `dump_agent_api::DumpSegmentGnarle`.
+const DUMP_SEGMENT_GNARLE: [u8; 4] = *b"GNRL";
+const DUMP_SEGMENT_GNARLE_SIZE: usize = 16;
+
+const ET_CORE: u16 = 4;
+const EM_ARM: u16 = 40;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+
+const ELF_HEADER_SIZE: usize = 52;
+const PROGRAM_HEADER_SIZE: usize = 32;
+
+/// Size of `struct elf_prstatus` on 32-bit ARM, and the offset of `pr_reg`
+/// within it.
+const PRSTATUS_SIZE: usize = 148;
+const PRSTATUS_REG_OFFSET: usize = 72;
+
+/// Size of `struct elf_prpsinfo` on 32-bit ARM.
+const PRPSINFO_SIZE: usize = 124;
+
+/// Register numbers as used in dump register segments (which follow the
+/// ARMv7-M/ARMv8-M `DCRSR.REGSEL` encoding).
+const REG_SP: u16 = 13;
+const REG_XPSR: u16 = 16;
+
+/// The subset of the archive's `app.toml` that we need: the tasks, in order.
+#[derive(Deserialize)]
+pub(crate) struct ArchiveConfig {
+    pub tasks: IndexMap<String, toml::Value>,
+}
+
+#[derive(Default)]
+struct Dump {
+    /// Task that was dumped, if this is a single-task dump
+    task: Option<u16>,
+    /// Dumped memory, by address
+    memory: BTreeMap<u32, Vec<u8>>,
+    /// Explicitly dumped registers, by register number
+    registers: BTreeMap<u16, u32>,
+}
+
+impl Dump {
+    fn read_u32(&self, addr: u32) -> Option<u32> {
+        let (base, data) = self.memory.range(..=addr).next_back()?;
+        let offset = (addr - base) as usize;
+        let bytes = data.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    ///
+    /// For a single-task dump, recovers the task's registers from its saved
+    /// state.  The first region dumped for a task is its TCB, which begins
+    /// with the (`repr(C)`) `SavedState`: r4-r11, PSP and EXC_RETURN.  The
+    /// remaining registers are in the exception frame at PSP.
+    ///
+    fn task_registers(&self, tcb: u32) -> Option<BTreeMap<u16, u32>> {
+        let mut regs = BTreeMap::new();
+
+        for (i, reg) in (4..=11).enumerate() {
+            regs.insert(reg, self.read_u32(tcb + i as u32 * 4)?);
+        }
+
+        let psp = self.read_u32(tcb + 32)?;
+        let exc_return = self.read_u32(tcb + 36)?;
+
+        let frame = [0, 1, 2, 3, 12, 14, 15, REG_XPSR];
+        for (i, reg) in frame.iter().enumerate() {
+            regs.insert(*reg, self.read_u32(psp + i as u32 * 4)?);
+        }
+
+        //
+        // Unwind the exception frame to recover the task's stack pointer:
+        // the basic frame is 8 words, an extended (floating point) frame
+        // adds another 18, and the hardware may have inserted a padding word
+        // to align the frame (indicated by bit 9 of the stacked xPSR).
+        //
+        let mut sp = psp + 32;
+
+        if exc_return & (1 << 4) == 0 {
+            sp += 18 * 4;
+        }
+
+        if regs[&REG_XPSR] & (1 << 9) != 0 {
+            sp += 4;
+        }
+
+        regs.insert(REG_SP, sp);
+        Some(regs)
+    }
+}
+
+fn parse_area(
+    area: &[u8],
+    tcb: &mut Option<u32>,
+    dump: &mut Dump,
+) -> Result<()> {
+    use humpty::{
+        DumpAreaHeader, DumpRegister, DumpSegmentData, DumpSegmentHeader,
+        DumpTask,
+    };
+
+    let header = DumpAreaHeader::read_from_prefix(area)
+        .ok_or_else(|| anyhow!("dump area is too short for its header"))?;
+
+    if header.magic != humpty::DUMP_MAGIC {
+        bail!("bad dump area magic {:x?}", { header.magic });
+    }
+
+    let written = header.written as usize;
+
+    if written > area.len() {
+        bail!("dump area claims {written} bytes, but has {}", area.len());
+    }
+
+    let mut offset = std::mem::size_of::<DumpAreaHeader>()
+        + header.nsegments as usize * std::mem::size_of::<DumpSegmentHeader>();
+
+    while offset < written {
+        let rest = &area[offset..written];
+
+        if rest[0] == humpty::DUMP_SEGMENT_PAD {
+            offset += 1;
+        } else if rest.starts_with(&DUMP_SEGMENT_GNARLE) {
+            let word = |i: usize| {
+                u32::from_le_bytes(rest[i * 4..(i + 1) * 4].try_into().unwrap())
+            };
+
+            if rest.len() < DUMP_SEGMENT_GNARLE_SIZE {
+                bail!("truncated compressed segment at offset {offset}");
+            }
+
+            let (address, clen, ulen) = (word(1), word(2), word(3));
+            let mut input = rest
+                .get(DUMP_SEGMENT_GNARLE_SIZE..)
+                .and_then(|r| r.get(..clen as usize))
+                .ok_or_else(|| anyhow!("truncated segment at {address:#x}"))?;
+
+            let mut state = gnarle::Decompressor::default();
+            let mut output = vec![0u8; ulen as usize];
+            let len =
+                gnarle::decompress(&mut state, &mut input, &mut output).len();
+
+            if len != output.len() || !input.is_empty() || !state.is_idle() {
+                bail!("bad compressed segment at {address:#x}");
+            }
+
+            if tcb.is_none() {
+                *tcb = Some(address);
+            }
+
+            dump.memory.insert(address, output);
+
+            // Compressed segments are padded to a 4-byte boundary
+            offset += DUMP_SEGMENT_GNARLE_SIZE + clen as usize;
+            offset = (offset + 3) & !3;
+        } else if rest.starts_with(&humpty::DUMP_REGISTER_MAGIC) {
+            let reg = DumpRegister::read_from_prefix(rest)
+                .ok_or_else(|| anyhow!("truncated register at {offset}"))?;
+            dump.registers.insert(reg.register, reg.val);
+            offset += std::mem::size_of::<DumpRegister>();
+        } else if rest.starts_with(&humpty::DUMP_TASK_MAGIC) {
+            let task = DumpTask::read_from_prefix(rest)
+                .ok_or_else(|| anyhow!("truncated task at {offset}"))?;
+            dump.task = Some(task.id);
+            offset += std::mem::size_of::<DumpTask>();
+        } else {
+            let data = DumpSegmentData::read_from_prefix(rest)
+                .ok_or_else(|| anyhow!("truncated segment at {offset}"))?;
+            let start = std::mem::size_of::<DumpSegmentData>();
+            let clen = data.compressed_length as usize;
+            let ulen = data.uncompressed_length as usize;
+            let address = data.address;
+
+            let input = rest
+                .get(start..start + clen)
+                .ok_or_else(|| anyhow!("truncated segment at {address:#x}"))?;
+
+            let output = humpty::DumpLzss::decompress(
+                lzss::SliceReader::new(input),
+                lzss::VecWriter::with_capacity(ulen),
+            )
+            .map_err(|e| anyhow!("bad segment at {address:#x}: {e:?}"))?;
+
+            if output.len() != ulen {
+                bail!("segment at {address:#x} decompressed to wrong size");
+            }
+
+            //
+            // Segments for a single region may be split across multiple
+            // data segments; coalesce them where they are contiguous.
+            //
+            match dump.memory.range_mut(..address).next_back() {
+                Some((base, prev)) if *base + prev.len() as u32 == address => {
+                    prev.extend_from_slice(&output);
+                }
+                _ => {
+                    if tcb.is_none() {
+                        *tcb = Some(address);
+                    }
+                    dump.memory.insert(address, output);
+                }
+            }
+
+            offset += start + clen;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_dump(raw: &[u8]) -> Result<Dump> {
+    let mut dump = Dump::default();
+    let mut tcb = None;
+    let mut offset = 0;
+
+    while offset + std::mem::size_of::<humpty::DumpAreaHeader>() <= raw.len() {
+        let header =
+            humpty::DumpAreaHeader::read_from_prefix(&raw[offset..]).unwrap();
+
+        if header.magic != humpty::DUMP_MAGIC {
+            break;
+        }
+
+        let written = header.written as usize;
+        let end = (offset + written).min(raw.len());
+
+        parse_area(&raw[offset..end], &mut tcb, &mut dump)
+            .with_context(|| format!("failed to parse area at {offset:#x}"))?;
+
+        let padded = (written + DUMP_READ_SIZE - 1) / DUMP_READ_SIZE;
+        offset += padded.max(1) * DUMP_READ_SIZE;
+    }
+
+    if offset == 0 {
+        bail!("no dump areas found");
+    }
+
+    if dump.registers.is_empty() && dump.task.is_some() {
+        if let Some(regs) = tcb.and_then(|tcb| dump.task_registers(tcb)) {
+            dump.registers = regs;
+        }
+    }
+
+    Ok(dump)
+}
+
+fn note(out: &mut Vec<u8>, ty: u32, desc: &[u8]) -> Result<()> {
+    let name = b"CORE\0";
+
+    out.write_u32::<LittleEndian>(name.len() as u32)?;
+    out.write_u32::<LittleEndian>(desc.len() as u32)?;
+    out.write_u32::<LittleEndian>(ty)?;
+    out.write_all(name)?;
+    out.resize((out.len() + 3) & !3, 0);
+    out.write_all(desc)?;
+    out.resize((out.len() + 3) & !3, 0);
+
+    Ok(())
+}
+
+fn prstatus(dump: &Dump) -> Result<Vec<u8>> {
+    let mut desc = vec![0u8; PRSTATUS_SIZE];
+
+    // pr_pid is the task index (or 0 for a whole-system dump)
+    desc[24..28]
+        .copy_from_slice(&(dump.task.unwrap_or(0) as u32).to_le_bytes());
+
+    //
+    // pr_reg is r0-r15, followed by CPSR (for which we use xPSR) and
+    // orig_r0 (which we leave as zero).
+    //
+    let mut regs = &mut desc[PRSTATUS_REG_OFFSET..];
+    for reg in 0..=REG_XPSR {
+        let val = dump.registers.get(&reg).copied().unwrap_or(0);
+        regs.write_u32::<LittleEndian>(val)?;
+    }
+
+    Ok(desc)
+}
+
+fn prpsinfo(name: &str) -> Vec<u8> {
+    let mut desc = vec![0u8; PRPSINFO_SIZE];
+
+    // pr_fname is 16 bytes at offset 28, followed by 80 bytes of pr_psargs
+    let fname = name.as_bytes();
+    let len = fname.len().min(15);
+    desc[28..28 + len].copy_from_slice(&fname[..len]);
+
+    let args = fname.len().min(79);
+    desc[44..44 + args].copy_from_slice(&fname[..args]);
+
+    desc
+}
+
+fn write_core(dump: &Dump, task_name: Option<&str>) -> Result<Vec<u8>> {
+    let mut notes = vec![];
+    note(&mut notes, NT_PRSTATUS, &prstatus(dump)?)?;
+
+    if let Some(name) = task_name {
+        note(&mut notes, NT_PRPSINFO, &prpsinfo(name))?;
+    }
+
+    let phnum = 1 + dump.memory.len();
+    let mut offset = ELF_HEADER_SIZE + phnum * PROGRAM_HEADER_SIZE;
+    let mut out = vec![];
+
+    // ELF header: 32-bit, little-endian, version 1
+    out.write_all(&[0x7f, b'E', b'L', b'F', 1, 1, 1, 0])?;
+    out.write_all(&[0; 8])?;
+    out.write_u16::<LittleEndian>(ET_CORE)?;
+    out.write_u16::<LittleEndian>(EM_ARM)?;
+    out.write_u32::<LittleEndian>(1)?; // e_version
+    out.write_u32::<LittleEndian>(0)?; // e_entry
+    out.write_u32::<LittleEndian>(ELF_HEADER_SIZE as u32)?; // e_phoff
+    out.write_u32::<LittleEndian>(0)?; // e_shoff
+    out.write_u32::<LittleEndian>(0)?; // e_flags
+    out.write_u16::<LittleEndian>(ELF_HEADER_SIZE as u16)?;
+    out.write_u16::<LittleEndian>(PROGRAM_HEADER_SIZE as u16)?;
+    out.write_u16::<LittleEndian>(phnum as u16)?;
+    out.write_u16::<LittleEndian>(0)?; // e_shentsize
+    out.write_u16::<LittleEndian>(0)?; // e_shnum
+    out.write_u16::<LittleEndian>(0)?; // e_shstrndx
+
+    let mut phdr = |ty: u32, vaddr: u32, len: usize, flags: u32, align| {
+        out.write_u32::<LittleEndian>(ty)?;
+        out.write_u32::<LittleEndian>(offset as u32)?;
+        out.write_u32::<LittleEndian>(vaddr)?;
+        out.write_u32::<LittleEndian>(0)?; // p_paddr
+        out.write_u32::<LittleEndian>(len as u32)?; // p_filesz
+        out.write_u32::<LittleEndian>(len as u32)?; // p_memsz
+        out.write_u32::<LittleEndian>(flags)?;
+        out.write_u32::<LittleEndian>(align)?;
+        offset += len;
+        Ok::<_, std::io::Error>(())
+    };
+
+    phdr(PT_NOTE, 0, notes.len(), 0, 4)?;
+
+    for (addr, data) in &dump.memory {
+        phdr(PT_LOAD, *addr, data.len(), PF_R | PF_W, 1)?;
+    }
+
+    out.write_all(&notes)?;
+
+    for data in dump.memory.values() {
+        out.write_all(data)?;
+    }
+
+    Ok(out)
+}
+
+pub fn dump_to_core(archive: &Path, dump: &Path, output: &Path) -> Result<()> {
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("could not open {}", archive.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("could not read {}", archive.display()))?;
+
+    let config: ArchiveConfig = {
+        let mut app = String::new();
+        archive
+            .by_name("app.toml")
+            .context("archive is missing app.toml")?
+            .read_to_string(&mut app)?;
+        toml::from_str(&app).context("could not parse app.toml")?
+    };
+
+    let raw = std::fs::read(dump)
+        .with_context(|| format!("could not read {}", dump.display()))?;
+    let dump = parse_dump(&raw)?;
+
+    let task_name = match dump.task {
+        Some(id) => Some(
+            config
+                .tasks
+                .get_index(id as usize)
+                .map(|(name, _)| name.as_str())
+                .ok_or_else(|| anyhow!("dump is of unknown task {id}"))?,
+        ),
+        None => None,
+    };
+
+    let elf = match task_name {
+        Some(name) => format!("elf/task/{name}"),
+        None => "img/final.elf".to_string(),
+    };
+
+    if archive.by_name(&elf).is_err() {
+        bail!("archive is missing {elf}");
+    }
+
+    if dump.registers.is_empty() {
+        eprintln!("warning: no registers found in dump");
+    }
+
+    std::fs::write(output, write_core(&dump, task_name)?)
+        .with_context(|| format!("could not write {}", output.display()))?;
+
+    println!(
+        "wrote {} ({} regions); load it with the archive's {elf}, e.g.:\n    \
+        arm-none-eabi-gdb {elf} -ex 'target core {}'",
+        output.display(),
+        dump.memory.len(),
+        output.display(),
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goblin::elf::{program_header, Elf};
+    use humpty::{DumpAreaHeader, DumpTask};
+    use zerocopy::AsBytes;
+
+    const TCB: u32 = 0x2000_0400;
+    const PSP: u32 = 0x2000_1000;
+
+    /// Appends a `gnarle`-compressed segment, as jefe writes them
+    fn gnarle_segment(area: &mut Vec<u8>, address: u32, data: &[u8]) {
+        while area.len() % 4 != 0 {
+            area.push(humpty::DUMP_SEGMENT_PAD);
+        }
+
+        let compressed = gnarle::compress_to_vec(data);
+        area.extend_from_slice(&DUMP_SEGMENT_GNARLE);
+        area.extend_from_slice(&address.to_le_bytes());
+        area.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        area.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        area.extend_from_slice(&compressed);
+    }
+
+    ///
+    /// Builds a raw dump of task 1, as read out of its dump area:  the
+    /// task's TCB (whose saved state has r4-r11 set to their register
+    /// numbers) and the exception frame at its PSP (likewise for r0-r3, r12,
+    /// LR and PC).
+    ///
+    fn task_dump() -> Vec<u8> {
+        let mut tcb =
+            (4..=11u32).flat_map(u32::to_le_bytes).collect::<Vec<_>>();
+        tcb.extend_from_slice(&PSP.to_le_bytes());
+        tcb.extend_from_slice(&0xffff_fffdu32.to_le_bytes());
+
+        let mut frame = [0, 1, 2, 3, 12, 14, 15]
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect::<Vec<_>>();
+        frame.extend_from_slice(&0x0100_0000u32.to_le_bytes());
+
+        let mut area = vec![0u8; std::mem::size_of::<DumpAreaHeader>()];
+        area.extend_from_slice(DumpTask::new(1, 0).as_bytes());
+        gnarle_segment(&mut area, TCB, &tcb);
+        gnarle_segment(&mut area, PSP, &frame);
+
+        let mut header = DumpAreaHeader::new_zeroed();
+        header.magic = humpty::DUMP_MAGIC;
+        header.written = area.len() as u32;
+        area[..std::mem::size_of::<DumpAreaHeader>()]
+            .copy_from_slice(header.as_bytes());
+
+        area.resize(
+            (area.len() + DUMP_READ_SIZE - 1) & !(DUMP_READ_SIZE - 1),
+            0,
+        );
+        area
+    }
+
+    #[test]
+    fn task_registers_from_saved_state() {
+        let dump = parse_dump(&task_dump()).unwrap();
+
+        assert_eq!(dump.task, Some(1));
+        assert_eq!(dump.memory.len(), 2);
+
+        for reg in (0..=12).chain([14, 15]) {
+            assert_eq!(dump.registers[&reg], u32::from(reg), "r{reg}");
+        }
+
+        // A basic frame, with no alignment padding
+        assert_eq!(dump.registers[&REG_SP], PSP + 32);
+        assert_eq!(dump.registers[&REG_XPSR], 0x0100_0000);
+    }
+
+    #[test]
+    fn core_file() {
+        let dump = parse_dump(&task_dump()).unwrap();
+        let core = write_core(&dump, Some("task1")).unwrap();
+        let elf = Elf::parse(&core).unwrap();
+
+        assert_eq!(elf.header.e_type, ET_CORE);
+        assert_eq!(elf.header.e_machine, EM_ARM);
+        assert!(!elf.is_64);
+
+        let loads = elf
+            .program_headers
+            .iter()
+            .filter(|ph| ph.p_type == program_header::PT_LOAD)
+            .map(|ph| {
+                let range = ph.file_range();
+                (ph.p_vaddr as u32, core[range].to_vec())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(loads.len(), 2);
+        for (addr, data) in &loads {
+            assert_eq!(Some(data), dump.memory.get(addr));
+        }
+
+        let notes = elf
+            .iter_note_headers(&core)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].n_type, NT_PRSTATUS);
+        assert_eq!(notes[0].name, "CORE");
+
+        let desc = notes[0].desc;
+        let pc = PRSTATUS_REG_OFFSET + 15 * 4;
+        assert_eq!(desc[pc..pc + 4], 15u32.to_le_bytes());
+        assert_eq!(desc[24..28], 1u32.to_le_bytes());
+
+        assert_eq!(notes[1].n_type, NT_PRPSINFO);
+        assert_eq!(&notes[1].desc[28..33], b"task1");
+    }
+
+    #[test]
+    fn missing_task_elf() {
+        use std::io::Write;
+        use zip::write::{FileOptions, ZipWriter};
+
+        let dir = std::env::temp_dir()
+            .join(format!("dump-to-core-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let archive = dir.join("build.zip");
+        let mut zip = ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        zip.start_file("app.toml", FileOptions::default()).unwrap();
+        zip.write_all(b"[tasks.jefe]\n[tasks.task1]\n").unwrap();
+        zip.finish().unwrap();
+
+        let input = dir.join("dump");
+        std::fs::write(&input, task_dump()).unwrap();
+
+        let output = dir.join("core");
+        let err = dump_to_core(&archive, &input, &output).unwrap_err();
+
+        assert!(err.to_string().contains("elf/task/task1"), "{err}");
+        assert!(!output.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -20,7 +20,7 @@ use zerocopy::AsBytes;
 
 use crate::{
     caboose_pos,
-    config::{BuildConfig, CabooseConfig, Config},
+    config::{BuildConfig, CabooseConfig, Config, RoTMfgSettings},
     elf,
     sizes::load_task_size,
     task_slot,
@@ -496,42 +496,7 @@ pub fn package(
         if let Some(signing) = &cfg.toml.signing {
             let mut archive = hubtools::RawHubrisArchive::load(&archive_name)
                 .context("loading archive with hubtools")?;
-            let private_key = lpc55_sign::cert::read_rsa_private_key(
-                &cfg.app_src_dir.join(&signing.certs.private_key),
-            )
-            .with_context(|| {
-                format!(
-                    "could not read private key {:?}",
-                    signing.certs.private_key
-                )
-            })?;
-
-            // Certificate paths are relative to the app.toml.  Resolve them
-            // before attempting to read them.
-            let root_cert_abspaths: Vec<PathBuf> = signing
-                .certs
-                .root_certs
-                .iter()
-                .map(|c| cfg.app_src_dir.join(c))
-                .collect();
-            let root_certs = lpc55_sign::cert::read_certs(&root_cert_abspaths)?;
-
-            let signing_cert_abspaths: Vec<PathBuf> = signing
-                .certs
-                .signing_certs
-                .iter()
-                .map(|c| cfg.app_src_dir.join(c))
-                .collect();
-            let signing_certs =
-                lpc55_sign::cert::read_certs(&signing_cert_abspaths)?;
-
-            archive.sign(
-                signing_certs,
-                root_certs.clone(),
-                &private_key,
-                0, // execution address (TODO)
-            )?;
-
+            sign_archive(&mut archive, signing, &cfg.app_src_dir)?;
             archive.overwrite()?;
         }
 
@@ -549,6 +514,48 @@ pub fn package(
     Ok(allocated)
 }
 
+/// Signs the image in `archive` with the certificates and key in `signing`,
+/// whose paths are relative to `app_src_dir`.
+pub fn sign_archive(
+    archive: &mut hubtools::RawHubrisArchive,
+    signing: &RoTMfgSettings,
+    app_src_dir: &Path,
+) -> Result<()> {
+    let private_key = lpc55_sign::cert::read_rsa_private_key(
+        &app_src_dir.join(&signing.certs.private_key),
+    )
+    .with_context(|| {
+        format!("could not read private key {:?}", signing.certs.private_key)
+    })?;
+
+    // Certificate paths are relative to the app.toml.  Resolve them
+    // before attempting to read them.
+    let root_cert_abspaths: Vec<PathBuf> = signing
+        .certs
+        .root_certs
+        .iter()
+        .map(|c| app_src_dir.join(c))
+        .collect();
+    let root_certs = lpc55_sign::cert::read_certs(&root_cert_abspaths)?;
+
+    let signing_cert_abspaths: Vec<PathBuf> = signing
+        .certs
+        .signing_certs
+        .iter()
+        .map(|c| app_src_dir.join(c))
+        .collect();
+    let signing_certs = lpc55_sign::cert::read_certs(&signing_cert_abspaths)?;
+
+    archive.sign(
+        signing_certs,
+        root_certs.clone(),
+        &private_key,
+        0, // execution address (TODO)
+    )?;
+
+    Ok(())
+}
+
 fn write_gdb_script(cfg: &PackageConfig, image_name: &str) -> Result<()> {
     // Humility doesn't know about images right now. The gdb symbol file
     // paths all assume a flat layout with everything in dist. For now,
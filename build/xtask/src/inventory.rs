@@ -0,0 +1,60 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Export of an image's I2C devices, sensors (including those that aren't on
+//! I2C) and power rails as JSON, for use by host software that needs to (for
+//! example) map a `SensorId` to the part and refdes that it measures.  The
+//! format is versioned by the `version` field; see
+//! `build_i2c::INVENTORY_VERSION`.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::config::Config;
+
+#[derive(Serialize)]
+struct AppInventory {
+    app: String,
+    board: String,
+    #[serde(flatten)]
+    i2c: build_i2c::Inventory,
+}
+
+pub fn run(cfg: &Path, output: Option<&Path>) -> Result<()> {
+    let toml = Config::from_file(cfg)?;
+
+    //
+    // This is the same configuration that the build hands to build scripts
+    // (in `HUBRIS_APP_CONFIG`), so we see exactly the devices that they do.
+    //
+    let i2c = match &toml.config {
+        Some(config) => build_i2c::inventory(&toml::to_string(config)?)
+            .context("could not build I2C inventory")?,
+        None => build_i2c::Inventory {
+            version: build_i2c::INVENTORY_VERSION,
+            devices: vec![],
+            other_devices: vec![],
+            sensors: vec![],
+            rails: vec![],
+        },
+    };
+
+    let inventory = AppInventory {
+        app: toml.name,
+        board: toml.board,
+        i2c,
+    };
+
+    let json = serde_json::to_string_pretty(&inventory)?;
+
+    match output {
+        Some(path) => std::fs::write(path, json + "\n")
+            .with_context(|| format!("could not write {}", path.display()))?,
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,320 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Streaming and decoding of `sys_log!` messages over the network.
+//!
+//! Tasks built with userlib's `log-task` feature send compact records to the
+//! logger task, which the log forwarder (see `task/log-forwarder`) streams to
+//! whoever last sent it a packet.  Each record names its format string by
+//! address; the strings themselves are in each task's `.hubris_log_fmt`
+//! section in the build archive.
+
+use std::io::Read;
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::coredump::ArchiveConfig;
+use crate::ringbuf::archive_image_id;
+
+/// Size of the header that the forwarder puts before each record
+const HEADER_SIZE: usize = 23;
+
+/// How long to wait for a record before asking again, in case our request
+/// (or the forwarder's state) was lost
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A task's interned format strings
+struct Formats {
+    name: String,
+    address: u64,
+    data: Vec<u8>,
+}
+
+impl Formats {
+    fn get(&self, address: u32) -> Option<&str> {
+        let offset = u64::from(address).checked_sub(self.address)? as usize;
+        let s = self.data.get(offset..)?;
+        let end = s.iter().position(|&b| b == 0)?;
+        std::str::from_utf8(&s[..end]).ok()
+    }
+}
+
+/// A record, as sent by the forwarder
+struct Packet<'a> {
+    image_id: [u8; 8],
+    index: u32,
+    task: u16,
+    timestamp: u64,
+    record: &'a [u8],
+}
+
+impl<'a> Packet<'a> {
+    /// Parses a packet, returning `None` if it's truncated.
+    fn parse(packet: &'a [u8]) -> Option<Self> {
+        if packet.len() < HEADER_SIZE {
+            return None;
+        }
+        let record_len = packet[22] as usize;
+        Some(Self {
+            image_id: packet[0..8].try_into().unwrap(),
+            index: u32::from_le_bytes(packet[8..12].try_into().unwrap()),
+            task: u16::from_le_bytes(packet[12..14].try_into().unwrap()),
+            timestamp: u64::from_le_bytes(packet[14..22].try_into().unwrap()),
+            record: packet[HEADER_SIZE..].get(..record_len)?,
+        })
+    }
+}
+
+/// Accepts the record at `index`, given that `next` is the index of the next
+/// one we expect.  Returns the number of records lost before it, or `None` if
+/// we've already seen it.
+fn accept(next: &mut u32, index: u32) -> Option<u32> {
+    let lost = index.checked_sub(*next)?;
+    *next = index + 1;
+    Some(lost)
+}
+
+/// Formats a record as a line of output, given the format strings of each
+/// task (in task index order).
+fn format_record(tasks: &[Formats], packet: &Packet) -> String {
+    let Packet {
+        task,
+        timestamp,
+        record,
+        ..
+    } = packet;
+    let formats = match tasks.get(*task as usize) {
+        Some(formats) => formats,
+        None => return format!("{timestamp:>10} task #{task}: {record:02x?}"),
+    };
+    let message = log_encoding::decode(record).and_then(|(fmt, args)| {
+        Some(log_encoding::format(formats.get(fmt)?, &args))
+    });
+    match message {
+        Some(message) => format!("{timestamp:>10} {}: {message}", formats.name),
+        None => format!(
+            "{timestamp:>10} {}: undecodable record {record:02x?}",
+            formats.name
+        ),
+    }
+}
+
+pub fn run(archive: &Path, target: SocketAddr, from: u32) -> Result<()> {
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("could not open {}", archive.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("could not read {}", archive.display()))?;
+
+    let mut read_file = |name: &str| -> Result<Vec<u8>> {
+        let mut data = vec![];
+        archive
+            .by_name(name)
+            .with_context(|| format!("archive is missing {name}"))?
+            .read_to_end(&mut data)?;
+        Ok(data)
+    };
+
+    let config: ArchiveConfig = {
+        let app = String::from_utf8(read_file("app.toml")?)?;
+        toml::from_str(&app).context("could not parse app.toml")?
+    };
+    let image_id = archive_image_id(&read_file("elf/kernel")?)?;
+
+    let mut tasks = vec![];
+    for task in config.tasks.keys() {
+        let data = read_file(&format!("elf/task/{task}"))?;
+        let elf = goblin::elf::Elf::parse(&data)
+            .with_context(|| format!("could not parse ELF for {task}"))?;
+        let (address, data) = elf
+            .section_headers
+            .iter()
+            .find(|s| {
+                elf.shdr_strtab.get_at(s.sh_name)
+                    == Some(log_encoding::FORMAT_SECTION)
+            })
+            .map(|s| {
+                let range = s.file_range().unwrap_or_default();
+                (s.sh_addr, data[range].to_vec())
+            })
+            .unwrap_or_default();
+        tasks.push(Formats {
+            name: task.clone(),
+            address,
+            data,
+        });
+    }
+
+    let bind = if target.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket
+        .connect(target)
+        .with_context(|| format!("could not connect to {target}"))?;
+    socket.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut next = from;
+    let mut rx = [0; 512];
+    socket.send(&next.to_le_bytes())?;
+
+    loop {
+        let len = match socket.recv(&mut rx) {
+            Ok(len) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock
+                        | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                socket.send(&next.to_le_bytes())?;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let packet = match Packet::parse(&rx[..len]) {
+            Some(packet) => packet,
+            None => continue,
+        };
+        if packet.image_id != image_id {
+            bail!(
+                "image ID mismatch: archive has {:#x}, target has {:#x}",
+                u64::from_le_bytes(image_id),
+                u64::from_le_bytes(packet.image_id),
+            );
+        }
+
+        // Records can be repeated if we asked again while the forwarder was
+        // still sending; skip ones we've already shown.
+        match accept(&mut next, packet.index) {
+            None => continue,
+            Some(0) => (),
+            Some(lost) => println!("... {lost} records lost"),
+        }
+        println!("{}", format_record(&tasks, &packet));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a packet as the forwarder would send it.
+    fn packet(index: u32, task: u16, timestamp: u64, record: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend_from_slice(&0x1de_u64.to_le_bytes());
+        out.extend_from_slice(&index.to_le_bytes());
+        out.extend_from_slice(&task.to_le_bytes());
+        out.extend_from_slice(&timestamp.to_le_bytes());
+        out.push(record.len() as u8);
+        out.extend_from_slice(record);
+        out
+    }
+
+    fn record(fmt: u32, f: impl FnOnce(&mut log_encoding::Encoder)) -> Vec<u8> {
+        let mut buf = [0; log_encoding::MAX_RECORD_SIZE];
+        let mut enc = log_encoding::Encoder::new(&mut buf, fmt);
+        f(&mut enc);
+        enc.finish().to_vec()
+    }
+
+    /// Format strings for a task, laid out as in `.hubris_log_fmt`
+    fn formats(name: &str, address: u64, strings: &[&str]) -> Formats {
+        let mut data = vec![];
+        for s in strings {
+            data.extend_from_slice(s.as_bytes());
+            data.push(0);
+        }
+        Formats {
+            name: name.to_string(),
+            address,
+            data,
+        }
+    }
+
+    #[test]
+    fn parses_packets() {
+        let data = packet(7, 3, 1234, &[1, 2, 3]);
+        let p = Packet::parse(&data).unwrap();
+        assert_eq!(u64::from_le_bytes(p.image_id), 0x1de);
+        assert_eq!(p.index, 7);
+        assert_eq!(p.task, 3);
+        assert_eq!(p.timestamp, 1234);
+        assert_eq!(p.record, &[1, 2, 3]);
+
+        // Anything after the record is ignored...
+        let mut padded = data.clone();
+        padded.extend_from_slice(&[0xff; 4]);
+        assert_eq!(Packet::parse(&padded).unwrap().record, &[1, 2, 3]);
+
+        // ...but a truncated record or header is rejected.
+        assert!(Packet::parse(&data[..data.len() - 1]).is_none());
+        assert!(Packet::parse(&data[..HEADER_SIZE - 1]).is_none());
+    }
+
+    #[test]
+    fn accepts_records_in_order() {
+        let mut next = 5;
+        assert_eq!(accept(&mut next, 5), Some(0));
+        assert_eq!(accept(&mut next, 6), Some(0));
+
+        // Repeats of records we've shown are skipped.
+        assert_eq!(accept(&mut next, 5), None);
+        assert_eq!(accept(&mut next, 6), None);
+        assert_eq!(next, 7);
+
+        // Gaps are counted as lost.
+        assert_eq!(accept(&mut next, 10), Some(3));
+        assert_eq!(next, 11);
+    }
+
+    #[test]
+    fn finds_format_strings() {
+        let f = formats("pong", 0x8000, &["first {}", "second"]);
+        assert_eq!(f.get(0x8000), Some("first {}"));
+        assert_eq!(f.get(0x8009), Some("second"));
+        assert_eq!(f.get(0x800b), Some("cond"));
+
+        // Out of the section, or past the last terminator
+        assert_eq!(f.get(0x7fff), None);
+        assert_eq!(f.get(0x8010), None);
+        assert_eq!(f.get(0x9000), None);
+    }
+
+    #[test]
+    fn formats_records() {
+        let tasks = [
+            formats("jefe", 0x8000, &[]),
+            formats("pong", 0x9000, &["cycled through {} LEDs"]),
+        ];
+
+        let r = record(0x9000, |enc| enc.unsigned(4));
+        let data = packet(0, 1, 42, &r);
+        assert_eq!(
+            format_record(&tasks, &Packet::parse(&data).unwrap()),
+            "        42 pong: cycled through 4 LEDs"
+        );
+
+        // A format string that isn't in the task's section
+        let r = record(0x9100, |enc| enc.unsigned(4));
+        let data = packet(0, 1, 42, &r);
+        assert_eq!(
+            format_record(&tasks, &Packet::parse(&data).unwrap()),
+            format!("        42 pong: undecodable record {r:02x?}")
+        );
+
+        // A task that isn't in the archive
+        let data = packet(0, 9, 42, &[1, 2]);
+        assert_eq!(
+            format_record(&tasks, &Packet::parse(&data).unwrap()),
+            "        42 task #9: [01, 02]"
+        );
+    }
+}
@@ -13,13 +13,18 @@ mod auxflash;
 mod caboose_pos;
 mod clippy;
 mod config;
+mod coredump;
 mod dist;
 mod elf;
 mod flash;
 mod graph;
 mod humility;
+mod inventory;
+mod logs;
 mod lsp;
+mod patch_config;
 mod print;
+mod ringbuf;
 mod sizes;
 mod task_slot;
 
@@ -167,6 +172,16 @@ enum Xtask {
         cfg: PathBuf,
     },
 
+    /// Export the I2C devices, sensors and power rails of an image as JSON,
+    /// including each sensor's `SensorId` and the mux path to each device.
+    Inventory {
+        /// Output file; by default, the inventory is printed to stdout.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// Path to the image configuration file, in TOML.
+        cfg: PathBuf,
+    },
+
     /// Print out information related to the build.
     ///
     /// Currently only useful to print the archive path, but may grow over time.
@@ -187,6 +202,62 @@ enum Xtask {
         expanded_config: bool,
     },
 
+    /// Converts a raw dump (as read from the dump agent) into an ELF core
+    /// file, which can be loaded into GDB along with the task ELFs in the
+    /// build archive.
+    DumpToCore {
+        /// Path to the build archive for the image that was dumped
+        archive: PathBuf,
+        /// Path to the raw dump
+        dump: PathBuf,
+        /// Output file for the ELF core
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+
+    /// Lists the ring buffers in a build archive or, given the address of a
+    /// running system's ringbuf agent, retrieves and decodes them over the
+    /// network.
+    Ringbuf {
+        /// Path to the build archive for the running image
+        archive: PathBuf,
+        /// Address of the ringbuf agent, e.g. `[fe80::1de:1%2]:11114`
+        #[clap(long)]
+        target: Option<std::net::SocketAddr>,
+        /// Only show ring buffers whose task or name contains one of these
+        filters: Vec<String>,
+    },
+
+    /// Sets a task's `patchable_task_config!` values in a build archive,
+    /// without rebuilding anything, and re-signs the image if the app is
+    /// signed.
+    PatchConfig {
+        /// Path to the image configuration file, in TOML.
+        cfg: PathBuf,
+        /// Build archive to patch, in place
+        archive: PathBuf,
+        /// Task whose config to patch
+        #[clap(long)]
+        task: String,
+        /// Values to set, as `name=value`, where `value` is in TOML syntax
+        /// (e.g. `gains=[1.5, 0.25]`)
+        #[clap(long = "set", required = true)]
+        settings: Vec<String>,
+    },
+
+    /// Streams `sys_log!` messages from a running system's log forwarder,
+    /// decoding them with the format strings in the build archive.
+    Logs {
+        /// Path to the build archive for the running image
+        archive: PathBuf,
+        /// Address of the log forwarder, e.g. `[fe80::1de:1%2]:11115`
+        target: std::net::SocketAddr,
+        /// Index of the first record to show; by default, we start with the
+        /// oldest record that the logger still has.
+        #[clap(long, default_value_t = 0)]
+        from: u32,
+    },
+
     /// Print a JSON blob with configuration info for `rust-analyzer`
     Lsp {
         /// Existing LSP clients.
@@ -356,6 +427,9 @@ fn run(xtask: Xtask) -> Result<()> {
         Xtask::Graph { output, cfg } => {
             graph::task_graph(&cfg, &output)?;
         }
+        Xtask::Inventory { output, cfg } => {
+            inventory::run(&cfg, output.as_deref())?;
+        }
         Xtask::Print {
             cfg,
             archive,
@@ -365,6 +439,35 @@ fn run(xtask: Xtask) -> Result<()> {
             print::run(&cfg, archive, image_name, expanded_config)
                 .context("could not print information about the build")?;
         }
+        Xtask::DumpToCore {
+            archive,
+            dump,
+            output,
+        } => {
+            coredump::dump_to_core(&archive, &dump, &output)?;
+        }
+        Xtask::Ringbuf {
+            archive,
+            target,
+            filters,
+        } => {
+            ringbuf::run(&archive, target, &filters)?;
+        }
+        Xtask::PatchConfig {
+            cfg,
+            archive,
+            task,
+            settings,
+        } => {
+            patch_config::run(&cfg, &archive, &task, &settings)?;
+        }
+        Xtask::Logs {
+            archive,
+            target,
+            from,
+        } => {
+            logs::run(&archive, target, from)?;
+        }
         Xtask::Lsp { clients, file } => {
             lsp::run(&file, &clients)?;
         }
@@ -0,0 +1,253 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Patching of a task's `patchable_task_config!` values in a built archive.
+//!
+//! The values live in the task's `.hubris_config` section, in flash; the
+//! `.hubris_config_schema` section describes their layout (see the
+//! `patchable-config` crate).  We patch both the final image and the task's
+//! ELF, then re-sign the image if the app is signed.
+//!
+//! Because the image's contents change, so must its ID:  we give it a new
+//! one, derived from its old ID and the values that we patched in, and write
+//! that into the kernel (in both the image and its ELF) before signing.  The
+//! archive thus continues to match the image that it describes.
+
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use patchable_config::Field;
+
+use crate::{config::Config, dist, elf, ringbuf::image_id_location};
+
+const CONFIG_SECTION: &str = ".hubris_config";
+const CONFIG_SCHEMA_SECTION: &str = ".hubris_config_schema";
+
+/// Parses a `name=value` setting, where `value` is in TOML syntax.
+fn parse_setting(s: &str) -> Result<(&str, toml::Value)> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected `name=value`; got {s:?}"))?;
+    let mut table: toml::Table = toml::from_str(&format!("v = {value}"))
+        .with_context(|| format!("could not parse value {value:?}"))?;
+    Ok((name.trim(), table.remove("v").unwrap()))
+}
+
+/// Applies `settings` to `data`, a config blob described by `schema`,
+/// returning the name, old value and new value of each setting.
+fn patch(
+    schema: &str,
+    data: &mut [u8],
+    settings: &[String],
+) -> Result<Vec<(String, toml::Value, toml::Value)>> {
+    let fields = schema
+        .lines()
+        .map(Field::parse)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut out = vec![];
+    for s in settings {
+        let (name, value) = parse_setting(s)?;
+        let field =
+            fields.iter().find(|f| f.name == name).ok_or_else(|| {
+                let names: Vec<_> =
+                    fields.iter().map(|f| f.name.as_str()).collect();
+                anyhow!(
+                    "no patchable config value {name}; the values are {}",
+                    names.join(", ")
+                )
+            })?;
+        let bytes = field
+            .encode(&value)
+            .with_context(|| format!("bad value for {name}"))?;
+        let old = field.decode(data).with_context(|| {
+            format!("{name} is outside of {CONFIG_SECTION}")
+        })?;
+        data[field.offset..field.offset + field.size()].copy_from_slice(&bytes);
+        out.push((field.name.clone(), old, field.decode(data)?));
+    }
+
+    Ok(out)
+}
+
+/// Derives the ID of an image from its ID before `data` was patched into
+/// `task`'s config at `address`
+fn patched_image_id(old: u64, task: &str, address: u32, data: &[u8]) -> u64 {
+    let mut id = fnv::FnvHasher::default();
+    (old, task, address, data).hash(&mut id);
+    id.finish()
+}
+
+pub fn run(
+    cfg: &Path,
+    archive_path: &Path,
+    task: &str,
+    settings: &[String],
+) -> Result<()> {
+    let toml = Config::from_file(cfg)?;
+    if !toml.tasks.contains_key(task) {
+        bail!("{} has no task named {task}", toml.name);
+    }
+
+    let mut archive = hubtools::RawHubrisArchive::load(archive_path)
+        .context("loading archive with hubtools")?;
+    let elf_name = format!("elf/task/{task}");
+    let mut elf_data = archive
+        .extract_file(&elf_name)
+        .with_context(|| format!("extracting {elf_name} from archive"))?;
+
+    let elf = goblin::elf::Elf::parse(&elf_data)
+        .with_context(|| format!("could not parse ELF for {task}"))?;
+    let section = |name| {
+        elf::get_section_by_name(&elf, name).ok_or_else(|| {
+            anyhow!("{task} has no patchable config ({name} is missing)")
+        })
+    };
+    let blob = section(CONFIG_SECTION)?;
+    let schema = section(CONFIG_SCHEMA_SECTION)?;
+
+    let address = blob.sh_addr as u32;
+    let blob_range = blob.file_range().unwrap_or_default();
+    let schema_range = schema.file_range().unwrap_or_default();
+
+    let schema = std::str::from_utf8(&elf_data[schema_range])
+        .context("config schema is not UTF-8")?;
+
+    let mut data = elf_data[blob_range.clone()].to_vec();
+    for (name, old, new) in patch(schema, &mut data, settings)
+        .with_context(|| format!("patching config for {task}"))?
+    {
+        println!("{task}: {name} = {new} (was {old})");
+    }
+
+    elf_data[blob_range].copy_from_slice(&data);
+    archive
+        .image
+        .write(address, &data)
+        .context("patching config into image")?;
+    archive
+        .add_file(&elf_name, &elf_data)
+        .with_context(|| format!("replacing {elf_name} in archive"))?;
+
+    let mut kernel = archive
+        .extract_file("elf/kernel")
+        .context("extracting kernel from archive")?;
+    let (id_address, id_offset) = image_id_location(&kernel)?;
+    let id = &mut kernel[id_offset..id_offset + 8];
+    let old_id = u64::from_le_bytes((&*id).try_into().unwrap());
+    let new_id = patched_image_id(old_id, task, address, &data);
+    id.copy_from_slice(&new_id.to_le_bytes());
+    println!("image ID: {new_id:#x} (was {old_id:#x})");
+
+    archive
+        .image
+        .write(id_address, &new_id.to_le_bytes())
+        .context("patching image ID into image")?;
+    archive
+        .add_file("elf/kernel", &kernel)
+        .context("replacing kernel in archive")?;
+
+    if let Some(signing) = &toml.signing {
+        let app_src_dir = cfg
+            .parent()
+            .ok_or_else(|| anyhow!("could not get app directory"))?;
+        dist::sign_archive(&mut archive, signing, app_src_dir)?;
+    }
+
+    archive.overwrite().context("overwriting archive")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lays out a blob and its schema the way `patchable_task_config!` does
+    fn blob(fields: &[(&str, &str, Option<usize>, &str)]) -> (String, Vec<u8>) {
+        let mut schema = String::new();
+        let mut blob = vec![];
+        for &(name, ty, len, value) in fields {
+            let (_, value) = parse_setting(&format!("{name}={value}")).unwrap();
+            let field = Field::new(name, ty, len, blob.len()).unwrap();
+            blob.extend(field.encode(&value).unwrap());
+            schema += &format!("{field}\n");
+        }
+        (schema, blob)
+    }
+
+    fn settings(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn patches_values() {
+        let (schema, mut data) = blob(&[
+            ("enabled", "bool", None, "true"),
+            ("gains", "f32", Some(3), "[1.75, 0.0135, 0.4]"),
+            ("interval_ms", "u64", None, "1000"),
+        ]);
+        assert_eq!(
+            schema,
+            "enabled bool 0\ngains [f32;3] 1\ninterval_ms u64 13\n"
+        );
+        let orig = data.clone();
+
+        let patched = patch(
+            &schema,
+            &mut data,
+            &settings(&["gains = [2, 0.01, 0.5]", "interval_ms=500"]),
+        )
+        .unwrap();
+        let patched: Vec<_> = patched
+            .iter()
+            .map(|(n, old, new)| format!("{n} {old} {new}"))
+            .collect();
+        assert_eq!(
+            patched,
+            [
+                "gains [1.75, 0.0135, 0.4] [2.0, 0.01, 0.5]",
+                "interval_ms 1000 500",
+            ]
+        );
+
+        // Only the patched values have changed, and they decode as they will
+        // on the target.
+        assert_eq!(data[0], orig[0]);
+        assert_eq!(data[1..5], 2.0f32.to_le_bytes());
+        assert_eq!(data[5..9], 0.01f32.to_le_bytes());
+        assert_eq!(data[9..13], 0.5f32.to_le_bytes());
+        assert_eq!(data[13..], 500u64.to_le_bytes());
+    }
+
+    #[test]
+    fn patched_image_ids_differ() {
+        let id = patched_image_id(0x1de, "thermal", 0x1000, &[1, 2, 3]);
+        assert_ne!(id, 0x1de);
+        assert_eq!(id, patched_image_id(0x1de, "thermal", 0x1000, &[1, 2, 3]));
+        for other in [
+            patched_image_id(0x1df, "thermal", 0x1000, &[1, 2, 3]),
+            patched_image_id(0x1de, "power", 0x1000, &[1, 2, 3]),
+            patched_image_id(0x1de, "thermal", 0x1004, &[1, 2, 3]),
+            patched_image_id(0x1de, "thermal", 0x1000, &[1, 2, 4]),
+        ] {
+            assert_ne!(id, other);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_settings() {
+        let (schema, orig) = blob(&[("count", "u8", None, "4")]);
+        let mut data = orig.clone();
+        for s in ["count=256", "count=true", "missing=1", "count", "count=["] {
+            assert!(patch(&schema, &mut data, &settings(&[s])).is_err(), "{s}");
+        }
+        assert_eq!(data, orig);
+
+        // A schema that doesn't match the blob is caught, too.
+        let schema = "count u32 0\n";
+        assert!(patch(schema, &mut data, &settings(&["count=1"])).is_err());
+    }
+}
@@ -0,0 +1,1503 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Retrieval and decoding of ring buffers over the network.
+//!
+//! Ring buffers are found in the DWARF of each task in the build archive: any
+//! static whose name ends in `RINGBUF` (as with Humility's automatic scan).
+//! Their contents are read from a running system via the ringbuf agent (see
+//! `task/ringbuf-agent`), then decoded using the types in that same DWARF, so
+//! that the output looks much like that of `humility ringbuf`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use ringbuf_agent_messages::{
+    version, Header, Request, RequestMessage, Response, ResponseMessage,
+    MAX_READ_SIZE, MAX_RESPONSE_SIZE,
+};
+
+use crate::coredump::ArchiveConfig;
+
+type Reader<'a> = gimli::EndianSlice<'a, gimli::RunTimeEndian>;
+
+/// A type DIE, as an index into [`DebugInfo::units`] and an offset within
+/// that unit
+type TypeRef = (usize, gimli::UnitOffset);
+
+#[derive(Clone, Debug)]
+enum Type {
+    Base {
+        name: String,
+        encoding: gimli::DwAte,
+        size: u64,
+    },
+    Pointer {
+        size: u64,
+    },
+    /// A fieldless enum
+    Enum {
+        size: u64,
+        enumerators: Vec<(u64, String)>,
+    },
+    Array {
+        elem: Rc<Type>,
+        count: u64,
+    },
+    /// A structure, or (if it has `variants`) a Rust enum with fields
+    Struct {
+        name: String,
+        size: u64,
+        members: Vec<Member>,
+        variants: Option<VariantPart>,
+    },
+    /// Anything that we don't know how to decode
+    Opaque {
+        name: String,
+        size: u64,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Member {
+    name: String,
+    offset: u64,
+    ty: Rc<Type>,
+}
+
+#[derive(Clone, Debug)]
+struct VariantPart {
+    /// The member holding the discriminant; absent if there is only one
+    /// variant
+    discr: Option<Member>,
+    variants: Vec<Variant>,
+}
+
+#[derive(Clone, Debug)]
+struct Variant {
+    /// Discriminant value for this variant, or `None` for the variant that
+    /// applies when no other matches (i.e. the dataful variant of an enum
+    /// with a niche)
+    discr_value: Option<u64>,
+    /// The variant's contents, as a structure named after the variant
+    member: Member,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Unsigned(u64),
+    Signed(i64),
+    Bool(bool),
+    Char(char),
+    Float(f64),
+    Pointer(u64),
+    Array(Vec<Value>),
+    Struct {
+        name: String,
+        fields: Vec<(String, Value)>,
+    },
+    Variant {
+        name: String,
+        fields: Vec<(String, Value)>,
+    },
+    Opaque(String),
+}
+
+/// Mask for the low `size` bytes of a `u64`
+fn mask(size: u64) -> u64 {
+    if size >= 8 {
+        u64::MAX
+    } else {
+        (1 << (size * 8)) - 1
+    }
+}
+
+/// Reads a little-endian unsigned integer of up to eight bytes
+fn read_uint(bytes: &[u8]) -> Result<u64> {
+    if bytes.len() > 8 {
+        bail!("can't read {}-byte integer", bytes.len());
+    }
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0, |acc, &b| (acc << 8) | u64::from(b)))
+}
+
+fn slice(mem: &[u8], offset: u64, size: u64) -> Result<&[u8]> {
+    mem.get(offset as usize..(offset + size) as usize)
+        .ok_or_else(|| anyhow!("{size} bytes at offset {offset} out of range"))
+}
+
+impl Type {
+    fn size(&self) -> u64 {
+        match self {
+            Type::Base { size, .. }
+            | Type::Pointer { size }
+            | Type::Enum { size, .. }
+            | Type::Struct { size, .. }
+            | Type::Opaque { size, .. } => *size,
+            Type::Array { elem, count } => elem.size() * count,
+        }
+    }
+
+    /// Decodes a value of this type from the start of `mem`
+    fn read(&self, mem: &[u8]) -> Result<Value> {
+        let size = self.size();
+        let bytes = slice(mem, 0, size)?;
+
+        Ok(match self {
+            Type::Base { name, .. } if size == 0 || size > 8 => {
+                Value::Opaque(name.clone())
+            }
+            Type::Base { encoding, .. } => {
+                let v = read_uint(bytes)?;
+                match *encoding {
+                    gimli::DW_ATE_boolean => Value::Bool(v != 0),
+                    gimli::DW_ATE_signed | gimli::DW_ATE_signed_char => {
+                        let shift = 64 - size * 8;
+                        Value::Signed(((v << shift) as i64) >> shift)
+                    }
+                    gimli::DW_ATE_float if size == 4 => {
+                        Value::Float(f32::from_bits(v as u32).into())
+                    }
+                    gimli::DW_ATE_float => Value::Float(f64::from_bits(v)),
+                    gimli::DW_ATE_UTF => {
+                        Value::Char(char::from_u32(v as u32).unwrap_or('?'))
+                    }
+                    _ => Value::Unsigned(v),
+                }
+            }
+            Type::Pointer { .. } => Value::Pointer(read_uint(bytes)?),
+            Type::Enum { enumerators, .. } => {
+                let v = read_uint(bytes)?;
+                match enumerators.iter().find(|(e, _)| e & mask(size) == v) {
+                    Some((_, name)) => Value::Variant {
+                        name: name.clone(),
+                        fields: vec![],
+                    },
+                    None => Value::Unsigned(v),
+                }
+            }
+            Type::Array { elem, count } => Value::Array(
+                (0..*count)
+                    .map(|i| elem.read(&mem[(i * elem.size()) as usize..]))
+                    .collect::<Result<_>>()?,
+            ),
+            Type::Struct {
+                name,
+                members,
+                variants: None,
+                ..
+            } => Value::Struct {
+                name: name.clone(),
+                fields: members
+                    .iter()
+                    .map(|m| Ok((m.name.clone(), m.read(mem)?)))
+                    .collect::<Result<_>>()?,
+            },
+            Type::Struct {
+                name,
+                variants: Some(part),
+                ..
+            } => {
+                let variant = match &part.discr {
+                    Some(discr) => {
+                        let size = discr.ty.size();
+                        let v = read_uint(slice(mem, discr.offset, size)?)?;
+                        part.variants
+                            .iter()
+                            .find(|x| {
+                                x.discr_value.map(|d| d & mask(size)) == Some(v)
+                            })
+                            .or_else(|| {
+                                part.variants
+                                    .iter()
+                                    .find(|x| x.discr_value.is_none())
+                            })
+                    }
+                    None => part.variants.first(),
+                }
+                .ok_or_else(|| anyhow!("no variant of {name} matches"))?;
+
+                match variant.member.read(mem)? {
+                    Value::Struct { name, fields } => {
+                        Value::Variant { name, fields }
+                    }
+                    v => v,
+                }
+            }
+            Type::Opaque { name, .. } => Value::Opaque(name.clone()),
+        })
+    }
+}
+
+impl Member {
+    fn read(&self, mem: &[u8]) -> Result<Value> {
+        let mem = mem
+            .get(self.offset as usize..)
+            .ok_or_else(|| anyhow!("member {} out of range", self.name))?;
+        self.ty.read(mem)
+    }
+}
+
+impl Value {
+    fn field(&self, name: &str) -> Result<&Value> {
+        match self {
+            Value::Struct { fields, .. } | Value::Variant { fields, .. } => {
+                fields
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v)
+                    .ok_or_else(|| anyhow!("missing field {name}"))
+            }
+            _ => bail!("expected a structure with field {name}"),
+        }
+    }
+
+    fn unsigned(&self) -> Result<u64> {
+        match self {
+            Value::Unsigned(v) => Ok(*v),
+            _ => bail!("expected an unsigned value, found {self}"),
+        }
+    }
+
+    fn fields(&self) -> &[(String, Value)] {
+        match self {
+            Value::Struct { fields, .. } | Value::Variant { fields, .. } => {
+                fields
+            }
+            _ => &[],
+        }
+    }
+}
+
+fn fmt_fields(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    fields: &[(String, Value)],
+) -> fmt::Result {
+    write!(f, "{name}")?;
+    if fields.is_empty() {
+        return Ok(());
+    }
+
+    // Tuple structs and variants have fields named `__0`, `__1`, etc.
+    let tuple = fields.iter().all(|(n, _)| n.starts_with("__"));
+    write!(f, "{}", if tuple { "(" } else { " { " })?;
+    for (i, (n, v)) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        if tuple {
+            write!(f, "{v}")?;
+        } else {
+            write!(f, "{n}: {v}")?;
+        }
+    }
+    write!(f, "{}", if tuple { ")" } else { " }" })
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Unsigned(v) if *v < 10 => write!(f, "{v}"),
+            Value::Unsigned(v) => write!(f, "{v:#x}"),
+            Value::Signed(v) => write!(f, "{v}"),
+            Value::Bool(v) => write!(f, "{v}"),
+            Value::Char(v) => write!(f, "{v:?}"),
+            Value::Float(v) => write!(f, "{v}"),
+            Value::Pointer(v) => write!(f, "{v:#010x}"),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{v}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Struct { name, fields }
+            | Value::Variant { name, fields } => fmt_fields(f, name, fields),
+            Value::Opaque(name) => write!(f, "<{name}>"),
+        }
+    }
+}
+
+/// A ring buffer found in a task's DWARF
+struct Variable {
+    /// Path of the static, e.g. `task_jefe::dump::__RINGBUF`
+    name: String,
+    address: u32,
+    ty: Rc<Type>,
+}
+
+struct DebugInfo<'a> {
+    dwarf: gimli::Dwarf<Reader<'a>>,
+    units: Vec<gimli::Unit<Reader<'a>>>,
+    types: RefCell<HashMap<TypeRef, Rc<Type>>>,
+}
+
+impl<'a> DebugInfo<'a> {
+    fn new(elf: &goblin::elf::Elf, bytes: &'a [u8]) -> Result<Self> {
+        let endian = if elf.little_endian {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+
+        Self::load(endian, |name| {
+            Ok(match crate::elf::get_section_by_name(elf, name) {
+                Some(s) => {
+                    let start = s.sh_offset as usize;
+                    bytes
+                        .get(start..start + s.sh_size as usize)
+                        .ok_or_else(|| anyhow!("bad section {name}"))?
+                }
+                None => &[],
+            })
+        })
+    }
+
+    /// Loads DWARF given a function that returns the contents of each
+    /// section by name (or an empty slice if it's absent)
+    fn load(
+        endian: gimli::RunTimeEndian,
+        section: impl Fn(&str) -> Result<&'a [u8]>,
+    ) -> Result<Self> {
+        let dwarf = gimli::Dwarf::load(|id| -> Result<_> {
+            Ok(gimli::EndianSlice::new(section(id.name())?, endian))
+        })?;
+
+        let mut units = vec![];
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next()? {
+            units.push(dwarf.unit(header)?);
+        }
+
+        Ok(Self {
+            dwarf,
+            units,
+            types: RefCell::new(HashMap::new()),
+        })
+    }
+
+    fn name(
+        &self,
+        unit: &gimli::Unit<Reader<'a>>,
+        entry: &gimli::DebuggingInformationEntry<Reader<'a>>,
+    ) -> Result<Option<String>> {
+        Ok(match entry.attr_value(gimli::DW_AT_name)? {
+            Some(v) => Some(
+                self.dwarf
+                    .attr_string(unit, v)?
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            None => None,
+        })
+    }
+
+    /// Resolves a `DW_AT_type` (or similar) reference from unit `u`
+    fn type_ref(
+        &self,
+        u: usize,
+        value: gimli::AttributeValue<Reader<'a>>,
+    ) -> Result<TypeRef> {
+        match value {
+            gimli::AttributeValue::UnitRef(offset) => Ok((u, offset)),
+            gimli::AttributeValue::DebugInfoRef(offset) => self
+                .units
+                .iter()
+                .enumerate()
+                .find_map(|(i, unit)| {
+                    offset.to_unit_offset(&unit.header).map(|o| (i, o))
+                })
+                .ok_or_else(|| anyhow!("bad type reference {offset:?}")),
+            v => bail!("unexpected type reference {v:?}"),
+        }
+    }
+
+    fn type_of(
+        &self,
+        u: usize,
+        entry: &gimli::DebuggingInformationEntry<Reader<'a>>,
+    ) -> Result<Rc<Type>> {
+        let value = entry
+            .attr_value(gimli::DW_AT_type)?
+            .ok_or_else(|| anyhow!("missing type at {:?}", entry.offset()))?;
+        self.ty(self.type_ref(u, value)?)
+    }
+
+    fn ty(&self, r: TypeRef) -> Result<Rc<Type>> {
+        if let Some(ty) = self.types.borrow().get(&r) {
+            return Ok(ty.clone());
+        }
+
+        let (u, offset) = r;
+        let mut tree = self.units[u].entries_tree(Some(offset))?;
+        let ty = Rc::new(self.parse_type(u, tree.root()?)?);
+        self.types.borrow_mut().insert(r, ty.clone());
+        Ok(ty)
+    }
+
+    fn parse_type(
+        &self,
+        u: usize,
+        node: gimli::EntriesTreeNode<Reader<'a>>,
+    ) -> Result<Type> {
+        let unit = &self.units[u];
+        let entry = node.entry();
+        let tag = entry.tag();
+        let name = self.name(unit, entry)?.unwrap_or_default();
+        let size = entry
+            .attr_value(gimli::DW_AT_byte_size)?
+            .and_then(|v| v.udata_value());
+
+        Ok(match tag {
+            gimli::DW_TAG_base_type => Type::Base {
+                name,
+                encoding: match entry.attr_value(gimli::DW_AT_encoding)? {
+                    Some(gimli::AttributeValue::Encoding(e)) => e,
+                    _ => gimli::DW_ATE_unsigned,
+                },
+                size: size.unwrap_or(0),
+            },
+            gimli::DW_TAG_pointer_type | gimli::DW_TAG_reference_type => {
+                Type::Pointer {
+                    size: size.unwrap_or(4),
+                }
+            }
+            gimli::DW_TAG_typedef
+            | gimli::DW_TAG_const_type
+            | gimli::DW_TAG_volatile_type => (*self.type_of(u, entry)?).clone(),
+            gimli::DW_TAG_enumeration_type => {
+                let mut enumerators = vec![];
+                let mut children = node.children();
+                while let Some(child) = children.next()? {
+                    let e = child.entry();
+                    if e.tag() != gimli::DW_TAG_enumerator {
+                        continue;
+                    }
+                    if let Some(v) = e.attr_value(gimli::DW_AT_const_value)? {
+                        let name = self.name(unit, e)?.unwrap_or_default();
+                        enumerators.push((discr_value(v)?, name));
+                    }
+                }
+                Type::Enum {
+                    size: size.unwrap_or(0),
+                    enumerators,
+                }
+            }
+            gimli::DW_TAG_array_type => {
+                let elem = self.type_of(u, entry)?;
+                let mut count = 0;
+                let mut children = node.children();
+                while let Some(child) = children.next()? {
+                    let e = child.entry();
+                    if e.tag() != gimli::DW_TAG_subrange_type {
+                        continue;
+                    }
+                    let attr = |at| -> Result<Option<u64>> {
+                        Ok(e.attr_value(at)?.and_then(|v| v.udata_value()))
+                    };
+                    count = match attr(gimli::DW_AT_count)? {
+                        Some(n) => n,
+                        None => attr(gimli::DW_AT_upper_bound)?
+                            .map(|n| n + 1)
+                            .unwrap_or(0),
+                    };
+                    break;
+                }
+                Type::Array { elem, count }
+            }
+            gimli::DW_TAG_structure_type => {
+                let mut members = vec![];
+                let mut variants = None;
+                let mut children = node.children();
+                while let Some(child) = children.next()? {
+                    match child.entry().tag() {
+                        gimli::DW_TAG_member => {
+                            members.push(self.member(u, child.entry())?)
+                        }
+                        gimli::DW_TAG_variant_part => {
+                            variants = Some(self.variant_part(u, child)?)
+                        }
+                        _ => {}
+                    }
+                }
+                Type::Struct {
+                    name,
+                    size: size.unwrap_or(0),
+                    members,
+                    variants,
+                }
+            }
+            _ => Type::Opaque {
+                name,
+                size: size.unwrap_or(0),
+            },
+        })
+    }
+
+    fn member(
+        &self,
+        u: usize,
+        entry: &gimli::DebuggingInformationEntry<Reader<'a>>,
+    ) -> Result<Member> {
+        Ok(Member {
+            name: self.name(&self.units[u], entry)?.unwrap_or_default(),
+            offset: entry
+                .attr_value(gimli::DW_AT_data_member_location)?
+                .and_then(|v| v.udata_value())
+                .unwrap_or(0),
+            ty: self.type_of(u, entry)?,
+        })
+    }
+
+    fn variant_part(
+        &self,
+        u: usize,
+        node: gimli::EntriesTreeNode<Reader<'a>>,
+    ) -> Result<VariantPart> {
+        let discr_offset = match node.entry().attr_value(gimli::DW_AT_discr)? {
+            Some(gimli::AttributeValue::UnitRef(offset)) => Some(offset),
+            _ => None,
+        };
+
+        let mut part = VariantPart {
+            discr: None,
+            variants: vec![],
+        };
+
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            match entry.tag() {
+                gimli::DW_TAG_member
+                    if Some(entry.offset()) == discr_offset =>
+                {
+                    part.discr = Some(self.member(u, entry)?);
+                }
+                gimli::DW_TAG_variant => {
+                    let discr_value =
+                        match entry.attr_value(gimli::DW_AT_discr_value)? {
+                            Some(v) => Some(discr_value(v)?),
+                            None => None,
+                        };
+                    let mut members = child.children();
+                    while let Some(m) = members.next()? {
+                        if m.entry().tag() == gimli::DW_TAG_member {
+                            part.variants.push(Variant {
+                                discr_value,
+                                member: self.member(u, m.entry())?,
+                            });
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(part)
+    }
+
+    /// Finds every ring buffer, i.e. every static whose name ends in
+    /// `RINGBUF`
+    fn ringbufs(&self) -> Result<Vec<Variable>> {
+        let mut out = vec![];
+
+        for (u, unit) in self.units.iter().enumerate() {
+            let mut namespaces: Vec<(isize, String)> = vec![];
+            let mut depth = 0;
+            let mut cursor = unit.entries();
+
+            while let Some((delta, entry)) = cursor.next_dfs()? {
+                depth += delta;
+                namespaces.retain(|(d, _)| *d < depth);
+
+                match entry.tag() {
+                    gimli::DW_TAG_namespace => {
+                        if let Some(name) = self.name(unit, entry)? {
+                            namespaces.push((depth, name));
+                        }
+                    }
+                    gimli::DW_TAG_variable => {
+                        let name = match self.name(unit, entry)? {
+                            Some(name) if name.ends_with("RINGBUF") => name,
+                            _ => continue,
+                        };
+                        let expr =
+                            match entry.attr_value(gimli::DW_AT_location)? {
+                                Some(gimli::AttributeValue::Exprloc(e)) => e,
+                                _ => continue,
+                            };
+                        let address =
+                            match expr.operations(unit.encoding()).next()? {
+                                Some(gimli::Operation::Address { address }) => {
+                                    address as u32
+                                }
+                                _ => continue,
+                            };
+
+                        let mut path: Vec<&str> = namespaces
+                            .iter()
+                            .map(|(_, n)| n.as_str())
+                            .collect();
+                        path.push(&name);
+
+                        out.push(Variable {
+                            name: path.join("::"),
+                            address,
+                            ty: self.type_of(u, entry)?,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        out.sort_by_key(|v| v.address);
+        out.dedup_by_key(|v| v.address);
+        Ok(out)
+    }
+}
+
+fn discr_value(value: gimli::AttributeValue<Reader>) -> Result<u64> {
+    match value {
+        gimli::AttributeValue::Sdata(v) => Ok(v as u64),
+        v => v
+            .udata_value()
+            .ok_or_else(|| anyhow!("bad discriminant {v:?}")),
+    }
+}
+
+/// Client for the ringbuf agent
+struct Agent {
+    socket: UdpSocket,
+    target: SocketAddr,
+    message_id: u32,
+}
+
+impl Agent {
+    /// How long to wait for each attempt at a request
+    const TIMEOUT: Duration = Duration::from_millis(500);
+    const ATTEMPTS: usize = 5;
+
+    fn new(target: SocketAddr) -> Result<Self> {
+        let bind = if target.is_ipv6() {
+            "[::]:0"
+        } else {
+            "0.0.0.0:0"
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket
+            .connect(target)
+            .with_context(|| format!("could not connect to {target}"))?;
+        Ok(Self {
+            socket,
+            target,
+            message_id: 0,
+        })
+    }
+
+    /// Sends a request (retrying as needed), returning the response and any
+    /// data that followed it
+    fn request(&mut self, request: Request) -> Result<(Response, Vec<u8>)> {
+        self.message_id = self.message_id.wrapping_add(1);
+        let msg = RequestMessage {
+            header: Header {
+                version: version::CURRENT,
+                message_id: self.message_id,
+            },
+            request,
+        };
+
+        let mut tx = [0; <RequestMessage as hubpack::SerializedSize>::MAX_SIZE];
+        let n = hubpack::serialize(&mut tx, &msg)
+            .map_err(|e| anyhow!("could not serialize request: {e:?}"))?;
+        let mut rx = [0; MAX_RESPONSE_SIZE];
+
+        for _ in 0..Self::ATTEMPTS {
+            self.socket.send(&tx[..n])?;
+            let deadline = Instant::now() + Self::TIMEOUT;
+
+            while let Some(timeout) =
+                deadline.checked_duration_since(Instant::now())
+            {
+                self.socket.set_read_timeout(Some(timeout))?;
+                let len = match self.socket.recv(&mut rx) {
+                    Ok(len) => len,
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            std::io::ErrorKind::WouldBlock
+                                | std::io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        break
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+
+                // Ignore anything that isn't a reply to this request (e.g. a
+                // late reply to an earlier attempt)
+                let (reply, data) =
+                    match hubpack::deserialize::<ResponseMessage>(&rx[..len]) {
+                        Ok(r) => r,
+                        Err(_) => continue,
+                    };
+                if reply.header.message_id != self.message_id {
+                    continue;
+                }
+
+                return match reply.response {
+                    Ok(r) => Ok((r, data.to_vec())),
+                    Err(e) => bail!("ringbuf agent returned {e:?}"),
+                };
+            }
+        }
+
+        bail!(
+            "no response from ringbuf agent at {} after {} attempts",
+            self.target,
+            Self::ATTEMPTS
+        )
+    }
+
+    fn image_id(&mut self) -> Result<[u8; 8]> {
+        match self.request(Request::GetImageId)? {
+            (Response::GetImageId(id), _) => Ok(id),
+            (r, _) => bail!("unexpected response {r:?}"),
+        }
+    }
+
+    fn read(&mut self, task: u16, address: u32, len: usize) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(len);
+
+        while out.len() < len {
+            let chunk = (len - out.len()).min(MAX_READ_SIZE);
+            let (r, data) = self.request(Request::ReadTaskMemory {
+                task_index: task,
+                address: address + out.len() as u32,
+                length: chunk as u16,
+            })?;
+            match r {
+                Response::ReadTaskMemory { length }
+                    if length as usize == chunk && data.len() >= chunk =>
+                {
+                    out.extend_from_slice(&data[..chunk]);
+                }
+                r => bail!("unexpected response {r:?}"),
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+/// Returns the address of the kernel's image ID, and its offset within the
+/// kernel's ELF
+pub(crate) fn image_id_location(kernel: &[u8]) -> Result<(u32, usize)> {
+    let elf = goblin::elf::Elf::parse(kernel)?;
+    let sym = elf
+        .syms
+        .iter()
+        .find(|s| elf.strtab.get_at(s.st_name) == Some("HUBRIS_IMAGE_ID"))
+        .ok_or_else(|| anyhow!("kernel is missing HUBRIS_IMAGE_ID"))?;
+    let offset = crate::elf::get_file_offset_by_vma(&elf, sym.st_value)?;
+    if kernel.len() < offset as usize + 8 {
+        bail!("HUBRIS_IMAGE_ID is out of range");
+    }
+    Ok((sym.st_value as u32, offset as usize))
+}
+
+/// Reads the image ID out of the kernel in the archive
+pub(crate) fn archive_image_id(kernel: &[u8]) -> Result<[u8; 8]> {
+    let (_, offset) = image_id_location(kernel)?;
+    Ok(kernel[offset..offset + 8].try_into().unwrap())
+}
+
+/// Writes out a decoded ring buffer of any flavor
+fn write_ringbuf(out: &mut impl Write, value: &Value) -> Result<()> {
+    // The static is a `StaticCell`, which wraps the ring buffer in an
+    // `UnsafeCell`.
+    let mut ringbuf = value.field("cell")?.field("value")?;
+
+    let flavor = match ringbuf.field("meta") {
+        Ok(meta) => match meta.field("flavor")? {
+            Value::Variant { name, .. } => Some(name.as_str()),
+            v => bail!("unknown ring buffer flavor {v}"),
+        },
+        Err(_) => None,
+    };
+
+    if flavor == Some("Counted") {
+        writeln!(out, "{:>24} {:>8}", "VARIANT", "COUNT")?;
+        for (name, count) in ringbuf.field("counts")?.fields() {
+            if count.unsigned()? != 0 {
+                writeln!(out, "{name:>24} {:>8}", count.unsigned()?)?;
+            }
+        }
+        ringbuf = ringbuf.field("ringbuf")?;
+    }
+
+    let last = match ringbuf.field("last")? {
+        Value::Variant { name, fields } if name == "Some" => {
+            Some(fields[0].1.unsigned()? as usize)
+        }
+        _ => None,
+    };
+    let entries = match ringbuf.field("buffer")? {
+        Value::Array(entries) => entries,
+        v => bail!("expected an array of entries, found {v}"),
+    };
+
+    let timestamped = flavor == Some("Timestamped");
+    if timestamped {
+        writeln!(
+            out,
+            "{:>4} {:>4} {:>8} {:>8} {:>12} PAYLOAD",
+            "NDX", "LINE", "GEN", "COUNT", "TIMESTAMP"
+        )?;
+    } else {
+        writeln!(
+            out,
+            "{:>4} {:>4} {:>8} {:>8} PAYLOAD",
+            "NDX", "LINE", "GEN", "COUNT"
+        )?;
+    }
+
+    let last = match last {
+        Some(last) => last,
+        None => return Ok(()),
+    };
+
+    // Oldest entries first, skipping any that have never been written
+    for i in 0..entries.len() {
+        let ndx = (last + 1 + i) % entries.len();
+        let entry = &entries[ndx];
+        let generation = entry.field("generation")?.unsigned()?;
+        if generation == 0 {
+            continue;
+        }
+
+        write!(
+            out,
+            "{ndx:>4} {:>4} {generation:>8} {:>8} ",
+            entry.field("line")?.unsigned()?,
+            entry.field("count")?.unsigned()?,
+        )?;
+        if timestamped {
+            write!(out, "{:>12} ", entry.field("timestamp")?.unsigned()?)?;
+        }
+        writeln!(out, "{}", entry.field("payload")?)?;
+    }
+
+    Ok(())
+}
+
+/// Lists the ring buffers in the archive's tasks or, given a `target`,
+/// retrieves them from the ringbuf agent there and prints them.  If any
+/// `filters` are given, only ring buffers whose task or name contains one of
+/// them are shown.
+pub fn run(
+    archive: &Path,
+    target: Option<SocketAddr>,
+    filters: &[String],
+) -> Result<()> {
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("could not open {}", archive.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("could not read {}", archive.display()))?;
+
+    let mut read_file = |name: &str| -> Result<Vec<u8>> {
+        let mut data = vec![];
+        archive
+            .by_name(name)
+            .with_context(|| format!("archive is missing {name}"))?
+            .read_to_end(&mut data)?;
+        Ok(data)
+    };
+
+    let config: ArchiveConfig = {
+        let app = String::from_utf8(read_file("app.toml")?)?;
+        toml::from_str(&app).context("could not parse app.toml")?
+    };
+
+    let mut agent = match target {
+        Some(target) => {
+            let mut agent = Agent::new(target)?;
+            let ours = archive_image_id(&read_file("elf/kernel")?)?;
+            let theirs = agent.image_id()?;
+            if ours != theirs {
+                bail!(
+                    "image ID mismatch: archive has {:#x}, target has {:#x}",
+                    u64::from_le_bytes(ours),
+                    u64::from_le_bytes(theirs),
+                );
+            }
+            Some(agent)
+        }
+        None => None,
+    };
+
+    let mut failed = 0;
+
+    for (index, task) in config.tasks.keys().enumerate() {
+        //
+        // The supervisor (always task 0) won't read its own memory on behalf
+        // of the ringbuf agent, so there's no point in asking.
+        //
+        if index == 0 && agent.is_some() {
+            println!("{task}: skipped (the supervisor can't be read remotely)");
+            println!();
+            continue;
+        }
+
+        let data = read_file(&format!("elf/task/{task}"))?;
+        let elf = goblin::elf::Elf::parse(&data)
+            .with_context(|| format!("could not parse ELF for {task}"))?;
+        let info = DebugInfo::new(&elf, &data)
+            .with_context(|| format!("could not load DWARF for {task}"))?;
+
+        for var in info.ringbufs()? {
+            if !filters.is_empty()
+                && !filters
+                    .iter()
+                    .any(|f| task.contains(f.as_str()) || var.name.contains(f))
+            {
+                continue;
+            }
+
+            let size = var.ty.size();
+            let agent = match agent.as_mut() {
+                Some(agent) => agent,
+                None => {
+                    println!(
+                        "{task:<20} {:#010x} {size:>6} {}",
+                        var.address, var.name
+                    );
+                    continue;
+                }
+            };
+
+            println!("{task}: {} ({:#x}, {size} bytes)", var.name, var.address);
+
+            // On failure, report it and carry on with the other ring buffers
+            let mem = match agent.read(index as u16, var.address, size as usize)
+            {
+                Ok(mem) => mem,
+                Err(e) => {
+                    println!("could not read {}: {e:#}", var.name);
+                    println!();
+                    failed += 1;
+                    continue;
+                }
+            };
+            let mut stdout = std::io::stdout();
+            if let Err(e) = var
+                .ty
+                .read(&mem)
+                .and_then(|value| write_ringbuf(&mut stdout, &value))
+            {
+                println!("could not decode {}: {e}", var.name);
+            }
+            println!();
+        }
+    }
+
+    if failed > 0 {
+        bail!("could not read {failed} ring buffer(s)");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::write::{
+        AttributeValue, DwarfUnit, EndianVec, Expression, Sections, UnitEntryId,
+    };
+
+    /// A member's name, type, and offset
+    type Fields<'a> = &'a [(&'a str, UnitEntryId, u64)];
+
+    /// Builds the DWARF for a task, laid out as rustc would for a 32-bit
+    /// target
+    struct Fixture {
+        dwarf: DwarfUnit,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let encoding = gimli::Encoding {
+                format: gimli::Format::Dwarf32,
+                version: 4,
+                address_size: 4,
+            };
+            Self {
+                dwarf: DwarfUnit::new(encoding),
+            }
+        }
+
+        fn add(
+            &mut self,
+            parent: Option<UnitEntryId>,
+            tag: gimli::DwTag,
+            attrs: Vec<(gimli::DwAt, AttributeValue)>,
+        ) -> UnitEntryId {
+            let parent = parent.unwrap_or_else(|| self.dwarf.unit.root());
+            let id = self.dwarf.unit.add(parent, tag);
+            let entry = self.dwarf.unit.get_mut(id);
+            for (name, value) in attrs {
+                entry.set(name, value);
+            }
+            id
+        }
+
+        fn base(
+            &mut self,
+            name: &str,
+            encoding: gimli::DwAte,
+            size: u64,
+        ) -> UnitEntryId {
+            self.add(
+                None,
+                gimli::DW_TAG_base_type,
+                vec![
+                    (gimli::DW_AT_name, name_attr(name)),
+                    (gimli::DW_AT_encoding, AttributeValue::Encoding(encoding)),
+                    (gimli::DW_AT_byte_size, AttributeValue::Udata(size)),
+                ],
+            )
+        }
+
+        fn structure(
+            &mut self,
+            parent: Option<UnitEntryId>,
+            name: &str,
+            size: u64,
+            members: Fields,
+        ) -> UnitEntryId {
+            let id = self.add(
+                parent,
+                gimli::DW_TAG_structure_type,
+                vec![
+                    (gimli::DW_AT_name, name_attr(name)),
+                    (gimli::DW_AT_byte_size, AttributeValue::Udata(size)),
+                ],
+            );
+            for &(name, ty, offset) in members {
+                self.member(id, name, ty, offset);
+            }
+            id
+        }
+
+        fn member(
+            &mut self,
+            parent: UnitEntryId,
+            name: &str,
+            ty: UnitEntryId,
+            offset: u64,
+        ) -> UnitEntryId {
+            self.add(
+                Some(parent),
+                gimli::DW_TAG_member,
+                vec![
+                    (gimli::DW_AT_name, name_attr(name)),
+                    (gimli::DW_AT_type, AttributeValue::UnitRef(ty)),
+                    (
+                        gimli::DW_AT_data_member_location,
+                        AttributeValue::Udata(offset),
+                    ),
+                ],
+            )
+        }
+
+        /// Adds a Rust enum with fields, given its discriminant type and
+        /// offset, and each variant's discriminant value and fields
+        fn rust_enum(
+            &mut self,
+            name: &str,
+            size: u64,
+            discr: (UnitEntryId, u64),
+            variants: &[(&str, u64, Fields)],
+        ) -> UnitEntryId {
+            let id = self.structure(None, name, size, &[]);
+            let part = self.add(Some(id), gimli::DW_TAG_variant_part, vec![]);
+            let discr = self.member(part, "", discr.0, discr.1);
+            self.dwarf
+                .unit
+                .get_mut(part)
+                .set(gimli::DW_AT_discr, AttributeValue::UnitRef(discr));
+
+            for &(variant, value, fields) in variants {
+                let ty = self.structure(Some(id), variant, size, fields);
+                let v = self.add(
+                    Some(part),
+                    gimli::DW_TAG_variant,
+                    vec![(
+                        gimli::DW_AT_discr_value,
+                        AttributeValue::Udata(value),
+                    )],
+                );
+                self.member(v, variant, ty, 0);
+            }
+            id
+        }
+
+        fn array(&mut self, elem: UnitEntryId, count: u64) -> UnitEntryId {
+            let id = self.add(
+                None,
+                gimli::DW_TAG_array_type,
+                vec![(gimli::DW_AT_type, AttributeValue::UnitRef(elem))],
+            );
+            self.add(
+                Some(id),
+                gimli::DW_TAG_subrange_type,
+                vec![(gimli::DW_AT_count, AttributeValue::Udata(count))],
+            );
+            id
+        }
+
+        fn variable(
+            &mut self,
+            parent: Option<UnitEntryId>,
+            name: &str,
+            ty: UnitEntryId,
+            address: u64,
+        ) {
+            let mut location = Expression::new();
+            location.op_addr(gimli::write::Address::Constant(address));
+            self.add(
+                parent,
+                gimli::DW_TAG_variable,
+                vec![
+                    (gimli::DW_AT_name, name_attr(name)),
+                    (gimli::DW_AT_type, AttributeValue::UnitRef(ty)),
+                    (gimli::DW_AT_location, AttributeValue::Exprloc(location)),
+                ],
+            );
+        }
+
+        /// Adds the types that every ring buffer uses, returning the
+        /// `RingbufEntry<Trace>` and `Option<usize>` types.  `Trace` is
+        /// `enum Trace { None, Retry(u8) }`.
+        fn ringbuf_types(&mut self) -> (UnitEntryId, UnitEntryId) {
+            let u8_ = self.base("u8", gimli::DW_ATE_unsigned, 1);
+            let u16_ = self.base("u16", gimli::DW_ATE_unsigned, 2);
+            let u32_ = self.base("u32", gimli::DW_ATE_unsigned, 4);
+            let usize_ = self.base("usize", gimli::DW_ATE_unsigned, 4);
+
+            let trace = self.rust_enum(
+                "Trace",
+                2,
+                (u8_, 0),
+                &[("None", 0, &[]), ("Retry", 1, &[("__0", u8_, 1)])],
+            );
+            let entry = self.structure(
+                None,
+                "RingbufEntry<task_foo::Trace>",
+                12,
+                &[
+                    ("line", u16_, 0),
+                    ("generation", u16_, 2),
+                    ("count", u32_, 4),
+                    ("payload", trace, 8),
+                ],
+            );
+            let option = self.rust_enum(
+                "Option<usize>",
+                8,
+                (u32_, 0),
+                &[("None", 0, &[]), ("Some", 1, &[("__0", usize_, 4)])],
+            );
+            (entry, option)
+        }
+
+        /// Wraps a ring buffer type in a `StaticCell`
+        fn static_cell(&mut self, ty: UnitEntryId, size: u64) -> UnitEntryId {
+            let bool_ = self.base("bool", gimli::DW_ATE_boolean, 1);
+            let cell =
+                self.structure(None, "UnsafeCell", size, &[("value", ty, 0)]);
+            self.structure(
+                None,
+                "StaticCell",
+                size + 4,
+                &[("cell", cell, 0), ("borrowed", bool_, size)],
+            )
+        }
+
+        fn sections(mut self) -> HashMap<&'static str, Vec<u8>> {
+            let mut sections =
+                Sections::new(EndianVec::new(gimli::LittleEndian));
+            self.dwarf.write(&mut sections).unwrap();
+
+            let mut out = HashMap::new();
+            sections
+                .for_each(|id, data| -> Result<()> {
+                    out.insert(id.name(), data.slice().to_vec());
+                    Ok(())
+                })
+                .unwrap();
+            out
+        }
+    }
+
+    fn name_attr(name: &str) -> AttributeValue {
+        AttributeValue::String(name.as_bytes().to_vec())
+    }
+
+    fn load<'a>(
+        sections: &'a HashMap<&'static str, Vec<u8>>,
+    ) -> Result<DebugInfo<'a>> {
+        DebugInfo::load(gimli::RunTimeEndian::Little, |name| {
+            Ok(sections.get(name).map(Vec::as_slice).unwrap_or(&[]))
+        })
+    }
+
+    /// Memory for a `Ringbuf<Trace, 2>`, whose last entry is at index 1
+    /// after it has wrapped
+    fn ringbuf_memory() -> Vec<u8> {
+        let mut mem = vec![];
+        // last: Some(1)
+        mem.extend(1u32.to_le_bytes());
+        mem.extend(1u32.to_le_bytes());
+        // buffer[0]: line 42, generation 2, count 1, Trace::None
+        mem.extend(42u16.to_le_bytes());
+        mem.extend(2u16.to_le_bytes());
+        mem.extend(1u32.to_le_bytes());
+        mem.extend([0, 0, 0, 0]);
+        // buffer[1]: line 57, generation 1, count 3, Trace::Retry(7)
+        mem.extend(57u16.to_le_bytes());
+        mem.extend(1u16.to_le_bytes());
+        mem.extend(3u32.to_le_bytes());
+        mem.extend([1, 7, 0, 0]);
+        mem
+    }
+
+    fn output(value: &Value) -> String {
+        let mut out = vec![];
+        write_ringbuf(&mut out, value).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn finds_ringbufs() {
+        let mut f = Fixture::new();
+        let (entry, option) = f.ringbuf_types();
+        let buffer = f.array(entry, 2);
+        let ringbuf = f.structure(
+            None,
+            "Ringbuf<task_foo::Trace, 2>",
+            32,
+            &[("last", option, 0), ("buffer", buffer, 8)],
+        );
+        let ty = f.static_cell(ringbuf, 32);
+
+        let root = f.dwarf.unit.root();
+        let ns = f.add(
+            Some(root),
+            gimli::DW_TAG_namespace,
+            vec![(gimli::DW_AT_name, name_attr("task_foo"))],
+        );
+        let inner = f.add(
+            Some(ns),
+            gimli::DW_TAG_namespace,
+            vec![(gimli::DW_AT_name, name_attr("bus"))],
+        );
+        f.variable(Some(inner), "BUS_RINGBUF", ty, 0x2400_0100);
+        f.variable(Some(ns), "__RINGBUF", ty, 0x2400_0000);
+        f.variable(Some(ns), "NOT_A_RING", ty, 0x2400_0200);
+
+        let sections = f.sections();
+        let info = load(&sections).unwrap();
+        let found = info.ringbufs().unwrap();
+
+        let found: Vec<_> = found
+            .iter()
+            .map(|v| (v.name.as_str(), v.address, v.ty.size()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("task_foo::__RINGBUF", 0x2400_0000, 36),
+                ("task_foo::bus::BUS_RINGBUF", 0x2400_0100, 36),
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_ringbuf() {
+        let mut f = Fixture::new();
+        let (entry, option) = f.ringbuf_types();
+        let buffer = f.array(entry, 2);
+        let ringbuf = f.structure(
+            None,
+            "Ringbuf<task_foo::Trace, 2>",
+            32,
+            &[("last", option, 0), ("buffer", buffer, 8)],
+        );
+        let ty = f.static_cell(ringbuf, 32);
+        f.variable(None, "__RINGBUF", ty, 0x2400_0000);
+
+        let sections = f.sections();
+        let info = load(&sections).unwrap();
+        let var = &info.ringbufs().unwrap()[0];
+
+        let mut mem = ringbuf_memory();
+        mem.extend([0, 0, 0, 0]);
+        let value = var.ty.read(&mem).unwrap();
+
+        let ringbuf = value.field("cell").unwrap().field("value").unwrap();
+        assert_eq!(
+            ringbuf.field("last").unwrap(),
+            &Value::Variant {
+                name: "Some".into(),
+                fields: vec![("__0".into(), Value::Unsigned(1))],
+            }
+        );
+
+        // Oldest first
+        assert_eq!(
+            output(&value),
+            " NDX LINE      GEN    COUNT PAYLOAD\n\
+             \x20  0   42        2        1 None\n\
+             \x20  1   57        1        3 Retry(7)\n"
+        );
+    }
+
+    #[test]
+    fn decodes_counted_ringbuf() {
+        let mut f = Fixture::new();
+        let (entry, option) = f.ringbuf_types();
+        let u8_ = f.base("u8", gimli::DW_ATE_unsigned, 1);
+        let u32_ = f.base("u32", gimli::DW_ATE_unsigned, 4);
+
+        let flavor = f.add(
+            None,
+            gimli::DW_TAG_enumeration_type,
+            vec![
+                (gimli::DW_AT_name, name_attr("RingbufFlavor")),
+                (gimli::DW_AT_byte_size, AttributeValue::Udata(1)),
+            ],
+        );
+        for (name, value) in [("Timestamped", 1), ("Counted", 2)] {
+            f.add(
+                Some(flavor),
+                gimli::DW_TAG_enumerator,
+                vec![
+                    (gimli::DW_AT_name, name_attr(name)),
+                    (gimli::DW_AT_const_value, AttributeValue::Udata(value)),
+                ],
+            );
+        }
+        let meta = f.structure(
+            None,
+            "RingbufMeta",
+            2,
+            &[("flavor", flavor, 0), ("version", u8_, 1)],
+        );
+        let counts = f.structure(
+            None,
+            "TraceCounts",
+            8,
+            &[("None", u32_, 0), ("Retry", u32_, 4)],
+        );
+        let buffer = f.array(entry, 2);
+        let ringbuf = f.structure(
+            None,
+            "Ringbuf<task_foo::Trace, 2>",
+            32,
+            &[("last", option, 0), ("buffer", buffer, 8)],
+        );
+        let counted = f.structure(
+            None,
+            "CountedRingbuf<task_foo::Trace, 2>",
+            44,
+            &[
+                ("meta", meta, 0),
+                ("counts", counts, 4),
+                ("ringbuf", ringbuf, 12),
+            ],
+        );
+        let ty = f.static_cell(counted, 44);
+        f.variable(None, "__RINGBUF", ty, 0x2400_0000);
+
+        let sections = f.sections();
+        let info = load(&sections).unwrap();
+        let var = &info.ringbufs().unwrap()[0];
+
+        let mut mem = vec![2, 1, 0, 0];
+        mem.extend(0u32.to_le_bytes());
+        mem.extend(1234u32.to_le_bytes());
+        mem.extend(ringbuf_memory());
+        mem.extend([0, 0, 0, 0]);
+        let value = var.ty.read(&mem).unwrap();
+
+        // Variants that were never recorded are left out of the counts
+        assert_eq!(
+            output(&value),
+            "                 VARIANT    COUNT\n\
+             \x20                  Retry     1234\n\
+             \x20NDX LINE      GEN    COUNT PAYLOAD\n\
+             \x20  0   42        2        1 None\n\
+             \x20  1   57        1        3 Retry(7)\n"
+        );
+    }
+
+    #[test]
+    fn decodes_base_types() {
+        let base = |encoding, size| Type::Base {
+            name: String::new(),
+            encoding,
+            size,
+        };
+
+        let i16_ = base(gimli::DW_ATE_signed, 2);
+        assert_eq!(i16_.read(&[0xfe, 0xff]).unwrap(), Value::Signed(-2));
+
+        let bool_ = base(gimli::DW_ATE_boolean, 1);
+        assert_eq!(bool_.read(&[1]).unwrap(), Value::Bool(true));
+
+        let f32_ = base(gimli::DW_ATE_float, 4);
+        assert_eq!(
+            f32_.read(&1.5f32.to_le_bytes()).unwrap(),
+            Value::Float(1.5)
+        );
+
+        let char_ = base(gimli::DW_ATE_UTF, 4);
+        assert_eq!(
+            char_.read(&u32::from('λ').to_le_bytes()).unwrap(),
+            Value::Char('λ')
+        );
+
+        // An enumerator that doesn't match is shown as a number
+        let e = Type::Enum {
+            size: 1,
+            enumerators: vec![(1, "One".into())],
+        };
+        assert_eq!(
+            e.read(&[1]).unwrap(),
+            Value::Variant {
+                name: "One".into(),
+                fields: vec![]
+            }
+        );
+        assert_eq!(e.read(&[5]).unwrap(), Value::Unsigned(5));
+
+        // Too little memory is an error rather than a panic
+        assert!(i16_.read(&[0]).is_err());
+    }
+}
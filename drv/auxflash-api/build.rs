@@ -29,6 +29,10 @@ struct GlobalConfig {
 struct AuxFlashConfig {
     memory_size: u32,
     slot_count: u32,
+    /// Slot reserved for dumps persisted by `dump-agent`.  The even/odd pair
+    /// containing this slot is never used for auxiliary flash images.
+    #[serde(default)]
+    dump_slot: Option<u32>,
 }
 
 fn generate_auxflash_config(
@@ -56,8 +60,26 @@ fn generate_auxflash_config(
         "auxflash slots must be page aligned"
     );
 
+    // d. If a slot is reserved for dumps, it exists and still leaves room
+    //    for the active and spare image pairs
+    if let Some(slot) = config.dump_slot {
+        assert!(
+            slot < config.slot_count,
+            "auxflash dump slot must be less than the slot count"
+        );
+        assert!(
+            config.slot_count >= 8,
+            "auxflash requires at least 8 slots to reserve a dump slot"
+        );
+    }
+
     writeln!(out, "pub const MEMORY_SIZE: u32 = {};", config.memory_size)?;
     writeln!(out, "pub const SLOT_COUNT: u32 = {};", config.slot_count)?;
+    writeln!(
+        out,
+        "pub const DUMP_SLOT: Option<u32> = {:?};",
+        config.dump_slot
+    )?;
 
     Ok(())
 }
@@ -185,5 +185,14 @@ mod config {
     include!(concat!(env!("OUT_DIR"), "/auxflash_config.rs"));
 }
 
-pub use self::config::SLOT_COUNT;
+pub use self::config::{DUMP_SLOT, SLOT_COUNT};
 pub const SLOT_SIZE: usize = (self::config::MEMORY_SIZE / SLOT_COUNT) as usize;
+
+/// Returns `true` if the given slot is in the even/odd pair that contains
+/// [`DUMP_SLOT`], and therefore must not be used for auxiliary flash images.
+pub const fn is_dump_slot(slot: u32) -> bool {
+    match DUMP_SLOT {
+        Some(dump) => dump / 2 == slot / 2,
+        None => false,
+    }
+}
@@ -8,6 +8,7 @@
 
 use derive_idol_err::IdolError;
 use userlib::*;
+use zerocopy::{AsBytes, FromBytes};
 
 // Re-export PowerState for client convenience.
 pub use drv_gimlet_state::PowerState;
@@ -23,6 +24,7 @@ pub enum SeqError {
     A1Timeout,
     A0TimeoutGroupC,
     A0Timeout,
+    NoSuchEvent,
 
     #[idol(server_death)]
     ServerRestarted,
@@ -33,4 +35,70 @@ pub enum SeqError {
 // packrat, all of which want to know at compile-time how many banks there are.
 pub const NUM_SPD_BANKS: usize = 2;
 
+/// Number of events retained by the sequencer's event log; older events are
+/// overwritten.
+pub const SEQ_EVENT_LOG_SIZE: usize = 32;
+
+/// What happened, in a [`SeqEvent`].
+#[derive(Copy, Clone, Debug, FromPrimitive, Eq, PartialEq)]
+#[repr(u8)]
+pub enum SeqEventKind {
+    /// The power state changed from `from` to `to`.
+    Transition = 1,
+    /// An attempt to sequence from `from` toward `to` failed; `detail` is the
+    /// `SeqError` and `regs` is indexed by [`a0_failure_regs`].
+    A0Failure,
+    /// The FPGA reported a THERMTRIP while in `from`.
+    Thermtrip,
+    /// The host reset itself while in `from`; `regs` holds the RESET_L and
+    /// PWROK falling edge counts, in that order.
+    HostReset,
+}
+
+/// Indices into [`SeqEvent::regs`] for an [`SeqEventKind::A0Failure`]: a
+/// snapshot of the sequencer FPGA's state when we gave up.
+pub mod a0_failure_regs {
+    pub const IFR: usize = 0;
+    pub const DBG_MAX_A0SMSTATUS: usize = 1;
+    pub const MAX_GROUPB_PG: usize = 2;
+    pub const MAX_GROUPC_PG: usize = 3;
+    pub const FLT_A0_SMSTATUS: usize = 4;
+    pub const FLT_GROUPB_PG: usize = 5;
+    pub const FLT_GROUPC_PG: usize = 6;
+}
+
+/// A single entry in the sequencer's event log.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, FromBytes, AsBytes)]
+#[repr(C)]
+pub struct SeqEvent {
+    /// Time of the event, in milliseconds since the SP booted.
+    pub timestamp: u64,
+    /// Position of this event in the log, counting from 0 at boot.
+    pub index: u32,
+    /// A `SeqEventKind`.
+    pub kind: u8,
+    /// The `PowerState` we were in.
+    pub from: u8,
+    /// The `PowerState` we moved to (or were trying to move to).
+    pub to: u8,
+    /// Kind-specific detail; see `SeqEventKind`.
+    pub detail: u8,
+    /// Kind-specific register snapshot; see `SeqEventKind`.
+    pub regs: [u8; 8],
+}
+
+impl SeqEvent {
+    pub fn kind(&self) -> Option<SeqEventKind> {
+        SeqEventKind::from_u8(self.kind)
+    }
+
+    pub fn from_state(&self) -> Option<PowerState> {
+        PowerState::from_u8(self.from)
+    }
+
+    pub fn to_state(&self) -> Option<PowerState> {
+        PowerState::from_u8(self.to)
+    }
+}
+
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
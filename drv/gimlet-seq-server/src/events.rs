@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! A log of notable sequencing events, kept so that they can be retrieved
+//! after the fact (unlike our ringbuf, which is quickly overwritten by
+//! routine status polling).
+
+use drv_gimlet_seq_api::{
+    PowerState, SeqEvent, SeqEventKind, SEQ_EVENT_LOG_SIZE,
+};
+use userlib::sys_get_timer;
+
+pub(crate) struct EventLog {
+    events: [SeqEvent; SEQ_EVENT_LOG_SIZE],
+    /// Total number of events ever recorded.
+    count: u32,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            events: [SeqEvent::default(); SEQ_EVENT_LOG_SIZE],
+            count: 0,
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        kind: SeqEventKind,
+        from: PowerState,
+        to: PowerState,
+        detail: u8,
+        regs: [u8; 8],
+    ) {
+        let index = self.count;
+        self.events[index as usize % SEQ_EVENT_LOG_SIZE] = SeqEvent {
+            timestamp: sys_get_timer().now,
+            index,
+            kind: kind as u8,
+            from: from as u8,
+            to: to as u8,
+            detail,
+            regs,
+        };
+        self.count = self.count.wrapping_add(1);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Returns the event at `index`, if it has been recorded and not yet
+    /// overwritten.
+    pub fn get(&self, index: u32) -> Option<SeqEvent> {
+        if index >= self.count || self.count - index > SEQ_EVENT_LOG_SIZE as u32
+        {
+            return None;
+        }
+        Some(self.events[index as usize % SEQ_EVENT_LOG_SIZE])
+    }
+}
@@ -7,13 +7,14 @@
 #![no_std]
 #![no_main]
 
+mod events;
 mod seq_spi;
 
 use ringbuf::*;
 use userlib::*;
 
 use drv_gimlet_hf_api as hf_api;
-use drv_gimlet_seq_api::{PowerState, SeqError};
+use drv_gimlet_seq_api::{PowerState, SeqError, SeqEvent, SeqEventKind};
 use drv_ice40_spi_program as ice40;
 use drv_packrat_vpd_loader::{read_vpd_and_load_packrat, Packrat};
 use drv_spi_api::{SpiDevice, SpiServer};
@@ -382,6 +383,7 @@ fn main() -> ! {
         jefe,
         hf,
         deadline: 0,
+        events: events::EventLog::new(),
     };
 
     // Power on, unless suppressed by the `stay-in-a2` feature
@@ -528,6 +530,7 @@ struct ServerImpl<S: SpiServer> {
     jefe: Jefe,
     hf: hf_api::HostFlash,
     deadline: u64,
+    events: events::EventLog,
 }
 
 const TIMER_INTERVAL: u64 = 10;
@@ -616,6 +619,13 @@ impl<S: SpiServer> NotificationHandler for ServerImpl<S> {
 impl<S: SpiServer> ServerImpl<S> {
     fn update_state_internal(&mut self, state: PowerState) {
         ringbuf_entry!(Trace::UpdateState(state));
+        self.events.record(
+            SeqEventKind::Transition,
+            self.state,
+            state,
+            0,
+            [0; 8],
+        );
         self.state = state;
         self.jefe.set_state(state as u32);
     }
@@ -848,25 +858,34 @@ impl<S: SpiServer> ServerImpl<S> {
     }
 
     fn a0_failure(&mut self, err: SeqError) -> SeqError {
-        let record_reg = |addr| {
-            ringbuf_entry!(Trace::A0FailureDetails(
-                addr,
-                self.seq.read_byte(addr).unwrap(),
-            ));
+        use drv_gimlet_seq_api::a0_failure_regs as r;
+
+        let mut regs = [0u8; 8];
+        let mut record_reg = |i, addr| {
+            let val = self.seq.read_byte(addr).unwrap();
+            ringbuf_entry!(Trace::A0FailureDetails(addr, val));
+            regs[i] = val;
         };
 
         //
         // We are not going to space today.  Record information in our ring
-        // buffer to allow this to be debugged.
+        // buffer and event log to allow this to be debugged.
         //
         ringbuf_entry!(Trace::A0Failed(err));
-        record_reg(Addr::IFR);
-        record_reg(Addr::DBG_MAX_A0SMSTATUS);
-        record_reg(Addr::MAX_GROUPB_PG);
-        record_reg(Addr::MAX_GROUPC_PG);
-        record_reg(Addr::FLT_A0_SMSTATUS);
-        record_reg(Addr::FLT_GROUPB_PG);
-        record_reg(Addr::FLT_GROUPC_PG);
+        record_reg(r::IFR, Addr::IFR);
+        record_reg(r::DBG_MAX_A0SMSTATUS, Addr::DBG_MAX_A0SMSTATUS);
+        record_reg(r::MAX_GROUPB_PG, Addr::MAX_GROUPB_PG);
+        record_reg(r::MAX_GROUPC_PG, Addr::MAX_GROUPC_PG);
+        record_reg(r::FLT_A0_SMSTATUS, Addr::FLT_A0_SMSTATUS);
+        record_reg(r::FLT_GROUPB_PG, Addr::FLT_GROUPB_PG);
+        record_reg(r::FLT_GROUPC_PG, Addr::FLT_GROUPC_PG);
+        self.events.record(
+            SeqEventKind::A0Failure,
+            self.state,
+            PowerState::A0,
+            err as u8,
+            regs,
+        );
 
         //
         // Now put ourselves back in A2.
@@ -892,6 +911,13 @@ impl<S: SpiServer> ServerImpl<S> {
 
         if ifr & thermtrip != 0 {
             self.seq.clear_bytes(Addr::IFR, &[thermtrip]).unwrap();
+            self.events.record(
+                SeqEventKind::Thermtrip,
+                self.state,
+                PowerState::A0Thermtrip,
+                0,
+                [0; 8],
+            );
             self.update_state_internal(PowerState::A0Thermtrip);
         }
     }
@@ -915,6 +941,13 @@ impl<S: SpiServer> ServerImpl<S> {
 
             let (rstn, pwrokn) = (cnts[0], cnts[1]);
             ringbuf_entry!(Trace::ResetCounts { rstn, pwrokn });
+            self.events.record(
+                SeqEventKind::HostReset,
+                self.state,
+                PowerState::A0Reset,
+                0,
+                [rstn, pwrokn, 0, 0, 0, 0, 0, 0],
+            );
 
             //
             // Clear the counts to denote that we wish to re-latch any
@@ -1007,6 +1040,24 @@ impl<S: SpiServer> idl::InOrderSequencerImpl for ServerImpl<S> {
 
         Ok(buf)
     }
+
+    fn event_count(
+        &mut self,
+        _: &RecvMessage,
+    ) -> Result<u32, RequestError<core::convert::Infallible>> {
+        Ok(self.events.count())
+    }
+
+    fn read_event(
+        &mut self,
+        _: &RecvMessage,
+        index: u32,
+    ) -> Result<SeqEvent, RequestError<SeqError>> {
+        self.events
+            .get(index)
+            .ok_or(SeqError::NoSuchEvent)
+            .map_err(RequestError::from)
+    }
 }
 
 fn reprogram_fpga<S: SpiServer>(
@@ -1197,7 +1248,7 @@ cfg_if::cfg_if! {
 }
 
 mod idl {
-    use super::SeqError;
+    use super::{SeqError, SeqEvent};
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
@@ -15,10 +15,15 @@
 //!
 //! - The I2C controller in the MCU
 //! - The port for that controller, identifying a bus
-//! - The multiplexer on the specified I2C bus, if any
+//! - The multiplexer on the specified I2C bus that the device is directly
+//!   behind, if any (which may itself be behind other multiplexers)
 //! - The segment on the multiplexer, if a multiplexer is specified
 //! - The address of the device itself
 //!
+//! Devices may additionally use SMBus Packet Error Checking (PEC), in which
+//! case every transaction with the device is checked by the server; see
+//! [`I2cDevice::with_pec`].
+//!
 
 #![no_std]
 
@@ -38,6 +43,7 @@ pub struct I2cDevice {
     pub port: PortIndex,
     pub segment: Option<(Mux, Segment)>,
     pub address: u8,
+    pub pec: bool,
 }
 
 type I2cMessage = (u8, Controller, PortIndex, Option<(Mux, Segment)>);
@@ -120,11 +126,32 @@ impl I2cDevice {
             port,
             segment,
             address,
+            pec: false,
         }
     }
+
+    ///
+    /// Returns this device with SMBus Packet Error Checking enabled:  the
+    /// server will append a PEC byte to writes, and read and check the PEC
+    /// byte at the end of reads, failing with [`ResponseCode::PecMismatch`]
+    /// if it does not match.  This should only be used with devices that
+    /// support PEC, as other devices will not send (or expect) the PEC byte.
+    ///
+    pub fn with_pec(self) -> Self {
+        Self { pec: true, ..self }
+    }
 }
 
 impl I2cDevice {
+    fn op(&self, block: bool) -> Op {
+        match (block, self.pec) {
+            (false, false) => Op::WriteRead,
+            (false, true) => Op::WriteReadPec,
+            (true, false) => Op::WriteReadBlock,
+            (true, true) => Op::WriteReadBlockPec,
+        }
+    }
+
     fn response_code<V>(&self, code: u32, val: V) -> Result<V, ResponseCode> {
         if code != 0 {
             if let Some(_g) = userlib::extract_new_generation(code) {
@@ -164,7 +191,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteRead as u16,
+            self.op(false) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -192,7 +219,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteRead as u16,
+            self.op(false) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -222,7 +249,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteReadBlock as u16,
+            self.op(true) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -249,7 +276,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteRead as u16,
+            self.op(false) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -273,7 +300,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteRead as u16,
+            self.op(false) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -296,7 +323,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteRead as u16,
+            self.op(false) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -328,7 +355,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteRead as u16,
+            self.op(false) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -370,7 +397,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteReadBlock as u16,
+            self.op(true) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -405,7 +432,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteRead as u16,
+            self.op(false) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
@@ -443,7 +470,7 @@ impl I2cDevice {
 
         let (code, _) = sys_send(
             self.task,
-            Op::WriteRead as u16,
+            self.op(false) as u16,
             &Marshal::marshal(&(
                 self.address,
                 self.controller,
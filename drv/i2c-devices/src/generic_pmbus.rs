@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Generic PMBus device driver
+//!
+//! Many PMBus regulators can be monitored with nothing but standard commands;
+//! what varies from part to part is which of those commands are supported,
+//! how their data is encoded, and which page each rail lives on.  Rather than
+//! writing a module for each such part, it can be described with a `pmbus`
+//! table on its device entry in the app TOML, which `build/i2c` turns into a
+//! [`CommandTable`] for this driver.
+//!
+//! Parts with vendor-specific features (blackboxes, event logs, per-phase
+//! current, etc.) should still get their own module.
+
+use core::cell::Cell;
+
+use crate::{
+    CurrentSensor, InputCurrentSensor, InputVoltageSensor, PowerSensor,
+    TempSensor, VoltageSensor,
+};
+use drv_i2c_api::*;
+use pmbus::commands::CommandCode;
+use userlib::units::*;
+
+pub use pmbus_format::Format;
+
+/// Description of a single rail of a generic PMBus device
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CommandTable {
+    /// PMBus page for this rail, or `None` if the device doesn't use PAGE
+    pub page: Option<u8>,
+    /// Value of VOUT_MODE, if fixed by configuration rather than read from
+    /// the device
+    pub vout_mode: Option<u8>,
+
+    pub read_vin: Option<Format>,
+    pub read_iin: Option<Format>,
+    pub read_vout: Option<Format>,
+    pub read_iout: Option<Format>,
+    pub read_temperature_1: Option<Format>,
+    pub read_pout: Option<Format>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    BadRead {
+        cmd: u8,
+        code: ResponseCode,
+    },
+    /// The command isn't in this device's command table
+    NotSupported {
+        cmd: u8,
+    },
+    /// VOUT_MODE isn't in linear mode, but the command table says that
+    /// READ_VOUT uses LINEAR16
+    BadVoutMode {
+        mode: u8,
+    },
+}
+
+impl From<Error> for ResponseCode {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::BadRead { code, .. } => code,
+            Error::NotSupported { .. } => ResponseCode::OperationNotSupported,
+            Error::BadVoutMode { .. } => ResponseCode::BadDeviceState,
+        }
+    }
+}
+
+pub struct GenericPmbus {
+    device: I2cDevice,
+    table: &'static CommandTable,
+    /// Our (cached) VOUT_MODE
+    mode: Cell<Option<u8>>,
+}
+
+impl core::fmt::Display for GenericPmbus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "pmbus: {}", &self.device)
+    }
+}
+
+impl GenericPmbus {
+    pub fn new(device: &I2cDevice, table: &'static CommandTable) -> Self {
+        Self {
+            device: *device,
+            table,
+            mode: Cell::new(table.vout_mode),
+        }
+    }
+
+    pub fn i2c_device(&self) -> &I2cDevice {
+        &self.device
+    }
+
+    pub fn read_mode(&self) -> Result<pmbus::VOutModeCommandData, Error> {
+        let mode = match self.mode.get() {
+            Some(mode) => mode,
+            None => {
+                let mode = self.read::<u8>(CommandCode::VOUT_MODE)?;
+                self.mode.set(Some(mode));
+                mode
+            }
+        };
+        Ok(pmbus::VOutModeCommandData(mode))
+    }
+
+    /// Reads a command on this rail's page
+    fn read<T>(&self, cmd: CommandCode) -> Result<T, Error>
+    where
+        T: zerocopy::AsBytes + zerocopy::FromBytes,
+    {
+        let cmd = cmd as u8;
+        match self.table.page {
+            Some(page) => self
+                .device
+                .write_read_reg(cmd, &[CommandCode::PAGE as u8, page]),
+            None => self.device.read_reg(cmd),
+        }
+        .map_err(|code| Error::BadRead { cmd, code })
+    }
+
+    /// Reads and decodes a command, if the command table says that the device
+    /// supports it
+    fn read_value(
+        &self,
+        cmd: CommandCode,
+        format: Option<Format>,
+    ) -> Result<f32, Error> {
+        let format = format.ok_or(Error::NotSupported { cmd: cmd as u8 })?;
+        let raw = self.read::<u16>(cmd)?;
+
+        Ok(match format {
+            Format::Linear11 => pmbus_format::linear11(raw),
+            Format::Linear16 => {
+                let mode = self.read_mode()?.0;
+                pmbus_format::linear16(raw, mode)
+                    .ok_or(Error::BadVoutMode { mode })?
+            }
+            Format::Direct { m, b, r } => pmbus_format::direct(raw, m, b, r),
+        })
+    }
+}
+
+impl TempSensor<Error> for GenericPmbus {
+    fn read_temperature(&self) -> Result<Celsius, Error> {
+        let t = self.read_value(
+            CommandCode::READ_TEMPERATURE_1,
+            self.table.read_temperature_1,
+        )?;
+        Ok(Celsius(t))
+    }
+}
+
+impl CurrentSensor<Error> for GenericPmbus {
+    fn read_iout(&self) -> Result<Amperes, Error> {
+        let iout =
+            self.read_value(CommandCode::READ_IOUT, self.table.read_iout)?;
+        Ok(Amperes(iout))
+    }
+}
+
+impl VoltageSensor<Error> for GenericPmbus {
+    fn read_vout(&self) -> Result<Volts, Error> {
+        let vout =
+            self.read_value(CommandCode::READ_VOUT, self.table.read_vout)?;
+        Ok(Volts(vout))
+    }
+}
+
+impl InputCurrentSensor<Error> for GenericPmbus {
+    fn read_iin(&self) -> Result<Amperes, Error> {
+        let iin =
+            self.read_value(CommandCode::READ_IIN, self.table.read_iin)?;
+        Ok(Amperes(iin))
+    }
+}
+
+impl InputVoltageSensor<Error> for GenericPmbus {
+    fn read_vin(&self) -> Result<Volts, Error> {
+        let vin =
+            self.read_value(CommandCode::READ_VIN, self.table.read_vin)?;
+        Ok(Volts(vin))
+    }
+}
+
+impl PowerSensor<Error> for GenericPmbus {
+    fn read_power(&mut self) -> Result<Watts, Error> {
+        let pout =
+            self.read_value(CommandCode::READ_POUT, self.table.read_pout)?;
+        Ok(Watts(pout))
+    }
+}
@@ -10,6 +10,8 @@
 //! - [`adt7420`]: ADT7420 temperature sensor
 //! - [`at24csw080`]: AT24CSW080 serial EEPROM
 //! - [`ds2482`]: DS2482-100 1-wire initiator
+//! - [`generic_pmbus`]: PMBus devices described by a command table in the
+//!   app TOML, rather than by a dedicated driver
 //! - [`isl68224`]: ISL68224 power controller
 //! - [`ltc4282`]: LTC4282 high current hot swap controller
 //! - [`m24c02`]: M24C02 EEPROM, used in MWOCP68 power shelf
@@ -222,6 +224,7 @@ pub mod adt7420;
 pub mod at24csw080;
 pub mod bmr491;
 pub mod ds2482;
+pub mod generic_pmbus;
 pub mod isl68224;
 pub mod ltc4282;
 pub mod m24c02;
@@ -36,6 +36,26 @@ pub enum Op {
     /// without interruption, this logic would not work, but that would be a
     /// very strange device indeed.
     WriteReadBlock = 2,
+
+    /// A `WriteRead` operation with SMBus Packet Error Checking: each
+    /// write/read pair is a distinct SMBus transaction, with a PEC byte
+    /// appended to a lone write or checked at the end of a read.
+    WriteReadPec = 3,
+
+    /// A `WriteReadBlock` operation with SMBus Packet Error Checking
+    WriteReadBlockPec = 4,
+}
+
+impl Op {
+    /// Returns true if this operation uses SMBus Packet Error Checking
+    pub fn is_pec(&self) -> bool {
+        matches!(self, Op::WriteReadPec | Op::WriteReadBlockPec)
+    }
+
+    /// Returns true if the final read of this operation is a block read
+    pub fn is_block(&self) -> bool {
+        matches!(self, Op::WriteReadBlock | Op::WriteReadBlockPec)
+    }
 }
 
 /// The response code returned from the I2C server.  These response codes pretty
@@ -106,6 +126,8 @@ pub enum ResponseCode {
     OperationNotSupported,
     /// Illegal number of leases
     IllegalLeaseCount,
+    /// SMBus Packet Error Check byte did not match the transaction
+    PecMismatch,
 }
 
 ///
@@ -174,7 +196,9 @@ pub struct PortIndex(pub u8);
 
 ///
 /// A multiplexer identifier for a given I2C bus.  Multiplexer identifiers
-/// need not start at 0.
+/// need not start at 0.  Multiplexers may themselves be behind a segment of
+/// another multiplexer on the same bus; the server knows this topology, and
+/// selects every segment on the path to the one requested.
 ///
 #[derive(
     Copy,
@@ -193,6 +217,9 @@ pub enum Mux {
     M2 = 2,
     M3 = 3,
     M4 = 4,
+    M5 = 5,
+    M6 = 6,
+    M7 = 7,
 }
 
 ///
@@ -221,3 +248,52 @@ pub enum Segment {
     S7 = 7,
     S8 = 8,
 }
+
+///
+/// Folds a byte into an SMBus Packet Error Code, which is a CRC-8 with the
+/// polynomial x^8 + x^2 + x + 1 (and an initial value of zero) computed over
+/// every byte of the transaction, including addresses.
+///
+pub fn pec_update(crc: u8, byte: u8) -> u8 {
+    let mut crc = crc ^ byte;
+
+    for _ in 0..8 {
+        crc = if crc & 0x80 != 0 {
+            (crc << 1) ^ 0x07
+        } else {
+            crc << 1
+        };
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pec(bytes: &[u8]) -> u8 {
+        bytes.iter().fold(0, |crc, &b| pec_update(crc, b))
+    }
+
+    #[test]
+    fn pec_known_answers() {
+        // The check value of CRC-8/SMBUS, from the catalogue of parametrised
+        // CRC algorithms
+        assert_eq!(pec(b"123456789"), 0xf4);
+
+        assert_eq!(pec(&[]), 0x00);
+        assert_eq!(pec(&[0x00]), 0x00);
+        assert_eq!(pec(&[0x01]), 0x07);
+        assert_eq!(pec(&[0x80]), 0x89);
+        assert_eq!(pec(&[0xff]), 0xf3);
+    }
+
+    #[test]
+    fn pec_over_message_and_pec_is_zero() {
+        // A PMBus READ_VOUT (0x8b) from a device at 0x58, and its reply
+        let msg = [0x58 << 1, 0x8b, (0x58 << 1) | 1, 0x34, 0x12];
+        let crc = pec(&msg);
+        assert_eq!(pec_update(crc, crc), 0);
+    }
+}
@@ -188,6 +188,23 @@ impl Ignition {
         })
     }
 
+    /// Return the number of events recorded since the server started. The most
+    /// recent `EVENT_HISTORY_SIZE` of these can be fetched using `events`.
+    #[inline]
+    pub fn event_count(&self) -> Result<u32, IgnitionError> {
+        self.controller.event_count()
+    }
+
+    /// Fetch up to `EVENTS_PER_BATCH` events, starting with the event at index
+    /// `first` (or the oldest retained event, if `first` has been overwritten).
+    pub fn events(
+        &self,
+        first: u32,
+    ) -> Result<impl Iterator<Item = Event>, IgnitionError> {
+        let events = self.controller.events(first)?;
+        Ok(events.into_iter().filter(|e| e.kind().is_some()))
+    }
+
     /// Fetch the `LinkEvents` for all ports in a single operation and provide
     /// an iterator over the individual ports.
     pub fn all_link_events(&self) -> Result<AllLinkEventsIter, IgnitionError> {
@@ -655,6 +672,51 @@ impl From<[u8; 3]> for LinkEvents {
     }
 }
 
+/// Number of events retained in the Ignition server's event history. Older
+/// events are overwritten.
+pub const EVENT_HISTORY_SIZE: usize = 64;
+
+/// Maximum number of events returned by a single `events` call.
+pub const EVENTS_PER_BATCH: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum EventKind {
+    /// A Target was first seen on the port.
+    TargetArrive = 1,
+    /// The Target present on the port went away.
+    TargetDepart,
+    /// New transceiver events were observed on the port; see `Event::txr`
+    /// and `Event::transceiver_events`.
+    LinkEvents,
+}
+
+/// An entry in the Ignition server's event history.
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, FromBytes, AsBytes, Serialize,
+)]
+#[repr(C)]
+pub struct Event {
+    /// Time of the event, in milliseconds since the SP booted.
+    pub timestamp: u64,
+    /// Position of this event in the history, counting from 0 at boot.
+    pub index: u32,
+    /// An `EventKind`, or 0 for an unused slot in a batch.
+    pub kind: u8,
+    pub port: u8,
+    /// For `EventKind::LinkEvents`, the `TransceiverSelect` which observed
+    /// the events.
+    pub txr: u8,
+    /// For `EventKind::LinkEvents`, the newly set `TransceiverEvents` bits.
+    pub transceiver_events: u8,
+}
+
+impl Event {
+    pub fn kind(&self) -> Option<EventKind> {
+        EventKind::from_u8(self.kind)
+    }
+}
+
 /// A flattened struct representing the state of a port which can be
 /// reconstructed by Humility from a ssmarshal encoded buffer using DWARF
 /// information.
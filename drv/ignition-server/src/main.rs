@@ -3,12 +3,23 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 //! Server for interacting with Ignition Controllers.
+//!
+//! In addition to the current state of each port, the server keeps a history
+//! of Targets arriving and departing and of transceiver events, retrievable
+//! with the `events` operation. Tasks which want to hear about new events as
+//! they happen can subscribe in `app.toml`:
+//!
+//! ```toml
+//! [tasks.ignition.config]
+//! subscribers = [{ name = "control_plane_agent", notification = "ignition" }]
+//! ```
 
 #![no_std]
 #![no_main]
 
 use drv_ignition_api::*;
 use drv_sidecar_mainboard_controller::ignition::*;
+use event_history::EventHistory;
 use ringbuf::*;
 use userlib::*;
 
@@ -27,6 +38,7 @@ enum Trace {
     TargetError(u8, IgnitionError),
     TargetArrive(u8),
     TargetDepart(u8),
+    LinkEvents(u8, TransceiverSelect, u8),
     SystemPowerRequest(u8, Request),
     SystemPowerRequestError(u8, IgnitionError),
 }
@@ -41,6 +53,8 @@ fn main() -> ! {
         controller: IgnitionController::new(FPGA.get_task_id()),
         port_count: 0,
         last_presence_summary: 0,
+        last_link_events: [[0; 3]; PORT_MAX as usize],
+        events: EventHistory::new(),
     };
 
     // This task is expected to run in an environment where a sequencer is
@@ -92,9 +106,27 @@ struct ServerImpl {
     controller: IgnitionController,
     port_count: u8,
     last_presence_summary: u64,
+    /// The transceiver events last seen for each port, used to detect new
+    /// ones.
+    last_link_events: [[u8; 3]; PORT_MAX as usize],
+    /// The most recent `EVENT_HISTORY_SIZE` events.
+    events: EventHistory<Event, EVENT_HISTORY_SIZE>,
 }
 
 impl ServerImpl {
+    fn record_event(&mut self, kind: EventKind, port: u8, txr: u8, bits: u8) {
+        let timestamp = sys_get_timer().now;
+
+        self.events.record(|index| Event {
+            timestamp,
+            index,
+            kind: kind as u8,
+            port,
+            txr,
+            transceiver_events: bits,
+        });
+    }
+
     /// Get the state of the given Target or an error if no Target present.
     fn target(&self, port: u8) -> Result<Target, IgnitionError> {
         Port::from(
@@ -127,12 +159,85 @@ impl ServerImpl {
             self.last_presence_summary = arrived_targets
                 | (self.last_presence_summary & !departed_targets);
 
+            for port in 0..self.port_count.min(PORT_MAX) {
+                let mask = 1 << port;
+
+                if arrived_targets & mask != 0 {
+                    self.record_event(EventKind::TargetArrive, port, 0, 0);
+                    // Anything latched from here on is news.
+                    self.last_link_events[port as usize] = [0; 3];
+                }
+                if departed_targets & mask != 0 {
+                    self.record_event(EventKind::TargetDepart, port, 0, 0);
+                }
+            }
+
             ringbuf_entry!(Trace::PresenceUpdate(self.last_presence_summary));
         }
 
         Ok(())
     }
 
+    /// Record an event for any transceiver events which have been latched on a
+    /// port with a Target present since we last looked.
+    fn poll_link_events(&mut self) -> Result<(), IgnitionError> {
+        let all_link_events = self.read_all_link_events()?;
+
+        for port in 0..self.port_count.min(PORT_MAX) {
+            if self.last_presence_summary & (1 << port) == 0 {
+                continue;
+            }
+
+            for (i, txr) in TransceiverSelect::ALL.into_iter().enumerate() {
+                let current = all_link_events[port as usize][i];
+                let last = &mut self.last_link_events[port as usize][i];
+                let new = current & !*last;
+                *last = current;
+
+                if new != 0 {
+                    ringbuf_entry!(Trace::LinkEvents(port, txr, new));
+                    self.record_event(
+                        EventKind::LinkEvents,
+                        port,
+                        txr as u8,
+                        new,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the transceiver events for each transceiver of the given port.
+    fn read_link_events(&self, port: u8) -> Result<[u8; 3], IgnitionError> {
+        let mut events = [0u8; 3];
+        for (i, txr) in TransceiverSelect::ALL.into_iter().enumerate() {
+            events[i] = self.controller.transceiver_events(port, txr)?;
+        }
+
+        Ok(events)
+    }
+
+    /// Get the transceiver events for all ports.
+    fn read_all_link_events(
+        &self,
+    ) -> Result<[[u8; 3]; PORT_MAX as usize], IgnitionError> {
+        let mut all_link_events = [[0u8; 3]; PORT_MAX as usize];
+        for port in 0..PORT_MAX.min(self.port_count) {
+            all_link_events[port as usize] = self.read_link_events(port)?;
+        }
+
+        Ok(all_link_events)
+    }
+
+    /// Let any subscribers know that there are new events.
+    fn notify_subscribers(&self) {
+        for (task, mask) in SUBSCRIBERS {
+            sys_post(sys_refresh_task_id(task), mask);
+        }
+    }
+
     /// Apply the given function to each port for which a bit in the `ports`
     /// vector is set. Returns a bit vector with bits set for ports for which
     /// the operation was succesful. Under normal circumstances this output
@@ -343,8 +448,14 @@ impl idl::InOrderIgnitionImpl for ServerImpl {
 
         self.controller
             .clear_transceiver_events(port, txr)
-            .map_err(IgnitionError::from)
-            .map_err(RequestError::from)
+            .map_err(IgnitionError::from)?;
+
+        // Keep our view in sync, so that events latched after this are
+        // recorded as new.
+        let i = txr as usize - TransceiverSelect::Controller as usize;
+        self.last_link_events[port as usize][i] = 0;
+
+        Ok(())
     }
 
     fn link_events(
@@ -356,15 +467,7 @@ impl idl::InOrderIgnitionImpl for ServerImpl {
             return Err(RequestError::from(IgnitionError::InvalidPort));
         }
 
-        let mut events = [0u8; 3];
-        for (i, txr) in TransceiverSelect::ALL.into_iter().enumerate() {
-            events[i] = self
-                .controller
-                .transceiver_events(port, txr)
-                .map_err(IgnitionError::from)?;
-        }
-
-        Ok(events)
+        Ok(self.read_link_events(port)?)
     }
 
     fn send_request(
@@ -403,15 +506,24 @@ impl idl::InOrderIgnitionImpl for ServerImpl {
 
     fn all_link_events(
         &mut self,
-        msg: &userlib::RecvMessage,
+        _: &userlib::RecvMessage,
     ) -> Result<[[u8; 3]; PORT_MAX as usize], RequestError> {
-        let mut all_link_events = [[0u8; 3]; PORT_MAX as usize];
+        Ok(self.read_all_link_events()?)
+    }
 
-        for port in 0..PORT_MAX.min(self.port_count) {
-            all_link_events[port as usize] = self.link_events(msg, port)?;
-        }
+    fn event_count(
+        &mut self,
+        _: &userlib::RecvMessage,
+    ) -> Result<u32, RequestError> {
+        Ok(self.events.count())
+    }
 
-        Ok(all_link_events)
+    fn events(
+        &mut self,
+        _: &userlib::RecvMessage,
+        first: u32,
+    ) -> Result<[Event; EVENTS_PER_BATCH], RequestError> {
+        Ok(self.events.batch(first))
     }
 }
 
@@ -427,9 +539,17 @@ impl idol_runtime::NotificationHandler for ServerImpl {
         // count of 0xff may occur if the FPGA is running an incorrect
         // bitstream.
         if self.port_count > 0 && self.port_count != 0xff {
-            if let Err(e) = self.poll_presence() {
+            let event_count = self.events.count();
+
+            if let Err(e) =
+                self.poll_presence().and_then(|()| self.poll_link_events())
+            {
                 ringbuf_entry!(Trace::PresencePollError(e));
             }
+
+            if self.events.count() != event_count {
+                self.notify_subscribers();
+            }
         }
 
         let finish = sys_get_timer().now;
@@ -453,3 +573,4 @@ mod idl {
 }
 
 include!(concat!(env!("OUT_DIR"), "/notifications.rs"));
+include!(concat!(env!("OUT_DIR"), "/subscribers.rs"));
@@ -7,10 +7,11 @@ use attest_api::Attest;
 use crc::{Crc, CRC_32_CKSUM};
 use drv_lpc55_update_api::{SlotId, Update};
 use drv_sprot_api::{
-    AttestReq, AttestRsp, CabooseReq, CabooseRsp, DumpReq, DumpRsp, ReqBody,
-    Request, Response, RotIoStats, RotState, RotStatus, RspBody,
-    SprocketsError, SprotError, SprotProtocolError, UpdateReq, UpdateRsp,
-    CURRENT_VERSION, MIN_VERSION, REQUEST_BUF_SIZE, RESPONSE_BUF_SIZE,
+    AttestReq, AttestRsp, CabooseReq, CabooseRsp, DumpReq, DumpRsp,
+    PostMortemReq, PostMortemRsp, ReqBody, Request, Response, RotIoStats,
+    RotState, RotStatus, RspBody, SprocketsError, SprotError,
+    SprotProtocolError, UpdateReq, UpdateRsp, CURRENT_VERSION, MIN_VERSION,
+    REQUEST_BUF_SIZE, RESPONSE_BUF_SIZE,
 };
 use dumper_api::Dumper;
 use lpc55_romapi::bootrom;
@@ -46,6 +47,7 @@ pub struct StartupState {
 pub enum TrailingData {
     Caboose { slot: SlotId, start: u32, size: u32 },
     Attest { index: u32, offset: u32, size: u32 },
+    PostMortem { offset: u32, size: u32 },
 }
 
 pub struct Handler {
@@ -152,6 +154,28 @@ impl Handler {
                     }
                 }
             }
+            Some(TrailingData::PostMortem { offset, size }) => {
+                let size: usize = usize::try_from(size).unwrap_lite();
+                if size > drv_sprot_api::MAX_BLOB_SIZE {
+                    Response::pack(
+                        &Err(SprotError::Protocol(
+                            SprotProtocolError::BadMessageLength,
+                        )),
+                        tx_buf,
+                    )
+                } else {
+                    let dumper = Dumper::from(DUMPER.get_task_id());
+                    match Response::pack_with_cb(&rsp_body, tx_buf, |buf| {
+                        dumper
+                            .read_post_mortem(offset, &mut buf[..size])
+                            .map_err(|e| RspBody::PostMortem(Err(e)))?;
+                        Ok(size)
+                    }) {
+                        Ok(size) => size,
+                        Err(e) => Response::pack(&Ok(e), tx_buf),
+                    }
+                }
+            }
             _ => Response::pack(&rsp_body, tx_buf),
         }
     }
@@ -300,6 +324,25 @@ impl Handler {
                 };
                 Ok((RspBody::Attest(rsp), None))
             }
+            ReqBody::PostMortem(PostMortemReq::Capture) => {
+                let dumper = Dumper::from(DUMPER.get_task_id());
+                let rsp =
+                    dumper.capture_post_mortem().map(PostMortemRsp::Capture);
+                Ok((RspBody::PostMortem(rsp), None))
+            }
+            ReqBody::PostMortem(PostMortemReq::Len) => {
+                let dumper = Dumper::from(DUMPER.get_task_id());
+                let rsp = dumper.post_mortem_len().map(PostMortemRsp::Len);
+                Ok((RspBody::PostMortem(rsp), None))
+            }
+            ReqBody::PostMortem(PostMortemReq::Read { offset, size }) => {
+                // Like certs, the capture is returned in the trailing data
+                // region of the response.
+                Ok((
+                    RspBody::PostMortem(Ok(PostMortemRsp::Read)),
+                    Some(TrailingData::PostMortem { offset, size }),
+                ))
+            }
         }
     }
 }
@@ -288,6 +288,14 @@ impl idl::InOrderSpCtrlImpl for ServerImpl {
         if cnt % 4 != 0 {
             return Err(SpCtrlError::BadLen.into());
         }
+
+        // If our transaction has been cleared out from under us (e.g., by
+        // someone else calling `setup`), fail rather than quietly returning
+        // nothing.
+        if self.transaction.is_none() {
+            return Err(SpCtrlError::Fault.into());
+        }
+
         let mut buf = LeaseBufWriter::<_, 32>::from(dest.into_inner());
 
         for _ in 0..cnt / 4 {
@@ -393,6 +401,9 @@ impl idl::InOrderSpCtrlImpl for ServerImpl {
             return Err(SpCtrlError::DongleDetected.into());
         }
 
+        // Setting up again abandons any transaction in progress.
+        self.transaction = None;
+
         match self.swd_setup() {
             Ok(_) => {
                 self.init = true;
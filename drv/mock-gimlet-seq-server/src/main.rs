@@ -7,7 +7,7 @@
 #![no_std]
 #![no_main]
 
-use drv_gimlet_seq_api::{PowerState, SeqError};
+use drv_gimlet_seq_api::{PowerState, SeqError, SeqEvent};
 use idol_runtime::RequestError;
 use task_jefe_api::Jefe;
 use userlib::{FromPrimitive, RecvMessage, UnwrapLite};
@@ -99,10 +99,25 @@ impl idl::InOrderSequencerImpl for ServerImpl {
     ) -> Result<[u8; 64], RequestError<SeqError>> {
         Ok([0; 64])
     }
+
+    fn event_count(
+        &mut self,
+        _: &RecvMessage,
+    ) -> Result<u32, RequestError<core::convert::Infallible>> {
+        Ok(0)
+    }
+
+    fn read_event(
+        &mut self,
+        _: &RecvMessage,
+        _index: u32,
+    ) -> Result<SeqEvent, RequestError<SeqError>> {
+        Err(RequestError::Runtime(SeqError::NoSuchEvent))
+    }
 }
 
 mod idl {
-    use super::SeqError;
+    use super::{SeqError, SeqEvent};
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
@@ -15,7 +15,10 @@ pub use vsc85xx::{
 };
 
 pub use vsc7448::{
-    config::{PortConfig, PortDev, PortMode, PortSerdes, Speed},
+    config::{
+        MirrorConfig, PortConfig, PortDev, PortMode, PortSerdes, Speed,
+        StormKind, StormPolicers,
+    },
     VscError,
 };
 
@@ -121,6 +124,9 @@ pub enum MonorailError {
     UnconfiguredPort,
     /// The given port does not have a PHY associated with it
     NoPhy,
+    /// The mirror destination is missing, out of range, or is itself one of
+    /// the mirrored ports
+    InvalidMirrorConfig,
 
     #[idol(server_death)]
     ServerDied,
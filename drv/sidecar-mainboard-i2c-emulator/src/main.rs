@@ -26,7 +26,10 @@ fn main() -> ! {
 
     loop {
         hl::recv_without_notification(&mut buffer, |op, msg| match op {
-            Op::WriteRead | Op::WriteReadBlock => {
+            Op::WriteRead
+            | Op::WriteReadBlock
+            | Op::WriteReadPec
+            | Op::WriteReadBlockPec => {
                 let (payload, caller) = msg
                     .fixed_with_leases::<[u8; 4], usize>(2)
                     .ok_or(ResponseCode::BadArg)?;
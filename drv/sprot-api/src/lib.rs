@@ -46,7 +46,7 @@ pub const MIN_VERSION: Version = Version(2);
 /// Code between the `CURRENT_VERSION` and `MIN_VERSION` must remain
 /// compatible. Use the rules described in the comments for [`Msg`] to evolve
 /// the protocol such that this remains true.
-pub const CURRENT_VERSION: Version = Version(3);
+pub const CURRENT_VERSION: Version = Version(4);
 
 /// We allow room in the buffer for message evolution
 pub const REQUEST_BUF_SIZE: usize = 1024;
@@ -330,6 +330,8 @@ pub enum ReqBody {
     // Added in sprot protocol version 3
     Caboose(CabooseReq),
     Attest(AttestReq),
+    // Added in sprot protocol version 4
+    PostMortem(PostMortemReq),
 }
 
 /// Instruct the RoT to take a dump of the SP via SWD
@@ -373,6 +375,16 @@ pub enum AttestReq {
     Record { algorithm: HashAlgorithm },
 }
 
+/// Capture or retrieve a post-mortem of the SP, taken by the RoT via SWD
+//
+// Added in sprot protocol version 4
+#[derive(Clone, Serialize, Deserialize, SerializedSize)]
+pub enum PostMortemReq {
+    Capture,
+    Len,
+    Read { offset: u32, size: u32 },
+}
+
 /// A response used for RoT updates
 #[derive(Clone, Serialize, Deserialize, SerializedSize, From)]
 pub enum UpdateRsp {
@@ -398,6 +410,16 @@ pub enum AttestRsp {
     Record,
 }
 
+/// A response used for post-mortem requests
+//
+// Added in sprot protocol version 4
+#[derive(Clone, Serialize, Deserialize, SerializedSize)]
+pub enum PostMortemRsp {
+    Capture(u32),
+    Len(u32),
+    Read,
+}
+
 /// The body of a sprot response.
 ///
 /// See [`Msg`] for details about versioning and message evolution.
@@ -424,6 +446,9 @@ pub enum RspBody {
     Caboose(Result<CabooseRsp, RawCabooseError>),
 
     Attest(Result<AttestRsp, AttestError>),
+
+    // Added in sprot protocol version 4
+    PostMortem(Result<PostMortemRsp, DumperError>),
 }
 
 /// A response from the Dumper
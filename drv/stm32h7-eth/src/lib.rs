@@ -534,6 +534,23 @@ impl Ethernet {
         can_recv
     }
 
+    /// Returns the EtherType of the next packet in the Rx ring, if it belongs
+    /// to the given VLAN, without consuming the packet.
+    ///
+    /// This lets callers pick out frames for protocols that they handle
+    /// themselves (e.g. LLDP) before handing the rest to the IP stack.
+    pub fn vlan_next_ethertype(
+        &self,
+        vid: u16,
+        vid_range: core::ops::Range<u16>,
+    ) -> Option<u16> {
+        if self.vlan_can_recv(vid, vid_range) {
+            self.rx_ring.vlan_next_ethertype()
+        } else {
+            None
+        }
+    }
+
     /// Same as `try_send`, but attaching the given VLAN tag to the outgoing
     /// packet (if present)
     #[cfg(feature = "vlan")]
@@ -724,4 +724,30 @@ impl RxRing {
 
         retval
     }
+
+    /// Returns the EtherType of the next packet in the ring, without
+    /// consuming it, or `None` if the packet is too short to have one.
+    ///
+    /// Like `vlan_with_next`, this should only be called after
+    /// `vlan_is_next_free` has confirmed that the next packet is valid, and
+    /// will panic otherwise.
+    pub fn vlan_next_ethertype(&self) -> Option<u16> {
+        let d = &self.storage[self.next.get()];
+        let rdes3 = d.rdes[3].load(Ordering::Acquire);
+        let own = rdes3 & (1 << RDES3_OWN_BIT) != 0;
+        assert!(!own);
+
+        let packet_len = (rdes3 & RDES3_PL_MASK) as usize;
+        if packet_len < 14 {
+            return None;
+        }
+
+        // Safety: as in `vlan_with_next`, the descriptor is free, so the
+        // buffer isn't aliased by the hardware; we only read from it.
+        let buffer = unsafe { &*self.buffers[self.next.get()].0.get() };
+
+        // The VLAN tag has already been stripped by the MAC, so the EtherType
+        // immediately follows the two addresses.
+        Some(u16::from_be_bytes([buffer[12], buffer[13]]))
+    }
 }
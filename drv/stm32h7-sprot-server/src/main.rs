@@ -906,6 +906,75 @@ impl<S: SpiServer> idl::InOrderSpRotImpl for ServerImpl<S> {
             Err(e) => Err(AttestOrSprotError::Sprot(e).into()),
         }
     }
+
+    fn capture_post_mortem(
+        &mut self,
+        _: &userlib::RecvMessage,
+    ) -> Result<u32, idol_runtime::RequestError<DumpOrSprotError>> {
+        let body = ReqBody::PostMortem(PostMortemReq::Capture);
+        let tx_size = Request::pack(&body, &mut self.tx_buf);
+        let rsp = self.do_send_recv_retries(tx_size, DUMP_TIMEOUT, 1)?;
+        match rsp.body? {
+            RspBody::PostMortem(Ok(PostMortemRsp::Capture(len))) => Ok(len),
+            RspBody::PostMortem(Err(e)) => DumpOrSprotError::Dump(e).into(),
+            _ => Err(SprotError::Protocol(
+                SprotProtocolError::UnexpectedResponse,
+            ))?,
+        }
+    }
+
+    fn post_mortem_len(
+        &mut self,
+        _: &userlib::RecvMessage,
+    ) -> Result<u32, idol_runtime::RequestError<DumpOrSprotError>> {
+        let body = ReqBody::PostMortem(PostMortemReq::Len);
+        let tx_size = Request::pack(&body, &mut self.tx_buf);
+        let rsp = self.do_send_recv_retries(tx_size, TIMEOUT_QUICK, 1)?;
+        match rsp.body? {
+            RspBody::PostMortem(Ok(PostMortemRsp::Len(len))) => Ok(len),
+            RspBody::PostMortem(Err(e)) => DumpOrSprotError::Dump(e).into(),
+            _ => Err(SprotError::Protocol(
+                SprotProtocolError::UnexpectedResponse,
+            ))?,
+        }
+    }
+
+    fn read_post_mortem(
+        &mut self,
+        _: &userlib::RecvMessage,
+        offset: u32,
+        data: idol_runtime::Leased<idol_runtime::W, [u8]>,
+    ) -> Result<(), idol_runtime::RequestError<DumpOrSprotError>> {
+        let body = ReqBody::PostMortem(PostMortemReq::Read {
+            offset,
+            size: data.len() as u32,
+        });
+        let tx_size = Request::pack(&body, &mut self.tx_buf);
+        let rsp =
+            self.do_send_recv_retries(tx_size, DUMP_TIMEOUT, DEFAULT_ATTEMPTS)?;
+
+        match rsp.body? {
+            RspBody::PostMortem(Ok(PostMortemRsp::Read)) => {
+                // Copy from the trailing data into the lease
+                if rsp.blob.len() < data.len() {
+                    return Err(idol_runtime::RequestError::Fail(
+                        idol_runtime::ClientError::BadLease,
+                    ));
+                }
+                data.write_range(0..data.len(), &rsp.blob[..data.len()])
+                    .map_err(|()| {
+                        idol_runtime::RequestError::Fail(
+                            idol_runtime::ClientError::WentAway,
+                        )
+                    })?;
+                Ok(())
+            }
+            RspBody::PostMortem(Err(e)) => DumpOrSprotError::Dump(e).into(),
+            _ => Err(SprotError::Protocol(
+                SprotProtocolError::UnexpectedResponse,
+            ))?,
+        }
+    }
 }
 
 mod idl {
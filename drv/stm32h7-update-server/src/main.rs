@@ -421,12 +421,10 @@ impl idl::InOrderUpdateImpl for ServerImpl<'_> {
         // `__REGION_BASE2_END` (which are symbols injected by the linker).
         //
         // We'll first want to read the image header, which is at a fixed
-        // location at the end of the vector table.  The length of the vector
-        // table is fixed in hardware, so this should never change.
-        const HEADER_OFFSET: u32 = 0x298;
+        // location at the end of the vector table.
         let header: ImageHeader = unsafe {
             core::ptr::read_volatile(
-                (image_start + HEADER_OFFSET) as *const ImageHeader,
+                (image_start + STM32H7_HEADER_OFFSET) as *const ImageHeader,
             )
         };
         if header.magic != HEADER_MAGIC {
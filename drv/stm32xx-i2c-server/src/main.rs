@@ -3,6 +3,37 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 //! A driver for the STM32H7 I2C interface
+//!
+//! Muxes may be behind other muxes, in which case each names the mux (by its
+//! 1-based index among its port's muxes) and segment in front of it; devices
+//! name only the mux that they're directly behind, and we enable every
+//! segment on the way to it:
+//!
+//! ```toml
+//! [[config.i2c.controllers.ports.B.muxes]]
+//! driver = "pca9545"
+//! address = 0x70
+//!
+//! [[config.i2c.controllers.ports.B.muxes]]
+//! driver = "pca9548"
+//! address = 0x71
+//! parent = { mux = 1, segment = 3 }
+//! ```
+//!
+//! Ports may optionally have an SMBus ALERT# line, routed to one of our
+//! notifications by the `sys` task.  When it's asserted, we find the
+//! asserting device(s) with the Alert Response Address -- on the port itself
+//! and on every mux segment with a device that has an alert owner -- and
+//! notify the owners, as configured in the `i2c` section of the app TOML:
+//!
+//! ```toml
+//! [config.i2c.controllers.ports.F]
+//! alert = { notification = "i2c2-alert", pin = 2 }
+//!
+//! [[config.i2c.devices]]
+//! device = "max5970"
+//! alert = { name = "power", notification = "hotswap-alert" }
+//! ```
 
 #![no_std]
 #![no_main]
@@ -42,6 +73,26 @@ fn validate_port(
     Ok(())
 }
 
+///
+/// Looks up the specified mux ID on the specified controller and port -- or
+/// returns `ResponseCode::MuxNotFound` if there is no such mux
+///
+fn lookup_mux<'a, 'b>(
+    controller: &I2cController<'_>,
+    port: PortIndex,
+    muxes: &'a [I2cMux<'b>],
+    id: Mux,
+) -> Result<&'a I2cMux<'b>, ResponseCode> {
+    muxes
+        .iter()
+        .find(|mux| {
+            mux.controller == controller.controller
+                && mux.port == port
+                && mux.id == id
+        })
+        .ok_or(ResponseCode::MuxNotFound)
+}
+
 ///
 /// Calls `func` for the specified mux ID on the specified controller and
 /// port -- or returns `ResponseCode::MuxNotFound` if there is no such mux
@@ -53,16 +104,36 @@ fn find_mux(
     id: Mux,
     mut func: impl FnMut(&I2cMux<'_>) -> Result<(), ResponseCode>,
 ) -> Result<(), ResponseCode> {
-    for mux in muxes {
-        if mux.controller == controller.controller
-            && mux.port == port
-            && mux.id == id
-        {
-            return func(mux);
+    func(lookup_mux(controller, port, muxes, id)?)
+}
+
+///
+/// Fills in `path` with the mux+segment pairs that must be enabled to reach
+/// the specified mux+segment (which may be behind other muxes), starting
+/// from the bus itself, and returns the number of them.
+///
+fn mux_path(
+    controller: &I2cController<'_>,
+    port: PortIndex,
+    muxes: &[I2cMux<'_>],
+    segment: Option<(Mux, Segment)>,
+    path: &mut MuxPath,
+) -> Result<usize, ResponseCode> {
+    let mut len = 0;
+    let mut next = segment;
+
+    while let Some((id, segment)) = next {
+        if len == path.len() {
+            return Err(ResponseCode::BadMux);
         }
+
+        path[len] = (id, segment);
+        len += 1;
+        next = lookup_mux(controller, port, muxes, id)?.parent;
     }
 
-    Err(ResponseCode::MuxNotFound)
+    path[..len].reverse();
+    Ok(len)
 }
 
 ///
@@ -99,81 +170,153 @@ fn configure_mux(
 ) -> Result<(), ResponseCode> {
     let bus = (controller.controller, port);
 
+    let mut target = EMPTY_MUX_PATH;
+    let len = mux_path(controller, port, muxes, mux, &mut target)?;
+    let target = &target[..len];
+
+    //
+    // The index in `target` of the first mux+segment that we need to enable;
+    // everything before it is already enabled.
+    //
+    let mut start = 0;
+
     match muxmap.get(bus) {
-        Some(MuxState::Enabled(current_id, current_segment)) => match mux {
-            Some((id, segment)) if id == current_id => {
-                //
-                // We have an enabled mux+segment on this bus, and it matches
-                // our desired mux.  (If the segment matches, we're done and
-                // can return; if the segment doesn't match we will set it
-                // to our desired segment below.)
-                //
-                if segment == current_segment {
-                    return Ok(());
-                }
+        Some(MuxState::Enabled(current_id, current_segment)) => {
+            let mut current = EMPTY_MUX_PATH;
+            let len = mux_path(
+                controller,
+                port,
+                muxes,
+                Some((current_id, current_segment)),
+                &mut current,
+            )?;
+            let current = &current[..len];
+
+            //
+            // If our currently enabled mux+segment matches our desired one,
+            // we're done.
+            //
+            if current == target {
+                return Ok(());
             }
-            _ => {
-                //
-                // We have an enabled mux+segment on this bus, but it doesn't
-                // match our desired mux -- which is to say that we have
-                // either enabled a different mux or no mux at all.  In
-                // either case, we will disable all segments on our currently
-                // enabled mux.  If we are not enabling a mux at all, this
-                // shouldn't be strictly necessarily (we generally design I2C
-                // addresses to avoid conflicts with enabled segments), but we
-                // want to minimize the ability of a bad component on a mux'd
-                // segment (e.g., a FRU) to wreak havoc elsewhere in the
-                // system -- especially because the failure mode of an
-                // (errant) address conflict can be pretty brutal.
-                //
-                find_mux(controller, port, muxes, current_id, |mux| {
+
+            //
+            // Otherwise, our paths agree up to some point -- which may be the
+            // bus itself.  Beyond that point, we will disable all segments
+            // on every currently enabled mux, deepest first (that is, while
+            // we can still reach it).  The exception is a mux that is on both
+            // paths but with different segments, which we merely switch to
+            // our desired segment below.  If we are not enabling a mux at
+            // all, disabling muxes shouldn't be strictly necessary (we
+            // generally design I2C addresses to avoid conflicts with enabled
+            // segments), but we want to minimize the ability of a bad
+            // component on a mux'd segment (e.g., a FRU) to wreak havoc
+            // elsewhere in the system -- especially because the failure mode
+            // of an (errant) address conflict can be pretty brutal.
+            //
+            start = current
+                .iter()
+                .zip(target)
+                .take_while(|(c, t)| c == t)
+                .count();
+
+            let keep = match (current.get(start), target.get(start)) {
+                (Some((c, _)), Some((t, _))) if c == t => start + 1,
+                _ => start,
+            };
+
+            for &(id, _) in current[keep..].iter().rev() {
+                find_mux(controller, port, muxes, id, |mux| {
                     mux.driver.enable_segment(mux, controller, None, ctrl)
                 })
                 .map_err(|err| {
                     //
-                    // We have failed to disable the segments on our current
+                    // We have failed to disable the segments on a current
                     // mux -- which means we are in an unknown mux state for
                     // this bus.  Set our state, and return the error.
                     //
                     muxmap.insert(bus, MuxState::Unknown);
                     err
                 })?;
+            }
 
-                //
-                // We now know that no mux+segment is enabled; indicate
-                // this by removing this bus from the muxmap.
-                //
-                muxmap.remove(bus);
+            //
+            // We now know that only the muxes that we kept are enabled (if
+            // any); if none are, indicate this by removing this bus from
+            // the muxmap.
+            //
+            match keep.checked_sub(1) {
+                Some(last) => {
+                    let (id, segment) = current[last];
+                    muxmap.insert(bus, MuxState::Enabled(id, segment));
+                }
+                None => muxmap.remove(bus),
             }
-        },
+        }
 
         Some(MuxState::Unknown) => {
             //
             // We are in an unknown mux state.  Before we can do anything, we
             // need to successfully talk to every mux (or successfully learn
-            // that the mux is gone entirely!), and disable every segment.  If
-            // there is any failure through here that isn't the mux being
-            // affirmatively gone, we'll just return the error, leaving our
-            // mux state as unknown.
+            // that the mux is gone entirely!), and disable every segment.
+            // Muxes come before any muxes behind them, so to reach a mux
+            // that is behind other muxes, we enable the segments that lead
+            // to it -- and then disable them again.  If there is any failure
+            // through here that isn't a mux being affirmatively gone, we'll
+            // just return the error, leaving our mux state as unknown.
             //
             all_muxes(controller, port, muxes, |mux| {
-                match mux.driver.enable_segment(mux, controller, None, ctrl) {
+                let mut path = EMPTY_MUX_PATH;
+                let len =
+                    mux_path(controller, port, muxes, mux.parent, &mut path)?;
+                let path = &path[..len];
+                let mut enabled = 0;
+                let mut rval = Ok(());
+
+                for &(id, segment) in path {
+                    let m = lookup_mux(controller, port, muxes, id)?;
+                    rval = m.driver.enable_segment(
+                        m,
+                        controller,
+                        Some(segment),
+                        ctrl,
+                    );
+
+                    if rval.is_err() {
+                        break;
+                    }
+
+                    enabled += 1;
+                }
+
+                if rval.is_ok() {
+                    rval =
+                        mux.driver.enable_segment(mux, controller, None, ctrl);
+                }
+
+                for &(id, _) in path[..enabled].iter().rev() {
+                    let m = lookup_mux(controller, port, muxes, id)?;
+                    m.driver.enable_segment(m, controller, None, ctrl)?;
+                }
+
+                match rval {
                     Err(ResponseCode::MuxMissing) => {
                         //
-                        // The mux is gone entirely.  We really don't expect
-                        // this on any production system, but it can be true on
-                        // some special lab systems (you know who you are!).
-                        // Regardless of its origin, we can limit the blast
-                        // radius in this case: if the mux is affirmatively
-                        // gone (that is, no device is acking its address), we
-                        // can assume that the mux is absent rather than
-                        // Byzantine -- and therefore assume that its segments
-                        // are as good as disabled and allow other traffic on
-                        // the bus.  So on this error (and only this error), we
-                        // note that we saw it, and drive on.  (Note that
-                        // attempting to speak to a device on a segment on the
-                        // missing mux will properly return MuxMissing -- and
-                        // set our bus's mux state to be unknown.)
+                        // The mux (or one in front of it) is gone entirely.
+                        // We really don't expect this on any production
+                        // system, but it can be true on some special lab
+                        // systems (you know who you are!).  Regardless of
+                        // its origin, we can limit the blast radius in this
+                        // case: if the mux is affirmatively gone (that is, no
+                        // device is acking its address), we can assume that
+                        // the mux is absent rather than Byzantine -- and
+                        // therefore assume that its segments are as good as
+                        // disabled and allow other traffic on the bus.  So on
+                        // this error (and only this error), we note that we
+                        // saw it, and drive on.  (Note that attempting to
+                        // speak to a device on a segment on the missing mux
+                        // will properly return MuxMissing -- and set our
+                        // bus's mux state to be unknown.)
                         //
                         ringbuf_entry!(Trace::MuxMissing(mux.address));
                         Ok(())
@@ -195,10 +338,10 @@ fn configure_mux(
     }
 
     //
-    // We know that no mux+segment is enabled OR we have the current mux
-    // but we need to enable a different segment.
+    // We know that everything on our desired path before `start` is enabled,
+    // and that nothing else is; enable the rest of it, root to leaf.
     //
-    if let Some((id, segment)) = mux {
+    for &(id, segment) in &target[start..] {
         find_mux(controller, port, muxes, id, |mux| {
             mux.driver
                 .enable_segment(mux, controller, Some(segment), ctrl)
@@ -213,8 +356,9 @@ fn configure_mux(
                 })?;
 
             //
-            // We have succeeded, and we are in a known state with our
-            // desired mux+segment correctly enabled.  Update our muxmap!
+            // We have succeeded, and we are in a known state with this
+            // mux+segment (and all before it) correctly enabled.  Update our
+            // muxmap!
             //
             muxmap.insert(bus, MuxState::Enabled(id, segment));
             Ok(())
@@ -237,6 +381,10 @@ enum Trace {
     SegmentFailed(ResponseCodeU8),
     ConfigureFailed(ResponseCodeU8),
     Wiggles(u8),
+    Alert(u32),
+    AlertResponse((Controller, PortIndex), u8),
+    AlertUnclaimed(u8),
+    AlertError(ResponseCodeU8),
     None,
 }
 
@@ -302,7 +450,8 @@ type PortMap = FixedMap<Controller, PortIndex, { i2c_config::NCONTROLLERS }>;
 
 #[derive(Copy, Clone, Debug)]
 enum MuxState {
-    /// a mux+segment have been explicitly enabled
+    /// a mux+segment have been explicitly enabled, along with the segments
+    /// of any muxes that it's behind
     Enabled(Mux, Segment),
 
     /// state is unknown: zero, one, or more mux+segment(s) may be enabled
@@ -318,6 +467,15 @@ enum MuxState {
 type MuxMap =
     FixedMap<(Controller, PortIndex), MuxState, { i2c_config::NMUXEDBUSES }>;
 
+///
+/// The mux+segment pairs leading from a bus to a segment, as filled in by
+/// [`mux_path`]
+///
+type MuxPath = [(Mux, Segment); i2c_config::MAX_MUX_DEPTH];
+
+const EMPTY_MUX_PATH: MuxPath =
+    [(Mux::M1, Segment::S1); i2c_config::MAX_MUX_DEPTH];
+
 #[export_name = "main"]
 fn main() -> ! {
     let controllers = i2c_config::controllers();
@@ -354,140 +512,190 @@ fn main() -> ! {
         &ctrl,
     );
 
-    loop {
-        hl::recv_without_notification(&mut buffer, |op, msg| match op {
-            Op::WriteRead | Op::WriteReadBlock => {
-                let lease_count = msg.lease_count();
-
-                let (payload, caller) = msg
-                    .fixed::<[u8; 4], usize>()
-                    .ok_or(ResponseCode::BadArg)?;
+    let alerts = i2c_config::alerts();
+    let alert_owners = i2c_config::alert_owners();
 
-                if lease_count < 2 || lease_count % 2 != 0 {
-                    return Err(ResponseCode::IllegalLeaseCount);
-                }
+    if i2c_config::ALERT_MASK != 0 {
+        let sys = Sys::from(SYS.get_task_id());
 
-                let (addr, controller, port, mux) =
-                    Marshal::unmarshal(payload)?;
-
-                if ReservedAddress::from_u8(addr).is_some() {
-                    return Err(ResponseCode::ReservedAddress);
-                }
+        //
+        // ALERT# is open-drain; boards are expected to have their own pull-up,
+        // but we add ours in case the line is on a header that doesn't.
+        //
+        for alert in &alerts {
+            sys.gpio_configure_input(alert.pin, Pull::Up);
+        }
 
-                let controller = lookup_controller(&controllers, controller)?;
-                validate_port(&pins, controller.controller, port)?;
+        sys.gpio_irq_enable(i2c_config::ALERT_MASK).unwrap_lite();
 
-                configure_port(&mut portmap, controller, port, &pins);
+        //
+        // ALERT# is level-triggered but we are notified of edges, so check
+        // for any alerts that were asserted before we started listening.
+        //
+        handle_alerts(
+            i2c_config::ALERT_MASK,
+            &alerts,
+            &alert_owners,
+            &controllers,
+            &pins,
+            &muxes,
+            &mut portmap,
+            &mut muxmap,
+            &ctrl,
+        );
+    }
 
-                match configure_mux(
-                    &mut muxmap,
-                    controller,
-                    port,
-                    mux,
+    loop {
+        hl::recv(
+            &mut buffer,
+            i2c_config::ALERT_MASK,
+            (&mut portmap, &mut muxmap),
+            |(portmap, muxmap), bits| {
+                let sys = Sys::from(SYS.get_task_id());
+                let pending = sys.gpio_irq_pending(bits).unwrap_or(bits);
+                handle_alerts(
+                    pending,
+                    &alerts,
+                    &alert_owners,
+                    &controllers,
+                    &pins,
                     &muxes,
+                    portmap,
+                    muxmap,
                     &ctrl,
-                ) {
-                    Ok(_) => {}
-                    Err(code) => {
-                        ringbuf_entry!(Trace::MuxError(code.into()));
-                        reset_if_needed(
-                            code,
-                            controller,
-                            port,
-                            &muxes,
-                            &mut muxmap,
-                        );
-                        return Err(code);
-                    }
-                }
-
-                let mut total = 0;
-
-                //
-                // Now iterate over our write/read pairs (we have already
-                // verified that we have an even number of leases).
-                //
-                for i in (0..lease_count).step_by(2) {
-                    let wbuf = caller.borrow(i);
-                    let winfo = wbuf.info().ok_or(ResponseCode::BadArg)?;
-
-                    if !winfo.attributes.contains(LeaseAttributes::READ) {
-                        return Err(ResponseCode::BadArg);
+                );
+            },
+            |(portmap, muxmap), op, msg| match op {
+                Op::WriteRead
+                | Op::WriteReadBlock
+                | Op::WriteReadPec
+                | Op::WriteReadBlockPec => {
+                    let lease_count = msg.lease_count();
+
+                    let (payload, caller) = msg
+                        .fixed::<[u8; 4], usize>()
+                        .ok_or(ResponseCode::BadArg)?;
+
+                    if lease_count < 2 || lease_count % 2 != 0 {
+                        return Err(ResponseCode::IllegalLeaseCount);
                     }
 
-                    let rbuf = caller.borrow(i + 1);
-                    let rinfo = rbuf.info().ok_or(ResponseCode::BadArg)?;
+                    let (addr, controller, port, mux) =
+                        Marshal::unmarshal(payload)?;
 
-                    if winfo.len == 0 && rinfo.len == 0 {
-                        // In a given lease pair, we must have either a write
-                        // OR a read -- while perhaps valid to support both
-                        // being zero as a way of testing an address for a
-                        // NACK, it's not a mode that we (currently) support.
-                        return Err(ResponseCode::BadArg);
+                    if ReservedAddress::from_u8(addr).is_some() {
+                        return Err(ResponseCode::ReservedAddress);
                     }
 
-                    if winfo.len > 255 || rinfo.len > 255 {
-                        // For now, we don't support writing or reading more
-                        // than 255 bytes.
-                        return Err(ResponseCode::BadArg);
-                    }
+                    let controller =
+                        lookup_controller(&controllers, controller)?;
+                    validate_port(&pins, controller.controller, port)?;
 
-                    let mut nread = 0;
-
-                    match controller.write_read(
-                        addr,
-                        winfo.len,
-                        |pos| wbuf.read_at(pos),
-                        // Only the final read operation in a WriteReadBlock is
-                        // a block read; everything else is a normal read.
-                        if op == Op::WriteReadBlock && i == lease_count - 2 {
-                            ReadLength::Variable
-                        } else {
-                            ReadLength::Fixed(rinfo.len)
-                        },
-                        |pos, byte| {
-                            if pos + 1 > nread {
-                                nread = pos + 1;
-                            }
+                    configure_port(portmap, controller, port, &pins);
 
-                            rbuf.write_at(pos, byte)
-                        },
-                        &ctrl,
+                    match configure_mux(
+                        muxmap, controller, port, mux, &muxes, &ctrl,
                     ) {
+                        Ok(_) => {}
                         Err(code) => {
-                            //
-                            // NoDevice errors aren't hugely interesting --
-                            // but on any other error, we want to record the
-                            // address of the failing device, the error code
-                            // and the mux+segment (if specified).
-                            //
-                            if code != ResponseCode::NoDevice {
-                                ringbuf_entry!(Trace::Error(addr, code.into()));
-
-                                if let Some(mux) = mux {
-                                    ringbuf_entry!(Trace::SegmentOnError(mux));
-                                }
-                            }
-
+                            ringbuf_entry!(Trace::MuxError(code.into()));
                             reset_if_needed(
-                                code,
-                                controller,
-                                port,
-                                &muxes,
-                                &mut muxmap,
+                                code, controller, port, &muxes, muxmap,
                             );
                             return Err(code);
                         }
-                        Ok(_) => {
-                            total += nread;
+                    }
+
+                    let mut total = 0;
+
+                    //
+                    // Now iterate over our write/read pairs (we have already
+                    // verified that we have an even number of leases).
+                    //
+                    for i in (0..lease_count).step_by(2) {
+                        let wbuf = caller.borrow(i);
+                        let winfo = wbuf.info().ok_or(ResponseCode::BadArg)?;
+
+                        if !winfo.attributes.contains(LeaseAttributes::READ) {
+                            return Err(ResponseCode::BadArg);
+                        }
+
+                        let rbuf = caller.borrow(i + 1);
+                        let rinfo = rbuf.info().ok_or(ResponseCode::BadArg)?;
+
+                        if winfo.len == 0 && rinfo.len == 0 {
+                            // In a given lease pair, we must have either a write
+                            // OR a read -- while perhaps valid to support both
+                            // being zero as a way of testing an address for a
+                            // NACK, it's not a mode that we (currently) support.
+                            return Err(ResponseCode::BadArg);
+                        }
+
+                        if winfo.len > 255 || rinfo.len > 255 {
+                            // For now, we don't support writing or reading more
+                            // than 255 bytes.
+                            return Err(ResponseCode::BadArg);
+                        }
+
+                        let mut nread = 0;
+
+                        match controller.write_read(
+                            addr,
+                            winfo.len,
+                            |pos| wbuf.read_at(pos),
+                            // Only the final read operation in a WriteReadBlock is
+                            // a block read; everything else is a normal read.
+                            if op.is_block() && i == lease_count - 2 {
+                                ReadLength::Variable
+                            } else {
+                                ReadLength::Fixed(rinfo.len)
+                            },
+                            |pos, byte| {
+                                if pos + 1 > nread {
+                                    nread = pos + 1;
+                                }
+
+                                rbuf.write_at(pos, byte)
+                            },
+                            op.is_pec(),
+                            &ctrl,
+                        ) {
+                            Err(code) => {
+                                //
+                                // NoDevice errors aren't hugely interesting --
+                                // but on any other error, we want to record the
+                                // address of the failing device, the error code
+                                // and the mux+segment (if specified).
+                                //
+                                if code != ResponseCode::NoDevice {
+                                    ringbuf_entry!(Trace::Error(
+                                        addr,
+                                        code.into()
+                                    ));
+
+                                    if let Some(mux) = mux {
+                                        ringbuf_entry!(Trace::SegmentOnError(
+                                            mux
+                                        ));
+                                    }
+                                }
+
+                                reset_if_needed(
+                                    code, controller, port, &muxes, muxmap,
+                                );
+                                return Err(code);
+                            }
+                            Ok(_) => {
+                                total += nread;
+                            }
                         }
                     }
-                }
 
-                caller.reply(total);
-                Ok(())
-            }
-        });
+                    caller.reply(total);
+                    Ok(())
+                }
+            },
+        );
     }
 }
 
@@ -722,6 +930,21 @@ fn configure_muxes(
 
         loop {
             match mux.driver.configure(mux, controller, &sys, ctrl) {
+                Ok(_) if mux.parent.is_some() => {
+                    //
+                    // This mux is behind another mux, whose segments we have
+                    // just disabled (muxes come before any muxes behind
+                    // them) -- so we can't reach it to disable its own
+                    // segments.  Instead, we put its bus into the unknown
+                    // mux state:  before the first transaction on the bus,
+                    // every mux (including this one) will be reached and
+                    // have its segments disabled.
+                    //
+                    let bus = (controller.controller, mux.port);
+                    ringbuf_entry!(Trace::MuxUnknown(bus));
+                    muxmap.insert(bus, MuxState::Unknown);
+                    break;
+                }
                 Ok(_) => {
                     //
                     // We are going to attempt to disable all segments.  If we
@@ -780,4 +1003,156 @@ fn configure_muxes(
     }
 }
 
+/// SMBus Alert Response Address
+const ARA: u8 = 0x0c;
+
+///
+/// Upper bound on the number of devices that we expect to be asserting
+/// ALERT# on any one segment.  Each ARA read clears the alert of the device
+/// that wins arbitration, so this protects us from a device that keeps its
+/// alert asserted.
+///
+const MAX_ALERTS_PER_SEGMENT: usize = 8;
+
+///
+/// Handles ALERT# on each port whose notification is in `bits`: performs ARA
+/// reads on the port and on each of its mux segments with an alert owner,
+/// and notifies the owner of each responding device.
+///
+#[allow(clippy::too_many_arguments)]
+fn handle_alerts(
+    bits: u32,
+    alerts: &[I2cAlert],
+    owners: &[I2cAlertOwner],
+    controllers: &[I2cController<'_>],
+    pins: &[I2cPins],
+    muxes: &[I2cMux<'_>],
+    portmap: &mut PortMap,
+    muxmap: &mut MuxMap,
+    ctrl: &I2cControl,
+) {
+    ringbuf_entry!(Trace::Alert(bits));
+
+    for alert in alerts.iter().filter(|a| a.notification & bits != 0) {
+        let controller = match lookup_controller(controllers, alert.controller)
+        {
+            Ok(controller) => controller,
+            Err(_) => continue,
+        };
+        let port = alert.port;
+        let on_bus = |o: &&I2cAlertOwner| {
+            o.controller == alert.controller && o.port == port
+        };
+
+        configure_port(portmap, controller, port, pins);
+
+        //
+        // Devices that aren't behind a mux see the ARA read no matter which
+        // segment is enabled, so check with all segments disabled first.
+        //
+        alert_response(controller, port, None, owners, muxes, muxmap, ctrl);
+
+        for (i, owner) in owners.iter().enumerate().filter(|(_, o)| on_bus(o)) {
+            let segment = match owner.segment {
+                Some(segment) => segment,
+                None => continue,
+            };
+
+            // Only visit each segment once
+            if owners[..i]
+                .iter()
+                .filter(on_bus)
+                .any(|o| o.segment == Some(segment))
+            {
+                continue;
+            }
+
+            alert_response(
+                controller,
+                port,
+                Some(segment),
+                owners,
+                muxes,
+                muxmap,
+                ctrl,
+            );
+        }
+    }
+}
+
+///
+/// Performs ARA reads on the given segment until no device responds,
+/// notifying the owner of each device that does.
+///
+fn alert_response(
+    controller: &I2cController<'_>,
+    port: PortIndex,
+    segment: Option<(Mux, Segment)>,
+    owners: &[I2cAlertOwner],
+    muxes: &[I2cMux<'_>],
+    muxmap: &mut MuxMap,
+    ctrl: &I2cControl,
+) {
+    if let Err(code) =
+        configure_mux(muxmap, controller, port, segment, muxes, ctrl)
+    {
+        ringbuf_entry!(Trace::AlertError(code.into()));
+        reset_if_needed(code, controller, port, muxes, muxmap);
+        return;
+    }
+
+    for _ in 0..MAX_ALERTS_PER_SEGMENT {
+        let mut response = 0;
+
+        match controller.write_read(
+            ARA,
+            0,
+            |_| None,
+            ReadLength::Fixed(1),
+            |_, byte| {
+                response = byte;
+                Some(())
+            },
+            false,
+            ctrl,
+        ) {
+            Ok(()) => {}
+            Err(ResponseCode::NoDevice) => {
+                // Nobody (else) is asserting ALERT# on this segment
+                return;
+            }
+            Err(code) => {
+                ringbuf_entry!(Trace::AlertError(code.into()));
+                reset_if_needed(code, controller, port, muxes, muxmap);
+                return;
+            }
+        }
+
+        //
+        // The responding device's address is in the upper seven bits.  A
+        // device that isn't behind a mux may answer on any segment, so we
+        // match those regardless of the segment.
+        //
+        let address = response >> 1;
+        ringbuf_entry!(Trace::AlertResponse(
+            (controller.controller, port),
+            address
+        ));
+
+        match owners.iter().find(|o| {
+            o.controller == controller.controller
+                && o.port == port
+                && o.address == address
+                && (o.segment.is_none() || o.segment == segment)
+        }) {
+            Some(owner) => {
+                sys_post(sys_refresh_task_id(owner.task), owner.notification);
+            }
+            None => {
+                ringbuf_entry!(Trace::AlertUnclaimed(address));
+            }
+        }
+    }
+}
+
 include!(concat!(env!("OUT_DIR"), "/notifications.rs"));
@@ -38,6 +38,7 @@ pub mod pca9548;
 use ringbuf::*;
 use userlib::*;
 
+use drv_i2c_api::pec_update;
 use drv_stm32xx_sys_api as sys_api;
 
 pub struct I2cPins {
@@ -115,6 +116,10 @@ pub struct I2cMux<'a> {
     pub id: drv_i2c_api::Mux,
     pub driver: &'a dyn I2cMuxDriver,
 
+    /// The mux and segment that this mux is behind, if it isn't directly on
+    /// the bus
+    pub parent: Option<(drv_i2c_api::Mux, drv_i2c_api::Segment)>,
+
     /// Optional enable / reset line
     ///
     /// When this is high, the chip is enabled; when it is low, the chip is held
@@ -124,6 +129,26 @@ pub struct I2cMux<'a> {
     pub address: u8,
 }
 
+/// A port with an SMBus ALERT# line, which is delivered to us as a
+/// notification
+pub struct I2cAlert {
+    pub controller: drv_i2c_api::Controller,
+    pub port: drv_i2c_api::PortIndex,
+    /// The ALERT# line itself
+    pub pin: sys_api::PinSet,
+    pub notification: u32,
+}
+
+/// A device that may assert ALERT#, and the task to notify when it does
+pub struct I2cAlertOwner {
+    pub controller: drv_i2c_api::Controller,
+    pub port: drv_i2c_api::PortIndex,
+    pub segment: Option<(drv_i2c_api::Mux, drv_i2c_api::Segment)>,
+    pub address: u8,
+    pub task: TaskId,
+    pub notification: u32,
+}
+
 ///
 /// An enum describing the amount to read
 ///
@@ -161,6 +186,7 @@ enum Trace {
     BusySleep,
     Stop,
     RepeatedStart(bool),
+    PecMismatch(u8, u8),
     None,
 }
 
@@ -497,6 +523,12 @@ impl I2cController<'_> {
     /// be non-zero.  Additionally, both lengths must be less than 256 bytes:
     /// the device can support longer buffers, and the implementation could
     /// be extended in the future to allow them.
+    ///
+    /// If `pec` is set, SMBus Packet Error Checking is performed: if there is
+    /// a read, the device's PEC byte is read after the data and checked
+    /// against the transaction; otherwise, a PEC byte is appended to the
+    /// write.  In either case, the PEC byte counts against the 255 byte limit.
+    #[allow(clippy::too_many_arguments)]
     pub fn write_read(
         &self,
         addr: u8,
@@ -504,6 +536,7 @@ impl I2cController<'_> {
         getbyte: impl Fn(usize) -> Option<u8>,
         mut rlen: ReadLength,
         mut putbyte: impl FnMut(usize, u8) -> Option<()>,
+        pec: bool,
         ctrl: &I2cControl,
     ) -> Result<(), drv_i2c_api::ResponseCode> {
         // Assert our preconditions as described above
@@ -514,15 +547,32 @@ impl I2cController<'_> {
             assert!(rlen <= 255);
         }
 
+        //
+        // A PEC byte is sent by us only on a write that isn't followed by a
+        // read; otherwise, it's sent by the device at the end of the read.
+        //
+        let write_pec = pec && rlen == ReadLength::Fixed(0);
+        let read_pec = pec && !write_pec;
+
+        if (write_pec && wlen == 255)
+            || (read_pec && rlen == ReadLength::Fixed(255))
+        {
+            return Err(drv_i2c_api::ResponseCode::BadArg);
+        }
+
         let i2c = self.registers;
         let notification = self.notification;
+        let mut crc = 0;
 
         self.wait_until_notbusy()?;
 
         if wlen > 0 {
+            let nbytes = wlen + usize::from(write_pec);
+            crc = pec_update(crc, addr << 1);
+
             #[rustfmt::skip]
             i2c.cr2.modify(|_, w| { w
-                .nbytes().bits(wlen as u8)
+                .nbytes().bits(nbytes as u8)
                 .autoend().clear_bit()
                 .add10().clear_bit()
                 .sadd().bits((addr << 1).into())
@@ -532,7 +582,7 @@ impl I2cController<'_> {
 
             let mut pos = 0;
 
-            while pos < wlen {
+            while pos < nbytes {
                 loop {
                     let isr = i2c.isr.read();
                     ringbuf_entry!(Trace::WriteISR(isr.bits()));
@@ -552,9 +602,14 @@ impl I2cController<'_> {
                     (ctrl.enable)(notification);
                 }
 
-                // Get a single byte.
-                let byte =
-                    getbyte(pos).ok_or(drv_i2c_api::ResponseCode::BadArg)?;
+                // Get a single byte -- or, if we're past the end of the
+                // buffer, our PEC byte.
+                let byte = if pos < wlen {
+                    getbyte(pos).ok_or(drv_i2c_api::ResponseCode::BadArg)?
+                } else {
+                    crc
+                };
+                crc = pec_update(crc, byte);
 
                 // And send it!
                 i2c.txdr.write(|w| w.txdata().bits(byte));
@@ -590,10 +645,14 @@ impl I2cController<'_> {
             // permit a STOP between a register address write and a subsequent
             // read).
             //
+            crc = pec_update(crc, (addr << 1) | 1);
+
             if let ReadLength::Fixed(rlen) = rlen {
+                let nbytes = rlen + usize::from(read_pec);
+
                 #[rustfmt::skip]
                 i2c.cr2.modify(|_, w| { w
-                    .nbytes().bits(rlen as u8)
+                    .nbytes().bits(nbytes as u8)
                     .autoend().clear_bit()
                     .add10().clear_bit()
                     .sadd().bits((addr << 1).into())
@@ -617,7 +676,7 @@ impl I2cController<'_> {
 
             loop {
                 if let ReadLength::Fixed(rlen) = rlen {
-                    if pos >= rlen {
+                    if pos >= rlen + usize::from(read_pec) {
                         break;
                     }
                 }
@@ -645,9 +704,32 @@ impl I2cController<'_> {
                 let byte: u8 = i2c.rxdr.read().rxdata().bits();
 
                 if rlen == ReadLength::Variable {
+                    //
+                    // The byte count is included in the PEC, but the PEC
+                    // byte itself is not included in the byte count.
+                    //
+                    let nbytes = match (read_pec, byte.checked_add(1)) {
+                        (false, _) => byte,
+                        (true, Some(nbytes)) => nbytes,
+                        (true, None) => {
+                            //
+                            // A count of 255 leaves no room for the PEC
+                            // byte, so we give up on this transfer -- but
+                            // send a STOP rather than leave the bus held.
+                            //
+                            #[rustfmt::skip]
+                            i2c.cr2.modify(|_, w| { w
+                                .reload().clear_bit()
+                                .stop().set_bit()
+                            });
+                            return Err(drv_i2c_api::ResponseCode::BadArg);
+                        }
+                    };
+                    crc = pec_update(crc, byte);
+
                     #[rustfmt::skip]
                     i2c.cr2.modify(|_, w| { w
-                        .nbytes().bits(byte)
+                        .nbytes().bits(nbytes)
                         .reload().clear_bit()
                     });
 
@@ -655,6 +737,19 @@ impl I2cController<'_> {
                     continue;
                 }
 
+                if rlen == ReadLength::Fixed(pos) {
+                    //
+                    // This is the PEC byte; we defer checking it until the
+                    // transfer is complete, so as to leave the bus in a
+                    // known state.  (Folding a correct PEC byte into the
+                    // CRC of the bytes that preceded it yields zero.)
+                    //
+                    crc = pec_update(crc, byte);
+                    pos += 1;
+                    continue;
+                }
+
+                crc = pec_update(crc, byte);
                 putbyte(pos, byte).ok_or(drv_i2c_api::ResponseCode::BadArg)?;
                 pos += 1;
             }
@@ -681,6 +776,14 @@ impl I2cController<'_> {
         //
         i2c.cr2.modify(|_, w| w.stop().set_bit());
 
+        //
+        // If we read a PEC byte, our CRC will only be zero if it matched.
+        //
+        if read_pec && crc != 0 {
+            ringbuf_entry!(Trace::PecMismatch(addr, crc));
+            return Err(drv_i2c_api::ResponseCode::PecMismatch);
+        }
+
         Ok(())
     }
 
@@ -85,6 +85,7 @@ fn read_regs(
             rbuf[pos] = byte;
             Some(())
         },
+        false,
         ctrl,
     ) {
         Err(code) => Err(mux.error_code(code)),
@@ -129,6 +130,7 @@ fn write_reg(
         |pos| Some(wbuf[pos]),
         ReadLength::Fixed(0),
         |_, _| Some(()),
+        false,
         ctrl,
     ) {
         Err(code) => Err(mux.error_code(code)),
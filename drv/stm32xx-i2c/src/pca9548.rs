@@ -82,6 +82,7 @@ impl I2cMuxDriver for Pca9548 {
             |_| Some(reg.0),
             ReadLength::Fixed(0),
             |_, _| Some(()),
+            false,
             ctrl,
         ) {
             Err(code) => Err(mux.error_code(code)),
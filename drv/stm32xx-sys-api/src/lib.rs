@@ -31,6 +31,13 @@ pub enum RccError {
     NoSuchPeripheral = 1,
 }
 
+#[derive(Copy, Clone, Debug, FromPrimitive, IdolError)]
+#[repr(u32)]
+pub enum GpioIrqError {
+    /// The SYS task was built without the `exti` feature.
+    NotSupported = 1,
+}
+
 impl Sys {
     /// Requests that the clock to a peripheral be turned on.
     ///
@@ -282,6 +289,19 @@ impl Sys {
         self.gpio_set(pinset);
         userlib::hl::sleep_for(wait_time_ms as u64);
     }
+
+    /// Unmasks the GPIO interrupts that are routed (in `app.toml`) to the
+    /// calling task's notification bits in `mask`.
+    pub fn gpio_irq_enable(&self, mask: u32) -> Result<(), GpioIrqError> {
+        self.gpio_irq_control(0, mask)
+    }
+
+    /// Masks the GPIO interrupts that are routed (in `app.toml`) to the
+    /// calling task's notification bits in `mask`. Edges that arrive while
+    /// masked are not latched.
+    pub fn gpio_irq_disable(&self, mask: u32) -> Result<(), GpioIrqError> {
+        self.gpio_irq_control(mask, 0)
+    }
 }
 
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
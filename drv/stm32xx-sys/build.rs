@@ -2,12 +2,141 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct SysConfig {
+    /// GPIO pins whose edges are delivered to other tasks as notifications,
+    /// keyed by a descriptive name.
+    #[serde(default)]
+    gpio_irqs: BTreeMap<String, GpioIrqConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct GpioIrqConfig {
+    port: char,
+    pin: u8,
+    edge: Edge,
+    owner: TaskNote,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct TaskNote {
+    name: String,
+    notification: String,
+}
+
+fn main() -> Result<()> {
     idol::server::build_server_support(
         "../../idl/stm32xx-sys.idol",
         "server_stub.rs",
         idol::server::ServerStyle::InOrder,
+    )
+    .map_err(|e| anyhow!(e))?;
+
+    let cfg = build_util::task_maybe_config::<SysConfig>()?.unwrap_or_default();
+
+    if build_util::has_feature("exti") {
+        build_util::build_notifications()?;
+        generate_exti_config(&cfg)?;
+    } else if !cfg.gpio_irqs.is_empty() {
+        bail!("`gpio-irqs` are configured but the `exti` feature is disabled");
+    }
+
+    Ok(())
+}
+
+fn generate_exti_config(cfg: &SysConfig) -> Result<()> {
+    let out_dir = build_util::out_dir();
+    let dest_path = out_dir.join("exti_config.rs");
+    let mut out = std::fs::File::create(dest_path)?;
+
+    let mut table: [Option<(&str, &GpioIrqConfig)>; 16] = Default::default();
+    for (name, irq) in &cfg.gpio_irqs {
+        if irq.pin >= 16 {
+            bail!("gpio-irq `{name}`: pin {} is out of range", irq.pin);
+        }
+        if !('A'..='K').contains(&irq.port) {
+            bail!("gpio-irq `{name}`: invalid port `{}`", irq.port);
+        }
+        // The EXTI block has a single line per pin number, shared between
+        // all of the ports, so two pins with the same number can't both be
+        // used as interrupt sources.
+        if let Some((other, _)) = table[irq.pin as usize] {
+            bail!("gpio-irqs `{other}` and `{name}` both use pin {}", irq.pin);
+        }
+
+        let task = build_util::other_task_full_config_toml(&irq.owner.name)
+            .map_err(|_| {
+                anyhow!(
+                    "gpio-irq `{name}`: unknown owner task `{}`",
+                    irq.owner.name
+                )
+            })?;
+        if !task.notifications.contains(&irq.owner.notification) {
+            bail!(
+                "gpio-irq `{name}`: task `{}` has no notification `{}`",
+                irq.owner.name,
+                irq.owner.notification
+            );
+        }
+
+        table[irq.pin as usize] = Some((name, irq));
+    }
+
+    writeln!(
+        out,
+        "pub(crate) const EXTI_DISPATCH_TABLE: [Option<ExtiDispatch>; 16] = ["
     )?;
+    for entry in &table {
+        match entry {
+            None => writeln!(out, "    None,")?,
+            Some((name, irq)) => {
+                // EXTI port selection codes start at 0 for port A and count
+                // up by letter, whether or not the part has every port.
+                let port = irq.port as u8 - b'A';
+                let (rising, falling) = match irq.edge {
+                    Edge::Rising => (true, false),
+                    Edge::Falling => (false, true),
+                    Edge::Both => (true, true),
+                };
+                let task = &irq.owner.name;
+                let note = format!(
+                    "{}_MASK",
+                    irq.owner.notification.to_uppercase().replace('-', "_")
+                );
+                writeln!(out, "    // {name}")?;
+                writeln!(
+                    out,
+                    "    Some(ExtiDispatch {{
+        port: {port},
+        rising: {rising},
+        falling: {falling},
+        task: userlib::TaskId::for_index_and_gen(
+            hubris_num_tasks::Task::{task} as usize,
+            userlib::Generation::ZERO,
+        ),
+        mask: crate::notifications::{task}::{note},
+    }}),"
+                )?;
+            }
+        }
+    }
+    writeln!(out, "];")?;
 
     Ok(())
 }
@@ -0,0 +1,234 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Delivery of GPIO edge interrupts to other tasks, via the EXTI block.
+//!
+//! Pins are routed to tasks in `app.toml`, in the `sys` task's config:
+//!
+//! ```toml
+//! [tasks.sys]
+//! features = ["exti"]
+//! uses = ["rcc", "gpios", "system_flash", "syscfg", "exti"]
+//! notifications = ["exti-irq"]
+//! interrupts = { "exti.exti3" = "exti-irq" }
+//!
+//! [tasks.sys.config.gpio-irqs.rot_irq]
+//! port = "E"
+//! pin = 3
+//! edge = "falling"
+//! owner = { name = "sprot", notification = "rot-irq" }
+//! ```
+//!
+//! (On the STM32G0, `syscfg` is not needed, and `gpios` is spelled `gpio`.)
+//!
+//! Every line starts out masked. The owning task unmasks it with
+//! `gpio_irq_control`; from then on, each edge posts the configured
+//! notification to the owner and is latched until the owner collects it with
+//! `gpio_irq_pending`. Since multiple pins may share a notification bit, the
+//! notification alone says "go look", not "exactly one edge happened".
+
+use crate::{device, notifications};
+use userlib::*;
+
+/// Routing information for a single EXTI line, generated from `app.toml`.
+pub(crate) struct ExtiDispatch {
+    /// EXTI port selection code (0 for port A, 1 for port B, ...)
+    pub port: u8,
+    pub rising: bool,
+    pub falling: bool,
+    /// Task to notify when the line fires
+    pub task: TaskId,
+    /// Notification bit(s) to post to `task`
+    pub mask: u32,
+}
+
+include!(concat!(env!("OUT_DIR"), "/exti_config.rs"));
+
+pub(crate) struct Exti {
+    /// Lines that have fired since their owner last asked.
+    pending: u16,
+}
+
+impl Exti {
+    /// Routes each configured pin to its EXTI line, sets its edge
+    /// sensitivity, masks it, and enables our interrupt.
+    ///
+    /// On the STM32H7, the SYSCFG clock must already be on.
+    pub fn new() -> Self {
+        let exti = exti_regs();
+
+        let mut exticr = [0u32; 4];
+        let mut rising = 0u32;
+        let mut falling = 0u32;
+        let mut used = 0u16;
+        for (i, d) in configured_lines() {
+            used |= 1 << i;
+            if d.rising {
+                rising |= 1 << i;
+            }
+            if d.falling {
+                falling |= 1 << i;
+            }
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "family-stm32h7")] {
+                    // Four 4-bit fields per SYSCFG_EXTICRx register.
+                    exticr[i / 4] |= u32::from(d.port) << ((i % 4) * 4);
+                } else {
+                    // Four 8-bit fields per EXTI_EXTICRx register.
+                    exticr[i / 4] |= u32::from(d.port) << ((i % 4) * 8);
+                }
+            }
+        }
+
+        let used_bits = u32::from(used);
+
+        // Each EXTICRx register has its own type, so these can't be handled
+        // as an array.
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "family-stm32h7")] {
+                // Safety: as with the RCC, this is only unsafe in the API.
+                let syscfg = unsafe { &*device::SYSCFG::ptr() };
+                syscfg.exticr1.write(|w| unsafe { w.bits(exticr[0]) });
+                syscfg.exticr2.write(|w| unsafe { w.bits(exticr[1]) });
+                syscfg.exticr3.write(|w| unsafe { w.bits(exticr[2]) });
+                syscfg.exticr4.write(|w| unsafe { w.bits(exticr[3]) });
+                exti.cpuimr1
+                    .modify(|r, w| unsafe { w.bits(r.bits() & !used_bits) });
+            } else {
+                exti.exticr1.write(|w| unsafe { w.bits(exticr[0]) });
+                exti.exticr2.write(|w| unsafe { w.bits(exticr[1]) });
+                exti.exticr3.write(|w| unsafe { w.bits(exticr[2]) });
+                exti.exticr4.write(|w| unsafe { w.bits(exticr[3]) });
+                exti.imr1
+                    .modify(|r, w| unsafe { w.bits(r.bits() & !used_bits) });
+            }
+        }
+
+        exti.rtsr1
+            .modify(|r, w| unsafe { w.bits((r.bits() & !used_bits) | rising) });
+        exti.ftsr1.modify(|r, w| unsafe {
+            w.bits((r.bits() & !used_bits) | falling)
+        });
+
+        // Discard anything that showed up while we were setting up.
+        clear_pending(used);
+
+        sys_irq_control(notifications::EXTI_IRQ_MASK, true);
+
+        Self { pending: 0 }
+    }
+
+    /// Latches and forwards every line that has fired, then re-arms our
+    /// interrupt.
+    pub fn handle_interrupt(&mut self) {
+        let fired = read_pending();
+        clear_pending(fired);
+
+        for (i, d) in configured_lines() {
+            if fired & (1 << i) != 0 {
+                self.pending |= 1 << i;
+                sys_post(sys_refresh_task_id(d.task), d.mask);
+            }
+        }
+
+        sys_irq_control(notifications::EXTI_IRQ_MASK, true);
+    }
+
+    /// Masks the caller's lines selected by `disable_mask`, then unmasks
+    /// those selected by `enable_mask`. Lines that are newly unmasked start
+    /// with no edges pending.
+    pub fn control(
+        &mut self,
+        caller: TaskId,
+        disable_mask: u32,
+        enable_mask: u32,
+    ) {
+        let disable = lines_for(caller, disable_mask);
+        let enable = lines_for(caller, enable_mask);
+        let exti = exti_regs();
+
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "family-stm32h7")] {
+                let imr = &exti.cpuimr1;
+            } else {
+                let imr = &exti.imr1;
+            }
+        }
+
+        imr.modify(|r, w| unsafe { w.bits(r.bits() & !u32::from(disable)) });
+
+        let newly_enabled = enable & !(imr.read().bits() as u16);
+        clear_pending(newly_enabled);
+        self.pending &= !newly_enabled;
+
+        imr.modify(|r, w| unsafe { w.bits(r.bits() | u32::from(enable)) });
+    }
+
+    /// Returns (and forgets) the subset of the caller's notification bits in
+    /// `mask` that belong to lines with latched edges.
+    pub fn take_pending(&mut self, caller: TaskId, mask: u32) -> u32 {
+        let lines = lines_for(caller, mask) & self.pending;
+        self.pending &= !lines;
+
+        let mut bits = 0;
+        for (i, d) in configured_lines() {
+            if lines & (1 << i) != 0 {
+                bits |= d.mask;
+            }
+        }
+        bits & mask
+    }
+}
+
+fn exti_regs() -> &'static device::exti::RegisterBlock {
+    // Safety: as with the RCC, this is only unsafe in the API; we're the only
+    // task with the EXTI block mapped.
+    unsafe { &*device::EXTI::ptr() }
+}
+
+/// Iterates over the lines that have been routed to a task, as
+/// `(line, dispatch)` pairs.
+fn configured_lines() -> impl Iterator<Item = (usize, &'static ExtiDispatch)> {
+    EXTI_DISPATCH_TABLE
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| e.as_ref().map(|d| (i, d)))
+}
+
+/// Returns the EXTI lines owned by `caller` and routed to any notification
+/// bit in `mask`.
+fn lines_for(caller: TaskId, mask: u32) -> u16 {
+    let mut lines = 0;
+    for (i, d) in configured_lines() {
+        if d.task.index() == caller.index() && d.mask & mask != 0 {
+            lines |= 1 << i;
+        }
+    }
+    lines
+}
+
+fn read_pending() -> u16 {
+    let exti = exti_regs();
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "family-stm32h7")] {
+            exti.cpupr1.read().bits() as u16
+        } else {
+            (exti.rpr1.read().bits() | exti.fpr1.read().bits()) as u16
+        }
+    }
+}
+
+fn clear_pending(lines: u16) {
+    let exti = exti_regs();
+    let lines = u32::from(lines);
+    // Pending bits are cleared by writing 1; zeros are ignored.
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "family-stm32h7")] {
+            exti.cpupr1.write(|w| unsafe { w.bits(lines) });
+        } else {
+            exti.rpr1.write(|w| unsafe { w.bits(lines) });
+            exti.fpr1.write(|w| unsafe { w.bits(lines) });
+        }
+    }
+}
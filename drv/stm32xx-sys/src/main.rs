@@ -3,6 +3,10 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 //! A driver for the STM32xx RCC and GPIO blocks, combined for compactness.
+//!
+//! With the `exti` feature, this task also owns the EXTI block, and delivers
+//! edges on selected GPIO pins to other tasks as notifications; see the
+//! `exti` module for configuration.
 
 #![no_std]
 #![no_main]
@@ -35,8 +39,11 @@ cfg_if::cfg_if! {
     }
 }
 
+#[cfg(feature = "exti")]
+mod exti;
+
 use drv_stm32xx_gpio_common::{server::get_gpio_regs, Port};
-use drv_stm32xx_sys_api::{Group, RccError};
+use drv_stm32xx_sys_api::{GpioIrqError, Group, RccError};
 use idol_runtime::RequestError;
 use task_jefe_api::{Jefe, ResetReason};
 use userlib::*;
@@ -128,6 +135,10 @@ fn main() -> ! {
                     .gpioken()
                     .set_bit()
             });
+
+            // SYSCFG holds the EXTI port selection registers.
+            #[cfg(feature = "exti")]
+            rcc.apb4enr.modify(|_, w| w.syscfgen().set_bit());
         }
     }
 
@@ -138,14 +149,23 @@ fn main() -> ! {
 
     // Field messages.
     let mut buffer = [0u8; idl::INCOMING_SIZE];
-    let mut server = ServerImpl { rcc };
+    let mut server = ServerImpl {
+        rcc,
+        #[cfg(feature = "exti")]
+        exti: exti::Exti::new(),
+    };
     loop {
+        #[cfg(feature = "exti")]
+        idol_runtime::dispatch_n(&mut buffer, &mut server);
+        #[cfg(not(feature = "exti"))]
         idol_runtime::dispatch(&mut buffer, &mut server);
     }
 }
 
 struct ServerImpl<'a> {
     rcc: &'a device::rcc::RegisterBlock,
+    #[cfg(feature = "exti")]
+    exti: exti::Exti,
 }
 
 impl ServerImpl<'_> {
@@ -242,6 +262,38 @@ impl idl::InOrderSysImpl for ServerImpl<'_> {
         Ok(unsafe { get_gpio_regs(port) }.read())
     }
 
+    fn gpio_irq_control(
+        &mut self,
+        msg: &RecvMessage,
+        disable_mask: u32,
+        enable_mask: u32,
+    ) -> Result<(), RequestError<GpioIrqError>> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "exti")] {
+                self.exti.control(msg.sender, disable_mask, enable_mask);
+                Ok(())
+            } else {
+                let _ = (msg, disable_mask, enable_mask);
+                Err(GpioIrqError::NotSupported.into())
+            }
+        }
+    }
+
+    fn gpio_irq_pending(
+        &mut self,
+        msg: &RecvMessage,
+        mask: u32,
+    ) -> Result<u32, RequestError<GpioIrqError>> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "exti")] {
+                Ok(self.exti.take_pending(msg.sender, mask))
+            } else {
+                let _ = (msg, mask);
+                Err(GpioIrqError::NotSupported.into())
+            }
+        }
+    }
+
     fn read_uid(
         &mut self,
         _: &RecvMessage,
@@ -250,6 +302,19 @@ impl idl::InOrderSysImpl for ServerImpl<'_> {
     }
 }
 
+#[cfg(feature = "exti")]
+impl idol_runtime::NotificationHandler for ServerImpl<'_> {
+    fn current_notification_mask(&self) -> u32 {
+        notifications::EXTI_IRQ_MASK
+    }
+
+    fn handle_notification(&mut self, bits: u32) {
+        if bits & notifications::EXTI_IRQ_MASK != 0 {
+            self.exti.handle_interrupt();
+        }
+    }
+}
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "family-stm32g0")] {
         fn enable_clock(
@@ -477,7 +542,10 @@ cfg_if::cfg_if! {
 }
 
 mod idl {
-    use super::{Port, RccError};
+    use super::{GpioIrqError, Port, RccError};
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
+
+#[cfg(feature = "exti")]
+include!(concat!(env!("OUT_DIR"), "/notifications.rs"));
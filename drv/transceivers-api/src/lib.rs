@@ -20,6 +20,7 @@ pub enum TransceiversError {
     InvalidPowerState,
     InvalidModuleResult,
     LedI2cError,
+    DomUnavailable,
 
     #[idol(server_death)]
     ServerRestarted,
@@ -59,6 +60,75 @@ pub const PAGE_SIZE_BYTES: usize = 128;
 /// ports.
 pub const NUM_PORTS: u8 = 32;
 
+/// Number of lanes for which we report digital diagnostics (DOM)
+///
+/// Both SFF-8636 and QSFP form-factor CMIS modules have at most four lanes.
+pub const NUM_DOM_LANES: usize = 4;
+
+/// Bits within each field of [`DomFlags`]
+pub const DOM_HIGH_ALARM: u8 = 1 << 0;
+pub const DOM_LOW_ALARM: u8 = 1 << 1;
+pub const DOM_HIGH_WARNING: u8 = 1 << 2;
+pub const DOM_LOW_WARNING: u8 = 1 << 3;
+
+/// Alarm and warning thresholds for a single monitored value, as advertised
+/// by the module.
+#[derive(Copy, Clone, Default, Debug, PartialEq, FromBytes, AsBytes)]
+#[repr(C)]
+pub struct DomThreshold {
+    pub high_alarm: f32,
+    pub low_alarm: f32,
+    pub high_warning: f32,
+    pub low_warning: f32,
+}
+
+/// Module-advertised thresholds for each monitored value
+///
+/// Temperatures are in degrees Celsius, voltages in volts, bias currents in
+/// amperes, and optical powers in watts, matching the values posted to the
+/// `sensor` task.
+#[derive(Copy, Clone, Default, Debug, PartialEq, FromBytes, AsBytes)]
+#[repr(C)]
+pub struct DomThresholds {
+    pub temperature: DomThreshold,
+    pub supply_voltage: DomThreshold,
+    pub tx_bias: DomThreshold,
+    pub tx_power: DomThreshold,
+    pub rx_power: DomThreshold,
+}
+
+/// Latched alarm and warning flags for each monitored value
+///
+/// Each field is a combination of `DOM_HIGH_ALARM`, `DOM_LOW_ALARM`,
+/// `DOM_HIGH_WARNING`, and `DOM_LOW_WARNING`.  Modules clear their flags when
+/// they are read, so the server accumulates them until they are explicitly
+/// cleared with `clear_dom_flags`.
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, FromBytes, AsBytes)]
+#[repr(C)]
+pub struct DomFlags {
+    pub temperature: u8,
+    pub supply_voltage: u8,
+    pub tx_bias: [u8; NUM_DOM_LANES],
+    pub tx_power: [u8; NUM_DOM_LANES],
+    pub rx_power: [u8; NUM_DOM_LANES],
+}
+
+impl DomFlags {
+    pub fn merge(&mut self, other: &DomFlags) {
+        self.temperature |= other.temperature;
+        self.supply_voltage |= other.supply_voltage;
+        for i in 0..NUM_DOM_LANES {
+            self.tx_bias[i] |= other.tx_bias[i];
+            self.tx_power[i] |= other.tx_power[i];
+            self.rx_power[i] |= other.rx_power[i];
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 pub const TRANSCEIVER_TEMPERATURE_SENSORS: [SensorId; NUM_PORTS as usize] = [
@@ -95,6 +165,118 @@ pub const TRANSCEIVER_TEMPERATURE_SENSORS: [SensorId; NUM_PORTS as usize] = [
     other_sensors::QSFP_XCVR30_TEMPERATURE_SENSOR,
     other_sensors::QSFP_XCVR31_TEMPERATURE_SENSOR,
 ];
+
+pub const TRANSCEIVER_VOLTAGE_SENSORS: [SensorId; NUM_PORTS as usize] = [
+    other_sensors::QSFP_XCVR0_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR1_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR2_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR3_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR4_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR5_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR6_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR7_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR8_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR9_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR10_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR11_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR12_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR13_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR14_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR15_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR16_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR17_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR18_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR19_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR20_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR21_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR22_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR23_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR24_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR25_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR26_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR27_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR28_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR29_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR30_DOM_VOLTAGE_SENSOR,
+    other_sensors::QSFP_XCVR31_DOM_VOLTAGE_SENSOR,
+];
+
+/// Per-lane TX bias current sensors
+pub const TRANSCEIVER_CURRENT_SENSORS: [[SensorId; NUM_DOM_LANES];
+    NUM_PORTS as usize] = [
+    other_sensors::QSFP_XCVR0_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR1_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR2_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR3_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR4_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR5_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR6_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR7_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR8_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR9_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR10_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR11_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR12_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR13_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR14_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR15_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR16_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR17_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR18_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR19_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR20_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR21_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR22_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR23_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR24_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR25_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR26_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR27_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR28_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR29_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR30_DOM_CURRENT_SENSORS,
+    other_sensors::QSFP_XCVR31_DOM_CURRENT_SENSORS,
+];
+
+/// Per-lane optical power sensors
+///
+/// The first `NUM_DOM_LANES` sensors for each port are TX power, and the
+/// remaining `NUM_DOM_LANES` are RX power.
+pub const TRANSCEIVER_POWER_SENSORS: [[SensorId; 2 * NUM_DOM_LANES];
+    NUM_PORTS as usize] = [
+    other_sensors::QSFP_XCVR0_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR1_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR2_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR3_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR4_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR5_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR6_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR7_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR8_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR9_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR10_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR11_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR12_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR13_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR14_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR15_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR16_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR17_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR18_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR19_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR20_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR21_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR22_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR23_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR24_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR25_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR26_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR27_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR28_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR29_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR30_DOM_POWER_SENSORS,
+    other_sensors::QSFP_XCVR31_DOM_POWER_SENSORS,
+];
 ////////////////////////////////////////////////////////////////////////////////
 
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
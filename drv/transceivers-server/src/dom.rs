@@ -0,0 +1,546 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Digital diagnostic monitoring (DOM) of transceivers
+//!
+//! In addition to temperature (which is handled by the thermal loop in
+//! `main.rs`), modules report their supply voltage and per-lane TX bias, TX
+//! power, and RX power.  We periodically read these values and post them to
+//! the `sensor` task.  We also cache the alarm and warning thresholds that
+//! each module advertises, and accumulate the (clear-on-read) flags that
+//! modules latch when a value crosses one of those thresholds.
+//!
+//! Like `udp.rs`, this simply adds more functions to our existing
+//! `ServerImpl`.
+use crate::ServerImpl;
+use drv_fpga_api::FpgaError;
+use drv_sidecar_front_io::{transceivers::LogicalPort, Reg};
+use drv_transceivers_api::{
+    DomFlags, DomThreshold, DomThresholds, DOM_HIGH_ALARM, DOM_HIGH_WARNING,
+    DOM_LOW_ALARM, DOM_LOW_WARNING, NUM_DOM_LANES, NUM_PORTS,
+    TRANSCEIVER_CURRENT_SENSORS, TRANSCEIVER_POWER_SENSORS,
+    TRANSCEIVER_VOLTAGE_SENSORS,
+};
+use ringbuf::*;
+use task_sensor_api::{NoData, SensorId};
+use transceiver_messages::mgmt::ManagementInterface;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Trace {
+    None,
+    Supported(usize),
+    Unsupported(usize),
+    ProbeError(usize, FpgaError),
+    ReadError(usize, FpgaError),
+    FlagsRaised(usize, DomFlags),
+    SensorError(usize, task_sensor_api::SensorError),
+}
+
+ringbuf!(Trace, 16, Trace::None);
+
+/// What we know about a module's support for digital diagnostics
+#[derive(Copy, Clone)]
+pub(crate) enum DomState {
+    /// We haven't talked to this module yet
+    Unknown,
+
+    /// The module has flat memory (e.g. a passive copper cable), so it has no
+    /// thresholds or lane monitors for us to read.
+    Unsupported,
+
+    /// The module has paged memory, and we have read its thresholds.
+    Supported {
+        thresholds: DomThresholds,
+
+        /// CMIS modules may scale their TX bias current readings
+        bias_scale: f32,
+    },
+}
+
+/// A single set of readings from a module
+struct DomReadings {
+    supply_voltage: f32,
+    tx_bias: [f32; NUM_DOM_LANES],
+    tx_power: [f32; NUM_DOM_LANES],
+    rx_power: [f32; NUM_DOM_LANES],
+}
+
+// Common to both CMIS and SFF-8636
+const BANK_SELECT: u8 = 0x7E;
+const PAGE_SELECT: u8 = 0x7F;
+
+/// Byte in the lower page which indicates a flat (unpaged) memory map
+const FLAT_MEM_REG: u8 = 2;
+
+// SFF-8636, Tables 6-4, 6-7, and 6-26
+const SFF8636_FLAT_MEM: u8 = 1 << 2;
+const SFF8636_FLAGS_REG: u8 = 6;
+const SFF8636_MONITORS_REG: u8 = 26;
+const SFF8636_THRESHOLDS_PAGE: u8 = 0x03;
+
+// CMIS 5.0, Tables 8-4, 8-9, 8-31, 8-62, and 8-77
+const CMIS_FLAT_MEM: u8 = 1 << 7;
+const CMIS_MODULE_FLAGS_REG: u8 = 9;
+const CMIS_SUPPLY_VOLTAGE_REG: u8 = 16;
+const CMIS_BIAS_SCALE_PAGE: u8 = 0x01;
+const CMIS_BIAS_SCALE_REG: u8 = 160;
+const CMIS_THRESHOLDS_PAGE: u8 = 0x02;
+const CMIS_LANE_PAGE: u8 = 0x11;
+const CMIS_LANE_FLAGS_REG: u8 = 139;
+
+/// Both specs put the thresholds at the start of the upper page
+const THRESHOLDS_REG: u8 = 128;
+
+/// Supply voltage is reported in units of 100 µV
+const VOLTS_PER_LSB: f32 = 100e-6;
+
+/// TX bias is reported in units of 2 µA (before any CMIS scaling)
+const AMPERES_PER_LSB: f32 = 2e-6;
+
+/// Optical power is reported in units of 0.1 µW
+const WATTS_PER_LSB: f32 = 0.1e-6;
+
+fn u16_at(buf: &[u8], offset: usize) -> f32 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]]) as f32
+}
+
+/// Decodes a threshold block of four big-endian values, ordered as high
+/// alarm, low alarm, high warning, and low warning (common to both specs).
+fn threshold(buf: &[u8], offset: usize, scale: f32) -> DomThreshold {
+    DomThreshold {
+        high_alarm: u16_at(buf, offset) * scale,
+        low_alarm: u16_at(buf, offset + 2) * scale,
+        high_warning: u16_at(buf, offset + 4) * scale,
+        low_warning: u16_at(buf, offset + 6) * scale,
+    }
+}
+
+/// Temperature thresholds are signed, in units of 1/256 °C
+fn temperature_threshold(buf: &[u8], offset: usize) -> DomThreshold {
+    let t = |o: usize| {
+        i16::from_be_bytes([buf[offset + o], buf[offset + o + 1]]) as f32
+            / 256.0
+    };
+    DomThreshold {
+        high_alarm: t(0),
+        low_alarm: t(2),
+        high_warning: t(4),
+        low_warning: t(6),
+    }
+}
+
+/// Builds a set of `DomFlags` bits from individual flags
+fn flags(
+    high_alarm: bool,
+    low_alarm: bool,
+    high_warning: bool,
+    low_warning: bool,
+) -> u8 {
+    let mut out = 0;
+    if high_alarm {
+        out |= DOM_HIGH_ALARM;
+    }
+    if low_alarm {
+        out |= DOM_LOW_ALARM;
+    }
+    if high_warning {
+        out |= DOM_HIGH_WARNING;
+    }
+    if low_warning {
+        out |= DOM_LOW_WARNING;
+    }
+    out
+}
+
+/// SFF-8636 packs flags into nibbles, ordered from the MSB as high alarm, low
+/// alarm, high warning, and low warning.
+fn sff8636_nibble(n: u8) -> u8 {
+    flags(n & 0b1000 != 0, n & 0b100 != 0, n & 0b10 != 0, n & 0b1 != 0)
+}
+
+/// CMIS module flags are packed into nibbles in the opposite order.
+fn cmis_nibble(n: u8) -> u8 {
+    flags(n & 0b1 != 0, n & 0b10 != 0, n & 0b100 != 0, n & 0b1000 != 0)
+}
+
+/// CMIS lane flags are one byte per flag, with a bit per lane.
+fn cmis_lane(buf: &[u8], lane: usize) -> u8 {
+    let bit = |b: u8| b & (1 << lane) != 0;
+    flags(bit(buf[0]), bit(buf[1]), bit(buf[2]), bit(buf[3]))
+}
+
+impl ServerImpl {
+    /// Waits for an I2C transaction on the given port to complete, returning
+    /// the status byte (as an `ImplError`) on failure.
+    ///
+    /// `buf[0]` receives the status byte, and the remainder of `buf` receives
+    /// the contents of the read buffer.
+    fn dom_wait(
+        &self,
+        port: LogicalPort,
+        buf: &mut [u8],
+    ) -> Result<(), FpgaError> {
+        loop {
+            self.transceivers
+                .get_i2c_status_and_read_buffer(port, buf)?;
+            let status = buf[0];
+            if status & Reg::QSFP::PORT0_STATUS::BUSY == 0 {
+                if status & Reg::QSFP::PORT0_STATUS::ERROR != 0 {
+                    return Err(FpgaError::ImplError(status));
+                }
+                return Ok(());
+            }
+            userlib::hl::sleep_for(1);
+        }
+    }
+
+    /// Reads `out.len()` bytes from a module, selecting the given upper page
+    /// beforehand if necessary.
+    fn dom_read(
+        &self,
+        port: LogicalPort,
+        interface: ManagementInterface,
+        page: Option<u8>,
+        reg: u8,
+        out: &mut [u8],
+    ) -> Result<(), FpgaError> {
+        if let Some(page) = page {
+            let result = if interface == ManagementInterface::Cmis {
+                // Lane-specific pages are banked; we only care about bank 0,
+                // and selecting it is harmless for unbanked pages.
+                self.transceivers.set_i2c_write_buffer(&[0, page]);
+                self.transceivers.setup_i2c_write(
+                    BANK_SELECT,
+                    2,
+                    port.as_mask(),
+                )
+            } else {
+                self.transceivers.set_i2c_write_buffer(&[page]);
+                self.transceivers.setup_i2c_write(
+                    PAGE_SELECT,
+                    1,
+                    port.as_mask(),
+                )
+            };
+            if !result.error().is_empty() {
+                return Err(FpgaError::CommsError);
+            }
+            self.dom_wait(port, &mut [0u8])?;
+        }
+
+        let result = self.transceivers.setup_i2c_read(
+            reg,
+            out.len() as u8,
+            port.as_mask(),
+        );
+        if !result.error().is_empty() {
+            return Err(FpgaError::CommsError);
+        }
+
+        let mut buf = [0u8; 129];
+        let buf = &mut buf[..out.len() + 1];
+        self.dom_wait(port, buf)?;
+        out.copy_from_slice(&buf[1..]);
+        Ok(())
+    }
+
+    /// Determines whether a module supports DOM, reading its thresholds if
+    /// so.
+    fn dom_probe(
+        &self,
+        port: LogicalPort,
+        interface: ManagementInterface,
+    ) -> Result<DomState, FpgaError> {
+        let mut flat = [0u8];
+        self.dom_read(port, interface, None, FLAT_MEM_REG, &mut flat)?;
+
+        let mut buf = [0u8; 72];
+        let state = match interface {
+            ManagementInterface::Sff8636 => {
+                if flat[0] & SFF8636_FLAT_MEM != 0 {
+                    return Ok(DomState::Unsupported);
+                }
+                self.dom_read(
+                    port,
+                    interface,
+                    Some(SFF8636_THRESHOLDS_PAGE),
+                    THRESHOLDS_REG,
+                    &mut buf,
+                )?;
+
+                // SFF-8636, Table 6-26
+                DomState::Supported {
+                    thresholds: DomThresholds {
+                        temperature: temperature_threshold(&buf, 0),
+                        supply_voltage: threshold(&buf, 16, VOLTS_PER_LSB),
+                        rx_power: threshold(&buf, 48, WATTS_PER_LSB),
+                        tx_bias: threshold(&buf, 56, AMPERES_PER_LSB),
+                        tx_power: threshold(&buf, 64, WATTS_PER_LSB),
+                    },
+                    bias_scale: 1.0,
+                }
+            }
+            ManagementInterface::Cmis => {
+                if flat[0] & CMIS_FLAT_MEM != 0 {
+                    return Ok(DomState::Unsupported);
+                }
+
+                // CMIS 5.0, Table 8-31: TxBiasCurrentScalingFactor
+                let mut scale = [0u8];
+                self.dom_read(
+                    port,
+                    interface,
+                    Some(CMIS_BIAS_SCALE_PAGE),
+                    CMIS_BIAS_SCALE_REG,
+                    &mut scale,
+                )?;
+                let bias_scale = match (scale[0] >> 3) & 0b11 {
+                    0b01 => 2.0,
+                    0b10 => 4.0,
+                    _ => 1.0,
+                };
+
+                self.dom_read(
+                    port,
+                    interface,
+                    Some(CMIS_THRESHOLDS_PAGE),
+                    THRESHOLDS_REG,
+                    &mut buf,
+                )?;
+
+                // CMIS 5.0, Table 8-62
+                let bias = AMPERES_PER_LSB * bias_scale;
+                DomState::Supported {
+                    thresholds: DomThresholds {
+                        temperature: temperature_threshold(&buf, 0),
+                        supply_voltage: threshold(&buf, 8, VOLTS_PER_LSB),
+                        tx_power: threshold(&buf, 48, WATTS_PER_LSB),
+                        tx_bias: threshold(&buf, 56, bias),
+                        rx_power: threshold(&buf, 64, WATTS_PER_LSB),
+                    },
+                    bias_scale,
+                }
+            }
+            ManagementInterface::Unknown(..) => DomState::Unsupported,
+        };
+        Ok(state)
+    }
+
+    /// Reads monitors and latched flags from an SFF-8636 module
+    fn dom_read_sff8636(
+        &self,
+        port: LogicalPort,
+    ) -> Result<(DomReadings, DomFlags), FpgaError> {
+        let interface = ManagementInterface::Sff8636;
+
+        // Bytes 6-14: temperature, supply voltage, and lane flags
+        let mut f = [0u8; 9];
+        self.dom_read(port, interface, None, SFF8636_FLAGS_REG, &mut f)?;
+
+        // Bytes 26-57: supply voltage, RX power, TX bias, and TX power
+        let mut m = [0u8; 32];
+        self.dom_read(port, interface, None, SFF8636_MONITORS_REG, &mut m)?;
+
+        // Lane flags are packed two lanes per byte, starting with lane 1 in
+        // the upper nibble.
+        let lane = |offset: usize, lane: usize| {
+            let b = f[offset + lane / 2];
+            sff8636_nibble(if lane % 2 == 0 { b >> 4 } else { b & 0xf })
+        };
+
+        let mut readings = DomReadings {
+            supply_voltage: u16_at(&m, 0) * VOLTS_PER_LSB,
+            tx_bias: [0.0; NUM_DOM_LANES],
+            tx_power: [0.0; NUM_DOM_LANES],
+            rx_power: [0.0; NUM_DOM_LANES],
+        };
+        let mut flags = DomFlags {
+            temperature: sff8636_nibble(f[0] >> 4),
+            supply_voltage: sff8636_nibble(f[1] >> 4),
+            ..Default::default()
+        };
+        for i in 0..NUM_DOM_LANES {
+            readings.rx_power[i] = u16_at(&m, 8 + i * 2) * WATTS_PER_LSB;
+            readings.tx_bias[i] = u16_at(&m, 16 + i * 2) * AMPERES_PER_LSB;
+            readings.tx_power[i] = u16_at(&m, 24 + i * 2) * WATTS_PER_LSB;
+
+            flags.rx_power[i] = lane(3, i);
+            flags.tx_bias[i] = lane(5, i);
+            flags.tx_power[i] = lane(7, i);
+        }
+        Ok((readings, flags))
+    }
+
+    /// Reads monitors and latched flags from a CMIS module
+    fn dom_read_cmis(
+        &self,
+        port: LogicalPort,
+        bias_scale: f32,
+    ) -> Result<(DomReadings, DomFlags), FpgaError> {
+        let interface = ManagementInterface::Cmis;
+
+        let mut module_flags = [0u8];
+        self.dom_read(
+            port,
+            interface,
+            None,
+            CMIS_MODULE_FLAGS_REG,
+            &mut module_flags,
+        )?;
+
+        let mut vcc = [0u8; 2];
+        self.dom_read(
+            port,
+            interface,
+            None,
+            CMIS_SUPPLY_VOLTAGE_REG,
+            &mut vcc,
+        )?;
+
+        // Bytes 139-193 of page 11h: lane flags, followed by TX power, TX
+        // bias, and RX power monitors.
+        let mut lane = [0u8; 55];
+        self.dom_read(
+            port,
+            interface,
+            Some(CMIS_LANE_PAGE),
+            CMIS_LANE_FLAGS_REG,
+            &mut lane,
+        )?;
+
+        let mut readings = DomReadings {
+            supply_voltage: u16_at(&vcc, 0) * VOLTS_PER_LSB,
+            tx_bias: [0.0; NUM_DOM_LANES],
+            tx_power: [0.0; NUM_DOM_LANES],
+            rx_power: [0.0; NUM_DOM_LANES],
+        };
+        let mut flags = DomFlags {
+            temperature: cmis_nibble(module_flags[0] & 0xf),
+            supply_voltage: cmis_nibble(module_flags[0] >> 4),
+            ..Default::default()
+        };
+        let bias = AMPERES_PER_LSB * bias_scale;
+        for i in 0..NUM_DOM_LANES {
+            flags.tx_power[i] = cmis_lane(&lane[0..], i);
+            flags.tx_bias[i] = cmis_lane(&lane[4..], i);
+            flags.rx_power[i] = cmis_lane(&lane[10..], i);
+
+            readings.tx_power[i] = u16_at(&lane, 15 + i * 2) * WATTS_PER_LSB;
+            readings.tx_bias[i] = u16_at(&lane, 31 + i * 2) * bias;
+            readings.rx_power[i] = u16_at(&lane, 47 + i * 2) * WATTS_PER_LSB;
+        }
+        Ok((readings, flags))
+    }
+
+    fn dom_post(&self, i: usize, id: SensorId, value: f32) {
+        if let Err(e) = self.sensor_api.post_now(id, value) {
+            ringbuf_entry!(Trace::SensorError(i, e));
+        }
+    }
+
+    fn dom_nodata(&self, i: usize, nodata: NoData) {
+        let ids = core::iter::once(TRANSCEIVER_VOLTAGE_SENSORS[i])
+            .chain(TRANSCEIVER_CURRENT_SENSORS[i])
+            .chain(TRANSCEIVER_POWER_SENSORS[i]);
+        for id in ids {
+            if let Err(e) = self.sensor_api.nodata_now(id, nodata) {
+                ringbuf_entry!(Trace::SensorError(i, e));
+            }
+        }
+    }
+
+    /// Forgets everything that we know about a module's diagnostics, e.g.
+    /// because it has been removed.
+    pub(crate) fn dom_remove(&mut self, i: usize) {
+        self.dom_state[i] = DomState::Unknown;
+        self.dom_flags[i] = DomFlags::default();
+        self.dom_nodata(i, NoData::DeviceNotPresent);
+    }
+
+    /// Reads diagnostics from every module that's in the thermal loop,
+    /// posting them to the `sensor` task.
+    pub(crate) fn handle_dom_loop(&mut self) {
+        for i in 0..NUM_PORTS as usize {
+            let port = LogicalPort(i as u8);
+            let interface = match self.thermal_models[i] {
+                Some(m) => m.interface,
+                None => continue,
+            };
+
+            if let DomState::Unknown = self.dom_state[i] {
+                match self.dom_probe(port, interface) {
+                    Ok(s @ DomState::Supported { .. }) => {
+                        ringbuf_entry!(Trace::Supported(i));
+                        self.dom_state[i] = s;
+                    }
+                    Ok(s) => {
+                        ringbuf_entry!(Trace::Unsupported(i));
+                        self.dom_state[i] = s;
+                        self.dom_nodata(i, NoData::DeviceUnavailable);
+                    }
+                    Err(e) => {
+                        // We'll try again on the next pass
+                        ringbuf_entry!(Trace::ProbeError(i, e));
+                    }
+                }
+            }
+
+            let result = match (self.dom_state[i], interface) {
+                (DomState::Supported { .. }, ManagementInterface::Sff8636) => {
+                    self.dom_read_sff8636(port)
+                }
+                (
+                    DomState::Supported { bias_scale, .. },
+                    ManagementInterface::Cmis,
+                ) => self.dom_read_cmis(port, bias_scale),
+                _ => continue,
+            };
+
+            let (readings, flags) = match result {
+                Ok(r) => r,
+                Err(e) => {
+                    // Persistent failures are handled by the thermal loop,
+                    // which disables ports that stop responding.
+                    ringbuf_entry!(Trace::ReadError(i, e));
+                    continue;
+                }
+            };
+
+            if !flags.is_empty() {
+                ringbuf_entry!(Trace::FlagsRaised(i, flags));
+                self.dom_flags[i].merge(&flags);
+            }
+
+            self.dom_post(
+                i,
+                TRANSCEIVER_VOLTAGE_SENSORS[i],
+                readings.supply_voltage,
+            );
+            for lane in 0..NUM_DOM_LANES {
+                self.dom_post(
+                    i,
+                    TRANSCEIVER_CURRENT_SENSORS[i][lane],
+                    readings.tx_bias[lane],
+                );
+                self.dom_post(
+                    i,
+                    TRANSCEIVER_POWER_SENSORS[i][lane],
+                    readings.tx_power[lane],
+                );
+                self.dom_post(
+                    i,
+                    TRANSCEIVER_POWER_SENSORS[i][NUM_DOM_LANES + lane],
+                    readings.rx_power[lane],
+                );
+            }
+        }
+    }
+
+    pub(crate) fn dom_thresholds(&self, i: usize) -> Option<DomThresholds> {
+        match self.dom_state[i] {
+            DomState::Supported { thresholds, .. } => Some(thresholds),
+            _ => None,
+        }
+    }
+}
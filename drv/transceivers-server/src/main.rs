@@ -15,7 +15,8 @@ use drv_sidecar_front_io::{
 };
 use drv_sidecar_seq_api::{SeqError, Sequencer};
 use drv_transceivers_api::{
-    ModuleStatus, TransceiversError, NUM_PORTS, TRANSCEIVER_TEMPERATURE_SENSORS,
+    DomFlags, DomThresholds, ModuleStatus, TransceiversError, NUM_PORTS,
+    TRANSCEIVER_TEMPERATURE_SENSORS,
 };
 use enum_map::Enum;
 use idol_runtime::{NotificationHandler, RequestError};
@@ -29,6 +30,7 @@ use transceiver_messages::{
 use userlib::{units::Celsius, *};
 use zerocopy::{AsBytes, FromBytes};
 
+mod dom; // Digital diagnostics are implemented in a separate file
 mod udp; // UDP API is implemented in a separate file
 
 task_slot!(I2C, i2c_driver);
@@ -118,6 +120,12 @@ struct ServerImpl {
 
     /// Thermal models are populated by the host
     thermal_models: [Option<ThermalModel>; NUM_PORTS as usize],
+
+    /// Digital diagnostic support and cached thresholds for each module
+    dom_state: [dom::DomState; NUM_PORTS as usize],
+
+    /// Latched diagnostic flags, accumulated until cleared by a client
+    dom_flags: [DomFlags; NUM_PORTS as usize],
 }
 
 #[derive(Copy, Clone)]
@@ -141,6 +149,13 @@ const I2C_INTERVAL: u64 = 100;
 /// Blink LEDs at a 50% duty cycle (in milliseconds)
 const BLINK_INTERVAL: u64 = 500;
 
+/// Controls how often we read digital diagnostics from the transceivers (in
+/// milliseconds).
+///
+/// This is slower than the thermal loop, because reading diagnostics requires
+/// several I2C transactions per module (including page selects).
+const DOM_INTERVAL: u64 = 2000;
+
 impl ServerImpl {
     fn led_init(&mut self) {
         match self.leds.initialize_current() {
@@ -381,6 +396,7 @@ impl ServerImpl {
                 ) {
                     ringbuf_entry!(Trace::SensorError(i, e));
                 }
+                self.dom_remove(i);
 
                 if (self.disabled & port).is_empty() {
                     ringbuf_entry!(Trace::UnpluggedModule(i));
@@ -578,6 +594,45 @@ impl idl::InOrderTransceiversImpl for ServerImpl {
         self.set_system_led_state(LedState::Blink);
         Ok(())
     }
+
+    fn get_dom_thresholds(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        port: u8,
+    ) -> Result<DomThresholds, idol_runtime::RequestError<TransceiversError>>
+    {
+        if port >= NUM_PORTS {
+            return Err(TransceiversError::InvalidPortNumber.into());
+        }
+        self.dom_thresholds(port as usize)
+            .ok_or_else(|| TransceiversError::DomUnavailable.into())
+    }
+
+    fn get_dom_flags(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        port: u8,
+    ) -> Result<DomFlags, idol_runtime::RequestError<TransceiversError>> {
+        if port >= NUM_PORTS {
+            return Err(TransceiversError::InvalidPortNumber.into());
+        }
+        if self.dom_thresholds(port as usize).is_none() {
+            return Err(TransceiversError::DomUnavailable.into());
+        }
+        Ok(self.dom_flags[port as usize])
+    }
+
+    fn clear_dom_flags(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        port: u8,
+    ) -> Result<(), idol_runtime::RequestError<TransceiversError>> {
+        if port >= NUM_PORTS {
+            return Err(TransceiversError::InvalidPortNumber.into());
+        }
+        self.dom_flags[port as usize] = DomFlags::default();
+        Ok(())
+    }
 }
 
 impl NotificationHandler for ServerImpl {
@@ -644,6 +699,8 @@ fn main() -> ! {
             thermal_api,
             sensor_api,
             thermal_models: [None; NUM_PORTS as usize],
+            dom_state: [dom::DomState::Unknown; NUM_PORTS as usize],
+            dom_flags: [DomFlags::default(); NUM_PORTS as usize],
         };
 
         ringbuf_entry!(Trace::LEDInit);
@@ -653,12 +710,14 @@ fn main() -> ! {
             Err(e) => ringbuf_entry!(Trace::LEDEnableError(e)),
         };
 
-        // There are two timers, one for each communication bus:
+        // There are two timers, one for each communication bus, plus timers
+        // for blinking LEDs and reading diagnostics:
         #[derive(Copy, Clone, Enum)]
         enum Timers {
             I2C,
             SPI,
             Blink,
+            Dom,
         }
         let mut multitimer =
             Multitimer::<Timers>::new(notifications::TIMER_BIT);
@@ -679,6 +738,11 @@ fn main() -> ! {
             now,
             Some(Repeat::AfterDeadline(BLINK_INTERVAL)),
         );
+        multitimer.set_timer(
+            Timers::Dom,
+            now,
+            Some(Repeat::AfterDeadline(DOM_INTERVAL)),
+        );
 
         let mut buffer = [0; idl::INCOMING_SIZE];
         loop {
@@ -694,6 +758,9 @@ fn main() -> ! {
                     Timers::Blink => {
                         server.blink_on = !server.blink_on;
                     }
+                    Timers::Dom => {
+                        server.handle_dom_loop();
+                    }
                 }
             }
             server.check_net(
@@ -721,7 +788,7 @@ pub fn claim_statics() -> (
 ////////////////////////////////////////////////////////////////////////////////
 
 mod idl {
-    use super::{ModuleStatus, TransceiversError};
+    use super::{DomFlags, DomThresholds, ModuleStatus, TransceiversError};
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
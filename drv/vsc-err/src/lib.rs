@@ -9,9 +9,18 @@
 
 #![no_std]
 
+#[cfg(target_os = "none")]
 use drv_spi_api::SpiError;
+#[cfg(target_os = "none")]
 use idol_runtime::ServerDeath;
 
+/// Stand-in for the SPI error type when building for the host (e.g. to test
+/// `drv/vsc7448` against its simulated register backend), where there is no
+/// SPI server and therefore no way to get an SPI error.
+#[cfg(not(target_os = "none"))]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SpiError {}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum VscError {
     SpiError(SpiError),
@@ -106,6 +115,7 @@ impl From<SpiError> for VscError {
     }
 }
 
+#[cfg(target_os = "none")]
 impl From<ServerDeath> for VscError {
     fn from(_s: ServerDeath) -> Self {
         Self::ServerDied
@@ -169,3 +169,86 @@ impl core::ops::Index<u8> for PortMap {
         &self.0[i as usize]
     }
 }
+
+/// Port mirroring configuration
+///
+/// Traffic entering the ports in `ingress` and leaving the ports in `egress`
+/// is copied to the `destination` port.  Port sets are bitmasks, with bit `n`
+/// representing port `n`.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    SerializedSize,
+    Deserialize,
+)]
+pub struct MirrorConfig {
+    pub destination: Option<u8>,
+    pub ingress: u64,
+    pub egress: u64,
+}
+
+impl MirrorConfig {
+    /// Checks whether the configuration can be applied, i.e. that every port
+    /// is in range and that the destination isn't itself being mirrored.
+    pub fn is_valid(&self) -> bool {
+        let all_ports = (1u64 << PORT_COUNT) - 1;
+        let mirrored = self.ingress | self.egress;
+        match self.destination {
+            None => mirrored == 0,
+            Some(d) => {
+                usize::from(d) < PORT_COUNT
+                    && mirrored & !all_ports == 0
+                    && mirrored & (1 << d) == 0
+            }
+        }
+    }
+}
+
+/// Class of traffic limited by a storm policer
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Serialize, SerializedSize, Deserialize,
+)]
+pub enum StormKind {
+    Broadcast,
+    Multicast,
+}
+
+/// Storm policer limits for a single port, in frames per second
+///
+/// `None` indicates that the given class of traffic is not policed.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    SerializedSize,
+    Deserialize,
+)]
+pub struct StormPolicers {
+    pub broadcast: Option<u32>,
+    pub multicast: Option<u32>,
+}
+
+impl StormPolicers {
+    pub fn get(&self, kind: StormKind) -> Option<u32> {
+        match kind {
+            StormKind::Broadcast => self.broadcast,
+            StormKind::Multicast => self.multicast,
+        }
+    }
+
+    pub fn set(&mut self, kind: StormKind, rate: Option<u32>) {
+        match kind {
+            StormKind::Broadcast => self.broadcast = rate,
+            StormKind::Multicast => self.multicast = rate,
+        }
+    }
+}
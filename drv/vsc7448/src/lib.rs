@@ -2,21 +2,31 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-#![no_std]
+#![cfg_attr(target_os = "none", no_std)]
 
 pub mod config;
 pub mod mac;
-pub mod miim_phy;
 pub mod serdes6g;
+
+#[cfg(target_os = "none")]
+pub mod miim_phy;
+#[cfg(target_os = "none")]
 pub mod spi;
 
+#[cfg(not(target_os = "none"))]
+pub mod sim;
+
 mod dev;
 mod port;
 mod serdes10g;
 mod serdes1g;
 
-use crate::config::{PortConfig, PortDev, PortMap, PortMode, PortSerdes};
-use userlib::{hl::sleep_for, UnwrapLite};
+use crate::config::{
+    MirrorConfig, PortConfig, PortDev, PortMap, PortMode, PortSerdes,
+    StormKind, StormPolicers,
+};
+use hl::sleep_for;
+use unwrap_lite::UnwrapLite;
 use vsc7448_pac::{types::RegisterAddress, *};
 
 pub use config::Speed;
@@ -25,9 +35,25 @@ pub use vsc_err::VscError;
 
 use crate::dev::Dev10g;
 
+// Use the real kernel sleep when running under Hubris; otherwise, we're
+// running against the simulated backend, and there's nothing to wait for.
+#[cfg(target_os = "none")]
+use userlib::hl;
+
+#[cfg(not(target_os = "none"))]
+mod hl {
+    pub fn sleep_for(_ticks: u64) {}
+}
+
 /// Maximum port count
 pub const PORT_COUNT: usize = 53;
 
+/// This indicates how many bytes we pad between (writing) the address bytes
+/// and (reading) data back, during SPI transactions to the VSC7448.  See
+/// section 5.5.2 for details.  1 padding byte should be good up to 6.5 MHz
+/// SPI clock.
+pub const SPI_NUM_PAD_BYTES: usize = 1;
+
 /// This trait abstracts over various ways of talking to a VSC7448.
 pub trait Vsc7448Rw {
     /// Writes to a VSC7448 register.  Depending on the underlying transit
@@ -391,7 +417,7 @@ impl<'a, R: Vsc7448Rw> Vsc7448<'a, R> {
 
         // Configure reads to include padding bytes, since we're reading quickly
         self.write_with(DEVCPU_ORG().DEVCPU_ORG().IF_CFGSTAT(), |r| {
-            r.set_if_cfg(spi::SPI_NUM_PAD_BYTES as u32);
+            r.set_if_cfg(SPI_NUM_PAD_BYTES as u32);
         })?;
 
         let chip_id = self.read(DEVCPU_GCB().CHIP_REGS().CHIP_ID())?;
@@ -827,6 +853,109 @@ impl<'a, R: Vsc7448Rw> Vsc7448<'a, R> {
         })
     }
 
+    /// Configures port mirroring.
+    ///
+    /// Each mirror probe has a single port mask and direction, so we use one
+    /// probe for ingress and another for egress, both pointing at the same
+    /// destination.  Passing a configuration with no destination disables
+    /// mirroring entirely.
+    pub fn configure_mirror(&self, cfg: &MirrorConfig) -> Result<(), VscError> {
+        if !cfg.is_valid() {
+            return Err(VscError::OutOfRange);
+        }
+
+        // Disable both probes before touching their port masks, so we don't
+        // briefly mirror a mix of the old and new configuration.
+        for probe in 0..2 {
+            self.modify(ANA_AC().MIRROR_PROBE(probe).PROBE_CFG(), |r| {
+                r.set_probe_direction(0);
+            })?;
+        }
+
+        let dest = match cfg.destination {
+            Some(dest) => dest,
+            None => return Ok(()),
+        };
+
+        for (probe, direction, ports) in [
+            (0, PROBE_DIRECTION_RX, cfg.ingress),
+            (1, PROBE_DIRECTION_TX, cfg.egress),
+        ] {
+            // The frame copy configuration for mirror probes comes after the
+            // eight CPU queues and the learn-all copy.
+            self.modify(QFWD().SYSTEM().FRAME_COPY_CFG(9 + probe), |r| {
+                r.set_frmc_port_val(dest.into());
+            })?;
+            let probe = ANA_AC().MIRROR_PROBE(probe);
+            self.write_port_mask(probe.PROBE_PORT_CFG(), ports)?;
+            if ports != 0 {
+                self.modify(probe.PROBE_CFG(), |r| {
+                    r.set_probe_direction(direction);
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Configures the storm policers for the given port.
+    ///
+    /// Each port has four port policers; we use the first for broadcast and
+    /// the second for multicast traffic (both known and unknown), running
+    /// them in frame rate mode.
+    pub fn configure_storm_policers(
+        &self,
+        port: u8,
+        cfg: &StormPolicers,
+    ) -> Result<(), VscError> {
+        if usize::from(port) >= PORT_COUNT {
+            return Err(VscError::OutOfRange);
+        }
+        for (i, kind) in [StormKind::Broadcast, StormKind::Multicast]
+            .into_iter()
+            .enumerate()
+        {
+            let pol = u32::from(port) * 4 + i as u32;
+            let mask = match kind {
+                StormKind::Broadcast => POL_TRAFFIC_BROADCAST,
+                StormKind::Multicast => POL_TRAFFIC_MULTICAST,
+            };
+            match cfg.get(kind) {
+                None => {
+                    self.modify(
+                        ANA_AC_POL().POL_PORT_CTRL(port).POL_PORT_CFG(i as u8),
+                        |r| r.set_traffic_type_mask(0),
+                    )?;
+                }
+                Some(rate) => {
+                    // The rate is programmed in units of 33⅓ frames/sec;
+                    // round up, so that small nonzero limits don't end up
+                    // dropping everything.
+                    let units = (u64::from(rate) * 3 + 99) / 100;
+                    if units > POL_PORT_RATE_MAX {
+                        return Err(VscError::OutOfRange);
+                    }
+                    self.write_with(
+                        ANA_AC_POL().POL_PORT_CFG().POL_PORT_RATE_CFG(pol),
+                        |r| r.set_port_cfg_rate(units as u32),
+                    )?;
+                    self.write_with(
+                        ANA_AC_POL().POL_PORT_CFG().POL_PORT_THRES_CFG_0(pol),
+                        |r| r.set_port_thres0(POL_PORT_BURST),
+                    )?;
+                    self.modify(
+                        ANA_AC_POL().POL_PORT_CTRL(port).POL_PORT_CFG(i as u8),
+                        |r| {
+                            r.set_frame_rate_ena(1);
+                            r.set_limit_noncpu_traffic_ena(1);
+                            r.set_traffic_type_mask(mask);
+                        },
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Checks the 10GBASE-KR autonegotiation state machine for the given dev.
     ///
     /// If it is stuck in `WAIT_RATE_DONE`, restarts autonegotiation and returns
@@ -836,6 +965,21 @@ impl<'a, R: Vsc7448Rw> Vsc7448<'a, R> {
     }
 }
 
+/// `PROBE_DIRECTION` bit to mirror frames transmitted by the probed ports
+const PROBE_DIRECTION_TX: u32 = 0b01;
+/// `PROBE_DIRECTION` bit to mirror frames received by the probed ports
+const PROBE_DIRECTION_RX: u32 = 0b10;
+
+/// `TRAFFIC_TYPE_MASK` bits for known and unknown broadcast frames
+const POL_TRAFFIC_BROADCAST: u32 = (1 << 1) | (1 << 4);
+/// `TRAFFIC_TYPE_MASK` bits for known and unknown multicast frames
+const POL_TRAFFIC_MULTICAST: u32 = (1 << 0) | (1 << 3);
+
+/// Largest value that fits in `PORT_CFG_RATE`
+const POL_PORT_RATE_MAX: u64 = (1 << 17) - 1;
+/// Burst size (in frames) allowed before a storm policer starts dropping
+const POL_PORT_BURST: u32 = 8;
+
 enum Bandwidth {
     None,
     Bw1G,
@@ -8,9 +8,8 @@
 
 use crate::{
     dev::{Dev10g, DevGeneric},
-    Vsc7448Rw, VscError,
+    hl, Vsc7448Rw, VscError,
 };
-use userlib::hl;
 use vsc7448_pac::*;
 
 /// Flushes a particular 1G port.  This is equivalent to `jr2_port_flush`
@@ -3,8 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 /// Tools for working with the 10G SERDES (sd10g65 in the SDK)
-use crate::{Vsc7448Rw, VscError};
-use userlib::hl;
+use crate::{hl, Vsc7448Rw, VscError};
 use vsc7448_pac::*;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -2,8 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use crate::{Vsc7448Rw, VscError};
-use userlib::hl;
+use crate::{hl, Vsc7448Rw, VscError};
 use vsc7448_pac::*;
 
 pub enum Mode {
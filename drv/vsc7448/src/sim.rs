@@ -0,0 +1,586 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Simulated register backend, for running the driver on the host
+//!
+//! [`SimVsc7448`] stores registers in a map, records every access, and models
+//! just enough of the chip for the bring-up sequences to run to completion:
+//!
+//! - A soft reset returns every register to zero.  This is _not_ the chip's
+//!   actual reset state, but the driver only does read-modify-write operations
+//!   on fields that it sets itself, so it doesn't matter here.
+//! - The chip ID reads back as a VSC7448
+//! - One-shot bits (RAM initialization, SERDES MCB accesses, MAC table
+//!   commands) clear themselves immediately
+//! - The LC-PLLs and SERDES10G PLLs report lock, and SERDES10G offset
+//!   calibration finishes
+//! - Port link status is down until set with [`SimVsc7448::set_link_1g`] or
+//!   [`SimVsc7448::set_link_10g`]
+//!
+//! Anything else can be modeled by forcing register fields with
+//! [`SimVsc7448::force`].
+//!
+//! The tests in this module compare the recorded trace against golden traces
+//! in `drv/vsc7448/golden`, so that any change to a bring-up sequence shows up
+//! as a diff.  A missing golden trace is a test failure; to create one, or to
+//! accept an intended change, rerun the tests with `VSC7448_BLESS=1` and
+//! commit the result.
+
+use crate::{DevGeneric, Vsc7448Rw, VscError};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use vsc7448_pac::{types::RegisterAddress, *};
+
+/// Number of SERDES10G instances (and DEV10G / PCS10G_BR instances)
+const SERDES10G_COUNT: u8 = 4;
+
+/// A single register access, as recorded by [`SimVsc7448`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Access {
+    Read { addr: u32, value: u32 },
+    Write { addr: u32, value: u32 },
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::Read { addr, value } => {
+                write!(f, "R {addr:08x} {value:08x}")
+            }
+            Access::Write { addr, value } => {
+                write!(f, "W {addr:08x} {value:08x}")
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    regs: BTreeMap<u32, u32>,
+    /// Fields whose value is fixed by the simulation, as `(mask, value)`.
+    /// These override whatever was last written, and survive a soft reset.
+    forced: BTreeMap<u32, (u32, u32)>,
+    /// Bits which clear themselves as soon as they're written, i.e. one-shot
+    /// operations that complete instantly.
+    self_clearing: BTreeMap<u32, u32>,
+    trace: Vec<Access>,
+}
+
+impl State {
+    fn read(&self, addr: u32) -> u32 {
+        let v = self.regs.get(&addr).cloned().unwrap_or(0);
+        match self.forced.get(&addr) {
+            Some((mask, forced)) => (v & !mask) | forced,
+            None => v,
+        }
+    }
+}
+
+/// Simulated VSC7448, which implements `Vsc7448Rw` without any hardware
+pub struct SimVsc7448 {
+    state: RefCell<State>,
+}
+
+impl Default for SimVsc7448 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimVsc7448 {
+    /// Builds a simulated chip, freshly out of reset
+    pub fn new() -> Self {
+        let out = Self {
+            state: RefCell::new(State::default()),
+        };
+
+        let chip_id = DEVCPU_GCB().CHIP_REGS().CHIP_ID();
+        out.force(chip_id, |r, v| r.set_rev_id(v), 0x3);
+        out.force(chip_id, |r, v| r.set_part_id(v), 0x7468);
+        out.force(chip_id, |r, v| r.set_mfg_id(v), 0x74);
+        out.force(chip_id, |r, v| r.set_one(v), 0x1);
+
+        out.self_clearing(DEVCPU_GCB().CHIP_REGS().SOFT_RST(), |r, v| {
+            r.set_soft_chip_rst(v)
+        });
+        out.self_clearing(ASM().CFG().STAT_CFG(), |r, v| {
+            r.set_stat_cnt_clr_shot(v)
+        });
+        // Each target has its own RAM_INIT type, so we can't loop over them
+        out.self_clearing(QSYS().RAM_CTRL().RAM_INIT(), |r, v| {
+            r.set_ram_init(v)
+        });
+        out.self_clearing(REW().RAM_CTRL().RAM_INIT(), |r, v| {
+            r.set_ram_init(v)
+        });
+        out.self_clearing(VOP().RAM_CTRL().RAM_INIT(), |r, v| {
+            r.set_ram_init(v)
+        });
+        out.self_clearing(ANA_AC().RAM_CTRL().RAM_INIT(), |r, v| {
+            r.set_ram_init(v)
+        });
+        out.self_clearing(ASM().RAM_CTRL().RAM_INIT(), |r, v| {
+            r.set_ram_init(v)
+        });
+        out.self_clearing(DSM().RAM_CTRL().RAM_INIT(), |r, v| {
+            r.set_ram_init(v)
+        });
+
+        let mcb1g = HSIO().MCB_SERDES1G_CFG().MCB_SERDES1G_ADDR_CFG();
+        out.self_clearing(mcb1g, |r, v| r.set_serdes1g_rd_one_shot(v));
+        out.self_clearing(mcb1g, |r, v| r.set_serdes1g_wr_one_shot(v));
+        let mcb6g = HSIO().MCB_SERDES6G_CFG().MCB_SERDES6G_ADDR_CFG();
+        out.self_clearing(mcb6g, |r, v| r.set_serdes6g_rd_one_shot(v));
+        out.self_clearing(mcb6g, |r, v| r.set_serdes6g_wr_one_shot(v));
+
+        out.self_clearing(LRN().COMMON().COMMON_ACCESS_CTRL(), |r, v| {
+            r.set_mac_table_access_shot(v)
+        });
+
+        // A gain in the middle of the range that `pll5g_setup` accepts
+        for i in 0..2 {
+            out.force(
+                HSIO().PLL5G_STATUS(i).PLL5G_STATUS1(),
+                |r, v| r.set_gain_stat(v),
+                5,
+            );
+        }
+
+        // PLL lock, with the FSM in its final state, and offset calibration
+        for i in 0..SERDES10G_COUNT {
+            let tx_rcpll = XGANA(i).SD10G65_TX_RCPLL();
+            out.force(
+                tx_rcpll.SD10G65_TX_RCPLL_STAT0(),
+                |r, v| r.set_pllf_lock_stat(v),
+                1,
+            );
+            out.force(
+                tx_rcpll.SD10G65_TX_RCPLL_STAT1(),
+                |r, v| r.set_pllf_fsm_stat(v),
+                13,
+            );
+            let rx_rcpll = XGANA(i).SD10G65_RX_RCPLL();
+            out.force(
+                rx_rcpll.SD10G65_RX_RCPLL_STAT0(),
+                |r, v| r.set_pllf_lock_stat(v),
+                1,
+            );
+            out.force(
+                rx_rcpll.SD10G65_RX_RCPLL_STAT1(),
+                |r, v| r.set_pllf_fsm_stat(v),
+                13,
+            );
+            out.force(
+                XGDIG(i).SD10G65_APC().APC_IS_CAL_CFG1(),
+                |r, v| r.set_offscal_done(v),
+                1,
+            );
+        }
+
+        out
+    }
+
+    /// Forces a register field to the given value, regardless of what the
+    /// driver writes to it.  `set` is the field's setter from the PAC, e.g.
+    /// `|r, v| r.set_gain_stat(v)`.
+    pub fn force<T, F>(&self, reg: RegisterAddress<T>, set: F, value: u32)
+    where
+        T: From<u32>,
+        u32: From<T>,
+        F: Fn(&mut T, u32),
+    {
+        let mask = field_mask(reg, &set);
+        let mut r = T::from(0);
+        set(&mut r, value);
+        let bits = u32::from(r);
+
+        let mut state = self.state.borrow_mut();
+        let e = state.forced.entry(reg.addr).or_default();
+        e.0 |= mask;
+        e.1 = (e.1 & !mask) | bits;
+    }
+
+    /// Marks a register field as self-clearing, so that it always reads back
+    /// as zero after a write.
+    pub fn self_clearing<T, F>(&self, reg: RegisterAddress<T>, set: F)
+    where
+        T: From<u32>,
+        u32: From<T>,
+        F: Fn(&mut T, u32),
+    {
+        let mask = field_mask(reg, &set);
+        *self
+            .state
+            .borrow_mut()
+            .self_clearing
+            .entry(reg.addr)
+            .or_default() |= mask;
+    }
+
+    /// Sets the PCS link status reported by a DEV1G or DEV2G5
+    pub fn set_link_1g(&self, dev: &DevGeneric, up: bool) {
+        let reg = dev.regs().PCS1G_CFG_STATUS().PCS1G_LINK_STATUS();
+        let v = u32::from(up);
+        self.force(reg, |r, v| r.set_link_status(v), v);
+        self.force(reg, |r, v| r.set_signal_detect(v), v);
+        self.force(reg, |r, v| r.set_sync_status(v), v);
+    }
+
+    /// Sets the PCS block lock reported by the given DEV10G
+    pub fn set_link_10g(&self, dev: u8, up: bool) {
+        self.force(
+            PCS10G_BR(dev).PCS_10GBR_STATUS().PCS_STATUS(),
+            |r, v| r.set_rx_block_lock(v),
+            u32::from(up),
+        );
+    }
+
+    /// Returns the current value of a register, without recording an access
+    pub fn peek(&self, addr: u32) -> u32 {
+        self.state.borrow().read(addr)
+    }
+
+    /// Returns every register access since the last call to `take_trace`
+    pub fn take_trace(&self) -> Vec<Access> {
+        core::mem::take(&mut self.state.borrow_mut().trace)
+    }
+
+    /// Renders a trace as text, one access per line
+    pub fn format_trace(trace: &[Access]) -> String {
+        trace.iter().map(|a| format!("{a}\n")).collect()
+    }
+}
+
+/// Returns the bits occupied by a field of the given register, given its setter
+fn field_mask<T, F>(_reg: RegisterAddress<T>, set: &F) -> u32
+where
+    T: From<u32>,
+    u32: From<T>,
+    F: Fn(&mut T, u32),
+{
+    let mut r = T::from(!0);
+    set(&mut r, 0);
+    !u32::from(r)
+}
+
+/// Checks an address in the same way as the SPI backend
+fn check_addr(addr: u32) -> Result<(), VscError> {
+    if (0x71000000..0x72000000).contains(&addr) {
+        Ok(())
+    } else {
+        Err(VscError::BadRegAddr(addr))
+    }
+}
+
+impl Vsc7448Rw for SimVsc7448 {
+    fn write<T>(
+        &self,
+        reg: RegisterAddress<T>,
+        value: T,
+    ) -> Result<(), VscError>
+    where
+        u32: From<T>,
+    {
+        check_addr(reg.addr)?;
+        let value: u32 = value.into();
+
+        let mut state = self.state.borrow_mut();
+        state.trace.push(Access::Write {
+            addr: reg.addr,
+            value,
+        });
+
+        let soft_rst = DEVCPU_GCB().CHIP_REGS().SOFT_RST();
+        if reg.addr == soft_rst.addr
+            && value & field_mask(soft_rst, &|r, v| r.set_soft_chip_rst(v)) != 0
+        {
+            state.regs.clear();
+        }
+
+        let mask = state.self_clearing.get(&reg.addr).cloned().unwrap_or(0);
+        state.regs.insert(reg.addr, value & !mask);
+        Ok(())
+    }
+
+    fn read<T>(&self, reg: RegisterAddress<T>) -> Result<T, VscError>
+    where
+        T: From<u32>,
+    {
+        check_addr(reg.addr)?;
+        let mut state = self.state.borrow_mut();
+        let value = state.read(reg.addr);
+        state.trace.push(Access::Read {
+            addr: reg.addr,
+            value,
+        });
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{PortMap, PortMode, PortMode::*, Speed::*},
+        RefClockFreq, Vsc7448,
+    };
+    use std::path::PathBuf;
+
+    const SGMII: Option<PortMode> = Some(Sgmii(Speed100M));
+    const QSGMII_100M: Option<PortMode> = Some(Qsgmii(Speed100M));
+    const QSGMII_1G: Option<PortMode> = Some(Qsgmii(Speed1G));
+    const SFI: Option<PortMode> = Some(Sfi);
+    const BASE_KR: Option<PortMode> = Some(BaseKr);
+
+    /// The Sidecar port map (see `task/monorail-server/src/bsp/sidecar_bc.rs`)
+    const SIDECAR_MAP: PortMap = PortMap::new([
+        SGMII,       // 0  | DEV1G_0   | SERDES1G_1  | Cubby 0
+        SGMII,       // 1  | DEV1G_1   | SERDES1G_2  | Cubby 1
+        SGMII,       // 2  | DEV1G_2   | SERDES1G_3  | Cubby 2
+        SGMII,       // 3  | DEV1G_3   | SERDES1G_4  | Cubby 3
+        SGMII,       // 4  | DEV1G_4   | SERDES1G_5  | Cubby 4
+        SGMII,       // 5  | DEV1G_5   | SERDES1G_6  | Cubby 5
+        SGMII,       // 6  | DEV1G_6   | SERDES1G_7  | Cubby 6
+        SGMII,       // 7  | DEV1G_7   | SERDES1G_8  | Cubby 7
+        SGMII,       // 8  | DEV2G5_0  | SERDES6G_0  | Cubby 8
+        SGMII,       // 9  | DEV2G5_1  | SERDES6G_1  | Cubby 9
+        SGMII,       // 10 | DEV2G5_2  | SERDES6G_2  | Cubby 10
+        SGMII,       // 11 | DEV2G5_3  | SERDES6G_3  | Cubby 11
+        SGMII,       // 12 | DEV2G5_4  | SERDES6G_4  | Cubby 12
+        SGMII,       // 13 | DEV2G5_5  | SERDES6G_5  | Cubby 13
+        SGMII,       // 14 | DEV2G5_6  | SERDES6G_6  | Cubby 14
+        SGMII,       // 15 | DEV2G5_7  | SERDES6G_7  | Cubby 15
+        SGMII,       // 16 | DEV2G5_8  | SERDES6G_8  | Cubby 16
+        SGMII,       // 17 | DEV2G5_9  | SERDES6G_9  | Cubby 17
+        SGMII,       // 18 | DEV2G5_10 | SERDES6G_10 | Cubby 18
+        SGMII,       // 19 | DEV2G5_11 | SERDES6G_11 | Cubby 19
+        SGMII,       // 20 | DEV2G5_12 | SERDES6G_12 | Cubby 20
+        SGMII,       // 21 | DEV2G5_13 | SERDES6G_13 | Cubby 21
+        None,        // 22
+        None,        // 23
+        SGMII,       // 24 | DEV2G5_16 | SERDES6G_16 | Cubby 22
+        SGMII,       // 25 | DEV2G5_17 | SERDES6G_17 | Cubby 23
+        SGMII,       // 26 | DEV2G5_18 | SERDES6G_18 | Cubby 24
+        SGMII,       // 27 | DEV2G5_19 | SERDES6G_19 | Cubby 25
+        SGMII,       // 28 | DEV2G5_20 | SERDES6G_20 | Cubby 26
+        SGMII,       // 29 | DEV2G5_21 | SERDES6G_21 | Cubby 27
+        SGMII,       // 30 | DEV2G5_22 | SERDES6G_22 | Cubby 28
+        SGMII,       // 31 | DEV2G5_23 | SERDES6G_23 | Cubby 29
+        None,        // 32
+        None,        // 33
+        None,        // 34
+        None,        // 35
+        None,        // 36
+        None,        // 37
+        None,        // 38
+        None,        // 39
+        QSGMII_100M, // 40 | DEV1G_16  | SERDES6G_14 | Peer SP
+        QSGMII_100M, // 41 | DEV1G_17  | SERDES6G_14 | PSC0
+        QSGMII_100M, // 42 | DEV1G_18  | SERDES6G_14 | PSC1
+        QSGMII_100M, // 43 | Unused
+        QSGMII_1G,   // 44 | DEV1G_20  | SERDES6G_15 | Technician 1
+        QSGMII_1G,   // 45 | DEV1G_21  | SERDES6G_15 | Technician 2
+        None,        // 46 | Unused (configured in QSGMII mode by port 44)
+        None,        // 47 | Unused (configured in QSGMII mode by port 44)
+        SGMII,       // 48 | DEV2G5_24 | SERDES1G_0 | Local SP
+        BASE_KR,     // 49 | DEV10G_0  | SERDES10G_0 | Tofino 2
+        None,        // 50 | Unused
+        SGMII, // 51 | DEV2G5_27 | SERDES10G_2 | Cubby 30 (shadows DEV10G_2)
+        SGMII, // 52 | DEV2G5_28 | SERDES10G_3 | Cubby 31 (shadows DEV10G_3)
+    ]);
+
+    /// Compares a trace against its golden file.  If `VSC7448_BLESS` is set,
+    /// the golden file is (re)written instead; otherwise, a missing golden
+    /// file is a test failure.
+    fn check_golden(name: &str, trace: &[Access]) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("golden")
+            .join(format!("{name}.trace"));
+        let actual = SimVsc7448::format_trace(trace);
+
+        if std::env::var_os("VSC7448_BLESS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+
+        let expected = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => panic!(
+                "could not read golden trace {}: {e} \
+                 (rerun with VSC7448_BLESS=1 to create it)",
+                path.display()
+            ),
+        };
+        if expected != actual {
+            let line = expected
+                .lines()
+                .zip(actual.lines())
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| {
+                    expected.lines().count().min(actual.lines().count())
+                });
+            panic!(
+                "register trace differs from {} at line {} \
+                 (rerun with VSC7448_BLESS=1 if this is intended)",
+                path.display(),
+                line + 1
+            );
+        }
+    }
+
+    fn init(sim: &mut SimVsc7448) -> Result<(), VscError> {
+        Vsc7448::new(sim, RefClockFreq::Clk156p25MHz, None).init()
+    }
+
+    #[test]
+    fn init_trace() {
+        let mut sim = SimVsc7448::new();
+        init(&mut sim).unwrap();
+        check_golden("init", &sim.take_trace());
+    }
+
+    #[test]
+    fn init_two_refclks_trace() {
+        let mut sim = SimVsc7448::new();
+        Vsc7448::new(
+            &mut sim,
+            RefClockFreq::Clk25MHz,
+            Some(RefClockFreq::Clk125MHz),
+        )
+        .init()
+        .unwrap();
+        check_golden("init_two_refclks", &sim.take_trace());
+    }
+
+    #[test]
+    fn sidecar_ports_trace() {
+        let mut sim = SimVsc7448::new();
+        init(&mut sim).unwrap();
+        sim.take_trace();
+
+        Vsc7448::new(&mut sim, RefClockFreq::Clk156p25MHz, None)
+            .configure_ports_from_map(&SIDECAR_MAP)
+            .unwrap();
+        check_golden("sidecar_ports", &sim.take_trace());
+    }
+
+    #[test]
+    fn sfi_ports_trace() {
+        let mut map = [None; crate::PORT_COUNT];
+        map[49..].copy_from_slice(&[SFI; 4]);
+
+        let mut sim = SimVsc7448::new();
+        init(&mut sim).unwrap();
+        sim.take_trace();
+
+        Vsc7448::new(&mut sim, RefClockFreq::Clk156p25MHz, None)
+            .configure_ports_from_map(&PortMap::new(map))
+            .unwrap();
+        check_golden("sfi_ports", &sim.take_trace());
+    }
+
+    #[test]
+    fn soft_reset_clears_registers() {
+        let mut sim = SimVsc7448::new();
+        let reg = QSYS().CALCFG().CAL_AUTO(0);
+        sim.write(reg, 0x1234.into()).unwrap();
+        assert_eq!(sim.peek(reg.addr), 0x1234);
+
+        init(&mut sim).unwrap();
+        assert_eq!(sim.peek(reg.addr), 0);
+    }
+
+    #[test]
+    fn bad_chip_id() {
+        let mut sim = SimVsc7448::new();
+        sim.force(
+            DEVCPU_GCB().CHIP_REGS().CHIP_ID(),
+            |r, v| r.set_part_id(v),
+            0x7464,
+        );
+        assert!(matches!(init(&mut sim), Err(VscError::BadChipId(_))));
+    }
+
+    #[test]
+    fn ram_init_stuck() {
+        let mut sim = SimVsc7448::new();
+        sim.force(QSYS().RAM_CTRL().RAM_INIT(), |r, v| r.set_ram_init(v), 1);
+        assert_eq!(init(&mut sim), Err(VscError::RamInitFailed));
+    }
+
+    #[test]
+    fn pll5g_no_lock() {
+        let mut sim = SimVsc7448::new();
+        sim.force(
+            HSIO().PLL5G_STATUS(0).PLL5G_STATUS1(),
+            |r, v| r.set_gain_stat(v),
+            0,
+        );
+        assert_eq!(init(&mut sim), Err(VscError::LcPllInitFailed(0)));
+    }
+
+    #[test]
+    fn serdes10g_no_lock() {
+        let mut sim = SimVsc7448::new();
+        init(&mut sim).unwrap();
+        sim.force(
+            XGANA(0).SD10G65_TX_RCPLL().SD10G65_TX_RCPLL_STAT0(),
+            |r, v| r.set_pllf_lock_stat(v),
+            0,
+        );
+        let mut map = [None; crate::PORT_COUNT];
+        map[49] = BASE_KR;
+        assert_eq!(
+            Vsc7448::new(&mut sim, RefClockFreq::Clk156p25MHz, None)
+                .configure_ports_from_map(&PortMap::new(map)),
+            Err(VscError::TxPllLockFailed)
+        );
+    }
+
+    #[test]
+    fn serdes6g_timeout() {
+        let mut sim = SimVsc7448::new();
+        init(&mut sim).unwrap();
+        sim.force(
+            HSIO().MCB_SERDES6G_CFG().MCB_SERDES6G_ADDR_CFG(),
+            |r, v| r.set_serdes6g_wr_one_shot(v),
+            1,
+        );
+        let mut map = [None; crate::PORT_COUNT];
+        map[8] = SGMII;
+        assert!(matches!(
+            Vsc7448::new(&mut sim, RefClockFreq::Clk156p25MHz, None)
+                .configure_ports_from_map(&PortMap::new(map)),
+            Err(VscError::Serdes6gWriteTimeout { .. })
+        ));
+    }
+
+    #[test]
+    fn link_status() {
+        let sim = SimVsc7448::new();
+        let dev = DevGeneric::new_2g5(0).unwrap();
+        let reg = dev.regs().PCS1G_CFG_STATUS().PCS1G_LINK_STATUS();
+        assert_eq!(sim.read(reg).unwrap().link_status(), 0);
+        sim.set_link_1g(&dev, true);
+        assert_eq!(sim.read(reg).unwrap().link_status(), 1);
+
+        let reg = PCS10G_BR(1).PCS_10GBR_STATUS().PCS_STATUS();
+        assert_eq!(sim.read(reg).unwrap().rx_block_lock(), 0);
+        sim.set_link_10g(1, true);
+        assert_eq!(sim.read(reg).unwrap().rx_block_lock(), 1);
+    }
+
+    #[test]
+    fn bad_address() {
+        let sim = SimVsc7448::new();
+        let mut reg = QSYS().CALCFG().CAL_AUTO(0);
+        reg.addr = 0x7000_0000;
+        assert_eq!(
+            sim.read(reg).map(u32::from),
+            Err(VscError::BadRegAddr(0x7000_0000))
+        );
+    }
+}
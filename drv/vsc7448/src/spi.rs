@@ -2,7 +2,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use crate::{Vsc7448Rw, VscError};
+use crate::{Vsc7448Rw, VscError, SPI_NUM_PAD_BYTES};
 use drv_spi_api::{SpiDevice, SpiServer};
 use ringbuf::*;
 use userlib::UnwrapLite;
@@ -15,12 +15,6 @@ enum Trace {
     Write { addr: u32, value: u32 },
 }
 
-/// This indicates how many bytes we pad between (writing) the address bytes
-/// and (reading) data back, during SPI transactions to the VSC7448.  See
-/// section 5.5.2 for details.  1 padding byte should be good up to 6.5 MHz
-/// SPI clock.
-pub const SPI_NUM_PAD_BYTES: usize = 1;
-
 ringbuf!(Trace, 16, Trace::None);
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -3,5 +3,9 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 fn main() {
-    build_util::expose_m_profile();
+    // There's no M profile to expose when we're built for the host, e.g. for
+    // the tests of a crate that uses us.
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("none") {
+        build_util::expose_m_profile();
+    }
 }
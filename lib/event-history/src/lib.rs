@@ -0,0 +1,133 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! A ring of the most recent events recorded by a task, which clients read
+//! in batches by index.
+//!
+//! Every event is given an index, counting from 0 at boot; a client that
+//! has read up to some index asks for the batch starting just past it.  The
+//! index is a `u32` that wraps, so it is compared relative to the index of
+//! the next event to be recorded rather than directly.
+
+#![cfg_attr(not(test), no_std)]
+
+pub struct EventHistory<T, const N: usize> {
+    events: [T; N],
+    /// Index of the next event to be recorded
+    count: u32,
+    /// Slot that the next event will be recorded in
+    next: usize,
+    /// Number of slots holding events, which stops growing at `N`
+    held: usize,
+}
+
+impl<T: Copy + Default, const N: usize> EventHistory<T, N> {
+    pub fn new() -> Self {
+        Self {
+            events: [T::default(); N],
+            count: 0,
+            next: 0,
+            held: 0,
+        }
+    }
+
+    /// Returns the index that the next event will be given
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Records the event returned by `event`, which is passed its index
+    pub fn record(&mut self, event: impl FnOnce(u32) -> T) {
+        self.events[self.next] = event(self.count);
+        self.count = self.count.wrapping_add(1);
+        self.next = (self.next + 1) % N;
+        self.held = usize::min(self.held + 1, N);
+    }
+
+    /// Returns up to `M` events starting with the one at index `first`,
+    /// followed by `T::default()` for want of more.  If `first` has fallen
+    /// out of the history, the batch starts with the oldest event that we
+    /// still have; if it is (at most 2^31) past the newest one, the batch is
+    /// empty.
+    pub fn batch<const M: usize>(&self, first: u32) -> [T; M] {
+        let mut batch = [T::default(); M];
+
+        let behind = self.count.wrapping_sub(first);
+        if behind > i32::MAX as u32 {
+            return batch;
+        }
+
+        let behind = usize::min(behind as usize, self.held);
+        let start = (self.next + N - behind) % N;
+
+        for (i, slot) in batch.iter_mut().take(behind).enumerate() {
+            *slot = self.events[(start + i) % N];
+        }
+
+        batch
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Default for EventHistory<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type History = EventHistory<u32, 4>;
+
+    fn recorded(n: u32, count: u32) -> History {
+        let mut h = History::new();
+        h.count = count;
+        for _ in 0..n {
+            h.record(|i| i);
+        }
+        h
+    }
+
+    #[test]
+    fn empty() {
+        let h = History::new();
+        assert_eq!(h.batch::<2>(0), [0, 0]);
+        assert_eq!(h.batch::<2>(5), [0, 0]);
+    }
+
+    #[test]
+    fn batches() {
+        let h = recorded(3, 0);
+        assert_eq!(h.count(), 3);
+        assert_eq!(h.batch::<2>(0), [0, 1]);
+        assert_eq!(h.batch::<4>(1), [1, 2, 0, 0]);
+        assert_eq!(h.batch::<2>(3), [0, 0]);
+        assert_eq!(h.batch::<2>(100), [0, 0]);
+    }
+
+    #[test]
+    fn skips_what_was_overwritten() {
+        let h = recorded(10, 0);
+        assert_eq!(h.batch::<4>(0), [6, 7, 8, 9]);
+        assert_eq!(h.batch::<4>(8), [8, 9, 0, 0]);
+    }
+
+    #[test]
+    fn crosses_the_wrap() {
+        let h = recorded(6, u32::MAX - 2);
+        assert_eq!(h.count(), 3);
+        assert_eq!(h.batch::<4>(u32::MAX), [u32::MAX, 0, 1, 2]);
+        assert_eq!(h.batch::<2>(0), [0, 1]);
+        assert_eq!(h.batch::<4>(1), [1, 2, 0, 0]);
+
+        // Indices from before the wrap that we no longer hold
+        assert_eq!(h.batch::<4>(u32::MAX - 1), [u32::MAX, 0, 1, 2]);
+        assert_eq!(h.batch::<4>(u32::MAX - 10), [u32::MAX, 0, 1, 2]);
+
+        // Indices past the newest event
+        assert_eq!(h.batch::<4>(3), [0, 0, 0, 0]);
+        assert_eq!(h.batch::<4>(i32::MAX as u32), [0, 0, 0, 0]);
+    }
+}
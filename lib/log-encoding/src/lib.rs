@@ -0,0 +1,559 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Compact encoding of `sys_log!` records for the `log-task` backend.
+//!
+//! Rather than formatting messages on the target, `sys_log!` interns its
+//! format string in an ELF section that isn't loaded (so the string costs no
+//! flash) and sends a record consisting of that string's address, followed by
+//! each argument:
+//!
+//! - Integers are sent as LEB128 (zigzag-encoded if signed), so that the
+//!   format spec (`{:#x}`, `{:08}`, etc.) can be applied on the host.
+//! - Booleans, characters and strings are sent as themselves.
+//! - Anything else is rendered on the target with its `Debug` impl.
+//!
+//! Records are at most [`MAX_RECORD_SIZE`] bytes; arguments that don't fit
+//! are dropped (or, for text, cut short).  With the `std` feature, [`decode`]
+//! and [`format`] turn a record back into a message, given its format string.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+/// Maximum size of an encoded record, including the format string ID
+pub const MAX_RECORD_SIZE: usize = 64;
+
+/// Name of the ELF section holding interned format strings
+pub const FORMAT_SECTION: &str = ".hubris_log_fmt";
+
+mod tag {
+    pub const UNSIGNED: u8 = 0;
+    pub const SIGNED: u8 = 1;
+    pub const BOOL: u8 = 2;
+    pub const CHAR: u8 = 3;
+    pub const STR: u8 = 4;
+    pub const DEBUG: u8 = 5;
+}
+
+/// Copies a format string into a NUL-terminated array, for interning.
+pub const fn fmt_bytes<const N: usize>(s: &str) -> [u8; N] {
+    let bytes = s.as_bytes();
+    let mut out = [0; N];
+    let mut i = 0;
+    while i < bytes.len() && i < N - 1 {
+        out[i] = bytes[i];
+        i += 1;
+    }
+    out
+}
+
+pub struct Encoder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    /// Set once an argument has been dropped; nothing more is encoded
+    full: bool,
+}
+
+impl<'a> Encoder<'a> {
+    /// Starts a record for the format string at address `fmt`.
+    pub fn new(buf: &'a mut [u8], fmt: u32) -> Self {
+        let mut enc = Self {
+            buf,
+            len: 0,
+            full: false,
+        };
+        enc.put(&fmt.to_le_bytes());
+        enc
+    }
+
+    /// Returns the encoded record
+    pub fn finish(self) -> &'a [u8] {
+        &self.buf[..self.len]
+    }
+
+    fn put(&mut self, bytes: &[u8]) {
+        if self.full {
+            return;
+        }
+        match self.buf.get_mut(self.len..self.len + bytes.len()) {
+            Some(dest) => {
+                dest.copy_from_slice(bytes);
+                self.len += bytes.len();
+            }
+            None => self.full = true,
+        }
+    }
+
+    fn leb128(&mut self, tag: u8, mut v: u64) {
+        let mut bytes = [tag; 11];
+        let mut n = 1;
+        loop {
+            let byte = (v & 0x7f) as u8;
+            v >>= 7;
+            if v == 0 {
+                bytes[n] = byte;
+                n += 1;
+                break;
+            }
+            bytes[n] = byte | 0x80;
+            n += 1;
+        }
+        self.put(&bytes[..n]);
+    }
+
+    pub fn unsigned(&mut self, v: u64) {
+        self.leb128(tag::UNSIGNED, v);
+    }
+
+    pub fn signed(&mut self, v: i64) {
+        self.leb128(tag::SIGNED, ((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    pub fn bool(&mut self, v: bool) {
+        self.put(&[tag::BOOL, v as u8]);
+    }
+
+    pub fn char(&mut self, v: char) {
+        self.leb128(tag::CHAR, v as u64);
+    }
+
+    pub fn str(&mut self, v: &str) {
+        let mut text = self.text(tag::STR);
+        core::fmt::Write::write_str(&mut text, v).ok();
+    }
+
+    pub fn debug(&mut self, v: &dyn core::fmt::Debug) {
+        let mut text = self.text(tag::DEBUG);
+        core::fmt::write(&mut text, format_args!("{v:?}")).ok();
+    }
+
+    /// Starts a text argument, which is cut short if it doesn't fit
+    fn text(&mut self, tag: u8) -> Text<'_, 'a> {
+        let start = self.len;
+        self.put(&[tag, 0]);
+        Text { enc: self, start }
+    }
+}
+
+/// A text argument that's being written; its length is filled in when it's
+/// dropped.
+struct Text<'e, 'a> {
+    enc: &'e mut Encoder<'a>,
+    start: usize,
+}
+
+impl core::fmt::Write for Text<'_, '_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.enc.full {
+            return Ok(());
+        }
+        let len = self.enc.len - self.start - 2;
+        let room = (self.enc.buf.len() - self.enc.len).min(255 - len);
+        let mut n = s.len().min(room);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.enc.put(&s.as_bytes()[..n]);
+        Ok(())
+    }
+}
+
+impl Drop for Text<'_, '_> {
+    fn drop(&mut self) {
+        if let Some(len) = self.enc.buf.get_mut(self.start + 1) {
+            *len = (self.enc.len - self.start).saturating_sub(2) as u8;
+        }
+    }
+}
+
+/// Types that are encoded as themselves, rather than via `Debug`
+pub trait Primitive {
+    fn encode(&self, enc: &mut Encoder);
+}
+
+macro_rules! primitive {
+    ($method:ident as $as:ty: $($t:ty),*) => {
+        $(
+            impl Primitive for $t {
+                fn encode(&self, enc: &mut Encoder) {
+                    enc.$method(*self as $as);
+                }
+            }
+        )*
+    };
+}
+
+primitive!(unsigned as u64: u8, u16, u32, u64, usize);
+primitive!(signed as i64: i8, i16, i32, i64, isize);
+
+impl Primitive for bool {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.bool(*self);
+    }
+}
+
+impl Primitive for char {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.char(*self);
+    }
+}
+
+impl Primitive for &str {
+    fn encode(&self, enc: &mut Encoder) {
+        enc.str(self);
+    }
+}
+
+/// Wrapper for a `sys_log!` argument.  Calling `encode_arg` on a reference
+/// to one (with both [`EncodePrimitive`] and [`EncodeDebug`] in scope) picks
+/// the primitive encoding where there is one, and `Debug` otherwise.
+pub struct Arg<'a, T: ?Sized>(pub &'a T);
+
+pub trait EncodePrimitive {
+    fn encode_arg(&self, enc: &mut Encoder);
+}
+
+impl<T: Primitive> EncodePrimitive for Arg<'_, T> {
+    fn encode_arg(&self, enc: &mut Encoder) {
+        self.0.encode(enc);
+    }
+}
+
+pub trait EncodeDebug {
+    fn encode_arg(&self, enc: &mut Encoder);
+}
+
+impl<T: core::fmt::Debug + ?Sized> EncodeDebug for &Arg<'_, T> {
+    fn encode_arg(&self, enc: &mut Encoder) {
+        enc.debug(&self.0);
+    }
+}
+
+/// A decoded argument
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unsigned(u64),
+    Signed(i64),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    /// An argument that was rendered with `Debug` on the target
+    Debug(String),
+}
+
+/// Decodes a record into its format string ID and arguments.  A record that
+/// was cut short yields the arguments that it does contain.
+#[cfg(feature = "std")]
+pub fn decode(record: &[u8]) -> Option<(u32, Vec<Value>)> {
+    let fmt = u32::from_le_bytes(record.get(..4)?.try_into().unwrap());
+    let mut rest = &record[4..];
+    let mut args = vec![];
+
+    fn leb128(rest: &mut &[u8]) -> Option<u64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&b, r) = rest.split_first()?;
+            *rest = r;
+            v |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Some(v);
+            }
+        }
+        None
+    }
+
+    fn text(rest: &mut &[u8]) -> Option<String> {
+        let (&len, r) = rest.split_first()?;
+        let len = (len as usize).min(r.len());
+        *rest = &r[len..];
+        Some(String::from_utf8_lossy(&r[..len]).into_owned())
+    }
+
+    while let Some((&t, r)) = rest.split_first() {
+        rest = r;
+        let arg = match t {
+            tag::UNSIGNED => leb128(&mut rest).map(Value::Unsigned),
+            tag::SIGNED => leb128(&mut rest)
+                .map(|v| Value::Signed((v >> 1) as i64 ^ -((v & 1) as i64))),
+            tag::BOOL => rest.split_first().map(|(&b, r)| {
+                rest = r;
+                Value::Bool(b != 0)
+            }),
+            tag::CHAR => leb128(&mut rest)
+                .map(|v| Value::Char(char::from_u32(v as u32).unwrap_or('?'))),
+            tag::STR => text(&mut rest).map(Value::Str),
+            tag::DEBUG => text(&mut rest).map(Value::Debug),
+            _ => None,
+        };
+        match arg {
+            Some(arg) => args.push(arg),
+            None => break,
+        }
+    }
+
+    Some((fmt, args))
+}
+
+/// A parsed `{...}` format spec; only the parts that we can apply to our
+/// values are kept.
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    plus: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    ty: String,
+}
+
+#[cfg(feature = "std")]
+impl Spec {
+    fn parse(s: &str) -> Spec {
+        let mut spec = Spec::default();
+        let chars: Vec<char> = s.chars().collect();
+        let mut i = 0;
+
+        let is_align = |c: char| matches!(c, '<' | '^' | '>');
+        if chars.len() >= 2 && is_align(chars[1]) {
+            spec.fill = Some(chars[0]);
+            spec.align = Some(chars[1]);
+            i = 2;
+        } else if !chars.is_empty() && is_align(chars[0]) {
+            spec.align = Some(chars[0]);
+            i = 1;
+        }
+        if chars.get(i) == Some(&'+') {
+            spec.plus = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'#') {
+            spec.alternate = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            spec.zero = true;
+            i += 1;
+        }
+        while let Some(d) = chars.get(i).and_then(|c| c.to_digit(10)) {
+            spec.width = spec.width * 10 + d as usize;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            let mut p = 0;
+            while let Some(d) = chars.get(i).and_then(|c| c.to_digit(10)) {
+                p = p * 10 + d as usize;
+                i += 1;
+            }
+            spec.precision = Some(p);
+        }
+        spec.ty = chars[i..].iter().collect();
+        spec
+    }
+
+    fn apply(&self, value: &Value) -> String {
+        let (sign, prefix, body, numeric) = match value {
+            Value::Unsigned(v) => {
+                let (p, b) = self.radix(*v);
+                (if self.plus { "+" } else { "" }, p, b, true)
+            }
+            Value::Signed(v) if self.ty.is_empty() || self.ty == "?" => {
+                let sign = if *v < 0 {
+                    "-"
+                } else if self.plus {
+                    "+"
+                } else {
+                    ""
+                };
+                (sign, "", v.unsigned_abs().to_string(), true)
+            }
+            Value::Signed(v) => {
+                let (p, b) = self.radix(*v as u64);
+                ("", p, b, true)
+            }
+            Value::Bool(v) => ("", "", v.to_string(), false),
+            Value::Char(c) if self.ty == "?" => {
+                ("", "", format!("{c:?}"), false)
+            }
+            Value::Char(c) => ("", "", c.to_string(), false),
+            Value::Str(s) if self.ty == "?" => {
+                ("", "", format!("{s:?}"), false)
+            }
+            Value::Str(s) => {
+                let s = match self.precision {
+                    Some(p) => s.chars().take(p).collect(),
+                    None => s.clone(),
+                };
+                ("", "", s, false)
+            }
+            Value::Debug(s) => ("", "", s.clone(), false),
+        };
+
+        let len = sign.len() + prefix.len() + body.chars().count();
+        let pad = self.width.saturating_sub(len);
+
+        if numeric && self.zero {
+            return format!("{sign}{prefix}{}{body}", "0".repeat(pad));
+        }
+
+        let fill = self.fill.unwrap_or(' ').to_string();
+        let align = self.align.unwrap_or(if numeric { '>' } else { '<' });
+        let (left, right) = match align {
+            '<' => (0, pad),
+            '^' => (pad / 2, pad - pad / 2),
+            _ => (pad, 0),
+        };
+        format!(
+            "{}{sign}{prefix}{body}{}",
+            fill.repeat(left),
+            fill.repeat(right)
+        )
+    }
+
+    fn radix(&self, v: u64) -> (&'static str, String) {
+        let alt = self.alternate;
+        match self.ty.as_str() {
+            "x" | "x?" => (if alt { "0x" } else { "" }, format!("{v:x}")),
+            "X" | "X?" => (if alt { "0x" } else { "" }, format!("{v:X}")),
+            "b" => (if alt { "0b" } else { "" }, format!("{v:b}")),
+            "o" => (if alt { "0o" } else { "" }, format!("{v:o}")),
+            _ => ("", v.to_string()),
+        }
+    }
+}
+
+/// Formats a message from its format string and decoded arguments, much as
+/// `format!` would have.  Arguments that are missing (because the record was
+/// cut short, or because they were captured by name) are shown as `{...}`.
+#[cfg(feature = "std")]
+pub fn format(fmt: &str, args: &[Value]) -> String {
+    let mut out = String::new();
+    let mut chars = fmt.chars().peekable();
+    let mut next = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut inner = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    inner.push(c);
+                }
+                let (arg, spec) = inner.split_once(':').unwrap_or((&inner, ""));
+
+                let index = if arg.is_empty() {
+                    next += 1;
+                    Some(next - 1)
+                } else {
+                    arg.parse::<usize>().ok()
+                };
+
+                match index.and_then(|i| args.get(i)) {
+                    Some(v) => out.push_str(&Spec::parse(spec).apply(v)),
+                    None => {
+                        out.push('{');
+                        out.push_str(&inner);
+                        out.push('}');
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn encode(f: impl FnOnce(&mut Encoder)) -> Vec<u8> {
+        let mut buf = [0; MAX_RECORD_SIZE];
+        let mut enc = Encoder::new(&mut buf, 0x1234);
+        f(&mut enc);
+        enc.finish().to_vec()
+    }
+
+    #[test]
+    fn round_trip() {
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        enum Fault {
+            Bad(u8),
+        }
+
+        // This is how `sys_log!` encodes its arguments.
+        macro_rules! arg {
+            ($enc:ident, $v:expr) => {
+                (&Arg(&$v)).encode_arg($enc)
+            };
+        }
+
+        let record = encode(|enc| {
+            arg!(enc, 3u8);
+            arg!(enc, 0x2000_1000u32);
+            arg!(enc, -5i32);
+            arg!(enc, "hi");
+            arg!(enc, Fault::Bad(7));
+        });
+
+        let (fmt, args) = decode(&record).unwrap();
+        assert_eq!(fmt, 0x1234);
+        assert_eq!(
+            args,
+            vec![
+                Value::Unsigned(3),
+                Value::Unsigned(0x2000_1000),
+                Value::Signed(-5),
+                Value::Str("hi".to_string()),
+                Value::Debug("Bad(7)".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            format("Task #{} fault at {:#010x} ({}, {:?}): {:?}", &args),
+            "Task #3 fault at 0x20001000 (-5, \"hi\"): Bad(7)"
+        );
+    }
+
+    #[test]
+    fn specs() {
+        let args = [Value::Unsigned(0xab), Value::Signed(-12)];
+        assert_eq!(format("{0:x} {0:X} {0:#b}", &args), "ab AB 0b10101011");
+        assert_eq!(
+            format("[{1:>5}] [{0:<6}] [{0:08}]", &args),
+            "[  -12] [171   ] [00000171]"
+        );
+        assert_eq!(format("{{{}}} {name} {}", &args[..1]), "{171} {name} {}");
+    }
+
+    #[test]
+    fn truncation() {
+        let long = "x".repeat(100);
+        let record = encode(|enc| {
+            enc.unsigned(1);
+            enc.str(&long);
+            enc.unsigned(2);
+        });
+        assert_eq!(record.len(), MAX_RECORD_SIZE);
+
+        let (_, args) = decode(&record).unwrap();
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[1], Value::Str("x".repeat(MAX_RECORD_SIZE - 8)));
+    }
+}
@@ -0,0 +1,421 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Decoders for PMBus data
+//!
+//! This covers the data formats used by the generic PMBus driver (in
+//! `drv-i2c-devices`) and the status registers decoded by the power task into
+//! [`RailFaults`].  They're kept apart from the driver and the power API so
+//! that they can be tested on the host.
+
+#![cfg_attr(not(test), no_std)]
+
+use num_traits::float::FloatCore;
+
+/// Encoding of a command's data
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    /// LINEAR11: a signed 5-bit exponent and a signed 11-bit mantissa
+    Linear11,
+    /// LINEAR16: an unsigned 16-bit mantissa, with the exponent taken from
+    /// VOUT_MODE (only used for output voltage)
+    Linear16,
+    /// DIRECT, with the given coefficients: X = (Y * 10^-R - b) / m
+    Direct { m: i32, b: i32, r: i8 },
+}
+
+/// Decodes a LINEAR11 value
+pub fn linear11(raw: u16) -> f32 {
+    let exp = (raw as i16) >> 11;
+    let mantissa = ((raw << 5) as i16) >> 5;
+    f32::from(mantissa) * FloatCore::powi(2.0f32, exp.into())
+}
+
+/// Decodes a LINEAR16 value, given the device's VOUT_MODE.  Returns `None` if
+/// VOUT_MODE isn't in linear mode.
+pub fn linear16(raw: u16, vout_mode: u8) -> Option<f32> {
+    // Bits 6:5 are the mode, which must be linear (0b00)
+    if ((vout_mode >> 5) & 0b11) != 0 {
+        return None;
+    }
+    // The exponent is a signed 5-bit value in the low bits
+    let exp = ((vout_mode << 3) as i8) >> 3;
+    Some(f32::from(raw) * FloatCore::powi(2.0f32, exp.into()))
+}
+
+/// Decodes a DIRECT value with the given coefficients
+pub fn direct(raw: u16, m: i32, b: i32, r: i8) -> f32 {
+    let y = f32::from(raw as i16);
+    (y * FloatCore::powi(10.0f32, -i32::from(r)) - b as f32) / m as f32
+}
+
+bitflags::bitflags! {
+    /// Faults and warnings decoded from a rail's PMBus status registers.
+    ///
+    /// Bits that are summaries in STATUS_WORD (e.g. `VOUT`) are only set
+    /// when the corresponding detailed status register couldn't be read;
+    /// otherwise, the detailed bits are used instead.
+    pub struct RailFaults: u32 {
+        // STATUS_VOUT
+        const VOUT_OV_FAULT = 1 << 0;
+        const VOUT_OV_WARN = 1 << 1;
+        const VOUT_UV_WARN = 1 << 2;
+        const VOUT_UV_FAULT = 1 << 3;
+        const VOUT_MAX_MIN_WARN = 1 << 4;
+        const TON_MAX_FAULT = 1 << 5;
+        const TOFF_MAX_WARN = 1 << 6;
+
+        // STATUS_IOUT
+        const IOUT_OC_FAULT = 1 << 8;
+        const IOUT_OC_LV_FAULT = 1 << 9;
+        const IOUT_OC_WARN = 1 << 10;
+        const IOUT_UC_FAULT = 1 << 11;
+        const CURRENT_SHARE_FAULT = 1 << 12;
+        const POUT_OP_FAULT = 1 << 13;
+        const POUT_OP_WARN = 1 << 14;
+
+        // STATUS_TEMPERATURE
+        const OT_FAULT = 1 << 16;
+        const OT_WARN = 1 << 17;
+        const UT_WARN = 1 << 18;
+        const UT_FAULT = 1 << 19;
+
+        // STATUS_WORD
+        const VIN_UV_FAULT = 1 << 24;
+        const CML = 1 << 25;
+        const INPUT = 1 << 26;
+        const MFR_SPECIFIC = 1 << 27;
+        const FANS = 1 << 28;
+        const OTHER = 1 << 29;
+        const VOUT = 1 << 30;
+        const IOUT = 1 << 31;
+    }
+}
+
+/// Bits of STATUS_VOUT, from most to least significant
+const STATUS_VOUT_BITS: [Option<RailFaults>; 8] = [
+    Some(RailFaults::VOUT_OV_FAULT),
+    Some(RailFaults::VOUT_OV_WARN),
+    Some(RailFaults::VOUT_UV_WARN),
+    Some(RailFaults::VOUT_UV_FAULT),
+    Some(RailFaults::VOUT_MAX_MIN_WARN),
+    Some(RailFaults::TON_MAX_FAULT),
+    Some(RailFaults::TOFF_MAX_WARN),
+    None, // power-on tracking error
+];
+
+/// Bits of STATUS_IOUT, from most to least significant
+const STATUS_IOUT_BITS: [Option<RailFaults>; 8] = [
+    Some(RailFaults::IOUT_OC_FAULT),
+    Some(RailFaults::IOUT_OC_LV_FAULT),
+    Some(RailFaults::IOUT_OC_WARN),
+    Some(RailFaults::IOUT_UC_FAULT),
+    Some(RailFaults::CURRENT_SHARE_FAULT),
+    None, // in power limiting mode
+    Some(RailFaults::POUT_OP_FAULT),
+    Some(RailFaults::POUT_OP_WARN),
+];
+
+/// Bits of STATUS_TEMPERATURE, from most to least significant
+const STATUS_TEMPERATURE_BITS: [Option<RailFaults>; 8] = [
+    Some(RailFaults::OT_FAULT),
+    Some(RailFaults::OT_WARN),
+    Some(RailFaults::UT_WARN),
+    Some(RailFaults::UT_FAULT),
+    None,
+    None,
+    None,
+    None,
+];
+
+/// Bits of STATUS_WORD that we report directly, as (bit, fault) pairs.  BUSY,
+/// OFF and POWER_GOOD# are states rather than faults, and are ignored.
+const STATUS_WORD_BITS: [(u32, RailFaults); 6] = [
+    (1, RailFaults::CML),
+    (3, RailFaults::VIN_UV_FAULT),
+    (9, RailFaults::OTHER),
+    (10, RailFaults::FANS),
+    (12, RailFaults::MFR_SPECIFIC),
+    (13, RailFaults::INPUT),
+];
+
+impl RailFaults {
+    /// Decodes PMBus status registers.  Each detailed register is only
+    /// consulted if its summary bit in `status_word` is set, and should be
+    /// `None` if it couldn't be read.
+    pub fn decode(
+        status_word: u16,
+        status_vout: Option<u8>,
+        status_iout: Option<u8>,
+        status_temperature: Option<u8>,
+    ) -> Self {
+        let bit = |n: u32| status_word & (1 << n) != 0;
+        let mut out = Self::empty();
+
+        for (n, fault) in STATUS_WORD_BITS {
+            if bit(n) {
+                out |= fault;
+            }
+        }
+
+        // STATUS_VOUT is summarized by VOUT (bit 15) and VOUT_OV_FAULT (bit 5)
+        if bit(15) || bit(5) {
+            out |=
+                Self::decode_byte(status_vout, &STATUS_VOUT_BITS, Self::VOUT);
+        }
+
+        // STATUS_IOUT is summarized by IOUT/POUT (bit 14) and IOUT_OC_FAULT
+        // (bit 4)
+        if bit(14) || bit(4) {
+            out |=
+                Self::decode_byte(status_iout, &STATUS_IOUT_BITS, Self::IOUT);
+        }
+
+        // STATUS_TEMPERATURE is summarized by TEMPERATURE (bit 2), which has
+        // no summary fault of its own; if we can't read the details, assume
+        // the worst.
+        if bit(2) {
+            out |= Self::decode_byte(
+                status_temperature,
+                &STATUS_TEMPERATURE_BITS,
+                Self::OT_FAULT,
+            );
+        }
+
+        out
+    }
+
+    fn decode_byte(
+        value: Option<u8>,
+        bits: &[Option<RailFaults>; 8],
+        fallback: RailFaults,
+    ) -> Self {
+        let value = match value {
+            Some(value) => value,
+            None => return fallback,
+        };
+        let mut out = Self::empty();
+        for (i, fault) in bits.iter().enumerate() {
+            if let Some(fault) = fault {
+                if value & (0x80 >> i) != 0 {
+                    out |= *fault;
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+// Binary literals are grouped by field (e.g., exponent and mantissa)
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear11_positive() {
+        // Exponent -2, mantissa 50: 12.5
+        assert_eq!(linear11(0b11110_000_0011_0010), 12.5);
+        // Exponent 0, mantissa 1023 (the largest positive value)
+        assert_eq!(linear11(0b00000_011_1111_1111), 1023.0);
+        // Exponent 3, mantissa 1: 8
+        assert_eq!(linear11(0b00011_000_0000_0001), 8.0);
+    }
+
+    #[test]
+    fn linear11_negative() {
+        // Exponent -1, mantissa -4: -2
+        assert_eq!(linear11(0b11111_111_1111_1100), -2.0);
+        // Exponent 0, mantissa -1024 (the largest negative value)
+        assert_eq!(linear11(0b00000_100_0000_0000), -1024.0);
+        // Exponent -16 (the smallest exponent), mantissa 1
+        assert_eq!(linear11(0b10000_000_0000_0001), 1.0 / 65536.0);
+    }
+
+    #[test]
+    fn linear16() {
+        // VOUT_MODE exponent -12: 0x1a00 is 1.625 V
+        assert_eq!(super::linear16(0x1a00, 0b000_10100), Some(1.625));
+        // VOUT_MODE exponent -9
+        assert_eq!(super::linear16(0x0600, 0b000_10111), Some(3.0));
+        // Positive exponent
+        assert_eq!(super::linear16(3, 0b000_00010), Some(12.0));
+        // The mantissa is unsigned
+        assert_eq!(super::linear16(0xffff, 0), Some(65535.0));
+    }
+
+    #[test]
+    fn linear16_bad_mode() {
+        // VID mode
+        assert_eq!(super::linear16(0x1a00, 0b001_10100), None);
+        // DIRECT mode
+        assert_eq!(super::linear16(0x1a00, 0b010_00000), None);
+    }
+
+    #[test]
+    fn direct() {
+        // m = 1, b = 0, R = 2: Y = 1234 is 12.34
+        assert!((super::direct(1234, 1, 0, 2) - 12.34).abs() < 1e-4);
+        // m = 10, b = 0, R = 0: Y = 1234 is 123.4
+        assert!((super::direct(1234, 10, 0, 0) - 123.4).abs() < 1e-4);
+        // Offset: X = (Y - b) / m
+        assert_eq!(super::direct(100, 2, 40, 0), 30.0);
+        // The raw value is signed
+        assert_eq!(super::direct(0xfff6, 1, 0, 0), -10.0);
+        // Negative R scales up
+        assert_eq!(super::direct(5, 1, 0, -2), 500.0);
+    }
+
+    #[test]
+    fn rail_faults_status_word() {
+        assert_eq!(
+            RailFaults::decode(0, None, None, None),
+            RailFaults::empty()
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 1, None, None, None),
+            RailFaults::CML
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 3, None, None, None),
+            RailFaults::VIN_UV_FAULT
+        );
+        assert_eq!(
+            RailFaults::decode(
+                (1 << 9) | (1 << 10) | (1 << 12) | (1 << 13),
+                None,
+                None,
+                None
+            ),
+            RailFaults::OTHER
+                | RailFaults::FANS
+                | RailFaults::MFR_SPECIFIC
+                | RailFaults::INPUT
+        );
+    }
+
+    #[test]
+    fn rail_faults_ignores_states() {
+        // BUSY (7), OFF (6) and POWER_GOOD# (11) aren't faults
+        assert_eq!(
+            RailFaults::decode(
+                (1 << 7) | (1 << 6) | (1 << 11),
+                None,
+                None,
+                None
+            ),
+            RailFaults::empty()
+        );
+    }
+
+    #[test]
+    fn rail_faults_status_vout() {
+        let all = RailFaults::VOUT_OV_FAULT
+            | RailFaults::VOUT_OV_WARN
+            | RailFaults::VOUT_UV_WARN
+            | RailFaults::VOUT_UV_FAULT
+            | RailFaults::VOUT_MAX_MIN_WARN
+            | RailFaults::TON_MAX_FAULT
+            | RailFaults::TOFF_MAX_WARN;
+
+        // STATUS_VOUT is ignored unless summarized in STATUS_WORD
+        assert_eq!(
+            RailFaults::decode(0, Some(0xff), None, None),
+            RailFaults::empty()
+        );
+        assert_eq!(RailFaults::decode(1 << 15, Some(0xff), None, None), all);
+        assert_eq!(
+            RailFaults::decode(1 << 5, Some(0x80), None, None),
+            RailFaults::VOUT_OV_FAULT
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 15, Some(0x10), None, None),
+            RailFaults::VOUT_UV_FAULT
+        );
+        // Power-on tracking error (bit 0) has no fault
+        assert_eq!(
+            RailFaults::decode(1 << 15, Some(0x01), None, None),
+            RailFaults::empty()
+        );
+        // If STATUS_VOUT couldn't be read, fall back to the summary
+        assert_eq!(
+            RailFaults::decode(1 << 15, None, None, None),
+            RailFaults::VOUT
+        );
+    }
+
+    #[test]
+    fn rail_faults_status_iout() {
+        assert_eq!(
+            RailFaults::decode(0, None, Some(0xff), None),
+            RailFaults::empty()
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 14, None, Some(0xff), None),
+            RailFaults::IOUT_OC_FAULT
+                | RailFaults::IOUT_OC_LV_FAULT
+                | RailFaults::IOUT_OC_WARN
+                | RailFaults::IOUT_UC_FAULT
+                | RailFaults::CURRENT_SHARE_FAULT
+                | RailFaults::POUT_OP_FAULT
+                | RailFaults::POUT_OP_WARN
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 4, None, Some(0x80), None),
+            RailFaults::IOUT_OC_FAULT
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 14, None, Some(0x01), None),
+            RailFaults::POUT_OP_WARN
+        );
+        // In power limiting mode (bit 2) has no fault
+        assert_eq!(
+            RailFaults::decode(1 << 14, None, Some(0x04), None),
+            RailFaults::empty()
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 4, None, None, None),
+            RailFaults::IOUT
+        );
+    }
+
+    #[test]
+    fn rail_faults_status_temperature() {
+        assert_eq!(
+            RailFaults::decode(0, None, None, Some(0xff)),
+            RailFaults::empty()
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 2, None, None, Some(0xff)),
+            RailFaults::OT_FAULT
+                | RailFaults::OT_WARN
+                | RailFaults::UT_WARN
+                | RailFaults::UT_FAULT
+        );
+        assert_eq!(
+            RailFaults::decode(1 << 2, None, None, Some(0x40)),
+            RailFaults::OT_WARN
+        );
+        // With no details, assume the worst
+        assert_eq!(
+            RailFaults::decode(1 << 2, None, None, None),
+            RailFaults::OT_FAULT
+        );
+    }
+
+    #[test]
+    fn rail_faults_combined() {
+        assert_eq!(
+            RailFaults::decode(
+                (1 << 15) | (1 << 14) | (1 << 3),
+                Some(0x20),
+                None,
+                Some(0x80)
+            ),
+            RailFaults::VOUT_UV_WARN
+                | RailFaults::IOUT
+                | RailFaults::VIN_UV_FAULT
+        );
+    }
+}
@@ -0,0 +1,154 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Types for messages exchanged with the ringbuf agent over UDP.
+//!
+//! Each request is a [`RequestMessage`]; each response is a
+//! [`ResponseMessage`], followed (for [`Response::ReadTaskMemory`]) by the
+//! bytes that were read.  Requests are idempotent, so a host that doesn't
+//! hear back is free to retry; the `message_id` in the header is echoed back
+//! so that late replies can be told apart.
+//!
+//! The agent doesn't know where any ring buffers are: the host finds them
+//! (and their types) in the DWARF of the image's archive, checks that the
+//! archive matches with [`Request::GetImageId`], then reads them a piece at a
+//! time with [`Request::ReadTaskMemory`].
+
+#![cfg_attr(not(test), no_std)]
+
+use hubpack::SerializedSize;
+use serde::{Deserialize, Serialize};
+
+pub mod version {
+    pub const MIN: u8 = 1;
+    pub const CURRENT: u8 = 1;
+}
+
+/// Maximum number of bytes returned by a single
+/// [`Request::ReadTaskMemory`].
+pub const MAX_READ_SIZE: usize = 512;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, SerializedSize,
+)]
+pub struct Header {
+    pub version: u8,
+    pub message_id: u32,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, SerializedSize,
+)]
+pub enum Request {
+    GetImageId,
+    ReadTaskMemory {
+        task_index: u16,
+        address: u32,
+        length: u16,
+    },
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, SerializedSize,
+)]
+pub enum Response {
+    GetImageId([u8; 8]),
+    /// Followed by `length` bytes of task memory
+    ReadTaskMemory {
+        length: u16,
+    },
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, SerializedSize,
+)]
+pub enum Error {
+    VersionMismatch {
+        ours: u8,
+        theirs: u8,
+    },
+    DeserializeError,
+    /// The requested length is more than [`MAX_READ_SIZE`]
+    ReadTooLong,
+    /// The task index names the supervisor, which can't be read
+    BadTask,
+    /// The task doesn't exist, or the requested memory isn't within one of
+    /// its dumpable regions
+    BadAddress,
+    /// The supervisor doesn't support reading task memory
+    Unsupported,
+    /// The read failed for some other reason
+    ReadFailed,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, SerializedSize,
+)]
+pub struct RequestMessage {
+    pub header: Header,
+    pub request: Request,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, SerializedSize,
+)]
+pub struct ResponseMessage {
+    pub header: Header,
+    pub response: Result<Response, Error>,
+}
+
+/// Largest possible response packet, including trailing data
+pub const MAX_RESPONSE_SIZE: usize = ResponseMessage::MAX_SIZE + MAX_READ_SIZE;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The encoding of requests is our wire protocol, so make sure that it
+    // doesn't change out from under older hosts.
+    #[test]
+    fn request_encoding() {
+        let mut buf = [0; RequestMessage::MAX_SIZE];
+        let msg = RequestMessage {
+            header: Header {
+                version: version::CURRENT,
+                message_id: 0x0403_0201,
+            },
+            request: Request::ReadTaskMemory {
+                task_index: 5,
+                address: 0x2400_1000,
+                length: 0x200,
+            },
+        };
+        let n = hubpack::serialize(&mut buf, &msg).unwrap();
+        assert_eq!(
+            &buf[..n],
+            &[1, 1, 2, 3, 4, 1, 5, 0, 0x00, 0x10, 0x00, 0x24, 0x00, 0x02]
+        );
+
+        let (out, rest) =
+            hubpack::deserialize::<RequestMessage>(&buf[..n]).unwrap();
+        assert_eq!(out, msg);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn response_with_data() {
+        let mut buf = [0; MAX_RESPONSE_SIZE];
+        let msg = ResponseMessage {
+            header: Header {
+                version: version::CURRENT,
+                message_id: 7,
+            },
+            response: Ok(Response::ReadTaskMemory { length: 3 }),
+        };
+        let n = hubpack::serialize(&mut buf, &msg).unwrap();
+        buf[n..n + 3].copy_from_slice(&[0xaa, 0xbb, 0xcc]);
+
+        let (out, rest) =
+            hubpack::deserialize::<ResponseMessage>(&buf[..n + 3]).unwrap();
+        assert_eq!(out, msg);
+        assert_eq!(rest, &[0xaa, 0xbb, 0xcc]);
+    }
+}
@@ -0,0 +1,70 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Derive macros for the `ringbuf` crate; use them through its re-exports.
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, DeriveInput};
+
+/// Implements `ringbuf::Count` for an `enum`.
+///
+/// For an `enum` named `Trace`, this generates a struct named `TraceCounts`
+/// with a `u32` field for each variant, named for the variant (so that the
+/// fields can be decoded by name from the debug info), and counts each value
+/// in the field for its variant.
+#[proc_macro_derive(Count)]
+pub fn derive_count(input: TokenStream) -> TokenStream {
+    let DeriveInput { ident, data, .. } = parse_macro_input!(input);
+
+    let data = match data {
+        syn::Data::Enum(data) => data,
+        syn::Data::Struct(_) | syn::Data::Union(_) => {
+            return syn::Error::new(
+                ident.span(),
+                "Count can only be derived on enums",
+            )
+            .into_compile_error()
+            .into();
+        }
+    };
+
+    let counts = format_ident!("{}Counts", ident);
+    let variants = data.variants.iter().map(|v| &v.ident).collect::<Vec<_>>();
+    let patterns = data.variants.iter().map(|v| {
+        let variant = &v.ident;
+        match v.fields {
+            syn::Fields::Named(_) => quote! { #ident::#variant { .. } },
+            syn::Fields::Unnamed(_) => quote! { #ident::#variant(..) },
+            syn::Fields::Unit => quote! { #ident::#variant },
+        }
+    });
+
+    let output = quote! {
+        #[allow(non_snake_case)]
+        #[derive(Copy, Clone, Debug)]
+        pub struct #counts {
+            #( pub #variants: u32, )*
+        }
+
+        impl ringbuf::Count for #ident {
+            type Counts = #counts;
+
+            const NEW_COUNTS: #counts = #counts {
+                #( #variants: 0, )*
+            };
+
+            fn count(&self, counts: &mut #counts) {
+                match self {
+                    #(
+                        #patterns => {
+                            counts.#variants = counts.#variants.wrapping_add(1);
+                        }
+                    )*
+                }
+            }
+        }
+    };
+    output.into()
+}
@@ -135,13 +135,45 @@
 //!      )
 //!    },...
 //! ```
+//!
+//! ## Other flavors of ring buffer
+//!
+//! Once a busy ring buffer wraps, it no longer says when something happened,
+//! or how often it has happened overall.  Two other flavors of ring buffer
+//! address this, at some cost in RAM:
+//!
+//! - [`timestamped_ringbuf!`] stamps each entry with the time (from
+//!   `sys_get_timer`) at which it was last recorded.  This needs the
+//!   `timestamp` feature, which pulls in `userlib`.
+//! - [`counted_ringbuf!`] additionally keeps a total for each variant of the
+//!   payload type, which is never overwritten.  The payload must be an `enum`
+//!   that derives [`Count`]:
+//!
+//! ```
+//! #[derive(Copy, Clone, PartialEq, Count)]
+//! enum Trace {
+//!     None,
+//!     Retry(u8),
+//! }
+//!
+//! counted_ringbuf!(Trace, 16, Trace::None);
+//! ```
+//!
+//! Both are declared with the same arguments as [`ringbuf!`], and entries are
+//! recorded with [`ringbuf_entry!`] as usual.  So that tools can tell them
+//! apart from a plain [`Ringbuf`] (and from any future layout changes), each
+//! starts with a [`RingbufMeta`] describing its flavor.  In a
+//! [`CountedRingbuf`], `counts` has one `u32` field per variant, named after
+//! the variant.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 /// Re-export the bits we use from `static_cell` so that code generated by the
 /// macros is guaranteed to be able to find them.
 pub use static_cell::StaticCell;
 
+pub use ringbuf_macros::Count;
+
 /// Declares a ringbuffer in the current module or context.
 ///
 /// `ringbuf!(NAME, Type, N, expr)` makes a ringbuffer named `NAME`,
@@ -190,6 +222,92 @@ macro_rules! ringbuf {
     };
 }
 
+/// Declares a ringbuffer whose entries are timestamped, in the current module
+/// or context.
+///
+/// This takes the same arguments as [`ringbuf!`]; the actual type of `name`
+/// will be `StaticCell<TimestampedRingbuf<T, N>>`.  It requires the
+/// `timestamp` feature.
+#[cfg(all(feature = "timestamp", not(feature = "disabled")))]
+#[macro_export]
+macro_rules! timestamped_ringbuf {
+    ($name:ident, $t:ty, $n:expr, $init:expr) => {
+        #[used]
+        static $name: $crate::StaticCell<$crate::TimestampedRingbuf<$t, $n>> =
+            $crate::StaticCell::new($crate::TimestampedRingbuf {
+                meta: $crate::RingbufMeta::TIMESTAMPED,
+                last: None,
+                buffer: [$crate::TimestampedRingbufEntry {
+                    line: 0,
+                    generation: 0,
+                    count: 0,
+                    timestamp: 0,
+                    payload: $init,
+                }; $n],
+            });
+    };
+    ($t:ty, $n:expr, $init:expr) => {
+        $crate::timestamped_ringbuf!(__RINGBUF, $t, $n, $init);
+    };
+}
+
+#[cfg(all(not(feature = "timestamp"), not(feature = "disabled")))]
+#[macro_export]
+macro_rules! timestamped_ringbuf {
+    ($($args:tt)*) => {
+        compile_error!(
+            "timestamped_ringbuf! requires the ringbuf `timestamp` feature"
+        );
+    };
+}
+
+#[cfg(feature = "disabled")]
+#[macro_export]
+macro_rules! timestamped_ringbuf {
+    ($($args:tt)*) => {
+        $crate::ringbuf!($($args)*);
+    };
+}
+
+/// Declares a ringbuffer that also counts its entries by variant, in the
+/// current module or context.
+///
+/// This takes the same arguments as [`ringbuf!`], but the entry type must
+/// implement [`Count`]; the actual type of `name` will be
+/// `StaticCell<CountedRingbuf<T, N>>`.
+#[cfg(not(feature = "disabled"))]
+#[macro_export]
+macro_rules! counted_ringbuf {
+    ($name:ident, $t:ty, $n:expr, $init:expr) => {
+        #[used]
+        static $name: $crate::StaticCell<$crate::CountedRingbuf<$t, $n>> =
+            $crate::StaticCell::new($crate::CountedRingbuf {
+                meta: $crate::RingbufMeta::COUNTED,
+                counts: <$t as $crate::Count>::NEW_COUNTS,
+                ringbuf: $crate::Ringbuf {
+                    last: None,
+                    buffer: [$crate::RingbufEntry {
+                        line: 0,
+                        generation: 0,
+                        count: 0,
+                        payload: $init,
+                    }; $n],
+                },
+            });
+    };
+    ($t:ty, $n:expr, $init:expr) => {
+        $crate::counted_ringbuf!(__RINGBUF, $t, $n, $init);
+    };
+}
+
+#[cfg(feature = "disabled")]
+#[macro_export]
+macro_rules! counted_ringbuf {
+    ($($args:tt)*) => {
+        $crate::ringbuf!($($args)*);
+    };
+}
+
 /// Inserts data into a named ringbuffer (which should have been declared with
 /// the `ringbuf!` macro).
 ///
@@ -209,7 +327,7 @@ macro_rules! ringbuf_entry {
         // Invoke these functions using slightly weird syntax to avoid
         // accidentally calling a _different_ routine called borrow_mut or
         // entry.
-        $crate::Ringbuf::entry(
+        $crate::RecordEntry::record_entry(
             &mut *$crate::StaticCell::borrow_mut(buf),
             line!() as u16,
             p,
@@ -307,3 +425,265 @@ impl<T: Copy + PartialEq, const N: usize> Ringbuf<T, { N }> {
         self.last = Some(ndx);
     }
 }
+
+/// Records entries in a ring buffer of any flavor; this is what
+/// [`ringbuf_entry!`] calls.
+pub trait RecordEntry<T: Copy + PartialEq> {
+    fn record_entry(&mut self, line: u16, payload: T);
+}
+
+impl<T: Copy + PartialEq, const N: usize> RecordEntry<T> for Ringbuf<T, N> {
+    fn record_entry(&mut self, line: u16, payload: T) {
+        self.entry(line, payload)
+    }
+}
+
+///
+/// Describes the flavor of a ring buffer (other than a plain [`Ringbuf`]) to
+/// the tools that decode it.
+///
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct RingbufMeta {
+    /// Which flavor of ring buffer this is
+    pub flavor: RingbufFlavor,
+    /// Layout version of this flavor, which is incremented whenever the
+    /// meaning of its fields changes
+    pub version: u8,
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(u8)]
+pub enum RingbufFlavor {
+    /// A [`TimestampedRingbuf`]
+    Timestamped = 1,
+    /// A [`CountedRingbuf`]
+    Counted = 2,
+}
+
+impl RingbufMeta {
+    pub const TIMESTAMPED: Self = Self {
+        flavor: RingbufFlavor::Timestamped,
+        version: 1,
+    };
+    pub const COUNTED: Self = Self {
+        flavor: RingbufFlavor::Counted,
+        version: 1,
+    };
+}
+
+///
+/// A [`RingbufEntry`] with the time that it was most recently recorded, in
+/// milliseconds since boot.  (When an entry is repeated, `count` is
+/// incremented and `timestamp` updated.)
+///
+#[derive(Debug, Copy, Clone)]
+pub struct TimestampedRingbufEntry<T: Copy + PartialEq> {
+    pub line: u16,
+    pub generation: u16,
+    pub count: u32,
+    pub timestamp: u64,
+    pub payload: T,
+}
+
+///
+/// A ring buffer whose entries are timestamped; see the
+/// [`timestamped_ringbuf!`] macro.
+///
+#[derive(Debug)]
+pub struct TimestampedRingbuf<T: Copy + PartialEq, const N: usize> {
+    pub meta: RingbufMeta,
+    pub last: Option<usize>,
+    pub buffer: [TimestampedRingbufEntry<T>; N],
+}
+
+impl<T: Copy + PartialEq, const N: usize> TimestampedRingbuf<T, { N }> {
+    /// Records an entry at the given time, in milliseconds since boot
+    pub fn entry_at(&mut self, line: u16, payload: T, timestamp: u64) {
+        let ndx = match self.last {
+            None => 0,
+            Some(last) => {
+                let ent = &mut self.buffer[last];
+
+                if ent.line == line && ent.payload == payload {
+                    if let Some(new_count) = ent.count.checked_add(1) {
+                        ent.count = new_count;
+                        ent.timestamp = timestamp;
+                        return;
+                    }
+                }
+
+                if last + 1 >= self.buffer.len() {
+                    0
+                } else {
+                    last + 1
+                }
+            }
+        };
+
+        let ent = &mut self.buffer[ndx];
+        ent.line = line;
+        ent.payload = payload;
+        ent.count = 1;
+        ent.timestamp = timestamp;
+        ent.generation = ent.generation.wrapping_add(1);
+
+        self.last = Some(ndx);
+    }
+}
+
+#[cfg(feature = "timestamp")]
+impl<T: Copy + PartialEq, const N: usize> RecordEntry<T>
+    for TimestampedRingbuf<T, N>
+{
+    fn record_entry(&mut self, line: u16, payload: T) {
+        self.entry_at(line, payload, userlib::sys_get_timer().now)
+    }
+}
+
+///
+/// A type whose values can be counted by variant in a [`CountedRingbuf`].
+/// This should be derived (with `#[derive(Count)]`) rather than implemented
+/// by hand.
+///
+pub trait Count: Copy + PartialEq {
+    /// A total for each variant
+    type Counts: Copy;
+
+    /// Totals that are all zero
+    const NEW_COUNTS: Self::Counts;
+
+    /// Increments the total for this value's variant
+    fn count(&self, counts: &mut Self::Counts);
+}
+
+///
+/// A ring buffer that also keeps a total of its entries by variant, which
+/// (unlike the entries themselves) is never overwritten; see the
+/// [`counted_ringbuf!`] macro.
+///
+#[derive(Debug)]
+pub struct CountedRingbuf<T: Count, const N: usize> {
+    pub meta: RingbufMeta,
+    pub counts: T::Counts,
+    pub ringbuf: Ringbuf<T, N>,
+}
+
+impl<T: Count, const N: usize> RecordEntry<T> for CountedRingbuf<T, N> {
+    fn record_entry(&mut self, line: u16, payload: T) {
+        payload.count(&mut self.counts);
+        self.ringbuf.entry(line, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // So that the code generated by `#[derive(Count)]` can find us
+    use crate as ringbuf;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Count)]
+    enum Trace {
+        None,
+        Retry(u8),
+        Error { code: u32 },
+    }
+
+    fn plain<const N: usize>() -> Ringbuf<Trace, N> {
+        Ringbuf {
+            last: None,
+            buffer: [RingbufEntry {
+                line: 0,
+                generation: 0,
+                count: 0,
+                payload: Trace::None,
+            }; N],
+        }
+    }
+
+    fn timestamped<const N: usize>() -> TimestampedRingbuf<Trace, N> {
+        TimestampedRingbuf {
+            meta: RingbufMeta::TIMESTAMPED,
+            last: None,
+            buffer: [TimestampedRingbufEntry {
+                line: 0,
+                generation: 0,
+                count: 0,
+                timestamp: 0,
+                payload: Trace::None,
+            }; N],
+        }
+    }
+
+    #[test]
+    fn repeated_entries_are_counted() {
+        let mut r = plain::<4>();
+        r.entry(10, Trace::Retry(1));
+        r.entry(10, Trace::Retry(1));
+        r.entry(10, Trace::Retry(2));
+        r.entry(11, Trace::Retry(2));
+
+        assert_eq!(r.last, Some(2));
+        assert_eq!(r.buffer[0].count, 2);
+        assert_eq!(r.buffer[0].payload, Trace::Retry(1));
+        assert_eq!(r.buffer[1].count, 1);
+        assert_eq!(r.buffer[1].payload, Trace::Retry(2));
+        assert_eq!((r.buffer[2].line, r.buffer[2].count), (11, 1));
+    }
+
+    #[test]
+    fn entries_wrap() {
+        let mut r = plain::<2>();
+        for i in 0..5 {
+            r.entry(1, Trace::Retry(i));
+        }
+
+        assert_eq!(r.last, Some(0));
+        assert_eq!(r.buffer[0].payload, Trace::Retry(4));
+        assert_eq!(r.buffer[0].generation, 3);
+        assert_eq!(r.buffer[1].payload, Trace::Retry(3));
+        assert_eq!(r.buffer[1].generation, 2);
+    }
+
+    #[test]
+    fn timestamps_follow_latest_repeat() {
+        let mut r = timestamped::<2>();
+        r.entry_at(1, Trace::Retry(0), 100);
+        r.entry_at(1, Trace::Retry(0), 250);
+        r.entry_at(2, Trace::Retry(0), 300);
+
+        assert_eq!(r.buffer[0].count, 2);
+        assert_eq!(r.buffer[0].timestamp, 250);
+        assert_eq!(r.buffer[1].timestamp, 300);
+
+        // Once we wrap, the overwritten entry gets the new time
+        r.entry_at(3, Trace::Error { code: 1 }, 400);
+        assert_eq!(r.last, Some(0));
+        assert_eq!((r.buffer[0].line, r.buffer[0].count), (3, 1));
+        assert_eq!(r.buffer[0].timestamp, 400);
+    }
+
+    #[test]
+    #[cfg(not(feature = "disabled"))]
+    fn counts_survive_wrapping() {
+        counted_ringbuf!(TEST_RINGBUF, Trace, 2, Trace::None);
+
+        for i in 0..10 {
+            ringbuf_entry!(TEST_RINGBUF, Trace::Retry(i));
+        }
+        for _ in 0..2 {
+            ringbuf_entry!(TEST_RINGBUF, Trace::Error { code: 3 });
+        }
+
+        let r = TEST_RINGBUF.borrow_mut();
+        assert_eq!(r.counts.None, 0);
+        assert_eq!(r.counts.Retry, 10);
+        assert_eq!(r.counts.Error, 2);
+
+        // The ring itself only has room for the last two distinct entries
+        let last = r.ringbuf.last.unwrap();
+        assert_eq!(r.ringbuf.buffer[last].payload, Trace::Error { code: 3 });
+        assert_eq!(r.ringbuf.buffer[last].count, 2);
+    }
+}
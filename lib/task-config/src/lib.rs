@@ -174,3 +174,147 @@ pub fn task_config(tokens: TokenStream) -> TokenStream {
     }
     .into()
 }
+
+/// Generates code to decode a scalar of type `ty` from `blob[offset..]`.
+fn scalar_decoder(ty: &str, offset: usize) -> proc_macro2::TokenStream {
+    if ty == "bool" {
+        return quote! { blob[#offset] != 0 };
+    }
+    let size = patchable_config::scalar_size(ty).unwrap();
+    let ty: proc_macro2::TokenStream = ty.parse().unwrap();
+    let bytes = (offset..offset + size).map(|i| quote! { blob[#i] });
+    quote! { #ty::from_le_bytes([ #(#bytes),* ]) }
+}
+
+/// Splits a field type into its scalar type and, for arrays, its length.
+fn patchable_type(ty: &syn::Type) -> (String, Option<usize>) {
+    let scalar = |ty: &syn::Type| -> String {
+        let name = ty.to_token_stream().to_string();
+        if patchable_config::scalar_size(&name).is_none() {
+            panic!(
+                "Patchable config fields must be scalars (integers, floats or \
+                 bools) or arrays of scalars; got {name}"
+            );
+        }
+        name
+    };
+    match ty {
+        syn::Type::Array(a) => {
+            let len = match &a.len {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(i),
+                    ..
+                }) => i.base10_parse::<usize>().unwrap(),
+                len => panic!(
+                    "Patchable config arrays must have a literal length; got {}",
+                    len.to_token_stream()
+                ),
+            };
+            (scalar(&a.elem), Some(len))
+        }
+        ty => (scalar(ty), None),
+    }
+}
+
+/// The `patchable_task_config!` macro is like `task_config!`, but places the
+/// values in a blob in the task's flash (in the `.hubris_config` section),
+/// which can be patched in a built image by `cargo xtask patch-config`
+/// without rebuilding anything.  Values come from the same `config` block as
+/// those of `task_config!`, and a task can use both macros.
+///
+/// Fields must be integers, floats or `bool`s, or arrays of them:
+/// ```ignore
+/// task_config::patchable_task_config! {
+///     poll_interval_ms: u32,
+///     gains: [f32; 3],
+/// }
+/// ```
+///
+/// This generates a `struct PatchableConfig` with those fields, whose
+/// `PatchableConfig::read()` reads the values from flash; tasks should call
+/// it once at startup.  The layout of the blob is recorded in the
+/// `.hubris_config_schema` section, with one `name type offset` line per
+/// field (e.g. `gains [f32;3] 4`); values are packed, little-endian, with
+/// `bool`s stored as a byte that is either 0 or 1.
+#[proc_macro]
+pub fn patchable_task_config(tokens: TokenStream) -> TokenStream {
+    let config = build_util::task_config::<toml::Value>().unwrap();
+
+    let input = parse_macro_input!(tokens as Config);
+    let mut blob = vec![];
+    let mut schema = String::new();
+    let mut decoders = vec![];
+
+    for f in input.items.iter() {
+        let ident = f.ident.as_ref().expect("Missing ident");
+        let v = config.get(ident.to_string()).expect(&format!(
+            "Missing config parameter in TOML file: {}",
+            ident.to_string()
+        ));
+        let offset = blob.len();
+        let (ty, len) = patchable_type(&f.ty);
+        let field =
+            patchable_config::Field::new(&ident.to_string(), &ty, len, offset)
+                .unwrap();
+
+        blob.extend(
+            field
+                .encode(v)
+                .unwrap_or_else(|e| panic!("Bad value for {ident}: {e}")),
+        );
+        schema += &format!("{field}\n");
+
+        let decoder = match len {
+            None => scalar_decoder(&ty, offset),
+            Some(len) => {
+                let size = patchable_config::scalar_size(&ty).unwrap();
+                let elems =
+                    (0..len).map(|i| scalar_decoder(&ty, offset + i * size));
+                quote! { [ #(#elems),* ] }
+            }
+        };
+        decoders.push(quote! { #ident: #decoder });
+    }
+
+    let app_toml_path = std::env::var("HUBRIS_APP_TOML")
+        .expect("Could not find 'HUBRIS_APP_TOML' environment variable");
+    let fields = input.items.iter();
+    let blob_len = blob.len();
+    let schema =
+        syn::LitByteStr::new(schema.as_bytes(), proc_macro2::Span::call_site());
+    let schema_len = schema.value().len();
+
+    quote! {
+        const PATCHABLE_APP_TOML_TO_ENSURE_REBUILD: &[u8] =
+            include_bytes!(#app_toml_path);
+
+        #[used]
+        #[link_section = ".hubris_config"]
+        static PATCHABLE_CONFIG_BLOB: [u8; #blob_len] = [ #(#blob),* ];
+
+        #[used]
+        #[link_section = ".hubris_config_schema"]
+        static PATCHABLE_CONFIG_SCHEMA: [u8; #schema_len] = *#schema;
+
+        #[derive(Copy, Clone)]
+        struct PatchableConfig {
+            #(#fields),*
+        }
+
+        impl PatchableConfig {
+            /// Reads the config from flash, where it may have been patched
+            /// since we were built.
+            fn read() -> Self {
+                // This must be a volatile read; otherwise, the compiler is
+                // free to use the values that it saw when we were built.
+                let blob = unsafe {
+                    core::ptr::read_volatile(&PATCHABLE_CONFIG_BLOB)
+                };
+                Self {
+                    #(#decoders),*
+                }
+            }
+        }
+    }
+    .into()
+}
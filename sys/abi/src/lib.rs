@@ -479,6 +479,27 @@ pub struct TaskDumpRegion {
     pub size: u32,
 }
 
+/// Size of the kernel's epitaph buffer, which holds a description of why it
+/// failed.
+pub const KERNEL_EPITAPH_LEN: usize = 128;
+
+/// Snapshot of processor state taken when the kernel failed, recorded by
+/// kernels with the `panic-reset` feature and returned by the
+/// `read_kernel_panic` kipc.  The fault status registers are zero on ARMv6-M,
+/// which doesn't have them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[repr(C)]
+pub struct KernelPanicRegisters {
+    /// Main (kernel) stack pointer
+    pub msp: u32,
+    /// Process stack pointer, i.e. that of the last task to run
+    pub psp: u32,
+    /// Number of the exception being handled, or 0 in thread mode
+    pub ipsr: u32,
+    pub cfsr: u32,
+    pub hfsr: u32,
+}
+
 /// Representation of kipc numbers
 pub enum Kipcnum {
     ReadTaskStatus = 1,
@@ -488,6 +509,7 @@ pub enum Kipcnum {
     Reset = 5,
     GetTaskDumpRegion = 6,
     ReadTaskDumpRegion = 7,
+    ReadKernelPanic = 8,
 }
 
 impl core::convert::TryFrom<u16> for Kipcnum {
@@ -502,12 +524,17 @@ impl core::convert::TryFrom<u16> for Kipcnum {
             5 => Ok(Self::Reset),
             6 => Ok(Self::GetTaskDumpRegion),
             7 => Ok(Self::ReadTaskDumpRegion),
+            8 => Ok(Self::ReadKernelPanic),
             _ => Err(()),
         }
     }
 }
 
 pub const HEADER_MAGIC: u32 = 0x64_CE_D6_CA;
+
+/// Offset of the `ImageHeader` in an STM32H7 image: it's at a fixed location
+/// at the end of the vector table, whose length is fixed in hardware.
+pub const STM32H7_HEADER_OFFSET: u32 = 0x298;
 pub const CABOOSE_MAGIC: u32 = 0xCAB0_005E;
 
 /// TODO: Add hash for integrity check
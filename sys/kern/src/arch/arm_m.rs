@@ -1446,6 +1446,39 @@ pub fn reset() -> ! {
     cortex_m::peripheral::SCB::sys_reset()
 }
 
+/// Takes a snapshot of the processor state, for the record of a kernel panic.
+#[cfg(feature = "panic-reset")]
+pub fn panic_registers() -> abi::KernelPanicRegisters {
+    // Safety: we're just reading the PSR.
+    let ipsr = unsafe {
+        let mut ipsr: u32;
+        arch::asm!(
+            "mrs {}, IPSR",
+            out(reg) ipsr,
+            options(pure, nomem, preserves_flags, nostack),
+        );
+        ipsr & 0x1FF
+    };
+
+    #[allow(unused_mut)]
+    let mut regs = abi::KernelPanicRegisters {
+        msp: cortex_m::register::msp::read(),
+        psp: cortex_m::register::psp::read(),
+        ipsr,
+        ..Default::default()
+    };
+
+    #[cfg(any(armv7m, armv8m))]
+    {
+        // Safety: see `handle_fault` for why this is okay.
+        let scb = unsafe { &*cortex_m::peripheral::SCB::PTR };
+        regs.cfsr = scb.cfsr.read();
+        regs.hfsr = scb.hfsr.read();
+    }
+
+    regs
+}
+
 /// Common implementation of fault handling.
 ///
 /// # Safety
@@ -18,6 +18,19 @@
 //!   this buffer (as UTF-8) as possible, truncating if the buffer fills. The
 //!   number of bytes written isn't recorded anywhere; instead, for printing,
 //!   trim off any trailing NUL bytes.
+//!
+//! With the `panic-reset` feature, rather than spinning forever, `die` also
+//! copies the epitaph and a snapshot of the processor state (see
+//! `abi::KernelPanicRegisters`) into a record in RAM that isn't initialized
+//! at boot, and resets the chip.  On the next boot, the kernel recovers a
+//! valid record and hands it to the supervisor through the
+//! `read_kernel_panic` kipc.  This relies on the chip retaining RAM across
+//! a system reset.
+//!
+//! So that a kernel that panics every time it boots doesn't reset forever
+//! (and so that someone can attach a debugger to find out why), `die` spins
+//! as above instead of resetting if the previous boot also ended in a panic
+//! and we haven't been up for long (see `panic_record::should_reset`).
 
 use core::fmt::{Display, Write};
 use core::sync::atomic::Ordering;
@@ -27,7 +40,7 @@ use core::sync::atomic::Ordering;
 #[used]
 static mut KERNEL_HAS_FAILED: bool = false;
 
-const EPITAPH_LEN: usize = 128;
+const EPITAPH_LEN: usize = abi::KERNEL_EPITAPH_LEN;
 
 /// The "epitaph" buffer records up to `EPITAPH_LEN` bytes of description of the
 /// event that caused the kernel to fail, padded with NULs.
@@ -64,6 +77,21 @@ fn die_impl(msg: &dyn Display) -> ! {
     let mut writer = Eulogist { dest: buf };
     write!(writer, "{}", msg).ok();
 
+    #[cfg(feature = "panic-reset")]
+    {
+        let len = EPITAPH_LEN - writer.dest.len();
+        // This must be decided before we overwrite the record of any
+        // previous panic with our own.
+        let reset = panic_record::should_reset();
+        // Safety: only one execution of this function makes it past
+        // `begin_epitaph`, so we have exclusive access to the epitaph.
+        panic_record::record(unsafe { &KERNEL_EPITAPH[..len] });
+        if reset {
+            crate::arch::reset();
+        }
+    }
+
+    #[allow(unreachable_code)]
     loop {
         // Platform-independent NOP
         core::sync::atomic::fence(Ordering::SeqCst);
@@ -88,6 +116,133 @@ impl Write for Eulogist {
     }
 }
 
+#[cfg(feature = "panic-reset")]
+pub mod panic_record {
+    use super::EPITAPH_LEN;
+    use abi::KernelPanicRegisters;
+    use core::mem::MaybeUninit;
+
+    /// Marks a valid record; changed whenever `PanicRecord` does.
+    const MAGIC: u32 = 0x4b50_4e43;
+
+    /// If we panic within this many ticks (milliseconds) of booting after a
+    /// panic, we assume that we'll keep doing so and stop instead of
+    /// resetting again.
+    const PANIC_LOOP_TICKS: u64 = 10_000;
+
+    #[derive(Copy, Clone)]
+    #[repr(C)]
+    struct PanicRecord {
+        magic: u32,
+        registers: KernelPanicRegisters,
+        len: u32,
+        epitaph: [u8; EPITAPH_LEN],
+        checksum: u32,
+    }
+
+    impl PanicRecord {
+        fn checksum(&self) -> u32 {
+            let r = &self.registers;
+            [r.msp, r.psp, r.ipsr, r.cfsr, r.hfsr, self.len]
+                .into_iter()
+                .chain(self.epitaph.iter().map(|&b| u32::from(b)))
+                .fold(MAGIC, |sum, v| sum.rotate_left(5) ^ v)
+        }
+
+        fn is_valid(&self) -> bool {
+            self.magic == MAGIC
+                && self.len as usize <= EPITAPH_LEN
+                && self.checksum == self.checksum()
+        }
+    }
+
+    /// The record of a panic, which survives the reset that follows it
+    /// because this section isn't initialized at boot.
+    #[link_section = ".uninit.kernel_panic"]
+    static mut PANIC_RECORD: MaybeUninit<PanicRecord> = MaybeUninit::uninit();
+
+    /// The record recovered at boot, if any -- which is to say, whether the
+    /// previous boot ended in a panic.
+    static mut RECOVERED: Option<PanicRecord> = None;
+
+    /// Records a panic, with the given epitaph, to be recovered after the
+    /// reset.
+    pub(crate) fn record(epitaph: &[u8]) {
+        let mut record = PanicRecord {
+            magic: MAGIC,
+            registers: crate::arch::panic_registers(),
+            len: epitaph.len() as u32,
+            epitaph: [0; EPITAPH_LEN],
+            checksum: 0,
+        };
+        record.epitaph[..epitaph.len()].copy_from_slice(epitaph);
+        record.checksum = record.checksum();
+
+        // Safety: we only get here from `die_impl`, which only one caller
+        // can reach, and nothing else touches `PANIC_RECORD` after boot.
+        unsafe {
+            core::ptr::write_volatile(PANIC_RECORD.as_mut_ptr(), record);
+        }
+    }
+
+    /// Recovers the record of a panic before the last reset, if there is a
+    /// valid one, and invalidates it so that it's only reported once.
+    ///
+    /// # Safety
+    ///
+    /// This must be called only once, at boot, before any tasks run.
+    pub(crate) unsafe fn recover() {
+        // Safety: whatever was in RAM at reset is a valid `PanicRecord` (it's
+        // all integers), and our caller guarantees exclusive access.
+        let record = unsafe { core::ptr::read_volatile(PANIC_RECORD.as_ptr()) };
+
+        if record.is_valid() {
+            // Safety: as above.
+            unsafe {
+                RECOVERED = Some(record);
+            }
+        }
+
+        // Safety: as above.
+        unsafe {
+            core::ptr::write_volatile(
+                core::ptr::addr_of_mut!((*PANIC_RECORD.as_mut_ptr()).magic),
+                0,
+            );
+        }
+    }
+
+    /// Decides whether a panic should reset the chip.  It shouldn't if the
+    /// previous boot also ended in a panic and we've been up for less than
+    /// `PANIC_LOOP_TICKS`, because resetting would most likely just lead to
+    /// another panic, and so on forever.
+    ///
+    /// This must be called before `record`.
+    pub(crate) fn should_reset() -> bool {
+        // If we panic before `recover` has run, the record of the previous
+        // panic will still be in place.
+        //
+        // Safety: we only get here from `die_impl`, which only one caller
+        // can reach, and whatever is in `PANIC_RECORD` is a valid
+        // `PanicRecord` (see `recover`).
+        let previous_panic = unsafe {
+            RECOVERED.is_some()
+                || core::ptr::read_volatile(PANIC_RECORD.as_ptr()).is_valid()
+        };
+
+        !previous_panic || u64::from(crate::arch::now()) >= PANIC_LOOP_TICKS
+    }
+
+    /// Returns the registers and epitaph of the record recovered at boot, if
+    /// any.
+    pub(crate) fn recovered() -> Option<(KernelPanicRegisters, &'static [u8])> {
+        // Safety: `RECOVERED` is only written by `recover`, before any tasks
+        // run, so shared references to it are fine from then on.
+        let record = unsafe { RECOVERED.as_ref() }?;
+        Some((record.registers, &record.epitaph[..record.len as usize]))
+    }
+}
+
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
     die(info)
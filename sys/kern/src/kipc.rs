@@ -39,6 +39,9 @@ pub fn handle_kernel_message(
         Ok(Kipcnum::ReadTaskDumpRegion) => {
             read_task_dump_region(tasks, caller, args.message?, args.response?)
         }
+        Ok(Kipcnum::ReadKernelPanic) => {
+            read_kernel_panic(tasks, caller, args.response?)
+        }
 
         _ => {
             // Task has sent an unknown message to the kernel. That's bad.
@@ -427,3 +430,38 @@ fn read_task_dump_region(
         .set_send_response_and_length(0, response_len);
     Ok(NextTask::Same)
 }
+
+fn read_kernel_panic(
+    tasks: &mut [Task],
+    caller: usize,
+    mut response: USlice<u8>,
+) -> Result<NextTask, UserError> {
+    if caller != 0 {
+        return Err(UserError::Unrecoverable(FaultInfo::SyscallUsage(
+            UsageError::NotSupervisor,
+        )));
+    }
+
+    #[cfg(feature = "panic-reset")]
+    let recovered = crate::fail::panic_record::recovered();
+    #[cfg(not(feature = "panic-reset"))]
+    let recovered: Option<(abi::KernelPanicRegisters, &[u8])> = None;
+
+    // The registers are serialized as usual, and the epitaph follows them
+    // verbatim (truncated if it doesn't fit).
+    let buf = tasks[caller].try_write(&mut response)?;
+    let mut len = match ssmarshal::serialize(buf, &recovered.map(|r| r.0)) {
+        Ok(size) => size,
+        Err(_) => return Err(UsageError::BadKernelMessage.into()),
+    };
+    if let Some((_, epitaph)) = recovered {
+        let n = epitaph.len().min(buf.len() - len);
+        buf[len..len + n].copy_from_slice(&epitaph[..n]);
+        len += n;
+    }
+
+    tasks[caller]
+        .save_mut()
+        .set_send_response_and_length(0, len);
+    Ok(NextTask::Same)
+}
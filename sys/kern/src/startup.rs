@@ -47,6 +47,16 @@ pub unsafe fn start_kernel(tick_divisor: u32) -> ! {
         crate::arch::set_clock_freq(tick_divisor);
     }
 
+    // Pick up the record of any panic that caused the last reset before we
+    // can possibly overwrite it.
+    //
+    // Safety: this is only called once per boot, per our contract, and
+    // before any tasks run.
+    #[cfg(feature = "panic-reset")]
+    unsafe {
+        crate::fail::panic_record::recover();
+    }
+
     // Grab references to all our statics.
     let task_descs = &HUBRIS_TASK_DESCS;
     // Safety: this reference will remain unique so long as the "only called
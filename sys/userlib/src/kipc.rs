@@ -110,3 +110,30 @@ pub fn read_image_id() -> u64 {
     assert_eq!(len, 8); // we *really* expect this to be a u64
     ssmarshal::deserialize(&response[..len]).unwrap_lite().0
 }
+
+/// Reads the record of a kernel panic that caused the last reset, if the
+/// kernel was built with the `panic-reset` feature and found one at boot.
+/// The epitaph is copied into `epitaph` (truncated if it doesn't fit), and
+/// the number of bytes copied is returned along with the registers.
+pub fn read_kernel_panic(
+    epitaph: &mut [u8],
+) -> Option<(abi::KernelPanicRegisters, usize)> {
+    const REGS_SIZE: usize =
+        core::mem::size_of::<Option<abi::KernelPanicRegisters>>();
+    let mut response = [0; REGS_SIZE + abi::KERNEL_EPITAPH_LEN];
+    let (rc, len) = sys_send(
+        TaskId::KERNEL,
+        Kipcnum::ReadKernelPanic as u16,
+        &[],
+        &mut response,
+        &[],
+    );
+    assert_eq!(rc, 0);
+    let (regs, used): (Option<abi::KernelPanicRegisters>, usize) =
+        ssmarshal::deserialize(&response[..len]).unwrap_lite();
+    let rest = &response[used..len];
+    let regs = regs?;
+    let n = rest.len().min(epitaph.len());
+    epitaph[..n].copy_from_slice(&rest[..n]);
+    Some((regs, n))
+}
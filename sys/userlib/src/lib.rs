@@ -41,6 +41,8 @@ use core::marker::PhantomData;
 
 pub mod hl;
 pub mod kipc;
+#[cfg(feature = "log-task")]
+pub mod log;
 pub mod task_slot;
 pub mod units;
 
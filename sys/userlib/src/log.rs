@@ -0,0 +1,46 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Support for the `log-task` backend of `sys_log!`.
+//!
+//! Each `sys_log!` encodes its arguments (see `log-encoding`) and sends the
+//! record to the task in the `logger` slot, which must be named in the
+//! logging task's `task-slots` in the app TOML.
+//!
+//! Logging never blocks for long: the logger never sends to anyone, and the
+//! task slot means that `xtask dist` requires it to be higher priority than
+//! every task that logs.  So whenever a logging task is running, the logger
+//! is waiting in `RECV`, and our `SEND` is delivered immediately.  (The
+//! supervisor can't use this backend for the same reason.)
+
+pub use log_encoding::{
+    fmt_bytes, Arg, EncodeDebug, EncodePrimitive, Encoder, MAX_RECORD_SIZE,
+};
+
+use crate::{sys_send, Lease};
+
+task_slot!(LOGGER, logger);
+
+/// Operation code of `Logger.log`; we send it directly, because `userlib`
+/// can't depend on the logger's generated client.
+const LOG_OP: u16 = 1;
+
+/// Sends a record to the logger.  `fmt` is the format string interned by
+/// `sys_log!`; `args` encodes the arguments.
+pub fn send(fmt: &'static [u8], args: impl FnOnce(&mut Encoder<'_>)) {
+    let mut buf = [0; MAX_RECORD_SIZE];
+    let mut enc = Encoder::new(&mut buf, fmt.as_ptr() as u32);
+    args(&mut enc);
+    let record = enc.finish();
+
+    // If the logger has died, the message is lost; there's nothing more
+    // useful to do with it.
+    let _ = sys_send(
+        LOGGER.get_task_id(),
+        LOG_OP,
+        &[],
+        &mut [],
+        &[Lease::from(record)],
+    );
+}
@@ -32,6 +32,35 @@ cfg_if::cfg_if! {
                 { let _ = cortex_m_semihosting::hprintln!($s, $($tt)*); }
             };
         }
+    } else if #[cfg(feature = "log-task")] {
+        /// Sends a log record to the logger task.  Rather than formatting the
+        /// message, this interns the format string and encodes the arguments
+        /// (see `userlib::log`); the message is formatted on the host.
+        /// Arguments must be passed explicitly, rather than captured by name.
+        #[macro_export]
+        macro_rules! sys_log {
+            ($s:literal $(, $x:expr)* $(,)?) => {
+                {
+                    // Check the format string and arguments as format! would,
+                    // without generating any code for it.
+                    if false {
+                        let _ = core::format_args!($s $(, $x)*);
+                    }
+
+                    #[used]
+                    #[link_section = ".hubris_log_fmt"]
+                    static FMT: [u8; $s.len() + 1] = $crate::log::fmt_bytes($s);
+
+                    $crate::log::send(&FMT, |_enc| {
+                        #[allow(unused_imports)]
+                        use $crate::log::{Arg, EncodeDebug, EncodePrimitive};
+                        $(
+                            (&Arg(&$x)).encode_arg(_enc);
+                        )*
+                    });
+                }
+            };
+        }
     } else if #[cfg(feature = "log-null")] {
         #[macro_export]
         macro_rules! sys_log {
@@ -53,14 +82,14 @@ cfg_if::cfg_if! {
         macro_rules! sys_log {
             ($s:expr) => {
                 compile_error!(concat!(
-                        "to use sys_log! must enable either ",
-                        "'log-semihosting' or 'log-itm' feature"
+                        "to use sys_log! must enable one of the ",
+                        "'log-semihosting', 'log-itm' or 'log-task' features"
                 ))
             };
             ($s:expr, $($tt:tt)*) => {
                 compile_error!(concat!(
-                        "to use sys_log! must enable either ",
-                        "'log-semihosting' or 'log-itm' feature"
+                        "to use sys_log! must enable one of the ",
+                        "'log-semihosting', 'log-itm' or 'log-task' features"
                 ))
             };
         }
@@ -37,4 +37,31 @@ pub enum HashAlgorithm {
     Sha3_256,
 }
 
+/// Components whose images are measured by the RoT
+#[derive(
+    Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, SerializedSize,
+)]
+pub enum MeasuredComponent {
+    /// The SP's flash, as read over SWD
+    Sp,
+}
+
+#[derive(
+    Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, SerializedSize,
+)]
+pub struct ImageVersion {
+    pub epoch: u32,
+    pub version: u32,
+}
+
+/// Identifies the image that a measurement passed to `record_image` is of
+#[derive(
+    Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, SerializedSize,
+)]
+pub struct ImageIdentity {
+    pub component: MeasuredComponent,
+    /// Version from the image's header, or `None` if it has no valid header
+    pub version: Option<ImageVersion>,
+}
+
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
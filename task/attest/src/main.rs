@@ -12,7 +12,7 @@
 mod config;
 
 use arrayvec::ArrayVec;
-use attest_api::{AttestError, HashAlgorithm};
+use attest_api::{AttestError, HashAlgorithm, ImageIdentity};
 use config::DataRegion;
 use core::slice;
 use crypto_common::{typenum::Unsigned, OutputSizeUser};
@@ -46,6 +46,7 @@ enum Trace {
     Offset(u32),
     Startup,
     Record(HashAlgorithm),
+    RecordImage(ImageIdentity),
     BadLease(usize),
     None,
 }
@@ -114,10 +115,20 @@ impl Measurement {
     }
 }
 
+/// An entry in the measurement log (which, for now, is only read by
+/// debuggers)
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)]
+struct Record {
+    /// The image that was measured, if it was recorded with `record_image`
+    image: Option<ImageIdentity>,
+    measurement: Measurement,
+}
+
 struct AttestServer {
     alias_data: Option<AliasData>,
     cert_data: Option<CertData>,
-    measurements: ArrayVec<Measurement, CAPACITY>,
+    measurements: ArrayVec<Record, CAPACITY>,
 }
 
 impl Default for AttestServer {
@@ -125,12 +136,33 @@ impl Default for AttestServer {
         Self {
             alias_data: load_data_from_region(&ALIAS_DATA),
             cert_data: load_data_from_region(&CERT_DATA),
-            measurements: ArrayVec::<Measurement, CAPACITY>::new(),
+            measurements: ArrayVec::<Record, CAPACITY>::new(),
         }
     }
 }
 
 impl AttestServer {
+    fn push_measurement(
+        &mut self,
+        image: Option<ImageIdentity>,
+        algorithm: HashAlgorithm,
+        data: idol_runtime::Leased<idol_runtime::R, [u8]>,
+    ) -> Result<(), RequestError<AttestError>> {
+        //
+        // The log is append-only: re-measuring a component adds an entry
+        // rather than replacing its earlier one, so that everything that
+        // has been measured remains attested to.
+        //
+        if self.measurements.is_full() {
+            return Err(AttestError::MeasurementLogFull.into());
+        }
+
+        let measurement = Measurement::new(algorithm, data)?;
+        self.measurements.push(Record { image, measurement });
+
+        Ok(())
+    }
+
     fn get_cert_bytes_from_index(
         &self,
         index: u32,
@@ -244,14 +276,19 @@ impl idl::InOrderAttestImpl for AttestServer {
         data: idol_runtime::Leased<idol_runtime::R, [u8]>,
     ) -> Result<(), RequestError<AttestError>> {
         ringbuf_entry!(Trace::Record(algorithm));
+        self.push_measurement(None, algorithm, data)
+    }
 
-        if self.measurements.is_full() {
-            return Err(AttestError::MeasurementLogFull.into());
-        }
-
-        self.measurements.push(Measurement::new(algorithm, data)?);
-
-        Ok(())
+    fn record_image(
+        &mut self,
+        _: &userlib::RecvMessage,
+        algorithm: HashAlgorithm,
+        image: ImageIdentity,
+        data: idol_runtime::Leased<idol_runtime::R, [u8]>,
+    ) -> Result<(), RequestError<AttestError>> {
+        ringbuf_entry!(Trace::Record(algorithm));
+        ringbuf_entry!(Trace::RecordImage(image));
+        self.push_measurement(Some(image), algorithm, data)
     }
 }
 
@@ -267,7 +304,7 @@ fn main() -> ! {
 }
 
 mod idl {
-    use super::{AttestError, HashAlgorithm};
+    use super::{AttestError, HashAlgorithm, ImageIdentity};
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
@@ -34,4 +34,19 @@ pub enum UartClient {
     Humility,
 }
 
+/// Describes the bytes returned by a read of the host console scrollback.
+///
+/// Offsets count every byte received from the host since the SP booted, so a
+/// reader can resume where it left off by asking for `start + len`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostConsoleScrollback {
+    /// Offset of the first byte written to the caller's buffer. This is later
+    /// than the requested offset if that data has already been overwritten.
+    pub start: u64,
+    /// Number of bytes written to the caller's buffer.
+    pub len: u32,
+    /// Offset just past the most recent byte received from the host.
+    pub end: u64,
+}
+
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
@@ -2,6 +2,39 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use serde::Deserialize;
+use std::io::Write;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// Number of bytes of host console output to retain, regardless of
+    /// whether anyone is attached.
+    #[serde(default = "default_scrollback_size")]
+    host_console_scrollback: usize,
+    /// Whether to send the retained output to MGS when it attaches to the
+    /// host console.
+    #[serde(default = "default_replay_on_attach")]
+    host_console_replay_on_attach: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host_console_scrollback: default_scrollback_size(),
+            host_console_replay_on_attach: default_replay_on_attach(),
+        }
+    }
+}
+
+fn default_scrollback_size() -> usize {
+    4096
+}
+
+fn default_replay_on_attach() -> bool {
+    true
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     build_util::build_notifications()?;
     idol::server::build_server_support(
@@ -10,5 +43,24 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         idol::server::ServerStyle::InOrder,
     )?;
 
+    let config = build_util::task_maybe_config::<Config>()?.unwrap_or_default();
+    if config.host_console_scrollback == 0 {
+        return Err("host-console-scrollback must be nonzero".into());
+    }
+
+    let out_dir = build_util::out_dir();
+    let mut out =
+        std::fs::File::create(out_dir.join("host_console_config.rs"))?;
+    writeln!(
+        out,
+        "pub(crate) const HOST_CONSOLE_SCROLLBACK_SIZE: usize = {};",
+        config.host_console_scrollback
+    )?;
+    writeln!(
+        out,
+        "pub(crate) const HOST_CONSOLE_REPLAY_ON_ATTACH: bool = {};",
+        config.host_console_replay_on_attach
+    )?;
+
     Ok(())
 }
@@ -43,6 +43,10 @@ impl Inventory {
         component: &SpComponent,
     ) -> Result<u32, SpError> {
         match Index::try_from(component)? {
+            // TODO: The LLDP neighbor on each management port (see
+            // `Net::lldp_neighbor`) would make useful details for our
+            // network ports, but `gateway_messages::ComponentDetails` has no
+            // variant that can carry its chassis ID, port ID and system name.
             Index::OurDevice(_) => Ok(0),
             Index::ValidateDevice(i) => {
                 Ok(VALIDATE_DEVICES[i].sensors.len() as u32)
@@ -18,7 +18,8 @@ use mutable_statics::mutable_statics;
 use ringbuf::{ringbuf, ringbuf_entry};
 use task_control_plane_agent_api::MAX_INSTALLINATOR_IMAGE_ID_LEN;
 use task_control_plane_agent_api::{
-    BarcodeParseError, ControlPlaneAgentError, UartClient, VpdIdentity,
+    BarcodeParseError, ControlPlaneAgentError, HostConsoleScrollback,
+    UartClient, VpdIdentity,
 };
 use task_net_api::{
     Address, LargePayloadBehavior, Net, RecvError, SendError, SocketName,
@@ -53,18 +54,36 @@ enum Log {
     Rx(UdpMetadata),
     SendError(SendError),
     MgsMessage(MgsMessage),
-    UsartTxFull { remaining: usize },
+    UsartTxFull {
+        remaining: usize,
+    },
     UsartRxOverrun,
-    UsartRxBufferDataDropped { num_bytes: u64 },
-    SerialConsoleSend { buffered: usize },
-    UpdatePartial { bytes_written: u32 },
+    UsartRxBufferDataDropped {
+        num_bytes: u64,
+    },
+    SerialConsoleSend {
+        buffered: usize,
+    },
+    UpdatePartial {
+        bytes_written: u32,
+    },
     UpdateComplete,
-    HostFlashSectorsErased { num_sectors: usize },
+    HostFlashSectorsErased {
+        num_sectors: usize,
+    },
     ExpectedRspTimeout,
     RotReset(SprotError),
     SprotCabooseSize(u32),
     ReadCaboose(u32, usize),
     GotCabooseChunk([u8; 4]),
+    #[cfg(feature = "sidecar")]
+    IgnitionEvent {
+        index: u32,
+        port: u8,
+        kind: u8,
+    },
+    #[cfg(feature = "sidecar")]
+    IgnitionEventsError(drv_ignition_api::IgnitionError),
 }
 
 // This enum does not define the actual MGS protocol - it is only used in the
@@ -188,9 +207,15 @@ impl ServerImpl {
 
 impl NotificationHandler for ServerImpl {
     fn current_notification_mask(&self) -> u32 {
+        #[cfg(feature = "sidecar")]
+        let board = notifications::IGNITION_MASK;
+        #[cfg(not(feature = "sidecar"))]
+        let board = 0;
+
         notifications::SOCKET_MASK
             | notifications::USART_IRQ_MASK
             | notifications::TIMER_MASK
+            | board
     }
 
     fn handle_notification(&mut self, bits: u32) {
@@ -202,6 +227,11 @@ impl NotificationHandler for ServerImpl {
             self.mgs_handler.handle_timer_fired();
         }
 
+        #[cfg(feature = "sidecar")]
+        if (bits & notifications::IGNITION_MASK) != 0 {
+            self.mgs_handler.handle_ignition_events();
+        }
+
         if (bits & notifications::SOCKET_MASK) != 0
             || self.net_handler.packet_to_send.is_some()
             || self.mgs_handler.wants_to_send_packet_to_mgs()
@@ -340,6 +370,17 @@ impl idl::InOrderControlPlaneAgentImpl for ServerImpl {
         self.mgs_handler.uart_write(data)
     }
 
+    #[cfg(feature = "gimlet")]
+    fn read_host_console_scrollback(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        offset: u64,
+        data: Leased<idol_runtime::W, [u8]>,
+    ) -> Result<HostConsoleScrollback, RequestError<ControlPlaneAgentError>>
+    {
+        self.mgs_handler.read_host_console_scrollback(offset, data)
+    }
+
     #[cfg(not(feature = "gimlet"))]
     fn get_uart_client(
         &mut self,
@@ -382,6 +423,19 @@ impl idl::InOrderControlPlaneAgentImpl for ServerImpl {
             ControlPlaneAgentError::OperationUnsupported,
         ))
     }
+
+    #[cfg(not(feature = "gimlet"))]
+    fn read_host_console_scrollback(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        _offset: u64,
+        _data: Leased<idol_runtime::W, [u8]>,
+    ) -> Result<HostConsoleScrollback, RequestError<ControlPlaneAgentError>>
+    {
+        Err(RequestError::from(
+            ControlPlaneAgentError::OperationUnsupported,
+        ))
+    }
 }
 
 struct NetHandler {
@@ -553,7 +607,8 @@ const fn usize_max(a: usize, b: usize) -> usize {
 
 mod idl {
     use task_control_plane_agent_api::{
-        ControlPlaneAgentError, HostStartupOptions, UartClient, VpdIdentity,
+        ControlPlaneAgentError, HostConsoleScrollback, HostStartupOptions,
+        UartClient, VpdIdentity,
     };
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
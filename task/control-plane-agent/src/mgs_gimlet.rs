@@ -27,7 +27,7 @@ use host_sp_messages::HostStartupOptions;
 use idol_runtime::{Leased, RequestError};
 use ringbuf::ringbuf_entry_root as ringbuf_entry;
 use task_control_plane_agent_api::{
-    ControlPlaneAgentError, UartClient, VpdIdentity,
+    ControlPlaneAgentError, HostConsoleScrollback, UartClient, VpdIdentity,
     MAX_INSTALLINATOR_IMAGE_ID_LEN,
 };
 use task_net_api::{Address, MacAddress, UdpMetadata};
@@ -84,6 +84,10 @@ static_assertions::const_assert!(
 /// is this old, even if our buffer isn't full yet.
 const SERIAL_CONSOLE_FLUSH_TIMEOUT_MILLIS: u64 = 500;
 
+// Defines `HOST_CONSOLE_SCROLLBACK_SIZE` and `HOST_CONSOLE_REPLAY_ON_ATTACH`,
+// from our task config in app.toml.
+include!(concat!(env!("OUT_DIR"), "/host_console_config.rs"));
+
 userlib::task_slot!(HOST_FLASH, hf);
 userlib::task_slot!(GIMLET_SEQ, gimlet_seq);
 userlib::task_slot!(USER_LEDS, user_leds);
@@ -301,18 +305,33 @@ impl MgsHandler {
             }),
         };
 
-        let (from_rx0, from_rx1) = self.usart.from_rx.as_slices();
-        let (n, written) = gateway_messages::serialize_with_trailing_data(
-            tx_buf,
-            &message,
-            &[from_rx0, from_rx1],
-        );
-
         // Note: We do not wait for an ack from MGS after sending this data; we
         // hope it receives it, but if not, it's lost. We don't have the buffer
         // space to keep a bunch of data around waiting for acks, and in
         // practice we don't expect lost packets to be a problem.
-        self.usart.drain_flushed_data(written);
+        let n = match self.usart.replay {
+            Some(next) => {
+                let (n, written) =
+                    gateway_messages::serialize_with_trailing_data(
+                        tx_buf,
+                        &message,
+                        &self.usart.scrollback_from(next),
+                    );
+                self.usart.drain_replayed_data(written);
+                n
+            }
+            None => {
+                let (from_rx0, from_rx1) = self.usart.from_rx.as_slices();
+                let (n, written) =
+                    gateway_messages::serialize_with_trailing_data(
+                        tx_buf,
+                        &message,
+                        &[from_rx0, from_rx1],
+                    );
+                self.usart.drain_flushed_data(written);
+                n
+            }
+        };
 
         Some(UdpMetadata {
             addr: Address::Ipv6(mgs_addr.ip.into()),
@@ -379,6 +398,32 @@ impl MgsHandler {
         Ok(i)
     }
 
+    pub(crate) fn read_host_console_scrollback(
+        &self,
+        offset: u64,
+        data: Leased<idol_runtime::W, [u8]>,
+    ) -> Result<HostConsoleScrollback, RequestError<ControlPlaneAgentError>>
+    {
+        let end = self.usart.scrollback_end;
+
+        // Clamp the requested offset into the range we still have.
+        let start = offset.clamp(self.usart.scrollback_start(), end);
+
+        let mut len = 0;
+        for chunk in self.usart.scrollback_from(start) {
+            let n = usize::min(chunk.len(), data.len() - len);
+            data.write_range(len..len + n, &chunk[..n])
+                .map_err(|()| RequestError::went_away())?;
+            len += n;
+        }
+
+        Ok(HostConsoleScrollback {
+            start,
+            len: len as u32,
+            end,
+        })
+    }
+
     pub(crate) fn fetch_host_phase2_data(
         &mut self,
         msg: &userlib::RecvMessage,
@@ -747,6 +792,13 @@ impl SpHandler for MgsHandler {
         // a dongle attached to even use humility.
         self.usart.set_client(UartClient::Mgs);
 
+        // Start the new client off with whatever the host said before it
+        // showed up (e.g., a panic message), as far back as our scrollback
+        // goes.
+        if HOST_CONSOLE_REPLAY_ON_ATTACH {
+            self.usart.replay_scrollback();
+        }
+
         Ok(())
     }
 
@@ -879,6 +931,10 @@ impl SpHandler for MgsHandler {
             component
         }));
 
+        // TODO: The sequencer's event log (see `Sequencer::read_event`)
+        // belongs here as the details of `SP3_HOST_CPU`, but
+        // `gateway_messages::ComponentDetails` has no variant that can carry
+        // an event and its timestamp.
         self.common.inventory().num_component_details(&component)
     }
 
@@ -1070,6 +1126,16 @@ struct UsartHandler {
     from_rx_flush_deadline: Option<u64>,
     from_rx_offset: u64,
     client: UartClient,
+    // Everything we've received from the host, most recent last, kept even
+    // when nobody is listening.
+    scrollback: &'static mut Deque<u8, HOST_CONSOLE_SCROLLBACK_SIZE>,
+    // Total number of bytes ever pushed into `scrollback`.
+    scrollback_end: u64,
+    // If we're replaying `scrollback` to MGS, the offset (in the same terms
+    // as `scrollback_end`) of the next byte to send. While this is set,
+    // data received from the host is only pushed into `scrollback`, and is
+    // sent to MGS as part of the replay.
+    replay: Option<u64>,
 }
 
 impl UsartHandler {
@@ -1077,6 +1143,7 @@ impl UsartHandler {
         let usart = configure_usart();
         let to_tx = claim_mgs_to_sp_usart_buf_static();
         let from_rx = claim_sp_to_mgs_usart_buf_static();
+        let scrollback = claim_scrollback_buf_static();
 
         // Enable USART interrupts.
         sys_irq_control(notifications::USART_IRQ_MASK, true);
@@ -1088,6 +1155,9 @@ impl UsartHandler {
             from_rx_flush_deadline: None,
             from_rx_offset: 0,
             client: UartClient::Mgs,
+            scrollback,
+            scrollback_end: 0,
+            replay: None,
         }
     }
 
@@ -1112,6 +1182,7 @@ impl UsartHandler {
             UartClient::Humility => {
                 // We never flush to humility; it polls us.
                 self.from_rx_flush_deadline = None;
+                self.replay = None;
             }
             UartClient::Mgs => {
                 // Humility might've disabled the rx interrupt if our rx buffer
@@ -1127,6 +1198,12 @@ impl UsartHandler {
             return false;
         }
 
+        // If we're replaying scrollback, we have data to send until we're
+        // caught up.
+        if self.replay.is_some() {
+            return true;
+        }
+
         // Bail out early if our buffer is empty or past the "we should flush"
         // watermark.
         let len = self.from_rx.len();
@@ -1145,6 +1222,57 @@ impl UsartHandler {
     fn clear_rx_data(&mut self) {
         self.from_rx.clear();
         self.from_rx_flush_deadline = None;
+        self.replay = None;
+    }
+
+    /// Offset of the oldest byte still in our scrollback.
+    fn scrollback_start(&self) -> u64 {
+        self.scrollback_end - self.scrollback.len() as u64
+    }
+
+    /// Returns our scrollback from offset `start` (which must be within it)
+    /// onwards, as a pair of slices.
+    fn scrollback_from(&self, start: u64) -> [&[u8]; 2] {
+        let skip = (start - self.scrollback_start()) as usize;
+        let (a, b) = self.scrollback.as_slices();
+        if skip < a.len() {
+            [&a[skip..], b]
+        } else {
+            [&b[skip - a.len()..], &[]]
+        }
+    }
+
+    fn push_scrollback(&mut self, b: u8) {
+        if self.scrollback.is_full() {
+            // If we're replaying and still had to send the byte we're about
+            // to drop, skip it, and bump our offset so MGS knows it's lost.
+            if self.replay == Some(self.scrollback_start()) {
+                self.replay = Some(self.scrollback_start() + 1);
+                self.from_rx_offset += 1;
+            }
+            self.scrollback.pop_front().unwrap_lite();
+        }
+        self.scrollback.push_back(b).unwrap_lite();
+        self.scrollback_end += 1;
+    }
+
+    /// Replaces any buffered rx data with a replay of our scrollback, to be
+    /// flushed to MGS starting at offset 0.
+    fn replay_scrollback(&mut self) {
+        self.clear_rx_data();
+        if !self.scrollback.is_empty() {
+            self.replay = Some(self.scrollback_start());
+        }
+    }
+
+    fn drain_replayed_data(&mut self, n: usize) {
+        let next = self.replay.unwrap_lite() + n as u64;
+        self.from_rx_offset += n as u64;
+        self.replay = if next < self.scrollback_end {
+            Some(next)
+        } else {
+            None
+        };
     }
 
     fn drain_flushed_data(&mut self, n: usize) {
@@ -1229,6 +1357,7 @@ impl UsartHandler {
                     let Some(b) = self.usart.try_rx_pop() else {
                     break;
                 };
+                    self.push_scrollback(b);
                     self.from_rx.push_back(b).unwrap_lite();
                     n_received += 1;
                 }
@@ -1246,6 +1375,10 @@ impl UsartHandler {
             UartClient::Mgs => {
                 while let Some(b) = self.usart.try_rx_pop() {
                     n_received += 1;
+                    self.push_scrollback(b);
+                    if self.replay.is_some() {
+                        continue;
+                    }
                     match self.from_rx.push_back(b) {
                         Ok(()) => (),
                         Err(b) => {
@@ -1272,7 +1405,10 @@ impl UsartHandler {
             });
         }
 
-        if n_received > 0 && self.from_rx_flush_deadline.is_none() {
+        if n_received > 0
+            && self.replay.is_none()
+            && self.from_rx_flush_deadline.is_none()
+        {
             self.set_from_rx_flush_deadline();
         }
 
@@ -1398,6 +1534,23 @@ fn claim_sp_to_mgs_usart_buf_static(
     unsafe { &mut UART_RX_BUF }
 }
 
+fn claim_scrollback_buf_static(
+) -> &'static mut Deque<u8, HOST_CONSOLE_SCROLLBACK_SIZE> {
+    static mut SCROLLBACK_BUF: Deque<u8, HOST_CONSOLE_SCROLLBACK_SIZE> =
+        Deque::new();
+
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+    if TAKEN.swap(true, Ordering::Relaxed) {
+        panic!()
+    }
+
+    // Safety: unsafe because of references to mutable statics; safe because of
+    // the AtomicBool swap above, combined with the lexical scoping of
+    // `SCROLLBACK_BUF`, means that this reference can't be aliased by any
+    // other reference in the program.
+    unsafe { &mut SCROLLBACK_BUF }
+}
+
 fn claim_installinator_image_id_static() -> &'static mut InstallinatorImageIdBuf
 {
     static mut INSTALLINATOR_IMAGE_ID_BUF: InstallinatorImageIdBuf = Vec::new();
@@ -101,6 +101,21 @@ impl MgsHandler {
         self.sp_update.step_preparation();
     }
 
+    /// Called when the ignition server tells us that it has recorded new
+    /// events, e.g. a sled being inserted or removed.
+    pub(crate) fn handle_ignition_events(&mut self) {
+        let r = self.ignition.new_events(|event| {
+            ringbuf_entry!(Log::IgnitionEvent {
+                index: event.index,
+                port: event.port,
+                kind: event.kind,
+            });
+        });
+        if let Err(err) = r {
+            ringbuf_entry!(Log::IgnitionEventsError(err));
+        }
+    }
+
     pub(crate) fn drive_usart(&mut self) {}
 
     pub(crate) fn wants_to_send_packet_to_mgs(&mut self) -> bool {
@@ -4,7 +4,7 @@
 
 use core::cell::Cell;
 use drv_ignition_api::{
-    AllLinkEventsIter, AllPortsIter, Ignition, IgnitionError,
+    AllLinkEventsIter, AllPortsIter, Event, Ignition, IgnitionError,
 };
 use gateway_messages::ignition::{
     IgnitionState, LinkEvents, ReceiverStatus, SystemFaults, SystemPowerState,
@@ -23,6 +23,9 @@ pub(super) struct IgnitionController {
     // into the FPGA image, not the number of present targets, which varies at
     // runtime).
     num_ports: Cell<Option<u32>>,
+    // Index of the next event in the ignition server's history that we have
+    // yet to see.
+    next_event: Cell<u32>,
 }
 
 impl IgnitionController {
@@ -30,6 +33,7 @@ impl IgnitionController {
         Self {
             task: Ignition::new(IGNITION.get_task_id()),
             num_ports: Cell::new(None),
+            next_event: Cell::new(0),
         }
     }
 
@@ -43,6 +47,31 @@ impl IgnitionController {
         Ok(n)
     }
 
+    /// Calls `f` for each event (Targets arriving and departing, and new
+    /// link events) that the ignition server has recorded since we last
+    /// asked. Events that were overwritten before we could fetch them are
+    /// skipped.
+    pub(super) fn new_events(
+        &self,
+        mut f: impl FnMut(Event),
+    ) -> Result<(), IgnitionError> {
+        let count = self.task.event_count()?;
+
+        while self.next_event.get() != count {
+            let mut fetched = false;
+            for event in self.task.events(self.next_event.get())? {
+                self.next_event.set(event.index.wrapping_add(1));
+                fetched = true;
+                f(event);
+            }
+            if !fetched {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     pub(super) fn target_state(
         &self,
         target: u8,
@@ -5,7 +5,7 @@
 use crate::mgs_handler::{BorrowedUpdateBuffer, UpdateBuffer};
 use core::ops::Range;
 use drv_auxflash_api::{
-    AuxFlash, AuxFlashChecksum, AuxFlashError, PAGE_SIZE_BYTES,
+    is_dump_slot, AuxFlash, AuxFlashChecksum, AuxFlashError, PAGE_SIZE_BYTES,
     SECTOR_SIZE_BYTES, SLOT_COUNT, SLOT_SIZE,
 };
 use gateway_messages::{
@@ -153,8 +153,14 @@ impl ScanningForChck {
         mut self,
         task: &AuxFlash,
     ) -> ChckScanResult {
-        // Scan the slot at `index`.
-        match task.read_slot_chck(self.index) {
+        // Scan the slot at `index`; slots reserved for dumps are treated as
+        // invalid, so they are skipped below.
+        let chck = if is_dump_slot(self.index) {
+            Err(AuxFlashError::InvalidSlot)
+        } else {
+            task.read_slot_chck(self.index)
+        };
+        match chck {
             Ok(chck) => {
                 // If this matches, we're done; transition to the
                 // next state and return the buffer to our caller.
@@ -203,7 +209,11 @@ impl ScanningForChck {
             // `active_slot` to the next even value.
             let target_slot = self.first_empty_even_slot.unwrap_or_else(|| {
                 // Round up to next even number...
-                let next_even = (self.index + 2) & !1;
+                let mut next_even = (self.index + 2) & !1;
+                // and skip over the slots reserved for dumps, if any...
+                if is_dump_slot(next_even % SLOT_COUNT) {
+                    next_even += 2;
+                }
                 // and wrap back around to 0 if needed.
                 next_even % SLOT_COUNT
             });
@@ -9,6 +9,7 @@
 use derive_idol_err::IdolError;
 use dumper_api::DumperError;
 use userlib::*;
+use zerocopy::{AsBytes, FromBytes};
 
 pub use humpty::*;
 
@@ -37,6 +38,13 @@ pub enum DumpAgentError {
     DumpFailedUnknown,
     DumpFailedUnknownError,
 
+    /// There is no room left in flash for another persisted dump
+    PersistFull,
+    /// Copying a dump area into flash failed
+    PersistFailed,
+    /// There is no valid persisted dump with the given index
+    InvalidPersistedDump,
+
     #[idol(server_death)]
     ServerRestarted,
 }
@@ -54,6 +62,8 @@ impl From<DumperError> for DumpAgentError {
             DumperError::FailedToHalt
             | DumperError::FailedToResumeAfterFailure
             | DumperError::FailedToResume => DumpAgentError::DumpFailedControl,
+            DumperError::NoPostMortem => DumpAgentError::InvalidArea,
+            DumperError::BadPostMortemRange => DumpAgentError::BadOffset,
             _ => DumpAgentError::DumpFailedUnknown,
         }
     }
@@ -91,6 +101,10 @@ impl From<DumpAgentError> for humpty::udp::Error {
             DumpAgentError::DumpFailedUnknownError => {
                 Error::DumpFailedUnknownError
             }
+            DumpAgentError::PersistFull | DumpAgentError::PersistFailed => {
+                Error::DumpFailedWrite
+            }
+            DumpAgentError::InvalidPersistedDump => Error::InvalidArea,
         }
     }
 }
@@ -104,4 +118,61 @@ pub const DUMP_AGENT_VERSION: u8 = 0x10_u8;
 pub const DUMP_AGENT_TASKS: u8 = 0x12_u8;
 pub const DUMP_AGENT_SYSTEM: u8 = 0x13_u8;
 
+//
+// Dumps that have been persisted to flash are read through the same
+// `read_dump` interface (and UDP protocol) as the dump areas in RAM:  an
+// area index with this bit set refers to the persisted dump with the
+// remaining bits as its index.  The contents of a persisted dump are a
+// byte-for-byte copy of the dump area it was taken from, so they can be
+// decoded exactly as if they had been read out of RAM.
+//
+pub const PERSISTED_DUMP_INDEX: u8 = 0x80;
+
+//
+// The RoT's post-mortem capture of the SP (see `dumper_api`) is read through
+// `read_dump` as the area with this index; it is an invalid area if the RoT
+// has no capture.
+//
+pub const ROT_POST_MORTEM_INDEX: u8 = 0x7f;
+
+/// Magic value at the start of every dump persisted to flash
+pub const PERSISTED_DUMP_MAGIC: [u8; 4] = *b"HDMP";
+pub const PERSISTED_DUMP_VERSION: u8 = 1;
+
+/// Header preceding a dump area that has been copied into flash.  The CRC
+/// (CRC-32/CKSUM) covers the `length` bytes of dump that follow the header.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, FromBytes, AsBytes)]
+#[repr(C)]
+pub struct PersistedDumpHeader {
+    pub magic: [u8; 4],
+    pub version: u8,
+    /// Index of the dump area that this dump was copied from
+    pub area: u8,
+    pub _pad: [u8; 2],
+    /// Monotonically increasing count of dumps persisted to this region
+    pub sequence: u32,
+    /// Number of bytes of dump following the header
+    pub length: u32,
+    pub crc: u32,
+    /// Image ID (little-endian) of the image that took the dump
+    pub image_id: [u8; 8],
+}
+
+/// Magic value at the start of a dump segment holding task memory that has
+/// been compressed with `gnarle`.  When jefe is built with `dump-compress`,
+/// these segments follow the segments written by `humpty`; each is padded to
+/// a 4-byte boundary.
+pub const DUMP_SEGMENT_GNARLE: [u8; 4] = *b"GNRL";
+
+/// Header preceding `compressed_length` bytes of `gnarle`-compressed memory,
+/// which decompress to `uncompressed_length` bytes starting at `address`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, FromBytes, AsBytes)]
+#[repr(C)]
+pub struct DumpSegmentGnarle {
+    pub magic: [u8; 4],
+    pub address: u32,
+    pub compressed_length: u32,
+    pub uncompressed_length: u32,
+}
+
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
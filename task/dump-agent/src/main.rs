@@ -16,6 +16,13 @@ use userlib::*;
 #[cfg(feature = "net")]
 mod udp;
 
+#[cfg(feature = "persist")]
+mod persist;
+
+/// Interval at which we check for completed dumps to persist to flash
+#[cfg(feature = "persist")]
+const PERSIST_INTERVAL: u64 = 1000;
+
 //
 // Our DUMP_READ_SIZE must be an even power of 2 -- and practically speaking
 // cannot be more than 1K
@@ -27,15 +34,23 @@ struct ServerImpl {
     jefe: Jefe,
     #[cfg(feature = "net")]
     net: task_net_api::Net,
+    #[cfg(feature = "persist")]
+    persist: persist::Persist,
 }
 
 #[cfg(not(feature = "no-rot"))]
 task_slot!(SPROT, sprot);
 
+#[cfg(feature = "persist")]
+task_slot!(AUXFLASH, auxflash);
+
 task_slot!(JEFE, jefe);
 
 impl ServerImpl {
-    fn initialize(&self) -> Result<(), DumpAgentError> {
+    fn initialize(&mut self) -> Result<(), DumpAgentError> {
+        #[cfg(feature = "persist")]
+        self.persist.forget(..);
+
         self.jefe.reinitialize_dump_areas()
     }
 
@@ -94,6 +109,15 @@ impl ServerImpl {
             return Err(DumpAgentError::UnalignedOffset);
         }
 
+        if index & PERSISTED_DUMP_INDEX != 0 {
+            return self
+                .read_persisted_dump(index & !PERSISTED_DUMP_INDEX, offset);
+        }
+
+        if index == ROT_POST_MORTEM_INDEX {
+            return self.read_rot_post_mortem(offset);
+        }
+
         let area = self.dump_area(index)?;
 
         let written = unsafe {
@@ -135,6 +159,9 @@ impl ServerImpl {
         &mut self,
         index: u8,
     ) -> Result<(), DumpAgentError> {
+        #[cfg(feature = "persist")]
+        self.persist.forget(index..);
+
         self.jefe.reinitialize_dump_from(index)?;
         Ok(())
     }
@@ -161,17 +188,169 @@ impl ServerImpl {
     fn take_dump(&mut self) -> Result<(), DumpAgentError> {
         Err(DumpAgentError::NotSupported)
     }
+
+    #[cfg(not(feature = "no-rot"))]
+    fn read_rot_post_mortem(
+        &mut self,
+        offset: u32,
+    ) -> Result<[u8; DUMP_READ_SIZE], DumpAgentError> {
+        use drv_sprot_api::DumpOrSprotError;
+
+        let sprot = drv_sprot_api::SpRot::from(SPROT.get_task_id());
+        let mut rval = [0u8; DUMP_READ_SIZE];
+
+        let len = match sprot.post_mortem_len() {
+            Err(DumpOrSprotError::Dump(e)) => return Err(e.into()),
+            Err(_) => return Err(DumpAgentError::DumpMessageFailed),
+            Ok(len) => len,
+        };
+
+        if offset >= len {
+            return Err(DumpAgentError::BadOffset);
+        }
+
+        let n = usize::min((len - offset) as usize, DUMP_READ_SIZE);
+
+        match sprot.read_post_mortem(offset, &mut rval[..n]) {
+            Err(DumpOrSprotError::Dump(e)) => Err(e.into()),
+            Err(_) => Err(DumpAgentError::DumpMessageFailed),
+            Ok(()) => Ok(rval),
+        }
+    }
+
+    #[cfg(feature = "no-rot")]
+    fn read_rot_post_mortem(
+        &mut self,
+        _offset: u32,
+    ) -> Result<[u8; DUMP_READ_SIZE], DumpAgentError> {
+        Err(DumpAgentError::NotSupported)
+    }
+
+    #[cfg(feature = "persist")]
+    fn persist_dump(&mut self, index: u8) -> Result<u8, DumpAgentError> {
+        let area = self.dump_area(index)?;
+
+        if !dump_complete(&area) {
+            return Err(DumpAgentError::UnclaimedDumpArea);
+        }
+
+        self.persist.persist(&area)
+    }
+
+    ///
+    /// Copies every completed dump area that hasn't yet been persisted into
+    /// flash.  This is called periodically, so that a dump taken by jefe (or
+    /// by the RoT) survives a subsequent power cycle.
+    ///
+    #[cfg(feature = "persist")]
+    fn persist_completed_dumps(&mut self) {
+        for index in 0..=u8::MAX {
+            let area = match self.dump_area(index) {
+                Ok(area) => area,
+                Err(_) => break,
+            };
+
+            if !dump_complete(&area) {
+                self.persist.forget(index..=index);
+            } else if let Err(DumpAgentError::PersistFull) =
+                self.persist.persist(&area)
+            {
+                break;
+            }
+        }
+    }
+
+    #[cfg(feature = "persist")]
+    fn get_persisted_dump(
+        &mut self,
+        index: u8,
+    ) -> Result<PersistedDumpHeader, DumpAgentError> {
+        self.persist.header(index)
+    }
+
+    #[cfg(feature = "persist")]
+    fn read_persisted_dump(
+        &mut self,
+        index: u8,
+        offset: u32,
+    ) -> Result<[u8; DUMP_READ_SIZE], DumpAgentError> {
+        self.persist.read(index, offset)
+    }
+
+    #[cfg(feature = "persist")]
+    fn erase_persisted_dumps(&mut self) -> Result<(), DumpAgentError> {
+        self.persist.erase()
+    }
+
+    #[cfg(not(feature = "persist"))]
+    fn persist_dump(&mut self, _index: u8) -> Result<u8, DumpAgentError> {
+        Err(DumpAgentError::NotSupported)
+    }
+
+    #[cfg(not(feature = "persist"))]
+    fn get_persisted_dump(
+        &mut self,
+        _index: u8,
+    ) -> Result<PersistedDumpHeader, DumpAgentError> {
+        Err(DumpAgentError::NotSupported)
+    }
+
+    #[cfg(not(feature = "persist"))]
+    fn read_persisted_dump(
+        &mut self,
+        _index: u8,
+        _offset: u32,
+    ) -> Result<[u8; DUMP_READ_SIZE], DumpAgentError> {
+        Err(DumpAgentError::NotSupported)
+    }
+
+    #[cfg(not(feature = "persist"))]
+    fn erase_persisted_dumps(&mut self) -> Result<(), DumpAgentError> {
+        Err(DumpAgentError::NotSupported)
+    }
 }
 
-#[cfg(feature = "net")]
+///
+/// Returns true if the given dump area holds a dump that has been taken (as
+/// opposed to one that is available, or claimed with segments but not yet
+/// dumped).
+///
+#[cfg(feature = "persist")]
+fn dump_complete(area: &DumpArea) -> bool {
+    area.contents != humpty::DumpContents::Available
+        && persist::area_header(area.region.address).dumper
+            != humpty::DUMPER_NONE
+}
+
+#[cfg(any(feature = "net", feature = "persist"))]
 impl idol_runtime::NotificationHandler for ServerImpl {
     fn current_notification_mask(&self) -> u32 {
-        notifications::SOCKET_MASK
+        let mut mask = 0;
+
+        #[cfg(feature = "net")]
+        {
+            mask |= notifications::SOCKET_MASK;
+        }
+
+        #[cfg(feature = "persist")]
+        {
+            mask |= notifications::TIMER_MASK;
+        }
+
+        mask
     }
     fn handle_notification(&mut self, bits: u32) {
+        #[cfg(feature = "net")]
         if (bits & notifications::SOCKET_MASK) != 0 {
             // Nothing to do here; we'll handle it in the main loop
         }
+
+        #[cfg(feature = "persist")]
+        if (bits & notifications::TIMER_MASK) != 0 {
+            self.persist_completed_dumps();
+            let deadline = sys_get_timer().now + PERSIST_INTERVAL;
+            sys_set_timer(Some(deadline), notifications::TIMER_MASK);
+        }
     }
 }
 
@@ -247,12 +426,41 @@ impl idl::InOrderDumpAgentImpl for ServerImpl {
     ) -> Result<(), RequestError<DumpAgentError>> {
         self.reinitialize_dump_from(index).map_err(|e| e.into())
     }
+
+    fn persist_dump(
+        &mut self,
+        _msg: &RecvMessage,
+        index: u8,
+    ) -> Result<u8, RequestError<DumpAgentError>> {
+        self.persist_dump(index).map_err(|e| e.into())
+    }
+
+    fn get_persisted_dump(
+        &mut self,
+        _msg: &RecvMessage,
+        index: u8,
+    ) -> Result<PersistedDumpHeader, RequestError<DumpAgentError>> {
+        self.get_persisted_dump(index).map_err(|e| e.into())
+    }
+
+    fn erase_persisted_dumps(
+        &mut self,
+        _msg: &RecvMessage,
+    ) -> Result<(), RequestError<DumpAgentError>> {
+        self.erase_persisted_dumps().map_err(|e| e.into())
+    }
 }
 
 #[export_name = "main"]
 fn main() -> ! {
     let mut buffer = [0; idl::INCOMING_SIZE];
 
+    #[cfg(feature = "persist")]
+    sys_set_timer(
+        Some(sys_get_timer().now + PERSIST_INTERVAL),
+        notifications::TIMER_MASK,
+    );
+
     #[cfg(feature = "net")]
     {
         task_slot!(NET, net);
@@ -260,6 +468,10 @@ fn main() -> ! {
         let mut server = ServerImpl {
             jefe: Jefe::from(JEFE.get_task_id()),
             net: task_net_api::Net::from(NET.get_task_id()),
+            #[cfg(feature = "persist")]
+            persist: persist::Persist::new(drv_auxflash_api::AuxFlash::from(
+                AUXFLASH.get_task_id(),
+            )),
         };
 
         loop {
@@ -275,8 +487,16 @@ fn main() -> ! {
     {
         let mut server = ServerImpl {
             jefe: Jefe::from(JEFE.get_task_id()),
+            #[cfg(feature = "persist")]
+            persist: persist::Persist::new(drv_auxflash_api::AuxFlash::from(
+                AUXFLASH.get_task_id(),
+            )),
         };
         loop {
+            #[cfg(feature = "persist")]
+            idol_runtime::dispatch_n(&mut buffer, &mut server);
+
+            #[cfg(not(feature = "persist"))]
             idol_runtime::dispatch(&mut buffer, &mut server);
         }
     }
@@ -0,0 +1,490 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Persistence of dumps into auxiliary flash
+//!
+//! Dump areas live in RAM, and are therefore lost on a power cycle.  When
+//! the `persist` feature is enabled, completed dump areas are copied into
+//! auxiliary flash.  The `dump-slot` in the global `auxflash` config reserves
+//! the even/odd pair of slots containing it, and we treat that pair as a
+//! single region.
+//!
+//! Within that region, each persisted dump starts on a sector boundary: the
+//! first page holds a [`PersistedDumpHeader`], and the dump area contents
+//! follow starting at the second page.  The header is written only after the
+//! contents have been written, and the space is only claimed once the header
+//! is written, so a failed copy is overwritten by the next one.  A copy that
+//! is interrupted by a reset leaves an unusable header, which we skip over
+//! on the next boot.
+
+use drv_auxflash_api::{
+    AuxFlash, AuxFlashError, DUMP_SLOT, PAGE_SIZE_BYTES, SECTOR_SIZE_BYTES,
+    SLOT_SIZE,
+};
+use dump_agent_api::*;
+use ringbuf::*;
+use userlib::*;
+use zerocopy::{AsBytes, FromBytes};
+
+use core::ops::RangeBounds;
+use crc::{Crc, CRC_32_CKSUM};
+
+/// First slot of the even/odd pair that holds our persisted dumps
+const BASE_SLOT: u32 = match DUMP_SLOT {
+    Some(slot) => slot & !1,
+    None => panic!("`persist` feature requires an auxflash `dump-slot`"),
+};
+
+/// Size of the region made up of both of our slots
+const REGION_SIZE: u32 = 2 * SLOT_SIZE as u32;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_CKSUM);
+
+/// Maximum number of dumps that we will track in flash
+const MAX_PERSISTED_DUMPS: usize = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Trace {
+    None,
+    Found {
+        offset: u32,
+        sequence: u32,
+        length: u32,
+    },
+    BadCrc {
+        offset: u32,
+    },
+    BadHeader {
+        offset: u32,
+    },
+    Persisting {
+        area: u8,
+        offset: u32,
+        length: u32,
+    },
+    Persisted {
+        index: u8,
+        sequence: u32,
+    },
+    Full,
+    Erased,
+    AuxFlashError(AuxFlashError),
+}
+
+ringbuf!(Trace, 16, Trace::None);
+
+#[derive(Copy, Clone)]
+struct Record {
+    /// Offset of the header within our region
+    offset: u32,
+    header: PersistedDumpHeader,
+    /// Whether we know this to be a copy of what is in its dump area, which
+    /// spares us from computing the CRC of the area to find out
+    current: bool,
+}
+
+pub struct Persist {
+    auxflash: AuxFlash,
+    records: [Option<Record>; MAX_PERSISTED_DUMPS],
+    /// Sector-aligned offset of the first free sector in our region
+    free: u32,
+    /// Sequence number for the next dump that we persist
+    sequence: u32,
+}
+
+/// Returns the number of bytes that a persisted dump of the given length
+/// occupies in flash, or `None` if that doesn't fit in a `u32`
+fn footprint(length: u32) -> Option<u32> {
+    let sector = SECTOR_SIZE_BYTES as u32;
+    let total = length.checked_add(PAGE_SIZE_BYTES as u32 + sector - 1)?;
+    Some((total / sector) * sector)
+}
+
+/// Returns the offset just past a persisted dump of the given length at
+/// `offset`, if it fits in our region
+fn end_of(offset: u32, length: u32) -> Option<u32> {
+    footprint(length)
+        .and_then(|f| offset.checked_add(f))
+        .filter(|&end| end <= REGION_SIZE)
+}
+
+/// Returns the slot and offset within it of an offset within our region
+fn locate(offset: u32) -> (u32, u32) {
+    let slot_size = SLOT_SIZE as u32;
+    (BASE_SLOT + offset / slot_size, offset % slot_size)
+}
+
+/// Reads from a dump area in RAM
+fn read_area(address: u32, offset: u32, buf: &mut [u8]) {
+    let base = address as *const u8;
+
+    // SAFETY: we have been given access to the dump areas in RAM via our
+    // `extern-regions`, and our callers only ask for bytes below the written
+    // size of the dump area.
+    let base = unsafe { base.add(offset as usize) };
+
+    for (i, b) in buf.iter_mut().enumerate() {
+        *b = unsafe { core::ptr::read_volatile(base.add(i)) };
+    }
+}
+
+/// Returns the header of the dump area at the given address
+pub fn area_header(address: u32) -> DumpAreaHeader {
+    // SAFETY: dump areas start with a header that is initialized by jefe
+    // before we can get our hands on them.
+    unsafe { core::ptr::read_volatile(address as *const DumpAreaHeader) }
+}
+
+impl Persist {
+    pub fn new(auxflash: AuxFlash) -> Self {
+        let mut persist = Self {
+            auxflash,
+            records: [None; MAX_PERSISTED_DUMPS],
+            free: 0,
+            sequence: 0,
+        };
+
+        persist.scan();
+        persist
+    }
+
+    /// Walks our region, finding the persisted dumps that it contains
+    fn scan(&mut self) {
+        let mut offset = 0;
+        let mut ndx = 0;
+
+        //
+        // We walk the entire region rather than stopping at the first header
+        // that isn't valid:  a copy that was interrupted by a reset leaves a
+        // header that is erased or only partially written, and anything
+        // persisted after it must still be found.  The free space starts
+        // after the last thing that we find.
+        //
+        self.free = 0;
+
+        while offset < REGION_SIZE {
+            let mut header = PersistedDumpHeader::new_zeroed();
+
+            if let Err(e) = self.flash_read(offset, header.as_bytes_mut()) {
+                ringbuf_entry!(Trace::AuxFlashError(e));
+                break;
+            }
+
+            if header.as_bytes().iter().all(|&b| b == 0xff) {
+                offset += SECTOR_SIZE_BYTES as u32;
+                continue;
+            }
+
+            let end = match end_of(offset, header.length) {
+                Some(end) if header.magic == PERSISTED_DUMP_MAGIC => end,
+                _ => {
+                    //
+                    // We can't trust the length of this one, but every
+                    // persisted dump starts on a sector boundary, so we can
+                    // look for the next one in the next sector.
+                    //
+                    ringbuf_entry!(Trace::BadHeader { offset });
+                    offset += SECTOR_SIZE_BYTES as u32;
+                    self.free = offset;
+                    continue;
+                }
+            };
+
+            ringbuf_entry!(Trace::Found {
+                offset,
+                sequence: header.sequence,
+                length: header.length
+            });
+
+            self.sequence = self.sequence.max(header.sequence.wrapping_add(1));
+
+            if ndx < MAX_PERSISTED_DUMPS
+                && header.version == PERSISTED_DUMP_VERSION
+                && self.crc(offset, header.length) == Some(header.crc)
+            {
+                self.records[ndx] = Some(Record {
+                    offset,
+                    header,
+                    current: false,
+                });
+                ndx += 1;
+            } else {
+                ringbuf_entry!(Trace::BadCrc { offset });
+            }
+
+            offset = end;
+            self.free = offset;
+        }
+    }
+
+    /// Computes the CRC of the persisted dump with its header at `offset`
+    fn crc(&self, offset: u32, length: u32) -> Option<u32> {
+        let mut digest = CRC32.digest();
+        let mut buf = [0u8; PAGE_SIZE_BYTES];
+        let base = offset + PAGE_SIZE_BYTES as u32;
+        let mut pos = 0;
+
+        while pos < length {
+            let amount = (length - pos).min(buf.len() as u32) as usize;
+            self.flash_read(base + pos, &mut buf[..amount]).ok()?;
+            digest.update(&buf[..amount]);
+            pos += amount as u32;
+        }
+
+        Some(digest.finalize())
+    }
+
+    /// Reads from our region, which may span both of our slots
+    fn flash_read(
+        &self,
+        offset: u32,
+        buf: &mut [u8],
+    ) -> Result<(), AuxFlashError> {
+        let mut pos = 0;
+
+        while pos < buf.len() {
+            let (slot, start) = locate(offset + pos as u32);
+            let amount = (buf.len() - pos).min(SLOT_SIZE - start as usize);
+            self.auxflash.read_slot_with_offset(
+                slot,
+                start,
+                &mut buf[pos..pos + amount],
+            )?;
+            pos += amount;
+        }
+
+        Ok(())
+    }
+
+    /// Writes to our region.  Writes are at most a page, and page aligned,
+    /// so they never span both of our slots.
+    fn flash_write(
+        &self,
+        offset: u32,
+        buf: &[u8],
+    ) -> Result<(), AuxFlashError> {
+        let (slot, offset) = locate(offset);
+        self.auxflash.write_slot_with_offset(slot, offset, buf)
+    }
+
+    fn record(&self, index: u8) -> Result<&Record, DumpAgentError> {
+        self.records
+            .get(index as usize)
+            .and_then(Option::as_ref)
+            .ok_or(DumpAgentError::InvalidPersistedDump)
+    }
+
+    /// Returns the index of the persisted copy of the dump area at
+    /// `address`, if there is one.
+    fn find(&mut self, area: u8, address: u32, length: u32) -> Option<u8> {
+        let plausible =
+            |r: &Record| r.header.area == area && r.header.length == length;
+
+        //
+        // We are called for every completed dump area each time that we
+        // look for something to persist, so if we already know which of our
+        // records holds this one, we don't read all of it again.
+        //
+        if let Some(ndx) = self
+            .records
+            .iter()
+            .position(|r| matches!(r, Some(r) if r.current && plausible(r)))
+        {
+            return Some(ndx as u8);
+        }
+
+        let mut crc = None;
+
+        for (ndx, r) in self.records.iter_mut().enumerate() {
+            let r = match r {
+                Some(r) if plausible(r) => r,
+                _ => continue,
+            };
+
+            //
+            // We only compute the CRC of the area in RAM if we have a
+            // plausible match, as doing so requires reading all of it.
+            //
+            let crc = *crc.get_or_insert_with(|| {
+                let mut digest = CRC32.digest();
+                let mut buf = [0u8; PAGE_SIZE_BYTES];
+                let mut pos = 0;
+
+                while pos < length {
+                    let amount = (length - pos).min(buf.len() as u32);
+                    read_area(address, pos, &mut buf[..amount as usize]);
+                    digest.update(&buf[..amount as usize]);
+                    pos += amount;
+                }
+
+                digest.finalize()
+            });
+
+            if r.header.crc == crc {
+                r.current = true;
+                return Some(ndx as u8);
+            }
+        }
+
+        None
+    }
+
+    /// Copies the given dump area into flash, if it hasn't been already
+    pub fn persist(&mut self, area: &DumpArea) -> Result<u8, DumpAgentError> {
+        let address = area.region.address;
+        let length = area_header(address).written;
+
+        if let Some(ndx) = self.find(area.index, address, length) {
+            return Ok(ndx);
+        }
+
+        let ndx = self
+            .records
+            .iter()
+            .position(Option::is_none)
+            .ok_or(DumpAgentError::PersistFull)?;
+
+        let offset = self.free;
+
+        let end = match end_of(offset, length) {
+            Some(end) => end,
+            None => {
+                ringbuf_entry!(Trace::Full);
+                return Err(DumpAgentError::PersistFull);
+            }
+        };
+
+        ringbuf_entry!(Trace::Persisting {
+            area: area.index,
+            offset,
+            length
+        });
+
+        //
+        // We only claim the space once the header has been written; if we
+        // fail before then, the next dump that we persist erases and reuses
+        // it.
+        //
+        let flash_err = |e: AuxFlashError| {
+            ringbuf_entry!(Trace::AuxFlashError(e));
+            DumpAgentError::PersistFailed
+        };
+
+        let mut sector = offset;
+
+        while sector < end {
+            let (slot, start) = locate(sector);
+            self.auxflash
+                .slot_sector_erase(slot, start)
+                .map_err(flash_err)?;
+            sector += SECTOR_SIZE_BYTES as u32;
+        }
+
+        let mut digest = CRC32.digest();
+        let mut buf = [0u8; PAGE_SIZE_BYTES];
+        let base = offset + PAGE_SIZE_BYTES as u32;
+        let mut pos = 0;
+
+        while pos < length {
+            let amount = (length - pos).min(buf.len() as u32);
+            let buf = &mut buf[..amount as usize];
+            read_area(address, pos, buf);
+            digest.update(buf);
+
+            self.flash_write(base + pos, buf).map_err(flash_err)?;
+
+            pos += amount;
+        }
+
+        let header = PersistedDumpHeader {
+            magic: PERSISTED_DUMP_MAGIC,
+            version: PERSISTED_DUMP_VERSION,
+            area: area.index,
+            _pad: [0; 2],
+            sequence: self.sequence,
+            length,
+            crc: digest.finalize(),
+            image_id: kipc::read_image_id().to_le_bytes(),
+        };
+
+        self.flash_write(offset, header.as_bytes())
+            .map_err(flash_err)?;
+        self.free = end;
+
+        ringbuf_entry!(Trace::Persisted {
+            index: ndx as u8,
+            sequence: header.sequence
+        });
+
+        self.records[ndx] = Some(Record {
+            offset,
+            header,
+            current: true,
+        });
+        self.sequence = self.sequence.wrapping_add(1);
+
+        Ok(ndx as u8)
+    }
+
+    /// Notes that the given dump areas have been (or are about to be)
+    /// reinitialized, so our copies of them are no longer known to match.
+    pub fn forget(&mut self, areas: impl RangeBounds<u8>) {
+        for r in self.records.iter_mut().flatten() {
+            if areas.contains(&r.header.area) {
+                r.current = false;
+            }
+        }
+    }
+
+    pub fn header(
+        &self,
+        index: u8,
+    ) -> Result<PersistedDumpHeader, DumpAgentError> {
+        Ok(self.record(index)?.header)
+    }
+
+    /// Reads from a persisted dump, with the same semantics as reading from
+    /// a dump area in RAM.
+    pub fn read(
+        &self,
+        index: u8,
+        offset: u32,
+    ) -> Result<[u8; DUMP_READ_SIZE], DumpAgentError> {
+        let record = self.record(index)?;
+        let mut rval = [0u8; DUMP_READ_SIZE];
+
+        if offset >= record.header.length {
+            return Err(DumpAgentError::BadOffset);
+        }
+
+        let amount =
+            usize::min((record.header.length - offset) as usize, rval.len());
+
+        self.flash_read(
+            record.offset + PAGE_SIZE_BYTES as u32 + offset,
+            &mut rval[..amount],
+        )
+        .map_err(|e| {
+            ringbuf_entry!(Trace::AuxFlashError(e));
+            DumpAgentError::PersistFailed
+        })?;
+
+        Ok(rval)
+    }
+
+    pub fn erase(&mut self) -> Result<(), DumpAgentError> {
+        for slot in [BASE_SLOT, BASE_SLOT + 1] {
+            self.auxflash.erase_slot(slot).map_err(|e| {
+                ringbuf_entry!(Trace::AuxFlashError(e));
+                DumpAgentError::PersistFailed
+            })?;
+        }
+
+        ringbuf_entry!(Trace::Erased);
+        self.records = [None; MAX_PERSISTED_DUMPS];
+        self.free = 0;
+
+        Ok(())
+    }
+}
@@ -10,6 +10,7 @@ use derive_idol_err::IdolError;
 use hubpack::SerializedSize;
 use serde::{Deserialize, Serialize};
 use userlib::*;
+use zerocopy::{AsBytes, FromBytes};
 
 ///
 /// These constitute an interface between the RoT and the SP in that the
@@ -50,6 +51,71 @@ pub enum DumperError {
     /// The dumper returned an unknown error, probably due to the SP
     /// being older than the RoT firmware
     UnknownFailureViaSprot,
+
+    /// No post-mortem capture has been taken
+    NoPostMortem = 14,
+    /// A read of the post-mortem capture was out of bounds
+    BadPostMortemRange = 15,
+}
+
+///
+/// A post-mortem capture of the SP, taken by halting it over SWD.  The
+/// capture is little-endian, and consists of a [`PostMortemHeader`], followed
+/// by `nregisters` [`PostMortemRegister`]s, followed by `nregions` regions of
+/// SP memory, each of which is a [`PostMortemRegion`] followed by `size`
+/// bytes of data.  The first region is always the fault status registers in
+/// the System Control Block (see [`FAULT_STATUS_ADDR`]).
+///
+pub const POST_MORTEM_MAGIC: [u8; 4] = *b"SPPM";
+pub const POST_MORTEM_VERSION: u32 = 1;
+
+/// Address of the SP's CFSR; the HFSR, DFSR, MMFAR, BFAR and AFSR follow it.
+pub const FAULT_STATUS_ADDR: u32 = 0xe000_ed28;
+pub const FAULT_STATUS_SIZE: u32 = 24;
+
+/// Why a post-mortem capture was taken, as recorded in its header
+#[derive(Copy, Clone, Debug, FromPrimitive, Eq, PartialEq)]
+#[repr(u32)]
+pub enum PostMortemTrigger {
+    /// Someone (e.g. the SP, via sprot) asked for the capture
+    Request = 1,
+    /// The RoT found the SP locked up
+    Lockup = 2,
+    /// The RoT found the SP had stopped retiring instructions without
+    /// having been halted
+    Hung = 3,
+    /// The RoT found that the SP had been reset since it last checked (only
+    /// with the dumper's `watch-resets` feature)
+    Reset = 4,
+}
+
+#[derive(Copy, Clone, Debug, AsBytes, FromBytes)]
+#[repr(C)]
+pub struct PostMortemHeader {
+    pub magic: [u8; 4],
+    pub version: u32,
+    /// RoT timestamp at which the capture was taken
+    pub timestamp: u64,
+    pub nregisters: u32,
+    pub nregions: u32,
+    /// A [`PostMortemTrigger`]
+    pub trigger: u32,
+    pub _reserved: u32,
+}
+
+#[derive(Copy, Clone, Debug, AsBytes, FromBytes)]
+#[repr(C)]
+pub struct PostMortemRegister {
+    /// Register number, as given to `DCRSR`
+    pub register: u32,
+    pub value: u32,
+}
+
+#[derive(Copy, Clone, Debug, AsBytes, FromBytes)]
+#[repr(C)]
+pub struct PostMortemRegion {
+    pub address: u32,
+    pub size: u32,
 }
 
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
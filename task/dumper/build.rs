@@ -2,6 +2,76 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// App configs (relative to this crate) of the SP images that we run
+    /// alongside, whose kernel RAM and dump area headers we include in a
+    /// post-mortem capture.
+    #[serde(default)]
+    sp_apps: Vec<PathBuf>,
+    /// Any other regions of SP memory to include in a post-mortem capture,
+    /// in addition to the core and fault status registers.
+    #[serde(default)]
+    post_mortem_regions: Vec<Region>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Region {
+    address: u32,
+    size: u32,
+}
+
+/// The parts of an SP app config that we need.  This must be a config that
+/// has the `chip`, `[kernel]` and `[tasks.jefe]` sections itself, rather
+/// than one that inherits them.
+#[derive(Deserialize)]
+struct SpApp {
+    chip: String,
+    memory: Option<String>,
+    kernel: SpKernel,
+    tasks: BTreeMap<String, SpTask>,
+}
+
+#[derive(Deserialize)]
+struct SpKernel {
+    requires: BTreeMap<String, u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SpTask {
+    #[serde(default)]
+    extern_regions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct MemoryRegion {
+    address: u32,
+}
+
+/// Amount of each of the SP's dump areas that we capture, which holds the
+/// area's header and those of its first segments
+const DUMP_AREA_HEADER_SIZE: u32 = 256;
+
+/// Size of the header at the start of a capture
+const HEADER_SIZE: usize = 32;
+
+/// Space for every core register, of which there are at most 32
+const REGISTERS_SIZE: usize = 32 * 8;
+
+/// Size of the header preceding each region
+const REGION_HEADER_SIZE: usize = 8;
+
+/// Size of the fault status registers, which are always captured
+const FAULT_STATUS_SIZE: usize = 24;
+
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     idol::server::build_server_support(
         "../../idl/dumper.idol",
@@ -9,5 +79,128 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         idol::server::ServerStyle::InOrder,
     )?;
 
+    build_util::build_notifications()?;
+
+    let config = build_util::task_maybe_config::<Config>()?.unwrap_or_default();
+
+    //
+    // Regions from the SP apps are merged by address, so that (for example)
+    // the kernel RAM that we capture is that of the largest SP kernel.
+    //
+    let mut sp_regions = BTreeMap::new();
+    for app in &config.sp_apps {
+        for r in sp_app_regions(app)? {
+            let size = sp_regions.entry(r.address).or_insert(0);
+            *size = r.size.max(*size);
+        }
+    }
+
+    let regions = sp_regions
+        .into_iter()
+        .map(|(address, size)| Region { address, size })
+        .chain(config.post_mortem_regions)
+        .collect::<Vec<_>>();
+
+    generate_post_mortem_config(&regions)?;
+
+    Ok(())
+}
+
+///
+/// Returns the regions of SP memory that a post-mortem capture should hold
+/// for the given SP app:  its kernel's RAM, which holds the task table, and
+/// the headers of its dump areas.
+///
+fn sp_app_regions(
+    path: &Path,
+) -> Result<Vec<Region>, Box<dyn std::error::Error + Send + Sync>> {
+    println!("cargo:rerun-if-changed={}", path.display());
+    let app: SpApp = toml::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| format!("parsing {}: {e}", path.display()))?;
+
+    let memory_path = path
+        .parent()
+        .unwrap()
+        .join(&app.chip)
+        .join(app.memory.as_deref().unwrap_or("memory.toml"));
+    println!("cargo:rerun-if-changed={}", memory_path.display());
+    let memory: BTreeMap<String, Vec<MemoryRegion>> =
+        toml::from_str(&std::fs::read_to_string(&memory_path)?)?;
+
+    let address = |name: &str| {
+        memory
+            .get(name)
+            .and_then(|r| r.first())
+            .map(|r| r.address)
+            .ok_or_else(|| {
+                format!("no {name} region in {}", memory_path.display())
+            })
+    };
+
+    //
+    // The kernel is the first thing allocated in `ram`.
+    //
+    let kernel_ram = *app.kernel.requires.get("ram").ok_or_else(|| {
+        format!("no kernel RAM requirement in {}", path.display())
+    })?;
+    let mut regions = vec![Region {
+        address: address("ram")?,
+        size: kernel_ram,
+    }];
+
+    //
+    // The dump areas are jefe's extern regions.
+    //
+    if let Some(jefe) = app.tasks.get("jefe") {
+        for name in &jefe.extern_regions {
+            regions.push(Region {
+                address: address(name)?,
+                size: DUMP_AREA_HEADER_SIZE,
+            });
+        }
+    }
+
+    Ok(regions)
+}
+
+fn generate_post_mortem_config(
+    regions: &[Region],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let out_dir = build_util::out_dir();
+    let mut out = std::fs::File::create(out_dir.join("post_mortem.rs"))?;
+
+    let mut size = HEADER_SIZE + REGISTERS_SIZE;
+    size += REGION_HEADER_SIZE + FAULT_STATUS_SIZE;
+
+    for r in regions {
+        if r.address % 4 != 0 || r.size % 4 != 0 || r.size == 0 {
+            return Err(format!(
+                "post-mortem region {:#x} (size {:#x}) must be a non-empty \
+                 multiple of 4 bytes at a 4-byte aligned address",
+                r.address, r.size
+            )
+            .into());
+        }
+        if r.address.checked_add(r.size - 1).is_none() {
+            return Err(format!(
+                "post-mortem region {:#x} (size {:#x}) overflows",
+                r.address, r.size
+            )
+            .into());
+        }
+        size += REGION_HEADER_SIZE + r.size as usize;
+    }
+
+    writeln!(out, "pub(crate) const POST_MORTEM_SIZE: usize = {size};")?;
+    writeln!(
+        out,
+        "pub(crate) const POST_MORTEM_REGIONS: [(u32, u32); {}] = [",
+        regions.len()
+    )?;
+    for r in regions {
+        writeln!(out, "    ({:#x}, {:#x}),", r.address, r.size)?;
+    }
+    writeln!(out, "];")?;
+
     Ok(())
 }
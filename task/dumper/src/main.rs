@@ -3,16 +3,34 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 //! Dumper
+//!
+//! In addition to dumping the SP into its own dump areas, we can take a
+//! post-mortem capture of the SP -- its core registers, fault status
+//! registers and the regions of memory in our `post-mortem-regions` config --
+//! which we hold until asked for it (e.g. by the SP via sprot, once it has
+//! recovered).
+//!
+//! Because a hung SP can't ask for a capture, we can also (with the `watch`
+//! feature) check on the SP periodically by reading its DHCSR:  if it has
+//! locked up or stopped retiring instructions without having been halted,
+//! we take a capture ourselves.  With the `watch-resets` feature, we also
+//! take one if the SP has been reset since we last looked.  (By the time we
+//! notice a reset, the SP has already started running again, so such a
+//! capture mostly serves as a record that -- and when -- the reset happened,
+//! and comes at the cost of halting the SP while it boots.)  We don't
+//! replace a capture that no one has read yet with one of our own, lest the
+//! capture of a hang be lost to the reset that follows it; a capture that is
+//! asked for always replaces whatever we have.
 
 #![no_std]
 #![no_main]
 
 use drv_sp_ctrl_api::{SpCtrl, SpCtrlError};
 use dumper_api::*;
-use idol_runtime::RequestError;
+use idol_runtime::{Leased, RequestError, W};
 use ringbuf::*;
 use userlib::*;
-use zerocopy::FromBytes;
+use zerocopy::{AsBytes, FromBytes};
 
 #[derive(Copy, Clone, PartialEq)]
 enum Trace {
@@ -33,6 +51,14 @@ enum Trace {
     ReinitFailed,
     ReinitSucceededButResumeFailed,
     ReinitResumed,
+    PostMortemInitiated(PostMortemTrigger),
+    PostMortemDone(Result<u32, DumperError>),
+    #[cfg(feature = "watch")]
+    WatchFailed(SpCtrlError),
+    #[cfg(feature = "watch")]
+    WatchState(u32, SpState),
+    #[cfg(feature = "watch")]
+    PostMortemKept(PostMortemTrigger),
     None,
 }
 
@@ -40,9 +66,195 @@ task_slot!(SP_CTRL, swd);
 
 const READ_SIZE: usize = 256;
 
+// Largest read that we hand to the SWD task in a post-mortem capture
+const READ_CHUNK_SIZE: usize = 4096;
+
+// How often we check on the SP, in milliseconds
+#[cfg(feature = "watch")]
+const WATCH_INTERVAL: u64 = 1000;
+
+// Number of consecutive faults reading the SP's DHCSR after which we will
+// assume that our SWD connection needs to be set up again.  (A fault on its
+// own may just mean that a read transaction is in progress.)
+#[cfg(feature = "watch")]
+const WATCH_MAX_FAULTS: u32 = 10;
+
+// Debug Halting Control and Status Register, and the status bits we watch;
+// the sticky bits are cleared when DHCSR is read.
+#[cfg(feature = "watch")]
+const DHCSR: u32 = 0xe000_edf0;
+#[cfg(feature = "watch")]
+const DHCSR_S_RESET_ST: u32 = 1 << 25;
+#[cfg(feature = "watch")]
+const DHCSR_S_RETIRE_ST: u32 = 1 << 24;
+#[cfg(feature = "watch")]
+const DHCSR_S_LOCKUP: u32 = 1 << 19;
+#[cfg(feature = "watch")]
+const DHCSR_S_HALT: u32 = 1 << 17;
+
 ringbuf!(Trace, 16, Trace::None);
 
-struct ServerImpl;
+/// What we made of the SP when we last checked on it
+#[cfg(feature = "watch")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum SpState {
+    Unknown,
+    Running,
+    Halted,
+    LockedUp,
+    Hung,
+}
+
+struct ServerImpl {
+    post_mortem: &'static mut [u8; POST_MORTEM_SIZE],
+    post_mortem_len: Option<usize>,
+    /// Whether any of our capture has been read since it was taken
+    #[cfg(feature = "watch")]
+    post_mortem_read: bool,
+    #[cfg(feature = "watch")]
+    sp_state: SpState,
+    #[cfg(feature = "watch")]
+    faults: u32,
+}
+
+impl ServerImpl {
+    ///
+    /// Halts the SP, captures its state and resumes it, replacing any
+    /// capture that we already have.
+    ///
+    fn take_post_mortem(
+        &mut self,
+        trigger: PostMortemTrigger,
+    ) -> Result<u32, DumperError> {
+        ringbuf_entry!(Trace::PostMortemInitiated(trigger));
+        let sp_ctrl = SpCtrl::from(SP_CTRL.get_task_id());
+
+        //
+        // Whatever happens, we don't want to leave a partial capture lying
+        // around.
+        //
+        self.post_mortem_len = None;
+
+        if let Err(err) = sp_ctrl.setup() {
+            ringbuf_entry!(Trace::SetupFailed(err));
+            return Err(DumperError::SetupFailed);
+        }
+
+        ringbuf_entry!(Trace::SetupDone);
+
+        if sp_ctrl.halt().is_err() {
+            return Err(DumperError::FailedToHalt);
+        }
+
+        ringbuf_entry!(Trace::Halted);
+
+        let r = capture(&sp_ctrl, self.post_mortem, trigger);
+
+        ringbuf_entry!(Trace::PostMortemDone(r.map(|len| len as u32)));
+
+        if !resume(&sp_ctrl) {
+            if r.is_err() {
+                return Err(DumperError::FailedToResumeAfterFailure);
+            } else {
+                return Err(DumperError::FailedToResume);
+            }
+        }
+
+        ringbuf_entry!(Trace::Resumed);
+
+        let len = r?;
+        self.post_mortem_len = Some(len);
+
+        #[cfg(feature = "watch")]
+        {
+            self.post_mortem_read = false;
+        }
+
+        Ok(len as u32)
+    }
+
+    ///
+    /// Checks on the SP, taking a capture if we find that it has hung or
+    /// been reset.
+    ///
+    #[cfg(feature = "watch")]
+    fn watch(&mut self) {
+        let sp_ctrl = SpCtrl::from(SP_CTRL.get_task_id());
+        let mut dhcsr = 0u32;
+
+        match sp_ctrl.read(DHCSR, dhcsr.as_bytes_mut()) {
+            Ok(()) => self.faults = 0,
+            Err(SpCtrlError::Fault) if self.faults < WATCH_MAX_FAULTS => {
+                self.faults += 1;
+                return;
+            }
+            Err(err) => {
+                //
+                // Either we haven't set up our SWD connection, or it isn't
+                // working (or someone has plugged in a debugger, in which
+                // case `setup` will fail until they unplug it).  We'll set
+                // it up and look again next time; having lost sight of the
+                // SP, we can't know what it did in the meantime.
+                //
+                ringbuf_entry!(Trace::WatchFailed(err));
+                self.faults = 0;
+                self.sp_state = SpState::Unknown;
+
+                if let Err(err) = sp_ctrl.setup() {
+                    ringbuf_entry!(Trace::SetupFailed(err));
+                }
+                return;
+            }
+        }
+
+        let state = if dhcsr & DHCSR_S_HALT != 0 {
+            SpState::Halted
+        } else if dhcsr & DHCSR_S_LOCKUP != 0 {
+            SpState::LockedUp
+        } else if dhcsr & DHCSR_S_RETIRE_ST == 0 {
+            SpState::Hung
+        } else {
+            SpState::Running
+        };
+
+        //
+        // The first time we look, the SP will have been reset (by us, at
+        // power on, or during the measurement of its flash) and may not
+        // have been running for long -- so we don't draw any conclusions
+        // until we've seen it once.
+        //
+        let trigger = match (self.sp_state, state) {
+            (SpState::Unknown, _) => None,
+            _ if cfg!(feature = "watch-resets")
+                && dhcsr & DHCSR_S_RESET_ST != 0 =>
+            {
+                Some(PostMortemTrigger::Reset)
+            }
+            (SpState::Running, SpState::LockedUp) => {
+                Some(PostMortemTrigger::Lockup)
+            }
+            (SpState::Running, SpState::Hung) => Some(PostMortemTrigger::Hung),
+            _ => None,
+        };
+
+        if state != self.sp_state {
+            ringbuf_entry!(Trace::WatchState(dhcsr, state));
+            self.sp_state = state;
+        }
+
+        if let Some(trigger) = trigger {
+            if self.post_mortem_len.is_some() && !self.post_mortem_read {
+                ringbuf_entry!(Trace::PostMortemKept(trigger));
+            } else {
+                //
+                // Any failure has been recorded in our ring buffer, and
+                // there's no one to report it to.
+                //
+                let _ = self.take_post_mortem(trigger);
+            }
+        }
+    }
+}
 
 impl idl::InOrderDumperImpl for ServerImpl {
     fn dump(
@@ -139,27 +351,7 @@ impl idl::InOrderDumperImpl for ServerImpl {
 
         ringbuf_entry!(Trace::Done(r));
 
-        if sp_ctrl.resume().is_err() {
-            ringbuf_entry!(Trace::ResumeFailed);
-
-            //
-            // This is bad: we have failed to resume a stopped SP.  We really
-            // (really!) don't want to leave the SP stopped, so we'll attempt
-            // to reinitialize and re-resume.  (Experience has indicated that
-            // when this occurs -- and it has been seen to occur as ~15%
-            // of the time on the bench! -- reinitialization is always
-            // sufficient to allow for us to resume the SP.)
-            //
-            if sp_ctrl.setup().is_err() {
-                ringbuf_entry!(Trace::ReinitFailed);
-            } else {
-                if sp_ctrl.resume().is_err() {
-                    ringbuf_entry!(Trace::ReinitSucceededButResumeFailed);
-                } else {
-                    ringbuf_entry!(Trace::ReinitResumed);
-                }
-            }
-
+        if !resume(&sp_ctrl) {
             if r.is_err() {
                 return Err(DumperError::FailedToResumeAfterFailure.into());
             } else {
@@ -171,14 +363,200 @@ impl idl::InOrderDumperImpl for ServerImpl {
 
         Ok(())
     }
+
+    fn capture_post_mortem(
+        &mut self,
+        _msg: &RecvMessage,
+    ) -> Result<u32, RequestError<DumperError>> {
+        self.take_post_mortem(PostMortemTrigger::Request)
+            .map_err(|e| e.into())
+    }
+
+    fn post_mortem_len(
+        &mut self,
+        _msg: &RecvMessage,
+    ) -> Result<u32, RequestError<DumperError>> {
+        match self.post_mortem_len {
+            Some(len) => Ok(len as u32),
+            None => Err(DumperError::NoPostMortem.into()),
+        }
+    }
+
+    fn read_post_mortem(
+        &mut self,
+        _msg: &RecvMessage,
+        offset: u32,
+        data: Leased<W, [u8]>,
+    ) -> Result<(), RequestError<DumperError>> {
+        let len = self.post_mortem_len.ok_or(DumperError::NoPostMortem)?;
+        let start = offset as usize;
+        let end = start
+            .checked_add(data.len())
+            .filter(|&end| end <= len)
+            .ok_or(DumperError::BadPostMortemRange)?;
+
+        data.write_range(0..data.len(), &self.post_mortem[start..end])
+            .map_err(|()| RequestError::went_away())?;
+
+        #[cfg(feature = "watch")]
+        {
+            self.post_mortem_read = true;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "watch")]
+impl idol_runtime::NotificationHandler for ServerImpl {
+    fn current_notification_mask(&self) -> u32 {
+        notifications::TIMER_MASK
+    }
+
+    fn handle_notification(&mut self, bits: u32) {
+        if (bits & notifications::TIMER_MASK) != 0 {
+            self.watch();
+            let deadline = sys_get_timer().now + WATCH_INTERVAL;
+            sys_set_timer(Some(deadline), notifications::TIMER_MASK);
+        }
+    }
+}
+
+///
+/// Resumes the (halted) SP, returning `false` if we failed to do so.
+///
+fn resume(sp_ctrl: &SpCtrl) -> bool {
+    if sp_ctrl.resume().is_err() {
+        ringbuf_entry!(Trace::ResumeFailed);
+
+        //
+        // This is bad: we have failed to resume a stopped SP.  We really
+        // (really!) don't want to leave the SP stopped, so we'll attempt
+        // to reinitialize and re-resume.  (Experience has indicated that
+        // when this occurs -- and it has been seen to occur as ~15%
+        // of the time on the bench! -- reinitialization is always
+        // sufficient to allow for us to resume the SP.)
+        //
+        if sp_ctrl.setup().is_err() {
+            ringbuf_entry!(Trace::ReinitFailed);
+        } else {
+            if sp_ctrl.resume().is_err() {
+                ringbuf_entry!(Trace::ReinitSucceededButResumeFailed);
+            } else {
+                ringbuf_entry!(Trace::ReinitResumed);
+            }
+        }
+
+        false
+    } else {
+        true
+    }
+}
+
+///
+/// Captures the state of the (halted) SP into `buf`, in the format described
+/// in `dumper_api`, returning the length of the capture.
+///
+fn capture(
+    sp_ctrl: &SpCtrl,
+    buf: &mut [u8; POST_MORTEM_SIZE],
+    trigger: PostMortemTrigger,
+) -> Result<usize, DumperError> {
+    let header_size = core::mem::size_of::<PostMortemHeader>();
+    let mut offset = header_size;
+    let mut nregisters = 0;
+
+    for r in 0..=31 {
+        ringbuf_entry!(Trace::ReadingRegister(r));
+        let value = match sp_ctrl.read_core_register(r) {
+            Ok(val) => val,
+            Err(SpCtrlError::InvalidCoreRegister) => continue,
+            Err(e) => {
+                ringbuf_entry!(Trace::RegisterReadFailed(e));
+                return Err(DumperError::RegisterReadFailed);
+            }
+        };
+
+        let reg = PostMortemRegister {
+            register: r.into(),
+            value,
+        };
+        let size = core::mem::size_of::<PostMortemRegister>();
+        buf[offset..offset + size].copy_from_slice(reg.as_bytes());
+        offset += size;
+        nregisters += 1;
+    }
+
+    let regions = core::iter::once((FAULT_STATUS_ADDR, FAULT_STATUS_SIZE))
+        .chain(POST_MORTEM_REGIONS.iter().copied());
+    let mut nregions = 0;
+
+    for (address, size) in regions {
+        let region = PostMortemRegion { address, size };
+        let header = core::mem::size_of::<PostMortemRegion>();
+        buf[offset..offset + header].copy_from_slice(region.as_bytes());
+        offset += header;
+
+        let data = &mut buf[offset..offset + size as usize];
+        let mut addr = address;
+
+        for chunk in data.chunks_mut(READ_CHUNK_SIZE) {
+            ringbuf_entry!(Trace::Reading(addr, chunk.len(), offset));
+
+            if sp_ctrl.read(addr, chunk).is_err() {
+                ringbuf_entry!(Trace::DataReadFailed);
+                return Err(DumperError::ReadFailed);
+            }
+
+            addr += chunk.len() as u32;
+        }
+
+        offset += size as usize;
+        nregions += 1;
+    }
+
+    let header = PostMortemHeader {
+        magic: POST_MORTEM_MAGIC,
+        version: POST_MORTEM_VERSION,
+        timestamp: sys_get_timer().now,
+        nregisters,
+        nregions,
+        trigger: trigger as u32,
+        _reserved: 0,
+    };
+    buf[..header_size].copy_from_slice(header.as_bytes());
+
+    Ok(offset)
 }
 
 #[export_name = "main"]
 fn main() -> ! {
-    let mut server = ServerImpl;
+    let post_mortem = mutable_statics::mutable_statics! {
+        static mut POST_MORTEM: [u8; POST_MORTEM_SIZE] = [|| 0u8; _];
+    };
+    let mut server = ServerImpl {
+        post_mortem,
+        post_mortem_len: None,
+        #[cfg(feature = "watch")]
+        post_mortem_read: false,
+        #[cfg(feature = "watch")]
+        sp_state: SpState::Unknown,
+        #[cfg(feature = "watch")]
+        faults: 0,
+    };
     let mut buffer = [0; idl::INCOMING_SIZE];
 
+    #[cfg(feature = "watch")]
+    sys_set_timer(
+        Some(sys_get_timer().now + WATCH_INTERVAL),
+        notifications::TIMER_MASK,
+    );
+
     loop {
+        #[cfg(feature = "watch")]
+        idol_runtime::dispatch_n(&mut buffer, &mut server);
+
+        #[cfg(not(feature = "watch"))]
         idol_runtime::dispatch(&mut buffer, &mut server);
     }
 }
@@ -188,3 +566,6 @@ mod idl {
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
+
+include!(concat!(env!("OUT_DIR"), "/post_mortem.rs"));
+include!(concat!(env!("OUT_DIR"), "/notifications.rs"));
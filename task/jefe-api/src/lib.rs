@@ -8,6 +8,7 @@
 
 use derive_idol_err::IdolError;
 pub use dump_agent_api::DumpAgentError;
+use hubpack::SerializedSize;
 use serde::{Deserialize, Serialize};
 use userlib::*;
 
@@ -25,6 +26,32 @@ pub enum ResetReason {
     ExitStandby,
     Other(u32),
     Unknown, // TODO remove and use `Option<ResetReason>` once we switch to hubpack
+    /// The kernel panicked, recorded what happened, and reset the chip; see
+    /// `Jefe::read_kernel_panic`.
+    KernelPanic,
+}
+
+/// What the kernel recorded about a panic that caused the last reset, other
+/// than its epitaph.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Serialize, Deserialize, SerializedSize,
+)]
+pub struct KernelPanicInfo {
+    pub msp: u32,
+    pub psp: u32,
+    pub ipsr: u32,
+    pub cfsr: u32,
+    pub hfsr: u32,
+    /// Length of the epitaph, which describes the panic
+    pub epitaph_len: u32,
+}
+
+#[derive(Copy, Clone, Debug, FromPrimitive, Eq, PartialEq, IdolError)]
+#[repr(C)]
+pub enum KernelPanicError {
+    /// The last reset wasn't the result of a kernel panic, or the kernel
+    /// wasn't built with the `panic-reset` feature
+    NoKernelPanic = 1,
 }
 
 #[derive(Copy, Clone, Debug, FromPrimitive, Eq, PartialEq, IdolError)]
@@ -10,6 +10,18 @@ use std::io::Write;
 fn main() -> Result<()> {
     let cfg = build_util::task_maybe_config::<Config>()?.unwrap_or_default();
 
+    //
+    // Whoever can call read_task_memory can read any task's RAM, so we
+    // don't let it be open to all comers.
+    //
+    #[cfg(feature = "read-task-memory")]
+    if !cfg.allowed_callers.contains_key("read_task_memory") {
+        anyhow::bail!(
+            "jefe's read-task-memory feature requires that read_task_memory \
+             be restricted in allowed-callers (e.g. to the ringbuf agent)"
+        );
+    }
+
     let allowed_callers = build_util::task_ids()
         .remap_allowed_caller_names_to_ids(&cfg.allowed_callers)?;
 
@@ -6,6 +6,7 @@
 
 use crate::generated::{DUMP_ADDRESS_MAX, DUMP_ADDRESS_MIN, DUMP_AREAS};
 use humpty::{DumpArea, DumpContents};
+use idol_runtime::{Leased, RequestError, W};
 use ringbuf::*;
 use task_jefe_api::DumpAgentError;
 use userlib::*;
@@ -45,6 +46,12 @@ enum Trace {
     DumpArea(Result<Option<DumpArea>, humpty::DumpError<()>>),
     DumpRegion(abi::TaskDumpRegion),
     DumpRegionsFailed(humpty::DumpError<()>),
+    #[cfg(feature = "read-task-memory")]
+    ReadingTaskMemory {
+        task: usize,
+        address: u32,
+        length: u32,
+    },
     DumpStart {
         base: u32,
     },
@@ -55,10 +62,24 @@ enum Trace {
     },
     DumpRead(usize),
     DumpDone(Result<(), humpty::DumpError<()>>),
+    #[cfg(feature = "dump-compress")]
+    Compressed {
+        base: u32,
+        uncompressed: u32,
+        compressed: u32,
+    },
+    #[cfg(feature = "dump-compress")]
+    CompressOverflow {
+        base: u32,
+    },
 }
 
 ringbuf!(Trace, 8, Trace::None);
 
+/// Size of the buffer through which [`read_task_memory`] copies task memory
+#[cfg(feature = "read-task-memory")]
+const READ_CHUNK_SIZE: usize = 256;
+
 pub fn initialize_dump_areas() -> u32 {
     let areas = humpty::initialize_dump_areas(
         &crate::generated::DUMP_AREAS,
@@ -253,29 +274,90 @@ pub fn dump_task(base: u32, task: usize) -> Result<u8, DumpAgentError> {
             Some(region) if !in_dump_area(region.base, region.size) => {
                 ringbuf_entry!(Trace::DumpRegion(region));
 
-                // SAFETY: we have configured memory so that humpty
-                // should only read headers which are properly initialized and
-                // readable by this task, and should only write memory which is
-                // writeable by this task (i.e. the dump areas).
-                if let Err(e) = humpty::add_dump_segment_header(
-                    area.region.address,
+                //
+                // If we're compressing, the regions are appended after
+                // `humpty` has written the task and its registers (below);
+                // otherwise, we have `humpty` dump them uncompressed.
+                //
+                #[cfg(not(feature = "dump-compress"))]
+                add_dump_segment_header(&area, region.base, region.size)?;
+            }
+            Some(_) => {}
+        }
+    }
+
+    dump_task_run(area.region.address, task)?;
+
+    #[cfg(feature = "dump-compress")]
+    for ndx in 0.. {
+        match kipc::get_task_dump_region(task, ndx) {
+            None => break,
+            Some(region) if !in_dump_area(region.base, region.size) => {
+                compress::add_compressed_segment(
+                    &area,
+                    task,
                     region.base,
                     region.size,
-                    |addr, buf, _| unsafe { humpty::from_mem(addr, buf) },
-                    |addr, buf| unsafe { humpty::to_mem(addr, buf) },
-                ) {
-                    ringbuf_entry!(Trace::DumpRegionsFailed(e));
-                    return Err(DumpAgentError::BadSegmentAdd);
-                }
+                )?;
             }
             Some(_) => {}
         }
     }
 
-    dump_task_run(area.region.address, task)?;
     Ok(area.index)
 }
 
+/// Adds a header for an uncompressed segment, to be filled in by `humpty`
+#[cfg(not(feature = "dump-compress"))]
+fn add_dump_segment_header(
+    area: &DumpArea,
+    start: u32,
+    length: u32,
+) -> Result<(), DumpAgentError> {
+    // SAFETY: we have configured memory so that humpty should only read
+    // headers which are properly initialized and readable by this task, and
+    // should only write memory which is writeable by this task (i.e. the
+    // segment header region within dump areas).
+    if let Err(e) = humpty::add_dump_segment_header(
+        area.region.address,
+        start,
+        length,
+        |addr, buf, _| unsafe { humpty::from_mem(addr, buf) },
+        |addr, buf| unsafe { humpty::to_mem(addr, buf) },
+    ) {
+        ringbuf_entry!(Trace::DumpRegionsFailed(e));
+        return Err(DumpAgentError::BadSegmentAdd);
+    }
+
+    Ok(())
+}
+
+/// Checks that the given memory lies entirely within one of the task's
+/// dumpable regions (and outside of any dump area).
+fn in_task_region(task: usize, start: u32, length: u32) -> bool {
+    let mem = match start.checked_add(length) {
+        Some(end) => start..end,
+        None => return false,
+    };
+
+    for ndx in 0.. {
+        // This is Accidentally Quadratic; see the note in `dump_task`
+        match kipc::get_task_dump_region(task, ndx) {
+            None => break,
+            Some(region) if !in_dump_area(region.base, region.size) => {
+                ringbuf_entry!(Trace::DumpRegion(region));
+                let region = region.base..region.base + region.size;
+                if mem.start >= region.start && mem.end <= region.end {
+                    return true;
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    false
+}
+
 /// Dumps a specific region from the given task
 pub fn dump_task_region(
     base: u32,
@@ -301,49 +383,175 @@ pub fn dump_task_region(
     let area = dump_task_setup(base, DumpTaskContents::TaskRegion)?;
 
     // We don't trust the caller; it may request to dump a region that isn't
-    // owned by this particular task!  To check this, we iterate over all of the
-    // valid dump regions and confirm that our desired region is within one of
-    // them.
-    let mem = start..start + length;
-    let mut okay = false;
+    // owned by this particular task!
+    if !in_task_region(task, start, length) {
+        return Err(DumpAgentError::BadSegmentAdd);
+    }
 
-    for ndx in 0.. {
-        // This is Accidentally Quadratic; see the note in `dump_task`
-        match kipc::get_task_dump_region(task, ndx) {
-            None => break,
-            Some(region) if !in_dump_area(region.base, region.size) => {
-                ringbuf_entry!(Trace::DumpRegion(region));
-                let region = region.base..region.base + region.size;
-                if mem.start >= region.start && mem.end <= region.end {
-                    okay = true;
-                    break;
+    #[cfg(not(feature = "dump-compress"))]
+    add_dump_segment_header(&area, start, length)?;
+
+    dump_task_run(area.region.address, task)?;
+
+    #[cfg(feature = "dump-compress")]
+    compress::add_compressed_segment(&area, task, start, length)?;
+
+    Ok(area.index)
+}
+
+/// Reads memory from one of the given task's dumpable regions into `data`,
+/// without involving a dump area.  This is how the ringbuf agent reads other
+/// tasks' ring buffers.
+#[cfg(feature = "read-task-memory")]
+pub fn read_task_memory(
+    task: usize,
+    address: u32,
+    data: Leased<W, [u8]>,
+) -> Result<(), RequestError<DumpAgentError>> {
+    let length = data.len() as u32;
+    ringbuf_entry!(Trace::ReadingTaskMemory {
+        task,
+        address,
+        length
+    });
+
+    if !in_task_region(task, address, length) {
+        return Err(DumpAgentError::BadOffset.into());
+    }
+
+    let mut buf = [0u8; READ_CHUNK_SIZE];
+    let mut pos = 0;
+
+    while pos < length {
+        let amount = (length - pos).min(READ_CHUNK_SIZE as u32) as usize;
+
+        kipc::read_task_dump_region(
+            task,
+            TaskDumpRegion {
+                base: address + pos,
+                size: amount as u32,
+            },
+            &mut buf[..amount],
+        );
+
+        data.write_range(pos as usize..pos as usize + amount, &buf[..amount])
+            .map_err(|()| RequestError::went_away())?;
+
+        pos += amount as u32;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "dump-compress")]
+mod compress {
+    use super::Trace;
+    use dump_agent_api::{DumpSegmentGnarle, DUMP_SEGMENT_GNARLE};
+    use humpty::{DumpArea, DumpAreaHeader};
+    use ringbuf::*;
+    use task_jefe_api::DumpAgentError;
+    use userlib::*;
+    use zerocopy::AsBytes;
+
+    /// Size of the buffer through which we read task memory.  This bounds
+    /// our stack usage; the time taken is linear in the size of the region.
+    const CHUNK_SIZE: usize = 256;
+
+    ///
+    /// Appends a segment containing the `gnarle`-compressed contents of the
+    /// given region of task memory to the (already dumped) dump area.  If
+    /// the compressed segment doesn't fit, the dump area is left as it was
+    /// before the call.
+    ///
+    pub fn add_compressed_segment(
+        area: &DumpArea,
+        task: usize,
+        base: u32,
+        size: u32,
+    ) -> Result<(), DumpAgentError> {
+        let header = area.region.address as *mut DumpAreaHeader;
+
+        // SAFETY: the dump area starts with a header that `humpty` has
+        // initialized, and that we are allowed to read and write.
+        let written =
+            unsafe { core::ptr::addr_of!((*header).written).read_volatile() };
+
+        let limit = area.region.address + area.region.length;
+        let segment = area.region.address + ((written + 3) & !3);
+        let start = segment + core::mem::size_of::<DumpSegmentGnarle>() as u32;
+
+        if start > limit {
+            ringbuf_entry!(Trace::CompressOverflow { base });
+            return Err(DumpAgentError::DumpFailed);
+        }
+
+        let mut out = start;
+        let mut buf = [0u8; CHUNK_SIZE];
+        let mut pos = 0;
+
+        while pos < size {
+            let amount = (size - pos).min(CHUNK_SIZE as u32) as usize;
+
+            kipc::read_task_dump_region(
+                task,
+                TaskDumpRegion {
+                    base: base + pos,
+                    size: amount as u32,
+                },
+                &mut buf[..amount],
+            );
+
+            let r = gnarle::compress(&buf[..amount], |bytes| {
+                if out + bytes.len() as u32 > limit {
+                    return Err(());
                 }
+
+                // SAFETY: we have checked that this write is within our dump
+                // area, and past any data that has already been written.
+                unsafe { humpty::to_mem(out, bytes) }.map_err(|_| ())?;
+                out += bytes.len() as u32;
+                Ok(())
+            });
+
+            if r.is_err() {
+                ringbuf_entry!(Trace::CompressOverflow { base });
+                return Err(DumpAgentError::DumpFailed);
             }
-            Some(_) => {}
+
+            pos += amount as u32;
         }
-    }
 
-    if !okay {
-        return Err(DumpAgentError::BadSegmentAdd);
-    }
+        let seg = DumpSegmentGnarle {
+            magic: DUMP_SEGMENT_GNARLE,
+            address: base,
+            compressed_length: out - start,
+            uncompressed_length: size,
+        };
 
-    // SAFETY: we have configured memory so that humpty should only read
-    // headers which are properly initialized and readable by this task, and
-    // should only write memory which is writeable by this task (i.e. the
-    // segment header region within dump areas).
-    if let Err(e) = humpty::add_dump_segment_header(
-        area.region.address,
-        start,
-        length,
-        |addr, buf, _| unsafe { humpty::from_mem(addr, buf) },
-        |addr, buf| unsafe { humpty::to_mem(addr, buf) },
-    ) {
-        ringbuf_entry!(Trace::DumpRegionsFailed(e));
-        return Err(DumpAgentError::BadSegmentAdd);
-    }
+        ringbuf_entry!(Trace::Compressed {
+            base,
+            uncompressed: size,
+            compressed: seg.compressed_length,
+        });
 
-    dump_task_run(area.region.address, task)?;
-    Ok(area.index)
+        // SAFETY: as above, the segment header lies within our dump area.
+        unsafe { humpty::to_mem(segment, seg.as_bytes()) }
+            .map_err(|_| DumpAgentError::DumpFailed)?;
+
+        //
+        // Only now that the segment is complete do we account for it in the
+        // header, so that a failure above leaves no trace of it.
+        //
+        let end = ((out + 3) & !3).min(limit);
+
+        // SAFETY: as above, we're allowed to write the dump area header.
+        unsafe {
+            core::ptr::addr_of_mut!((*header).written)
+                .write_volatile(end - area.region.address);
+        }
+
+        Ok(())
+    }
 }
 
 pub fn reinitialize_dump_from(
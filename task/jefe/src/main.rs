@@ -35,10 +35,18 @@ use core::convert::Infallible;
 
 use hubris_num_tasks::NUM_TASKS;
 use humpty::DumpArea;
-use idol_runtime::RequestError;
-use task_jefe_api::{DumpAgentError, ResetReason};
+use idol_runtime::{Leased, RequestError, W};
+use task_jefe_api::{
+    DumpAgentError, KernelPanicError, KernelPanicInfo, ResetReason,
+};
 use userlib::*;
 
+/// What the kernel recorded about a panic that caused the last reset
+struct KernelPanic {
+    info: KernelPanicInfo,
+    epitaph: [u8; abi::KERNEL_EPITAPH_LEN],
+}
+
 fn log_fault(t: usize, fault: &abi::FaultInfo) {
     match fault {
         abi::FaultInfo::MemoryAccess { address, .. } => match address {
@@ -132,11 +140,32 @@ fn main() -> ! {
 
     external::set_ready();
 
+    let mut epitaph = [0; abi::KERNEL_EPITAPH_LEN];
+    let kernel_panic =
+        kipc::read_kernel_panic(&mut epitaph).map(|(regs, len)| KernelPanic {
+            info: KernelPanicInfo {
+                msp: regs.msp,
+                psp: regs.psp,
+                ipsr: regs.ipsr,
+                cfsr: regs.cfsr,
+                hfsr: regs.hfsr,
+                epitaph_len: len as u32,
+            },
+            epitaph,
+        });
+    let reset_reason = if kernel_panic.is_some() {
+        sys_log!("last reset was due to a kernel panic");
+        ResetReason::KernelPanic
+    } else {
+        ResetReason::Unknown
+    };
+
     let mut server = ServerImpl {
         state: 0,
         deadline,
         task_states: &mut task_states,
-        reset_reason: ResetReason::Unknown,
+        reset_reason,
+        kernel_panic,
         #[cfg(feature = "dump")]
         dump_areas: dump::initialize_dump_areas(),
     };
@@ -152,6 +181,7 @@ struct ServerImpl<'s> {
     task_states: &'s mut [TaskStatus; NUM_TASKS],
     deadline: u64,
     reset_reason: ResetReason,
+    kernel_panic: Option<KernelPanic>,
     #[cfg(feature = "dump")]
     dump_areas: u32,
 }
@@ -178,10 +208,31 @@ impl idl::InOrderJefeImpl for ServerImpl<'_> {
         _msg: &userlib::RecvMessage,
         reason: ResetReason,
     ) -> Result<(), RequestError<Infallible>> {
-        self.reset_reason = reason;
+        // The reset that the kernel does after a panic looks like any other
+        // system reset to whoever reads the hardware's reset status; don't
+        // let them hide the panic.
+        if self.reset_reason != ResetReason::KernelPanic {
+            self.reset_reason = reason;
+        }
         Ok(())
     }
 
+    fn read_kernel_panic(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        epitaph: Leased<W, [u8]>,
+    ) -> Result<KernelPanicInfo, RequestError<KernelPanicError>> {
+        let panic = self
+            .kernel_panic
+            .as_ref()
+            .ok_or(KernelPanicError::NoKernelPanic)?;
+        let len = (panic.info.epitaph_len as usize).min(epitaph.len());
+        epitaph
+            .write_range(0..len, &panic.epitaph[..len])
+            .map_err(|()| RequestError::went_away())?;
+        Ok(panic.info)
+    }
+
     fn get_state(
         &mut self,
         _msg: &userlib::RecvMessage,
@@ -289,6 +340,33 @@ impl idl::InOrderJefeImpl for ServerImpl<'_> {
                 dump::reinitialize_dump_from(self.dump_areas, index)
                     .map_err(|e| e.into())
             }
+
+            #[cfg(feature = "read-task-memory")]
+            fn read_task_memory(
+                &mut self,
+                _msg: &userlib::RecvMessage,
+                task_index: u32,
+                address: u32,
+                data: Leased<W, [u8]>,
+            ) -> Result<(), RequestError<DumpAgentError>> {
+                if task_index == 0 {
+                    return Err(DumpAgentError::NotSupported.into());
+                } else if task_index as usize >= self.task_states.len() {
+                    return Err(DumpAgentError::BadOffset.into());
+                }
+                dump::read_task_memory(task_index as usize, address, data)
+            }
+
+            #[cfg(not(feature = "read-task-memory"))]
+            fn read_task_memory(
+                &mut self,
+                _msg: &userlib::RecvMessage,
+                _task_index: u32,
+                _address: u32,
+                _data: Leased<W, [u8]>,
+            ) -> Result<(), RequestError<DumpAgentError>> {
+                Err(DumpAgentError::NotSupported.into())
+            }
         } else {
             fn get_dump_area(
                 &mut self,
@@ -337,6 +415,16 @@ impl idl::InOrderJefeImpl for ServerImpl<'_> {
             ) -> Result<(), RequestError<DumpAgentError>> {
                 Err(DumpAgentError::DumpAgentUnsupported.into())
             }
+
+            fn read_task_memory(
+                &mut self,
+                _msg: &userlib::RecvMessage,
+                _task_index: u32,
+                _address: u32,
+                _data: Leased<W, [u8]>,
+            ) -> Result<(), RequestError<DumpAgentError>> {
+                Err(DumpAgentError::DumpAgentUnsupported.into())
+            }
         }
     }
 }
@@ -423,6 +511,8 @@ include!(concat!(env!("OUT_DIR"), "/notifications.rs"));
 
 // And the Idol bits
 mod idl {
-    use task_jefe_api::{DumpAgentError, ResetReason};
+    use task_jefe_api::{
+        DumpAgentError, KernelPanicError, KernelPanicInfo, ResetReason,
+    };
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
@@ -0,0 +1,161 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Log forwarder
+//!
+//! Streams the logger's records over the management network.  Any packet
+//! sent to our socket makes its sender the destination for records; if the
+//! packet holds a little-endian `u32`, we (re)start from that record index
+//! (so sending 0 replays everything that the logger still has).  We're
+//! notified by the logger whenever a record is logged, and send each in its
+//! own packet:
+//!
+//! | Offset | Size | Field                                   |
+//! |--------|------|-----------------------------------------|
+//! | 0      | 8    | Image ID                                |
+//! | 8      | 4    | Record index                            |
+//! | 12     | 2    | Index of the task that logged it        |
+//! | 14     | 8    | Timestamp                               |
+//! | 22     | 1    | Record length                           |
+//! | 23     | ...  | Record, as encoded by `log-encoding`    |
+//!
+//! All fields are little-endian.  `cargo xtask logs` decodes the stream.
+//! We must be subscribed to the logger:
+//!
+//! ```toml
+//! [tasks.logger.config]
+//! subscribers = [{ name = "log_forwarder", notification = "log" }]
+//! ```
+
+#![no_std]
+#![no_main]
+
+use ringbuf::*;
+use task_logger_api::{Logger, LoggerError, MAX_RECORD_SIZE};
+use task_net_api::*;
+use userlib::*;
+
+task_slot!(LOGGER, logger);
+task_slot!(NET, net);
+
+#[derive(Copy, Clone, PartialEq)]
+enum Trace {
+    None,
+    Peer(UdpMetadata),
+    Skipped { from: u32, to: u32 },
+    ReadFailed(LoggerError),
+    SendError(SendError),
+}
+
+ringbuf!(Trace, 16, Trace::None);
+
+const SOCKET: SocketName = SocketName::log_forwarder;
+
+const SOCKET_TX_SIZE: usize = task_net_api::SOCKET_TX_SIZE[SOCKET as usize];
+const SOCKET_RX_SIZE: usize = task_net_api::SOCKET_RX_SIZE[SOCKET as usize];
+
+/// Size of the header that precedes each record; see the module docs.
+const HEADER_SIZE: usize = 23;
+
+static_assertions::const_assert!(
+    HEADER_SIZE + MAX_RECORD_SIZE <= SOCKET_TX_SIZE
+);
+
+#[export_name = "main"]
+fn main() -> ! {
+    let logger = Logger::from(LOGGER.get_task_id());
+    let net = Net::from(NET.get_task_id());
+
+    let (rx_data_buf, tx_data_buf) = mutable_statics::mutable_statics! {
+        static mut RX_BUF: [u8; SOCKET_RX_SIZE] = [|| 0u8; _];
+        static mut TX_BUF: [u8; SOCKET_TX_SIZE] = [|| 0u8; _];
+    };
+
+    let image_id = kipc::read_image_id().to_le_bytes();
+    let mut peer: Option<UdpMetadata> = None;
+    let mut next = 0;
+
+    loop {
+        // Take a new peer (and starting index) from any incoming packets.
+        loop {
+            match net.recv_packet(
+                SOCKET,
+                LargePayloadBehavior::Discard,
+                rx_data_buf,
+            ) {
+                Ok(meta) => {
+                    ringbuf_entry!(Trace::Peer(meta));
+                    peer = Some(meta);
+                    if let Ok(index) = rx_data_buf[..meta.size as usize]
+                        .try_into()
+                        .map(u32::from_le_bytes)
+                    {
+                        next = index;
+                    }
+                }
+                Err(RecvError::QueueEmpty) => break,
+                Err(RecvError::ServerRestarted) => {
+                    // `net` restarted (probably due to the watchdog); just
+                    // retry.
+                }
+                Err(RecvError::NotYours | RecvError::Other) => panic!(),
+            }
+        }
+
+        // Send whatever records we haven't yet, until we run out or the
+        // outgoing queue fills up; in the latter case, we'll pick up where we
+        // left off on the next notification.
+        if let Some(mut meta) = peer {
+            loop {
+                let tx = &mut tx_data_buf[..];
+                let header =
+                    match logger.read_record(next, &mut tx[HEADER_SIZE..]) {
+                        Ok(header) => header,
+                        Err(LoggerError::NoRecord) => break,
+                        Err(e) => {
+                            ringbuf_entry!(Trace::ReadFailed(e));
+                            break;
+                        }
+                    };
+                if header.index != next {
+                    ringbuf_entry!(Trace::Skipped {
+                        from: next,
+                        to: header.index
+                    });
+                }
+
+                tx[0..8].copy_from_slice(&image_id);
+                tx[8..12].copy_from_slice(&header.index.to_le_bytes());
+                tx[12..14].copy_from_slice(&header.task.to_le_bytes());
+                tx[14..22].copy_from_slice(&header.timestamp.to_le_bytes());
+                tx[22] = header.len;
+
+                let len = HEADER_SIZE + header.len as usize;
+                meta.size = len as u32;
+                match net.send_packet(SOCKET, meta, &tx[..len]) {
+                    Ok(()) => next = header.index + 1,
+                    Err(e) => {
+                        ringbuf_entry!(Trace::SendError(e));
+                        match e {
+                            SendError::QueueFull
+                            | SendError::ServerRestarted => break,
+                            SendError::Other
+                            | SendError::NotYours
+                            | SendError::InvalidVLan => panic!(),
+                        }
+                    }
+                }
+            }
+        }
+
+        sys_recv_closed(
+            &mut [],
+            notifications::SOCKET_MASK | notifications::LOG_MASK,
+            TaskId::KERNEL,
+        )
+        .unwrap();
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/notifications.rs"));
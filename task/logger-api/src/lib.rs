@@ -0,0 +1,45 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Client API for the logger task.
+
+#![no_std]
+
+use derive_idol_err::IdolError;
+use hubpack::SerializedSize;
+use serde::{Deserialize, Serialize};
+use userlib::*;
+
+/// Largest record that the logger accepts
+pub const MAX_RECORD_SIZE: usize = 64;
+
+#[derive(Copy, Clone, Debug, FromPrimitive, Eq, PartialEq, IdolError)]
+pub enum LoggerError {
+    /// There's no record at or after the requested index (yet)
+    NoRecord = 1,
+    /// The lease is too small for the record
+    BadLease,
+
+    #[idol(server_death)]
+    ServerRestarted,
+}
+
+/// Describes a record returned by `read_record`; the record itself (as
+/// encoded by `log-encoding`) is written to the lease.
+#[derive(
+    Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, SerializedSize,
+)]
+pub struct LogRecordHeader {
+    /// Sequence number of this record.  A gap between this and the index that
+    /// was asked for means that records were overwritten before being read.
+    pub index: u32,
+    /// Index of the task that logged the record
+    pub task: u16,
+    /// Kernel timestamp when the record was logged
+    pub timestamp: u64,
+    /// Length of the record
+    pub len: u8,
+}
+
+include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
@@ -0,0 +1,27 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// Tasks to notify whenever a new record is logged.
+    #[serde(default)]
+    subscribers: Vec<build_util::Subscriber>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    build_util::build_notifications()?;
+    idol::server::build_server_support(
+        "../../idl/logger.idol",
+        "server_stub.rs",
+        idol::server::ServerStyle::InOrder,
+    )?;
+
+    let config = build_util::task_maybe_config::<Config>()?.unwrap_or_default();
+    build_util::build_subscribers(&config.subscribers)?;
+
+    Ok(())
+}
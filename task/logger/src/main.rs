@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Logger task
+//!
+//! Receives the records sent by `sys_log!` in tasks built with userlib's
+//! `log-task` feature, and keeps the most recent of them in a ring, stamped
+//! with the sending task and the time.  Records can be read back over IPC
+//! (by a forwarding task, for instance), and tasks listed as `subscribers` in
+//! our config are notified whenever a new one arrives:
+//!
+//! ```toml
+//! [tasks.logger.config]
+//! subscribers = [{ name = "log_forwarder", notification = "log" }]
+//! ```
+//!
+//! Records are stored as they were encoded; they're decoded and formatted on
+//! the host, using the format strings in the logging task's ELF.
+//!
+//! Because logging tasks must not block on us, we never send to another
+//! task, and must be higher priority than every task that logs (which `xtask
+//! dist` enforces, given their `logger` task slot).
+
+#![no_std]
+#![no_main]
+
+use core::convert::Infallible;
+use idol_runtime::{Leased, RequestError, R, W};
+use task_logger_api::{LogRecordHeader, LoggerError, MAX_RECORD_SIZE};
+use userlib::*;
+
+/// Number of records that we retain
+const RING_SIZE: usize = 32;
+
+#[derive(Copy, Clone)]
+struct Record {
+    task: u16,
+    timestamp: u64,
+    len: u8,
+    data: [u8; MAX_RECORD_SIZE],
+}
+
+struct ServerImpl {
+    ring: &'static mut [Record; RING_SIZE],
+    /// Index of the next record to be logged; records before this (up to
+    /// `RING_SIZE` of them) are in the ring.
+    next: u32,
+}
+
+impl ServerImpl {
+    /// Index of the oldest record that's still in the ring
+    fn oldest(&self) -> u32 {
+        self.next.saturating_sub(RING_SIZE as u32)
+    }
+
+    /// Let any subscribers know that there's a new record.
+    fn notify_subscribers(&self) {
+        for (task, mask) in SUBSCRIBERS {
+            sys_post(sys_refresh_task_id(task), mask);
+        }
+    }
+}
+
+impl idl::InOrderLoggerImpl for ServerImpl {
+    fn log(
+        &mut self,
+        msg: &userlib::RecvMessage,
+        record: Leased<R, [u8]>,
+    ) -> Result<(), RequestError<Infallible>> {
+        let slot = &mut self.ring[self.next as usize % RING_SIZE];
+        let len = record.len();
+        record
+            .read_range(0..len, &mut slot.data[..len])
+            .map_err(|()| RequestError::went_away())?;
+        slot.task = msg.sender.index() as u16;
+        slot.timestamp = sys_get_timer().now;
+        slot.len = len as u8;
+
+        self.next += 1;
+        self.notify_subscribers();
+        Ok(())
+    }
+
+    fn read_record(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        index: u32,
+        data: Leased<W, [u8]>,
+    ) -> Result<LogRecordHeader, RequestError<LoggerError>> {
+        let index = index.max(self.oldest());
+        if index >= self.next {
+            return Err(LoggerError::NoRecord.into());
+        }
+
+        let slot = &self.ring[index as usize % RING_SIZE];
+        let len = slot.len as usize;
+        if data.len() < len {
+            return Err(LoggerError::BadLease.into());
+        }
+        data.write_range(0..len, &slot.data[..len])
+            .map_err(|()| RequestError::went_away())?;
+
+        Ok(LogRecordHeader {
+            index,
+            task: slot.task,
+            timestamp: slot.timestamp,
+            len: slot.len,
+        })
+    }
+}
+
+#[export_name = "main"]
+fn main() -> ! {
+    let mut buffer = [0; idl::INCOMING_SIZE];
+    let ring = mutable_statics::mutable_statics! {
+        static mut RING: [Record; RING_SIZE] = [|| Record {
+            task: 0,
+            timestamp: 0,
+            len: 0,
+            data: [0; MAX_RECORD_SIZE],
+        }; _];
+    };
+    let mut server = ServerImpl { ring, next: 0 };
+
+    loop {
+        idol_runtime::dispatch(&mut buffer, &mut server);
+    }
+}
+
+mod idl {
+    use task_logger_api::{LogRecordHeader, LoggerError};
+
+    include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
+}
+
+include!(concat!(env!("OUT_DIR"), "/notifications.rs"));
+include!(concat!(env!("OUT_DIR"), "/subscribers.rs"));
@@ -8,7 +8,9 @@ use drv_sidecar_seq_api::{SeqError, Sequencer, TofinoSeqState};
 use ringbuf::*;
 use userlib::{hl::sleep_for, task_slot};
 use vsc7448::{
-    config::Speed, miim_phy::Vsc7448MiimPhy, Vsc7448, Vsc7448Rw, VscError,
+    config::{MirrorConfig, Speed, StormPolicers},
+    miim_phy::Vsc7448MiimPhy,
+    Vsc7448, Vsc7448Rw, VscError, PORT_COUNT,
 };
 use vsc7448_pac::{DEVCPU_GCB, HSIO, VAUI0, VAUI1};
 use vsc85xx::{vsc8504::Vsc8504, vsc8562::Vsc8562Phy, PhyRw};
@@ -64,6 +66,12 @@ pub struct Bsp<'a, R> {
 
     /// Time at which the 10G link went down
     link_down_at: Option<u64>,
+
+    /// Port mirroring configuration, which is reapplied on `reinit`
+    mirror: MirrorConfig,
+
+    /// Per-port storm policers, which are reapplied on `reinit`
+    storm_policers: [StormPolicers; PORT_COUNT],
 }
 
 pub const REFCLK_SEL: vsc7448::RefClockFreq =
@@ -164,6 +172,8 @@ impl<'a, R: Vsc7448Rw> Bsp<'a, R> {
             },
             front_io_speed: [Speed::Speed1G; 2],
             link_down_at: None,
+            mirror: MirrorConfig::default(),
+            storm_policers: [StormPolicers::default(); PORT_COUNT],
             seq,
         };
 
@@ -219,6 +229,14 @@ impl<'a, R: Vsc7448Rw> Bsp<'a, R> {
         self.vsc7448.configure_vlan_semistrict()?;
         self.vsc7448_postconfig()?;
 
+        // Restore debug configuration, which isn't touched by `init`
+        self.vsc7448.configure_mirror(&self.mirror)?;
+        for (port, p) in self.storm_policers.iter().enumerate() {
+            if *p != StormPolicers::default() {
+                self.vsc7448.configure_storm_policers(port as u8, p)?;
+            }
+        }
+
         // Some front IO boards have a faulty oscillator driving the PHY,
         // causing its clock to misbehave some fraction of (re-)boots. Init
         // the PHY in a loop, requesting the sequencer to reset as much as
@@ -255,6 +273,37 @@ impl<'a, R: Vsc7448Rw> Bsp<'a, R> {
         Ok(())
     }
 
+    pub fn mirror_config(&self) -> MirrorConfig {
+        self.mirror
+    }
+
+    /// Applies a new port mirroring configuration, saving it so that it
+    /// survives `reinit`
+    pub fn set_mirror_config(
+        &mut self,
+        cfg: MirrorConfig,
+    ) -> Result<(), VscError> {
+        self.vsc7448.configure_mirror(&cfg)?;
+        self.mirror = cfg;
+        Ok(())
+    }
+
+    pub fn storm_policers(&self, port: u8) -> StormPolicers {
+        self.storm_policers[usize::from(port)]
+    }
+
+    /// Applies new storm policers to the given port, saving them so that they
+    /// survive `reinit`
+    pub fn set_storm_policers(
+        &mut self,
+        port: u8,
+        p: StormPolicers,
+    ) -> Result<(), VscError> {
+        self.vsc7448.configure_storm_policers(port, &p)?;
+        self.storm_policers[usize::from(port)] = p;
+        Ok(())
+    }
+
     fn vsc7448_postconfig(&mut self) -> Result<(), VscError> {
         // The SERDES6G going to the front IO board needs to be tuned from
         // its default settings, otherwise the signal quality is bad.
@@ -7,8 +7,9 @@ use crate::{
     notifications,
 };
 use drv_monorail_api::{
-    LinkStatus, MacTableEntry, MonorailError, PacketCount, PhyStatus, PhyType,
-    PortCounters, PortDev, PortStatus, VscError,
+    LinkStatus, MacTableEntry, MirrorConfig, MonorailError, PacketCount,
+    PhyStatus, PhyType, PortCounters, PortDev, PortStatus, StormKind,
+    StormPolicers, VscError,
 };
 use idol_runtime::{NotificationHandler, RequestError};
 use userlib::{sys_get_timer, sys_set_timer};
@@ -781,6 +782,70 @@ impl<'a, R: Vsc7448Rw> idl::InOrderMonorailImpl for ServerImpl<'a, R> {
         Ok(out)
     }
 
+    fn get_mirror_config(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+    ) -> Result<MirrorConfig, RequestError<MonorailError>> {
+        Ok(self.bsp.mirror_config())
+    }
+
+    fn set_mirror_config(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        cfg: MirrorConfig,
+    ) -> Result<(), RequestError<MonorailError>> {
+        if !cfg.is_valid() {
+            return Err(MonorailError::InvalidMirrorConfig.into());
+        }
+        if let Some(dest) = cfg.destination {
+            self.check_port(dest)?;
+        }
+        self.bsp
+            .set_mirror_config(cfg)
+            .map_err(MonorailError::from)
+            .map_err(RequestError::from)
+    }
+
+    fn get_storm_policers(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        port: u8,
+    ) -> Result<StormPolicers, RequestError<MonorailError>> {
+        self.check_port(port)?;
+        Ok(self.bsp.storm_policers(port))
+    }
+
+    fn set_storm_policer(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        port: u8,
+        kind: StormKind,
+        rate: u32,
+    ) -> Result<(), RequestError<MonorailError>> {
+        self.check_port(port)?;
+        let mut p = self.bsp.storm_policers(port);
+        p.set(kind, Some(rate));
+        self.bsp
+            .set_storm_policers(port, p)
+            .map_err(MonorailError::from)
+            .map_err(RequestError::from)
+    }
+
+    fn clear_storm_policer(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        port: u8,
+        kind: StormKind,
+    ) -> Result<(), RequestError<MonorailError>> {
+        self.check_port(port)?;
+        let mut p = self.bsp.storm_policers(port);
+        p.set(kind, None);
+        self.bsp
+            .set_storm_policers(port, p)
+            .map_err(MonorailError::from)
+            .map_err(RequestError::from)
+    }
+
     fn reinit(
         &mut self,
         _msg: &userlib::RecvMessage,
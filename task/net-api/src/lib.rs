@@ -160,6 +160,66 @@ pub enum MgmtError {
     ServerRestarted,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, IdolError)]
+#[repr(u32)]
+pub enum LldpError {
+    /// LLDP is not enabled on this board
+    NotAvailable = 1,
+    /// The given port is not one of our VLANs
+    InvalidPort,
+    /// We haven't heard from a neighbor on this port, or its TTL has expired
+    NoNeighbor,
+
+    #[idol(server_death)]
+    ServerRestarted,
+}
+
+/// Maximum length of an LLDP ID or name that we keep; longer values are
+/// truncated.
+pub const LLDP_STRING_LEN: usize = 32;
+
+#[derive(
+    Copy, Clone, Debug, Default, Serialize, SerializedSize, Deserialize,
+)]
+pub struct LldpString {
+    pub len: u8,
+    pub data: [u8; LLDP_STRING_LEN],
+}
+
+impl LldpString {
+    /// Builds a string from the given bytes, truncating if necessary
+    pub fn new(bytes: &[u8]) -> Self {
+        let len = bytes.len().min(LLDP_STRING_LEN);
+        let mut data = [0; LLDP_STRING_LEN];
+        data[..len].copy_from_slice(&bytes[..len]);
+        Self {
+            len: len as u8,
+            data,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..usize::from(self.len).min(LLDP_STRING_LEN)]
+    }
+}
+
+/// The most recent LLDP advertisement received on a management port
+#[derive(
+    Copy, Clone, Debug, Default, Serialize, SerializedSize, Deserialize,
+)]
+pub struct LldpNeighbor {
+    pub chassis_id_subtype: u8,
+    pub chassis_id: LldpString,
+    pub port_id_subtype: u8,
+    pub port_id: LldpString,
+    /// Empty if the neighbor didn't send a System Name TLV
+    pub system_name: LldpString,
+    /// Time at which the advertisement arrived, in milliseconds since boot
+    pub last_seen: u64,
+    /// Time-to-live given by the neighbor, in seconds
+    pub ttl: u16,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(
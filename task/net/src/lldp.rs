@@ -0,0 +1,283 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! LLDP (IEEE 802.1AB) on the management network
+//!
+//! Each management port is a VLAN from our perspective, so we advertise
+//! ourselves on every VLAN and keep one neighbor per VLAN. Our chassis ID is
+//! the base MAC address, our port ID is the MAC address used on that VLAN,
+//! and our system name is the board identity from VPD.  Packrat may not have
+//! the identity yet when we start, so we keep asking for it each time we
+//! transmit until we get it.
+//!
+//! `smoltcp` would silently discard LLDP frames, so the server pulls them out
+//! of the Rx ring before the IP stack sees them.
+
+use drv_stm32h7_eth as eth;
+use ringbuf::*;
+use smoltcp::wire::EthernetAddress;
+use task_net_api::{LldpError, LldpNeighbor, LldpString};
+use task_packrat_api::{Packrat, VpdIdentity};
+
+use crate::generated::{VLAN_COUNT, VLAN_RANGE};
+use crate::PACKRAT;
+
+pub const LLDP_ETHERTYPE: u16 = 0x88CC;
+
+/// Nearest-bridge group address, which is not forwarded by 802.1D bridges
+const LLDP_MULTICAST: [u8; 6] = [0x01, 0x80, 0xC2, 0x00, 0x00, 0x0E];
+
+/// Interval between our own advertisements, in milliseconds
+pub const TX_INTERVAL: u64 = 30_000;
+
+/// Time-to-live sent in our advertisements, in seconds (the standard's
+/// recommended 4x the transmit interval)
+const TX_TTL: u16 = (TX_INTERVAL * 4 / 1000) as u16;
+
+// TLV types that we send or understand
+const TLV_END: u8 = 0;
+const TLV_CHASSIS_ID: u8 = 1;
+const TLV_PORT_ID: u8 = 2;
+const TLV_TTL: u8 = 3;
+const TLV_SYSTEM_NAME: u8 = 5;
+
+/// Chassis ID subtype for a MAC address
+const CHASSIS_ID_MAC: u8 = 4;
+/// Port ID subtype for a MAC address
+const PORT_ID_MAC: u8 = 3;
+
+/// Minimum Ethernet frame size (excluding FCS)
+const MIN_FRAME_LEN: usize = 60;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Trace {
+    None,
+    NewNeighbor(usize),
+    NeighborGone(usize),
+    BadFrame(usize),
+    TxFull(usize),
+}
+ringbuf!(Trace, 8, Trace::None);
+
+pub(crate) struct Lldp {
+    chassis_mac: EthernetAddress,
+    port_macs: [EthernetAddress; VLAN_COUNT],
+    packrat: Packrat,
+    system_name: LldpString,
+
+    neighbors: [Option<LldpNeighbor>; VLAN_COUNT],
+}
+
+impl Lldp {
+    /// Builds a new LLDP state, given our base MAC address and the address
+    /// used on each VLAN (in order)
+    pub fn new(
+        chassis_mac: EthernetAddress,
+        port_mac_list: &[EthernetAddress],
+    ) -> Self {
+        let mut port_macs = [EthernetAddress([0; 6]); VLAN_COUNT];
+        port_macs.copy_from_slice(port_mac_list);
+
+        let mut lldp = Self {
+            chassis_mac,
+            port_macs,
+            packrat: Packrat::from(PACKRAT.get_task_id()),
+            system_name: LldpString::default(),
+            neighbors: [None; VLAN_COUNT],
+        };
+        lldp.refresh_system_name();
+        lldp
+    }
+
+    /// Fetches our system name from packrat, if we don't already have it
+    fn refresh_system_name(&mut self) {
+        if self.system_name.len == 0 {
+            if let Ok(id) = self.packrat.get_identity() {
+                self.system_name = identity_name(&id);
+            }
+        }
+    }
+
+    /// Consumes any LLDP frames at the front of the Rx ring for the given
+    /// VLAN index, returning `true` if there were any.
+    pub fn receive(
+        &mut self,
+        eth: &eth::Ethernet,
+        index: usize,
+        now: u64,
+    ) -> bool {
+        let vid = VLAN_RANGE.start + index as u16;
+        let mut any = false;
+        while eth.vlan_next_ethertype(vid, VLAN_RANGE) == Some(LLDP_ETHERTYPE) {
+            any = true;
+            match eth.vlan_recv(vid, |frame| parse(frame, now)) {
+                Some(n) if n.ttl == 0 => {
+                    // A TTL of zero means the neighbor is shutting down
+                    ringbuf_entry!(Trace::NeighborGone(index));
+                    self.neighbors[index] = None;
+                }
+                Some(n) => {
+                    if self.neighbors[index].is_none() {
+                        ringbuf_entry!(Trace::NewNeighbor(index));
+                    }
+                    self.neighbors[index] = Some(n);
+                }
+                None => ringbuf_entry!(Trace::BadFrame(index)),
+            }
+        }
+        any
+    }
+
+    /// Sends our advertisement on every VLAN
+    pub fn transmit(&mut self, eth: &eth::Ethernet) {
+        self.refresh_system_name();
+        for (i, port_mac) in self.port_macs.iter().enumerate() {
+            let vid = VLAN_RANGE.start + i as u16;
+            let mut frame = [0u8; 128];
+            let len = self.build_frame(port_mac, &mut frame);
+            if eth
+                .vlan_try_send(len, vid, |buf| {
+                    buf.copy_from_slice(&frame[..len])
+                })
+                .is_none()
+            {
+                ringbuf_entry!(Trace::TxFull(i));
+            }
+        }
+    }
+
+    pub fn neighbor(
+        &mut self,
+        port: u8,
+        now: u64,
+    ) -> Result<LldpNeighbor, LldpError> {
+        let slot = self
+            .neighbors
+            .get_mut(usize::from(port))
+            .ok_or(LldpError::InvalidPort)?;
+        let expired = matches!(
+            slot,
+            Some(n) if now >= n.last_seen + u64::from(n.ttl) * 1000
+        );
+        if expired {
+            *slot = None;
+        }
+        (*slot).ok_or(LldpError::NoNeighbor)
+    }
+
+    /// Writes an LLDPDU into `out`, returning the frame length
+    fn build_frame(&self, port_mac: &EthernetAddress, out: &mut [u8]) -> usize {
+        out[0..6].copy_from_slice(&LLDP_MULTICAST);
+        out[6..12].copy_from_slice(port_mac.as_bytes());
+        out[12..14].copy_from_slice(&LLDP_ETHERTYPE.to_be_bytes());
+
+        let mut w = TlvWriter { out, pos: 14 };
+        w.tlv(
+            TLV_CHASSIS_ID,
+            &[CHASSIS_ID_MAC],
+            self.chassis_mac.as_bytes(),
+        );
+        w.tlv(TLV_PORT_ID, &[PORT_ID_MAC], port_mac.as_bytes());
+        w.tlv(TLV_TTL, &TX_TTL.to_be_bytes(), &[]);
+        if self.system_name.len > 0 {
+            w.tlv(TLV_SYSTEM_NAME, &[], self.system_name.as_bytes());
+        }
+        w.tlv(TLV_END, &[], &[]);
+
+        // The buffer started out zeroed, so short frames are already padded
+        w.pos.max(MIN_FRAME_LEN)
+    }
+}
+
+struct TlvWriter<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl TlvWriter<'_> {
+    fn tlv(&mut self, ty: u8, prefix: &[u8], body: &[u8]) {
+        let len = prefix.len() + body.len();
+        let header = (u16::from(ty) << 9) | len as u16;
+        self.out[self.pos..][..2].copy_from_slice(&header.to_be_bytes());
+        self.out[self.pos + 2..][..prefix.len()].copy_from_slice(prefix);
+        self.out[self.pos + 2 + prefix.len()..][..body.len()]
+            .copy_from_slice(body);
+        self.pos += 2 + len;
+    }
+}
+
+/// Builds our system name from the VPD identity, as `PART:REV:SERIAL`
+fn identity_name(id: &VpdIdentity) -> LldpString {
+    let mut buf = [0u8; task_net_api::LLDP_STRING_LEN];
+    let mut len = 0;
+    let mut push = |bytes: &[u8]| {
+        for &b in bytes.iter().take_while(|b| **b != 0) {
+            if len < buf.len() {
+                buf[len] = b;
+                len += 1;
+            }
+        }
+    };
+    let mut rev = [0u8; 10];
+    push(&id.part_number);
+    push(b":");
+    push(format_u32(id.revision, &mut rev));
+    push(b":");
+    push(&id.serial);
+    LldpString::new(&buf[..len])
+}
+
+fn format_u32(mut v: u32, buf: &mut [u8; 10]) -> &[u8] {
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (v % 10) as u8;
+        v /= 10;
+        if v == 0 {
+            break;
+        }
+    }
+    &buf[i..]
+}
+
+/// Parses an LLDPDU, returning `None` if it's malformed or is missing one of
+/// the mandatory TLVs.
+fn parse(frame: &[u8], now: u64) -> Option<LldpNeighbor> {
+    let mut out = LldpNeighbor {
+        last_seen: now,
+        ..Default::default()
+    };
+    let (mut chassis, mut port, mut ttl) = (false, false, false);
+
+    let mut tlvs = frame.get(14..)?;
+    while tlvs.len() >= 2 {
+        let header = u16::from_be_bytes([tlvs[0], tlvs[1]]);
+        let ty = (header >> 9) as u8;
+        let body = tlvs.get(2..2 + usize::from(header & 0x1FF))?;
+        match ty {
+            TLV_END => break,
+            TLV_CHASSIS_ID => {
+                let (subtype, id) = body.split_first()?;
+                out.chassis_id_subtype = *subtype;
+                out.chassis_id = LldpString::new(id);
+                chassis = true;
+            }
+            TLV_PORT_ID => {
+                let (subtype, id) = body.split_first()?;
+                out.port_id_subtype = *subtype;
+                out.port_id = LldpString::new(id);
+                port = true;
+            }
+            TLV_TTL => {
+                out.ttl = u16::from_be_bytes(body.get(..2)?.try_into().ok()?);
+                ttl = true;
+            }
+            TLV_SYSTEM_NAME => out.system_name = LldpString::new(body),
+            _ => (),
+        }
+        tlvs = &tlvs[2 + body.len()..];
+    }
+
+    (chassis && port && ttl).then_some(out)
+}
@@ -47,11 +47,15 @@ mod server_impl;
 #[cfg(feature = "mgmt")]
 pub(crate) mod mgmt;
 
+#[cfg(feature = "lldp")]
+mod lldp;
+
 mod idl {
     use task_net_api::{
-        KszError, KszMacTableEntry, LargePayloadBehavior, MacAddress,
-        MacAddressBlock, ManagementCounters, ManagementLinkStatus, MgmtError,
-        PhyError, RecvError, SendError, SocketName, UdpMetadata,
+        KszError, KszMacTableEntry, LargePayloadBehavior, LldpError,
+        LldpNeighbor, MacAddress, MacAddressBlock, ManagementCounters,
+        ManagementLinkStatus, MgmtError, PhyError, RecvError, SendError,
+        SocketName, UdpMetadata,
     };
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
@@ -78,7 +82,7 @@ use crate::bsp_support::Bsp;
 task_slot!(SYS, sys);
 task_slot!(JEFE, jefe);
 
-#[cfg(feature = "vpd-mac")]
+#[cfg(any(feature = "vpd-mac", feature = "lldp"))]
 task_slot!(PACKRAT, packrat);
 
 /////////////////////////////////////////////////////////////////////////////
@@ -214,11 +218,13 @@ fn main() -> ! {
     // Turn on our IRQ.
     userlib::sys_irq_control(notifications::ETH_IRQ_MASK, true);
 
-    // We use two timers:
+    // We use two timers (or three, if LLDP is enabled):
     #[derive(Copy, Clone, Enum)]
     enum Timers {
         Wake,
         Watchdog,
+        #[cfg(feature = "lldp")]
+        Lldp,
     }
     let mut multitimer =
         Multitimer::<Timers>::new(notifications::WAKE_TIMER_BIT);
@@ -238,6 +244,14 @@ fn main() -> ! {
     // Start the watchdog timer running.
     multitimer.set_timer(Timers::Watchdog, now + RX_WATCHDOG_INTERVAL, None);
 
+    // Send our first LLDP advertisement right away, then periodically.
+    #[cfg(feature = "lldp")]
+    multitimer.set_timer(
+        Timers::Lldp,
+        now,
+        Some(Repeat::AfterWake(lldp::TX_INTERVAL)),
+    );
+
     // Go!
     loop {
         ITER_COUNT.fetch_add(1, Ordering::Relaxed);
@@ -272,6 +286,11 @@ fn main() -> ! {
                     Timers::Watchdog => {
                         jefe.restart_me();
                     }
+                    #[cfg(feature = "lldp")]
+                    Timers::Lldp => {
+                        server.lldp_transmit();
+                        // timer is set to auto-repeat
+                    }
                 }
             }
             let mut msgbuf = [0u8; idl::INCOMING_SIZE];
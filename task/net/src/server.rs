@@ -13,9 +13,9 @@ use crate::generated::VLAN_RANGE;
 use drv_stm32h7_eth as eth;
 use idol_runtime::{ClientError, RequestError};
 use task_net_api::{
-    KszError, KszMacTableEntry, LargePayloadBehavior, MacAddress,
-    ManagementCounters, ManagementLinkStatus, MgmtError, PhyError, RecvError,
-    SendError, SocketName, UdpMetadata,
+    KszError, KszMacTableEntry, LargePayloadBehavior, LldpError, LldpNeighbor,
+    MacAddress, ManagementCounters, ManagementLinkStatus, MgmtError, PhyError,
+    RecvError, SendError, SocketName, UdpMetadata,
 };
 
 use core::iter::zip;
@@ -239,6 +239,28 @@ where
         let out = bsp.management_counters(eth).map_err(MgmtError::from)?;
         Ok(out)
     }
+
+    ////////////////////////////////////////////////////////////////////////////
+    // LLDP neighbor table
+    #[cfg(not(feature = "lldp"))]
+    fn lldp_neighbor(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        _port: u8,
+    ) -> Result<LldpNeighbor, RequestError<LldpError>> {
+        Err(LldpError::NotAvailable.into())
+    }
+
+    #[cfg(feature = "lldp")]
+    fn lldp_neighbor(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        port: u8,
+    ) -> Result<LldpNeighbor, RequestError<LldpError>> {
+        let now = userlib::sys_get_timer().now;
+        let out = self.lldp.neighbor(port, now)?;
+        Ok(out)
+    }
 }
 
 pub trait DeviceExt: smoltcp::phy::Device {
@@ -265,6 +287,9 @@ where
 
     mac: EthernetAddress,
     spare_macs: MacAddressBlock,
+
+    #[cfg(feature = "lldp")]
+    lldp: crate::lldp::Lldp,
 }
 
 struct VLanState<E>
@@ -359,11 +384,17 @@ where
         assert!(mac_address_block.count.get() as usize >= N);
         let mut mac: [u8; 6] = mac_address_block.base_mac;
 
+        #[cfg(feature = "lldp")]
+        let mut port_macs: Vec<EthernetAddress, N> = Vec::new();
+
         // Each of these is replicated once per VID. Loop over them in lockstep.
         for (i, (sockets, storage)) in zip(sockets.0, storage).enumerate() {
             let mac_addr = EthernetAddress::from_bytes(&mac);
             let ipv6_addr = link_local_iface_addr(mac_addr);
 
+            #[cfg(feature = "lldp")]
+            port_macs.push(mac_addr).unwrap_lite();
+
             // Make some types explicit to try and make this clearer.
             let sockets: [udp::Socket<'_>; SOCKET_COUNT] = sockets;
 
@@ -436,6 +467,11 @@ where
                 count: U16::new(mac_address_block.count.get() - N as u16),
                 stride: mac_address_block.stride,
             },
+            #[cfg(feature = "lldp")]
+            lldp: crate::lldp::Lldp::new(
+                EthernetAddress::from_bytes(&mac_address_block.base_mac),
+                &port_macs,
+            ),
         }
     }
 
@@ -445,12 +481,22 @@ where
         // we really do want to poll all of them.
         let mut ip = false;
         let mut mac_rx = false;
-        for vlan in &mut self.vlan_state {
-            ip |= vlan.iface.poll(
-                instant,
-                &mut vlan.device,
-                &mut vlan.socket_set,
-            );
+        for (_i, vlan) in self.vlan_state.iter_mut().enumerate() {
+            loop {
+                ip |= vlan.iface.poll(
+                    instant,
+                    &mut vlan.device,
+                    &mut vlan.socket_set,
+                );
+                // The device stops handing frames to smoltcp when it reaches
+                // an LLDP frame; consume those ourselves, then keep going.
+                #[cfg(feature = "lldp")]
+                if self.lldp.receive(self.eth, _i, t) {
+                    mac_rx = true;
+                    continue;
+                }
+                break;
+            }
             // Test and clear our receive activity flag.
             mac_rx |= vlan.device.read_and_clear_activity_flag();
             ip |= vlan.check_socket_watchdog();
@@ -459,6 +505,12 @@ where
         crate::Activity { ip, mac_rx }
     }
 
+    /// Sends an LLDP advertisement on each VLAN
+    #[cfg(feature = "lldp")]
+    pub(crate) fn lldp_transmit(&mut self) {
+        self.lldp.transmit(self.eth);
+    }
+
     /// Iterate over sockets, waking any that can do work.
     ///
     /// A task can do work if...
@@ -43,6 +43,14 @@ impl<'a> smoltcp::phy::Device for VLanEthernet<'a> {
         &mut self,
         _timestamp: smoltcp::time::Instant,
     ) -> Option<(Self::RxToken<'a>, Self::TxToken<'a>)> {
+        // LLDP frames are handled by the server rather than smoltcp, so we
+        // refuse to hand them over (see `GenServerImpl::poll`)
+        #[cfg(feature = "lldp")]
+        if self.eth.vlan_next_ethertype(self.vid, VLAN_RANGE)
+            == Some(crate::lldp::LLDP_ETHERTYPE)
+        {
+            return None;
+        }
         if self.eth.vlan_can_recv(self.vid, VLAN_RANGE) && self.eth.can_send() {
             self.mac_rx.set(true);
             Some((
@@ -42,6 +42,8 @@ pub fn main() -> ! {
                         break;
                     }
                     Err(drv_user_leds_api::LedError::NotPresent) => {
+                        #[cfg(feature = "log-task")]
+                        sys_log!("cycled through {} LEDs", current >> 1);
                         current = 0;
                     }
                 };
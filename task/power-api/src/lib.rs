@@ -185,4 +185,47 @@ impl RenesasBlackbox {
     }
 }
 
+/// Number of entries retained by the power task's rail status log; older
+/// entries are overwritten.
+pub const RAIL_STATUS_LOG_SIZE: usize = 16;
+
+pub use pmbus_format::RailFaults;
+
+/// A single entry in the power task's rail status log, recorded whenever a
+/// rail reports a fault or warning that it wasn't reporting at the previous
+/// poll.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, SerializedSize)]
+pub struct RailStatusEvent {
+    /// Time of the event, in milliseconds since the SP booted.
+    pub timestamp: u64,
+    /// Position of this event in the log, counting from 0 at boot.
+    pub index: u32,
+    /// Index of the rail within the power task's `CONTROLLER_CONFIG`
+    pub rail: u32,
+    /// Voltage sensor of the rail, which identifies it to humans
+    pub sensor: SensorId,
+    /// Faults and warnings that appeared since the previous poll, as
+    /// [`RailFaults`] bits
+    pub new: u32,
+    /// All faults and warnings being reported, as [`RailFaults`] bits
+    pub active: u32,
+    /// Raw STATUS_WORD
+    pub status_word: u16,
+    /// Raw STATUS_VOUT, STATUS_IOUT and STATUS_TEMPERATURE, or 0 if they
+    /// weren't read
+    pub status_vout: u8,
+    pub status_iout: u8,
+    pub status_temperature: u8,
+}
+
+impl RailStatusEvent {
+    pub fn new_faults(&self) -> RailFaults {
+        RailFaults::from_bits_truncate(self.new)
+    }
+
+    pub fn active_faults(&self) -> RailFaults {
+        RailFaults::from_bits_truncate(self.active)
+    }
+}
+
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
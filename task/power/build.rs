@@ -2,6 +2,16 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use serde::Deserialize;
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// Tasks to notify whenever a rail reports a new fault or warning.
+    #[serde(default)]
+    subscribers: Vec<build_util::Subscriber>,
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     build_util::expose_target_board();
     build_util::build_notifications()?;
@@ -13,6 +23,10 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     )?;
 
     build_i2c::codegen(build_i2c::Disposition::Sensors)?;
+    build_i2c::codegen_generic_pmbus()?;
+
+    let config = build_util::task_maybe_config::<Config>()?.unwrap_or_default();
+    build_util::build_subscribers(&config.subscribers)?;
 
     Ok(())
 }
@@ -3,13 +3,14 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
-    i2c_config, i2c_config::sensors, DeviceType, Ohms, PowerControllerConfig,
-    PowerState,
+    generic_pmbus, i2c_config, i2c_config::sensors, DeviceType, Ohms,
+    PowerControllerConfig, PowerState,
 };
 
-pub(crate) const CONTROLLER_CONFIG_LEN: usize = 37;
+pub(crate) const CONTROLLER_CONFIG_LEN: usize =
+    37 + generic_pmbus::CONTROLLER_COUNT;
 pub(crate) static CONTROLLER_CONFIG: [PowerControllerConfig;
-    CONTROLLER_CONFIG_LEN] = [
+    CONTROLLER_CONFIG_LEN] = with_generic_pmbus_controllers![
     rail_controller!(IBC, bmr491, v12_sys_a2, A2),
     rail_controller!(Core, raa229618, vdd_vcore, A0),
     rail_controller!(Core, raa229618, vddcr_soc, A0),
@@ -3,13 +3,15 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
+    generic_pmbus,
     i2c_config::{self, sensors},
     DeviceType, Ohms, PowerControllerConfig, PowerState,
 };
 
-pub(crate) const CONTROLLER_CONFIG_LEN: usize = 1;
+pub(crate) const CONTROLLER_CONFIG_LEN: usize =
+    1 + generic_pmbus::CONTROLLER_COUNT;
 pub(crate) static CONTROLLER_CONFIG: [PowerControllerConfig;
-    CONTROLLER_CONFIG_LEN] = [
+    CONTROLLER_CONFIG_LEN] = with_generic_pmbus_controllers![
     // The DC2024 has 10 3mΩ current sense resistors in parallel (5 on each
     // channel), given a total current sense resistance of 300µΩ
     ltc4282_controller!(HotSwapQSFP, v12_out_100a, A2, Ohms(0.003 / 10.0)),
@@ -3,13 +3,15 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
+    generic_pmbus,
     i2c_config::{self, sensors},
     DeviceType, PowerControllerConfig, PowerState,
 };
 
-pub(crate) const CONTROLLER_CONFIG_LEN: usize = 12;
+pub(crate) const CONTROLLER_CONFIG_LEN: usize =
+    12 + generic_pmbus::CONTROLLER_COUNT;
 pub(crate) static CONTROLLER_CONFIG: [PowerControllerConfig;
-    CONTROLLER_CONFIG_LEN] = [
+    CONTROLLER_CONFIG_LEN] = with_generic_pmbus_controllers![
     mwocp68_controller!(PowerShelf, v54_psu0, A2),
     mwocp68_controller!(PowerShelf, v12_psu0, A2),
     mwocp68_controller!(PowerShelf, v54_psu1, A2),
@@ -3,13 +3,15 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
+    generic_pmbus,
     i2c_config::{self, sensors},
     DeviceType, Ohms, PowerControllerConfig, PowerState,
 };
 
-pub(crate) const CONTROLLER_CONFIG_LEN: usize = 16;
+pub(crate) const CONTROLLER_CONFIG_LEN: usize =
+    16 + generic_pmbus::CONTROLLER_COUNT;
 pub(crate) static CONTROLLER_CONFIG: [PowerControllerConfig;
-    CONTROLLER_CONFIG_LEN] = [
+    CONTROLLER_CONFIG_LEN] = with_generic_pmbus_controllers![
     rail_controller!(IBC, bmr491, v12p0_sys, A2),
     adm1272_controller!(Fan, v54_fan0, A2, Ohms(0.001)),
     adm1272_controller!(Fan, v54_fan1, A2, Ohms(0.001)),
@@ -12,6 +12,7 @@
 
 use drv_i2c_devices::adm1272::*;
 use drv_i2c_devices::bmr491::*;
+use drv_i2c_devices::generic_pmbus::{CommandTable, GenericPmbus};
 use drv_i2c_devices::isl68224::*;
 use drv_i2c_devices::ltc4282::*;
 use drv_i2c_devices::max5970::*;
@@ -21,7 +22,8 @@ use drv_i2c_devices::tps546b24a::*;
 use pmbus::Phase;
 use ringbuf::*;
 use task_power_api::{
-    Bmr491Event, PmbusValue, RawPmbusBlock, RenesasBlackbox, MAX_BLOCK_LEN,
+    Bmr491Event, PmbusValue, RailStatusEvent, RawPmbusBlock, RenesasBlackbox,
+    MAX_BLOCK_LEN,
 };
 use task_sensor_api as sensor_api;
 use userlib::units::*;
@@ -57,6 +59,7 @@ task_slot!(I2C, i2c_driver);
 task_slot!(SENSOR, sensor);
 
 include!(concat!(env!("OUT_DIR"), "/i2c_config.rs"));
+include!(concat!(env!("OUT_DIR"), "/generic_pmbus.rs"));
 
 #[allow(dead_code, clippy::upper_case_acronyms)]
 #[derive(Clone, Copy)]
@@ -72,6 +75,12 @@ enum DeviceType {
     HotSwapIO(Ohms),
     HotSwapQSFP(Ohms),
     PowerShelf,
+    /// Driven by the generic PMBus driver, with the given command table (which
+    /// is generated as `generic_pmbus::<DEVICE>_<RAIL>` from the `pmbus` table
+    /// of the device in the app TOML).  Controllers for these rails are
+    /// generated too, and appended to each BSP's `CONTROLLER_CONFIG` by
+    /// `with_generic_pmbus_controllers!`.
+    Pmbus(&'static CommandTable),
 }
 
 struct PowerControllerConfig {
@@ -95,6 +104,7 @@ enum Device {
     Max5970(Max5970),
     Mwocp68(Mwocp68),
     Ltc4282(Ltc4282),
+    GenericPmbus(GenericPmbus),
 }
 
 impl Device {
@@ -105,6 +115,7 @@ impl Device {
             Device::Isl68224(dev) => dev.read_temperature()?,
             Device::Tps546B24A(dev) => dev.read_temperature()?,
             Device::Adm1272(dev) => dev.read_temperature()?,
+            Device::GenericPmbus(dev) => dev.read_temperature()?,
             Device::Mwocp68(..) => {
                 // The MWOCP68 actually has three temperature sensors, but they
                 // aren't associated with power rails, so we don't read them
@@ -128,6 +139,7 @@ impl Device {
             Device::Max5970(dev) => dev.read_iout()?,
             Device::Mwocp68(dev) => dev.read_iout()?,
             Device::Ltc4282(dev) => dev.read_iout()?,
+            Device::GenericPmbus(dev) => dev.read_iout()?,
         };
         Ok(r)
     }
@@ -142,6 +154,7 @@ impl Device {
             Device::Max5970(dev) => dev.read_vout()?,
             Device::Mwocp68(dev) => dev.read_vout()?,
             Device::Ltc4282(dev) => dev.read_vout()?,
+            Device::GenericPmbus(dev) => dev.read_vout()?,
         };
         Ok(r)
     }
@@ -160,6 +173,7 @@ impl Device {
     fn read_vin(&self) -> Result<Volts, ResponseCode> {
         let r = match &self {
             Device::Mwocp68(dev) => dev.read_vin()?,
+            Device::GenericPmbus(dev) => dev.read_vin()?,
             // Do any other devices have VIN? For now we only added support to
             // MWOCP68
             _ => return Err(ResponseCode::NoDevice),
@@ -170,6 +184,7 @@ impl Device {
     fn read_iin(&self) -> Result<Amperes, ResponseCode> {
         let r = match &self {
             Device::Mwocp68(dev) => dev.read_iin()?,
+            Device::GenericPmbus(dev) => dev.read_iin()?,
             // Do any other devices have IIN? For now we only added support to
             // MWOCP68
             _ => return Err(ResponseCode::NoDevice),
@@ -189,7 +204,8 @@ impl Device {
             | Device::Tps546B24A(_)
             | Device::Adm1272(_)
             | Device::Ltc4282(_)
-            | Device::Max5970(_) => {
+            | Device::Max5970(_)
+            | Device::GenericPmbus(_) => {
                 return Err(ResponseCode::OperationNotSupported)
             }
         };
@@ -203,6 +219,7 @@ impl Device {
             Device::Raa229618(dev) => dev.read_mode()?,
             Device::Isl68224(dev) => dev.read_mode()?,
             Device::Tps546B24A(dev) => dev.read_mode()?,
+            Device::GenericPmbus(dev) => dev.read_mode()?,
             Device::Adm1272(..) | Device::Ltc4282(..) | Device::Max5970(..) => {
                 return Err(ResponseCode::OperationNotSupported)
             }
@@ -220,6 +237,7 @@ impl Device {
             Device::Adm1272(dev) => dev.i2c_device(),
             Device::Ltc4282(dev) => dev.i2c_device(),
             Device::Max5970(dev) => dev.i2c_device(),
+            Device::GenericPmbus(dev) => dev.i2c_device(),
         }
     }
 }
@@ -246,6 +264,9 @@ impl PowerControllerConfig {
             DeviceType::HotSwapQSFP(sense) => {
                 Device::Ltc4282(Ltc4282::new(&dev, *sense))
             }
+            DeviceType::Pmbus(table) => {
+                Device::GenericPmbus(GenericPmbus::new(&dev, table))
+            }
         }
     }
 }
@@ -400,6 +421,8 @@ macro_rules! mwocp68_controller {
 #[cfg_attr(target_board = "gimletlet-2", path = "bsp/gimletlet_2.rs")]
 mod bsp;
 
+mod status;
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[export_name = "main"]
@@ -412,6 +435,7 @@ fn main() -> ! {
         i2c_task,
         sensor: sensor_api::Sensor::from(SENSOR.get_task_id()),
         devices: claim_devices(i2c_task),
+        status: status::RailStatus::claim(),
     };
     let mut buffer = [0; idl::INCOMING_SIZE];
 
@@ -428,17 +452,22 @@ struct ServerImpl {
     i2c_task: TaskId,
     sensor: sensor_api::Sensor,
     devices: &'static mut [Device; bsp::CONTROLLER_CONFIG_LEN],
+    status: status::RailStatus,
 }
 
 impl ServerImpl {
     fn handle_timer_fired(&mut self) {
         let state = bsp::get_state();
         let sensor = &self.sensor;
+        let mut new_faults = false;
 
-        for (c, dev) in
-            bsp::CONTROLLER_CONFIG.iter().zip(self.devices.iter_mut())
+        for (i, (c, dev)) in bsp::CONTROLLER_CONFIG
+            .iter()
+            .zip(self.devices.iter_mut())
+            .enumerate()
         {
             if c.state == PowerState::A0 && state != PowerState::A0 {
+                self.status.clear(i);
                 let now = sys_get_timer().now;
                 sensor.nodata(c.voltage, NoData::DeviceOff, now).unwrap();
                 sensor.nodata(c.current, NoData::DeviceOff, now).unwrap();
@@ -500,6 +529,12 @@ impl ServerImpl {
                     }
                 }
             }
+
+            new_faults |= self.status.poll(i, c, self.i2c_task);
+        }
+
+        if new_faults {
+            self.status.notify_subscribers();
         }
     }
 
@@ -812,6 +847,34 @@ impl idl::InOrderPowerImpl for ServerImpl {
         Err(ResponseCode::BadArg.into())
     }
 
+    fn rail_status_event_count(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+    ) -> Result<u32, idol_runtime::RequestError<core::convert::Infallible>>
+    {
+        Ok(self.status.count())
+    }
+
+    fn read_rail_status_event(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        index: u32,
+    ) -> Result<RailStatusEvent, idol_runtime::RequestError<ResponseCode>> {
+        Ok(self.status.get(index).ok_or(ResponseCode::BadArg)?)
+    }
+
+    fn rail_faults(
+        &mut self,
+        _msg: &userlib::RecvMessage,
+        index: u32,
+    ) -> Result<u32, idol_runtime::RequestError<ResponseCode>> {
+        let faults = self
+            .status
+            .active(index as usize)
+            .ok_or(ResponseCode::NoDevice)?;
+        Ok(faults.bits())
+    }
+
     fn bmr491_event_log_read(
         &mut self,
         _msg: &userlib::RecvMessage,
@@ -0,0 +1,240 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! PMBus status monitoring
+//!
+//! At each poll, we read STATUS_WORD from every PMBus rail that is powered,
+//! along with whichever of STATUS_VOUT, STATUS_IOUT and STATUS_TEMPERATURE it
+//! points at. Faults and warnings that weren't present at the previous poll
+//! are recorded in a log (which, unlike our ringbuf, isn't overwritten by
+//! routine polling) and announced to any subscribers listed in the app TOML:
+//!
+//! ```toml
+//! [tasks.power.config]
+//! subscribers = [{ name = "thermal", notification = "power-fault" }]
+//! ```
+//!
+//! We never send CLEAR_FAULTS: the devices' own latched status is what the
+//! sequencer (and anyone debugging a failure) wants to see. As a result, a
+//! fault that stays latched in the device is only logged once.
+
+use crate::{bsp, DeviceType, PowerControllerConfig};
+use drv_i2c_api::{I2cDevice, ResponseCode};
+use pmbus::commands::CommandCode;
+use ringbuf::*;
+use task_power_api::{RailFaults, RailStatusEvent, RAIL_STATUS_LOG_SIZE};
+use userlib::*;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Trace {
+    None,
+    NewFaults {
+        rail: usize,
+        new: u32,
+    },
+    StatusFailed {
+        rail: usize,
+        cmd: u8,
+        code: ResponseCode,
+    },
+}
+
+ringbuf!(Trace, 8, Trace::None);
+
+include!(concat!(env!("OUT_DIR"), "/subscribers.rs"));
+
+/// How a rail's status registers are read
+#[derive(Copy, Clone)]
+enum StatusAccess {
+    /// The device has a single page
+    Unpaged,
+    /// The rail is on the given page
+    Paged(u8),
+}
+
+impl PowerControllerConfig {
+    /// Returns how to read this rail's PMBus status, or `None` if the device
+    /// doesn't speak PMBus.
+    fn status_access(&self, rail: u8) -> Option<StatusAccess> {
+        match self.device {
+            DeviceType::IBC | DeviceType::Sys => Some(StatusAccess::Unpaged),
+            DeviceType::HotSwap(..) | DeviceType::Fan(..) => {
+                Some(StatusAccess::Unpaged)
+            }
+            DeviceType::Core
+            | DeviceType::Mem
+            | DeviceType::MemVpp
+            | DeviceType::SerDes
+            | DeviceType::PowerShelf => Some(StatusAccess::Paged(rail)),
+            DeviceType::Pmbus(table) => Some(match table.page {
+                Some(page) => StatusAccess::Paged(page),
+                None => StatusAccess::Unpaged,
+            }),
+            DeviceType::HotSwapIO(..) | DeviceType::HotSwapQSFP(..) => None,
+        }
+    }
+}
+
+pub(crate) struct RailStatus {
+    /// Faults and warnings reported by each rail at its most recent poll
+    active: &'static mut [RailFaults; bsp::CONTROLLER_CONFIG_LEN],
+    events: &'static mut [Option<RailStatusEvent>; RAIL_STATUS_LOG_SIZE],
+    /// Total number of events ever recorded
+    count: u32,
+}
+
+impl RailStatus {
+    /// Claims the (static) status storage.
+    ///
+    /// This function can only be called once, and will panic otherwise!
+    pub fn claim() -> Self {
+        let (active, events) = mutable_statics::mutable_statics! {
+            static mut ACTIVE: [RailFaults; bsp::CONTROLLER_CONFIG_LEN] =
+                [RailFaults::empty; _];
+            static mut EVENTS: [Option<RailStatusEvent>; RAIL_STATUS_LOG_SIZE] =
+                [|| None; _];
+        };
+        Self {
+            active,
+            events,
+            count: 0,
+        }
+    }
+
+    /// Polls the status of the rail at `index` in `CONTROLLER_CONFIG`,
+    /// returning `true` if it reported any new faults or warnings.
+    pub fn poll(
+        &mut self,
+        index: usize,
+        c: &PowerControllerConfig,
+        i2c_task: TaskId,
+    ) -> bool {
+        let (dev, rail) = (c.builder)(i2c_task);
+        let access = match c.status_access(rail) {
+            Some(access) => access,
+            None => return false,
+        };
+
+        let read_byte =
+            |cmd: CommandCode| match read_status::<u8>(&dev, access, cmd) {
+                Ok(v) => Some(v),
+                Err(code) => {
+                    ringbuf_entry!(Trace::StatusFailed {
+                        rail: index,
+                        cmd: cmd as u8,
+                        code
+                    });
+                    None
+                }
+            };
+
+        let status_word =
+            match read_status::<u16>(&dev, access, CommandCode::STATUS_WORD) {
+                Ok(v) => v,
+                Err(code) => {
+                    ringbuf_entry!(Trace::StatusFailed {
+                        rail: index,
+                        cmd: CommandCode::STATUS_WORD as u8,
+                        code
+                    });
+                    return false;
+                }
+            };
+
+        // Only read the detailed registers that STATUS_WORD points at
+        let bit = |n: u32| status_word & (1 << n) != 0;
+        let status_vout = (bit(15) || bit(5))
+            .then(|| read_byte(CommandCode::STATUS_VOUT))
+            .flatten();
+        let status_iout = (bit(14) || bit(4))
+            .then(|| read_byte(CommandCode::STATUS_IOUT))
+            .flatten();
+        let status_temperature = bit(2)
+            .then(|| read_byte(CommandCode::STATUS_TEMPERATURE))
+            .flatten();
+
+        let active = RailFaults::decode(
+            status_word,
+            status_vout,
+            status_iout,
+            status_temperature,
+        );
+        let new = active - self.active[index];
+        self.active[index] = active;
+
+        if new.is_empty() {
+            return false;
+        }
+
+        ringbuf_entry!(Trace::NewFaults {
+            rail: index,
+            new: new.bits()
+        });
+        self.events[self.count as usize % RAIL_STATUS_LOG_SIZE] =
+            Some(RailStatusEvent {
+                timestamp: sys_get_timer().now,
+                index: self.count,
+                rail: index as u32,
+                sensor: c.voltage,
+                new: new.bits(),
+                active: active.bits(),
+                status_word,
+                status_vout: status_vout.unwrap_or(0),
+                status_iout: status_iout.unwrap_or(0),
+                status_temperature: status_temperature.unwrap_or(0),
+            });
+        self.count = self.count.wrapping_add(1);
+        true
+    }
+
+    /// Forgets the status of a rail that has been powered off, so that any
+    /// faults it reports when it comes back are logged afresh.
+    pub fn clear(&mut self, index: usize) {
+        self.active[index] = RailFaults::empty();
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Returns the event at `index`, if it has been recorded and not yet
+    /// overwritten.
+    pub fn get(&self, index: u32) -> Option<RailStatusEvent> {
+        if index >= self.count
+            || self.count - index > RAIL_STATUS_LOG_SIZE as u32
+        {
+            return None;
+        }
+        self.events[index as usize % RAIL_STATUS_LOG_SIZE]
+    }
+
+    /// Returns the faults reported by the rail at `index` at its most recent
+    /// poll.
+    pub fn active(&self, index: usize) -> Option<RailFaults> {
+        self.active.get(index).copied()
+    }
+
+    /// Lets any subscribers know that there are new events.
+    pub fn notify_subscribers(&self) {
+        for (task, mask) in SUBSCRIBERS {
+            sys_post(sys_refresh_task_id(task), mask);
+        }
+    }
+}
+
+fn read_status<T>(
+    dev: &I2cDevice,
+    access: StatusAccess,
+    cmd: CommandCode,
+) -> Result<T, ResponseCode>
+where
+    T: zerocopy::AsBytes + zerocopy::FromBytes,
+{
+    match access {
+        StatusAccess::Paged(page) => {
+            dev.write_read_reg(cmd as u8, &[CommandCode::PAGE as u8, page])
+        }
+        StatusAccess::Unpaged => dev.read_reg(cmd as u8),
+    }
+}
@@ -0,0 +1,216 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Ringbuf agent
+//!
+//! Serves reads of other tasks' memory over the management network, so that
+//! their ring buffers can be retrieved without a debug probe.  The agent
+//! knows nothing about ring buffers itself: `cargo xtask ringbuf` finds them
+//! in the DWARF of the image's archive, reads them through us, and decodes
+//! them.  The protocol is described in `ringbuf-agent-messages`.
+//!
+//! Reads are performed by the supervisor (which must be built with its
+//! `read-task-memory` feature), and are confined to the target task's
+//! dumpable regions.  Because that makes this task able to read any task's
+//! RAM, the supervisor's `read_task_memory` operation must be restricted to
+//! it:
+//!
+//! ```toml
+//! [tasks.jefe.config.allowed-callers]
+//! read_task_memory = ["ringbuf_agent"]
+//! ```
+//!
+//! There is no authentication on the network side, so anyone who can reach
+//! our socket can read any task's RAM: this is for lab use only, and must
+//! not be built into production images.
+
+#![no_std]
+#![no_main]
+
+use hubpack::SerializedSize;
+use ringbuf::*;
+use ringbuf_agent_messages::{
+    version, Error, Header, Request, RequestMessage, Response, ResponseMessage,
+    MAX_READ_SIZE, MAX_RESPONSE_SIZE,
+};
+use task_jefe_api::{DumpAgentError, Jefe};
+use task_net_api::*;
+use userlib::*;
+
+task_slot!(JEFE, jefe);
+task_slot!(NET, net);
+
+#[derive(Copy, Clone, PartialEq)]
+enum Trace {
+    None,
+    Request(Request),
+    ReadFailed(DumpAgentError),
+    DeserializeError(hubpack::Error),
+    WrongVersion(u8),
+    SendError(SendError),
+}
+
+ringbuf!(Trace, 16, Trace::None);
+
+const SOCKET: SocketName = SocketName::ringbuf_agent;
+
+const SOCKET_TX_SIZE: usize = task_net_api::SOCKET_TX_SIZE[SOCKET as usize];
+const SOCKET_RX_SIZE: usize = task_net_api::SOCKET_RX_SIZE[SOCKET as usize];
+
+// Check our buffer sizes against packet sizes in the TOML file
+static_assertions::const_assert!(MAX_RESPONSE_SIZE <= SOCKET_TX_SIZE);
+static_assertions::const_assert!(RequestMessage::MAX_SIZE <= SOCKET_RX_SIZE);
+
+#[export_name = "main"]
+fn main() -> ! {
+    let jefe = Jefe::from(JEFE.get_task_id());
+    let net = Net::from(NET.get_task_id());
+
+    let (rx_data_buf, tx_data_buf) = mutable_statics::mutable_statics! {
+        static mut RX_BUF: [u8; SOCKET_RX_SIZE] = [|| 0u8; _];
+        static mut TX_BUF: [u8; SOCKET_TX_SIZE] = [|| 0u8; _];
+    };
+
+    loop {
+        match net.recv_packet(
+            SOCKET,
+            LargePayloadBehavior::Discard,
+            rx_data_buf,
+        ) {
+            Ok(mut meta) => {
+                let rx = &rx_data_buf[..meta.size as usize];
+                if let Some(len) = handle_packet(&jefe, rx, tx_data_buf) {
+                    meta.size = len as u32;
+                    if let Err(e) =
+                        net.send_packet(SOCKET, meta, &tx_data_buf[..len])
+                    {
+                        // We'll drop packets if the outgoing queue is full or
+                        // the server has died; the host is responsible for
+                        // retrying.
+                        ringbuf_entry!(Trace::SendError(e));
+                        match e {
+                            SendError::QueueFull
+                            | SendError::ServerRestarted => (),
+                            SendError::Other
+                            | SendError::NotYours
+                            | SendError::InvalidVLan => panic!(),
+                        }
+                    }
+                }
+            }
+            Err(RecvError::QueueEmpty) => {
+                // Our incoming queue is empty. Wait for more packets.
+                sys_recv_closed(
+                    &mut [],
+                    notifications::SOCKET_MASK,
+                    TaskId::KERNEL,
+                )
+                .unwrap();
+            }
+            Err(RecvError::ServerRestarted) => {
+                // `net` restarted (probably due to the watchdog); just retry.
+            }
+            Err(RecvError::NotYours | RecvError::Other) => panic!(),
+        }
+    }
+}
+
+/// Handles a single request packet, returning the length of the response in
+/// `tx`, or `None` if there's nobody to reply to.
+fn handle_packet(jefe: &Jefe, rx: &[u8], tx: &mut [u8]) -> Option<usize> {
+    // Decode the header on its own first, so that we can reply to a request
+    // that's from a newer version of the protocol.
+    let mut header = match hubpack::deserialize::<Header>(rx) {
+        Ok((header, _)) => header,
+        Err(e) => {
+            ringbuf_entry!(Trace::DeserializeError(e));
+            return None;
+        }
+    };
+
+    let (response, data_len) = match handle_request(jefe, header, rx, tx) {
+        Ok((response, data_len)) => (Ok(response), data_len),
+        Err(e) => (Err(e), 0),
+    };
+
+    header.version = version::CURRENT;
+    let msg = ResponseMessage { header, response };
+    let n = hubpack::serialize(tx, &msg).unwrap_lite();
+
+    // Any data was read to just past the largest possible message; slide it
+    // down to sit right after this one.
+    tx.copy_within(
+        ResponseMessage::MAX_SIZE..ResponseMessage::MAX_SIZE + data_len,
+        n,
+    );
+    Some(n + data_len)
+}
+
+/// Handles a request, reading any data into `tx` after the space reserved for
+/// the response message
+fn handle_request(
+    jefe: &Jefe,
+    header: Header,
+    rx: &[u8],
+    tx: &mut [u8],
+) -> Result<(Response, usize), Error> {
+    if header.version < version::MIN {
+        ringbuf_entry!(Trace::WrongVersion(header.version));
+        return Err(Error::VersionMismatch {
+            ours: version::CURRENT,
+            theirs: header.version,
+        });
+    }
+
+    let request = match hubpack::deserialize::<RequestMessage>(rx) {
+        Ok((msg, _)) => msg.request,
+        Err(e) => {
+            // If this message is from a newer version, it makes sense that we
+            // failed to deserialize it.
+            if header.version > version::CURRENT {
+                ringbuf_entry!(Trace::WrongVersion(header.version));
+                return Err(Error::VersionMismatch {
+                    ours: version::CURRENT,
+                    theirs: header.version,
+                });
+            }
+            ringbuf_entry!(Trace::DeserializeError(e));
+            return Err(Error::DeserializeError);
+        }
+    };
+    ringbuf_entry!(Trace::Request(request));
+
+    match request {
+        Request::GetImageId => {
+            Ok((Response::GetImageId(kipc::read_image_id().to_le_bytes()), 0))
+        }
+        Request::ReadTaskMemory {
+            task_index,
+            address,
+            length,
+        } => {
+            let len = length as usize;
+            if len > MAX_READ_SIZE {
+                return Err(Error::ReadTooLong);
+            }
+            let data = &mut tx
+                [ResponseMessage::MAX_SIZE..ResponseMessage::MAX_SIZE + len];
+            jefe.read_task_memory(task_index.into(), address, data)
+                .map_err(|e| {
+                    ringbuf_entry!(Trace::ReadFailed(e));
+                    match e {
+                        DumpAgentError::NotSupported => Error::BadTask,
+                        DumpAgentError::BadOffset => Error::BadAddress,
+                        DumpAgentError::DumpAgentUnsupported => {
+                            Error::Unsupported
+                        }
+                        _ => Error::ReadFailed,
+                    }
+                })?;
+            Ok((Response::ReadTaskMemory { length }, len))
+        }
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/notifications.rs"));
@@ -12,22 +12,7 @@ use std::{fmt::Write as FmtWrite, io::Write as IoWrite};
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct GlobalConfig {
-    sensor: Option<SensorConfig>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "kebab-case", deny_unknown_fields)]
-struct SensorConfig {
-    devices: Vec<Sensor>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "kebab-case", deny_unknown_fields)]
-struct Sensor {
-    name: String,
-    device: String,
-    description: String,
-    sensors: BTreeMap<String, usize>,
+    sensor: Option<build_i2c::SensorConfig>,
 }
 
 fn main() -> Result<()> {
@@ -39,8 +24,11 @@ fn main() -> Result<()> {
     let config: GlobalConfig = build_util::config()?;
 
     let (count, text) = if let Some(config_sensor) = &config.sensor {
-        let sensor_count: usize =
-            config_sensor.devices.iter().map(|d| d.sensors.len()).sum();
+        let sensor_count: usize = config_sensor
+            .devices
+            .iter()
+            .map(|d| d.sensors.values().sum::<usize>())
+            .sum();
 
         let mut by_device: BTreeMap<String, Vec<usize>> = BTreeMap::new();
         let mut names = BTreeSet::new();
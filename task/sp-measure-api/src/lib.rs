@@ -0,0 +1,46 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Client API for the SP measurement task.
+
+#![no_std]
+
+use derive_idol_err::IdolError;
+use hubpack::SerializedSize;
+use serde::{Deserialize, Serialize};
+use userlib::*;
+
+pub use attest_api::{ImageIdentity, ImageVersion, MeasuredComponent};
+
+/// Size of the SHA3-256 digest of the SP's flash
+pub const SP_DIGEST_SIZE: usize = 32;
+
+#[derive(Copy, Clone, Debug, FromPrimitive, Eq, PartialEq, IdolError)]
+pub enum SpMeasureError {
+    /// We couldn't read the SP's flash over SWD
+    SwdFailed = 1,
+    /// The attest task's measurement log is full, so the measurement wasn't
+    /// recorded
+    MeasurementLogFull,
+    /// The attest task failed to record the measurement
+    AttestFailed,
+
+    #[idol(server_death)]
+    ServerRestarted,
+}
+
+/// The result of measuring the SP's flash, as recorded with the attest task
+#[derive(
+    Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, SerializedSize,
+)]
+pub struct SpMeasurement {
+    pub image: ImageIdentity,
+    /// SHA3-256 digest of the SP's flash
+    pub digest: [u8; SP_DIGEST_SIZE],
+    /// Whether the digest matches that of the image that we were built to
+    /// expect
+    pub expected: bool,
+}
+
+include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
@@ -14,7 +14,13 @@ struct TaskConfig {
 
 const TEST_SIZE: usize = 0x0010_0000;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    idol::server::build_server_support(
+        "../../idl/sp-measure.idol",
+        "server_stub.rs",
+        idol::server::ServerStyle::InOrder,
+    )?;
+
     let out_dir = build_util::out_dir();
     let dest_path = out_dir.join("expected.rs");
     let mut file = std::fs::File::create(&dest_path)?;
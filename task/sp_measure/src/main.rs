@@ -2,78 +2,169 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+//! SP measurement task
+//!
+//! Hashes the SP's flash over SWD, and records the measurement -- tagged
+//! with the version in the SP image's header -- in the attest task's
+//! measurement log.  We measure once at startup, and again whenever asked
+//! to via our `measure` operation; each measurement replaces the last in the
+//! log.
+
 #![no_std]
 #![no_main]
 
+use abi::{ImageHeader, HEADER_MAGIC, STM32H7_HEADER_OFFSET};
+use attest_api::{Attest, AttestError, HashAlgorithm};
 use drv_sp_ctrl_api::*;
+use idol_runtime::RequestError;
 use ringbuf::*;
 use sha3::{Digest, Sha3_256};
+use task_sp_measure_api::{
+    ImageIdentity, ImageVersion, MeasuredComponent, SpMeasureError,
+    SpMeasurement,
+};
 use userlib::*;
+use zerocopy::{AsBytes, FromBytes};
 
 const READ_SIZE: usize = 256;
 
 const TRANSACTION_SIZE: u32 = 1024;
 
 task_slot!(SP_CTRL, swd);
+task_slot!(ATTEST, attest);
 
 #[derive(Copy, Clone, PartialEq)]
 enum Trace {
     Start(u64),
     End(u64),
+    Image(ImageIdentity),
     ShaGood,
     ShaBad,
+    SwdError(SpCtrlError),
+    AttestError(AttestError),
     None,
 }
 
 ringbuf!(Trace, 16, Trace::None);
 
-#[export_name = "main"]
-fn main() -> ! {
-    loop {
-        let mut sha = Sha3_256::new();
-        let sp_ctrl = SpCtrl::from(SP_CTRL.get_task_id());
+struct ServerImpl {
+    sp_ctrl: SpCtrl,
+    attest: Attest,
+}
 
-        if sp_ctrl.setup().is_err() {
-            panic!();
-        }
+impl ServerImpl {
+    /// Reads the SP image's header, to identify the image being measured.
+    fn read_identity(&self) -> Result<ImageIdentity, SpCtrlError> {
+        let mut header = ImageHeader::new_zeroed();
+        self.sp_ctrl
+            .read(FLASH_START + STM32H7_HEADER_OFFSET, header.as_bytes_mut())?;
+
+        let version = if header.magic == HEADER_MAGIC {
+            Some(ImageVersion {
+                epoch: header.epoch,
+                version: header.version,
+            })
+        } else {
+            None
+        };
 
+        Ok(ImageIdentity {
+            component: MeasuredComponent::Sp,
+            version,
+        })
+    }
+
+    /// Hashes the SP's flash.
+    fn hash(&self) -> Result<[u8; 32], SpCtrlError> {
+        let mut sha = Sha3_256::new();
         let mut data: [u8; READ_SIZE] = [0; READ_SIZE];
 
-        let start = sys_get_timer().now;
-        ringbuf_entry!(Trace::Start(start));
         for addr in (FLASH_START..FLASH_END).step_by(READ_SIZE) {
-            if addr % TRANSACTION_SIZE == 0
-                && sp_ctrl
-                    .read_transaction_start(addr, addr + TRANSACTION_SIZE)
-                    .is_err()
-            {
-                panic!();
+            if addr % TRANSACTION_SIZE == 0 {
+                self.sp_ctrl
+                    .read_transaction_start(addr, addr + TRANSACTION_SIZE)?;
             }
 
             data.fill(0);
-            if sp_ctrl.read_transaction(&mut data).is_err() {
-                panic!();
-            }
-
-            sha.update(&data);
+            self.sp_ctrl.read_transaction(&mut data)?;
+            sha.update(data);
         }
 
-        let sha_out = sha.finalize();
+        Ok(sha.finalize().into())
+    }
+
+    /// Measures the SP's flash, and records the measurement with `attest`.
+    fn measure_sp(&self) -> Result<SpMeasurement, SpMeasureError> {
+        let swd_error = |e| {
+            ringbuf_entry!(Trace::SwdError(e));
+            SpMeasureError::SwdFailed
+        };
+
+        self.sp_ctrl.setup().map_err(swd_error)?;
+        let image = self.read_identity().map_err(swd_error)?;
+        ringbuf_entry!(Trace::Image(image));
 
-        let end = sys_get_timer().now;
-        ringbuf_entry!(Trace::End(end));
-        if sha_out.as_slice() == EXPECTED.as_slice() {
-            ringbuf_entry!(Trace::ShaGood);
+        ringbuf_entry!(Trace::Start(sys_get_timer().now));
+        let digest = self.hash().map_err(swd_error)?;
+        ringbuf_entry!(Trace::End(sys_get_timer().now));
+
+        let expected = digest == EXPECTED;
+        ringbuf_entry!(if expected {
+            Trace::ShaGood
         } else {
-            ringbuf_entry!(Trace::ShaBad);
-        }
+            Trace::ShaBad
+        });
+
+        self.attest
+            .record_image(HashAlgorithm::Sha3_256, image, &digest)
+            .map_err(|e| {
+                ringbuf_entry!(Trace::AttestError(e));
+                match e {
+                    AttestError::MeasurementLogFull => {
+                        SpMeasureError::MeasurementLogFull
+                    }
+                    _ => SpMeasureError::AttestFailed,
+                }
+            })?;
+
+        Ok(SpMeasurement {
+            image,
+            digest,
+            expected,
+        })
+    }
+}
 
-        // Wait for a notification that will never come, politer than
-        // busy looping forever
-        if sys_recv_closed(&mut [], 1, TaskId::KERNEL).is_err() {
-            panic!();
-        }
+impl idl::InOrderSpMeasureImpl for ServerImpl {
+    fn measure(
+        &mut self,
+        _: &RecvMessage,
+    ) -> Result<SpMeasurement, RequestError<SpMeasureError>> {
+        self.measure_sp().map_err(RequestError::from)
+    }
+}
+
+#[export_name = "main"]
+fn main() -> ! {
+    let mut server = ServerImpl {
+        sp_ctrl: SpCtrl::from(SP_CTRL.get_task_id()),
+        attest: Attest::from(ATTEST.get_task_id()),
+    };
+
+    // Measure the SP as soon as we start; failures are recorded in our
+    // ringbuf, and can be retried with the `measure` operation.
+    let _ = server.measure_sp();
+
+    let mut buffer = [0; idl::INCOMING_SIZE];
+    loop {
+        idol_runtime::dispatch(&mut buffer, &mut server);
     }
 }
 
+mod idl {
+    use task_sp_measure_api::{SpMeasureError, SpMeasurement};
+
+    include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
+}
+
 include!(concat!(env!("OUT_DIR"), "/expected.rs"));
@@ -6,8 +6,7 @@
 
 use crate::{
     control::{
-        ChannelType, Device, FanControl, Fans, InputChannel, PidConfig,
-        TemperatureSensor,
+        ChannelType, Device, FanControl, Fans, InputChannel, TemperatureSensor,
     },
     i2c_config::{devices, sensors},
 };
@@ -66,9 +65,6 @@ pub(crate) struct Bsp {
 
     /// Id of the I2C task, to query MAX5970 status
     i2c_task: TaskId,
-
-    /// Tuning for the PID controller
-    pub pid_config: PidConfig,
 }
 
 bitflags::bitflags! {
@@ -175,14 +171,6 @@ impl Bsp {
             i2c_task,
             fctrl,
 
-            // Based on experimental tuning!
-            pid_config: PidConfig {
-                zero: 35.0,
-                gain_p: 1.75,
-                gain_i: 0.0135,
-                gain_d: 0.4,
-            },
-
             inputs: &INPUTS,
             dynamic_inputs: &[],
 
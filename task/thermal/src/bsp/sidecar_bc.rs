@@ -5,8 +5,7 @@
 //! BSP for Sidecar
 
 use crate::control::{
-    ChannelType, Device, FanControl, Fans, InputChannel, PidConfig,
-    TemperatureSensor,
+    ChannelType, Device, FanControl, Fans, InputChannel, TemperatureSensor,
 };
 use core::convert::TryInto;
 use drv_i2c_devices::max31790::Max31790;
@@ -69,8 +68,6 @@ pub(crate) struct Bsp {
     fctrl_west: Max31790,
 
     seq: Sequencer,
-
-    pub pid_config: PidConfig,
 }
 
 impl Bsp {
@@ -170,14 +167,6 @@ impl Bsp {
             fctrl_east,
             fctrl_west,
 
-            // TODO: this is all made up, copied from tuned Gimlet values
-            pid_config: PidConfig {
-                zero: 35.0,
-                gain_p: 1.75,
-                gain_i: 0.0135,
-                gain_d: 0.4,
-            },
-
             inputs: &INPUTS,
             dynamic_inputs:
                 &drv_transceivers_api::TRANSCEIVER_TEMPERATURE_SENSORS,
@@ -313,9 +313,13 @@ pub(crate) struct ThermalControl<'a> {
     /// Most recent power mode mask
     power_mode: PowerBitmask,
 
-    /// PID parameters, pulled from the BSP by default but user-modifiable
+    /// PID parameters, from our patchable config by default but
+    /// user-modifiable
     pid_config: PidConfig,
 
+    /// PID parameters to return to on reset
+    default_pid_config: PidConfig,
+
     /// Dynamic inputs are fixed in number but configured at runtime.
     ///
     /// `None` values in this list are ignored.
@@ -529,7 +533,12 @@ impl<'a> ThermalControl<'a> {
     /// # Panics
     /// This function can only be called once, because it claims mutable static
     /// buffers.
-    pub fn new(bsp: &'a Bsp, i2c_task: TaskId, sensor_api: SensorApi) -> Self {
+    pub fn new(
+        bsp: &'a Bsp,
+        i2c_task: TaskId,
+        sensor_api: SensorApi,
+        pid_config: PidConfig,
+    ) -> Self {
         use mutable_statics::mutable_statics;
         let [err_blackbox, prev_err_blackbox] = mutable_statics! {
             static mut ERR_BLACKBOX: [ThermalSensorErrors; 2] =
@@ -543,7 +552,8 @@ impl<'a> ThermalControl<'a> {
             state: ThermalControlState::Boot {
                 values: [None; TEMPERATURE_ARRAY_SIZE],
             },
-            pid_config: bsp.pid_config,
+            pid_config,
+            default_pid_config: pid_config,
 
             overheat_hysteresis: Celsius(1.0),
             overheat_timeout_ms: 60_000,
@@ -610,8 +620,8 @@ impl<'a> ThermalControl<'a> {
     pub fn reset(&mut self) {
         self.reset_state();
 
-        // Reset the PID configuration from the BSP
-        self.pid_config = self.bsp.pid_config;
+        // Reset the PID configuration to its defaults
+        self.pid_config = self.default_pid_config;
 
         // Set the target_margin to 0, indicating no overcooling
         self.target_margin = Celsius(0.0f32);
@@ -30,7 +30,7 @@ mod control;
 
 use crate::{
     bsp::{Bsp, PowerBitmask, SeqError},
-    control::ThermalControl,
+    control::{PidConfig, ThermalControl},
 };
 use core::convert::TryFrom;
 use drv_i2c_api::ResponseCode;
@@ -59,6 +59,19 @@ impl From<usize> for Fan {
 task_slot!(I2C, i2c_driver);
 task_slot!(SENSOR, sensor);
 
+// These can be patched into a built image with `cargo xtask patch-config`,
+// e.g. to try out new PID tuning without a rebuild.
+task_config::patchable_task_config! {
+    pid_zero: f32,
+    pid_gains: [f32; 3],
+    interval_ms: u64,
+}
+
+/// Shortest interval at which we will run the control loop, in milliseconds,
+/// regardless of `interval_ms` (which, being patchable, isn't checked when
+/// the image is built)
+const MIN_INTERVAL: u64 = 100;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum Trace {
     None,
@@ -89,10 +102,10 @@ struct ServerImpl<'a> {
     control: ThermalControl<'a>,
     deadline: u64,
     runtime: u64,
+    /// How often we run the control loop, in milliseconds
+    interval: u64,
 }
 
-const TIMER_INTERVAL: u64 = 1000;
-
 impl<'a> ServerImpl<'a> {
     /// Configures the control loop to run in manual mode, loading the given
     /// PWM value immediately to all fans.
@@ -323,7 +336,7 @@ impl<'a> NotificationHandler for ServerImpl<'a> {
                     panic!("Mode must not be 'Off' when server is running")
                 }
             }
-            self.deadline = now + TIMER_INTERVAL;
+            self.deadline = now + self.interval;
         }
         self.runtime = sys_get_timer().now - now;
         sys_set_timer(Some(self.deadline), notifications::TIMER_MASK);
@@ -339,8 +352,17 @@ fn main() -> ! {
 
     ringbuf_entry!(Trace::Start);
 
+    let config = PatchableConfig::read();
+    let [gain_p, gain_i, gain_d] = config.pid_gains;
+    let pid_config = PidConfig {
+        zero: config.pid_zero,
+        gain_p,
+        gain_i,
+        gain_d,
+    };
+
     let bsp = Bsp::new(i2c_task);
-    let control = ThermalControl::new(&bsp, i2c_task, sensor_api);
+    let control = ThermalControl::new(&bsp, i2c_task, sensor_api, pid_config);
 
     // This will put our timer in the past, and should immediately kick us.
     let deadline = sys_get_timer().now;
@@ -351,6 +373,7 @@ fn main() -> ! {
         control,
         deadline,
         runtime: 0,
+        interval: config.interval_ms.max(MIN_INTERVAL),
     };
     if bsp::USE_CONTROLLER {
         server.set_mode_auto().unwrap();
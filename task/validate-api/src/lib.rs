@@ -8,6 +8,8 @@
 
 use derive_idol_err::IdolError;
 use drv_i2c_api::ResponseCode;
+use hubpack::SerializedSize;
+use serde::{Deserialize, Serialize};
 use userlib::*;
 use zerocopy::AsBytes;
 
@@ -22,6 +24,10 @@ pub enum ValidateError {
     Unavailable,
     DeviceTimeout,
     DeviceOff,
+    /// No power-on self test has been run
+    PostNotRun,
+    /// The lease is too small for the power-on self test report
+    BadLease,
 }
 
 impl From<ResponseCode> for ValidateError {
@@ -45,6 +51,57 @@ pub enum ValidateOk {
     Removed = 3,
 }
 
+/// Summary of an I2C power-on self test, in which every device is validated
+/// in turn.  Each device is counted in exactly one of `present` (it responded,
+/// but has no driver to validate it), `validated`, `bad` (its driver failed
+/// validation) or `absent` (it returned an error).
+///
+/// The full report is `len` bytes, made up of four bitmaps followed by error
+/// codes.  Each bitmap is [`i2c_post_bitmap_len`] bytes, with device `n` in
+/// bit `n % 8` of byte `n / 8`:
+///
+/// - devices that are present;
+/// - devices that were validated;
+/// - devices that failed validation;
+/// - devices that are marked `removable`, and may legitimately be absent.
+///
+/// A device that's in none of the first three bitmaps is absent; for each
+/// absent device, in order, a byte follows the bitmaps with its
+/// `ResponseCode`.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Deserialize,
+    Eq,
+    PartialEq,
+    Serialize,
+    SerializedSize,
+)]
+pub struct I2cPostSummary {
+    pub devices: u16,
+    pub present: u16,
+    pub validated: u16,
+    pub bad: u16,
+    pub absent: u16,
+    /// Number of absent devices that are marked `removable`
+    pub absent_removable: u16,
+    /// Length of the full report
+    pub len: u16,
+    /// Kernel timestamp when the test finished
+    pub timestamp: u64,
+}
+
+/// Size of each bitmap in an I2C power-on self test report
+pub const fn i2c_post_bitmap_len(devices: usize) -> usize {
+    (devices + 7) / 8
+}
+
+/// Largest possible I2C power-on self test report, for this board's devices
+pub const I2C_POST_REPORT_MAX_SIZE: usize =
+    4 * i2c_post_bitmap_len(DEVICES_CONST.len()) + DEVICES_CONST.len();
+
 include!(concat!(env!("OUT_DIR"), "/client_stub.rs"));
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
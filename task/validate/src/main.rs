@@ -7,19 +7,39 @@
 #![no_std]
 #![no_main]
 
-use idol_runtime::RequestError;
+use idol_runtime::{Leased, RequestError, W};
 use ringbuf::*;
-use task_validate_api::{ValidateError, ValidateOk};
+use task_validate_api::{
+    i2c_post_bitmap_len, I2cPostSummary, ValidateError, ValidateOk,
+};
 use userlib::*;
 
 include!(concat!(env!("OUT_DIR"), "/i2c_config.rs"));
 
-struct ServerImpl;
+use i2c_config::validation::{I2cValidation, NUM_DEVICES, REMOVABLE};
+
+const BITMAP_LEN: usize = i2c_post_bitmap_len(NUM_DEVICES);
+const REPORT_SIZE: usize = 4 * BITMAP_LEN + NUM_DEVICES;
+
+/// Results of the last I2C power-on self test, in the format described by
+/// `I2cPostSummary`
+struct Post {
+    summary: I2cPostSummary,
+    report: [u8; REPORT_SIZE],
+}
+
+struct ServerImpl {
+    post: Option<Post>,
+}
 
 #[derive(Copy, Clone, PartialEq)]
 enum Trace {
     Validate(usize),
     ValidateFailure(drv_i2c_api::ResponseCode),
+    PostStart,
+    PostBad(usize),
+    PostAbsent(usize, drv_i2c_api::ResponseCode),
+    PostDone { bad: u16, absent: u16 },
     None,
 }
 
@@ -27,14 +47,73 @@ ringbuf!(Trace, 64, Trace::None);
 
 task_slot!(I2C, i2c_driver);
 
+impl ServerImpl {
+    /// Validates every device in turn, recording the results
+    fn run_post(&mut self) -> I2cPostSummary {
+        ringbuf_entry!(Trace::PostStart);
+
+        let task = I2C.get_task_id();
+        let mut report = [0; REPORT_SIZE];
+        let (bitmaps, codes) = report.split_at_mut(4 * BITMAP_LEN);
+        let (present, rest) = bitmaps.split_at_mut(BITMAP_LEN);
+        let (validated, rest) = rest.split_at_mut(BITMAP_LEN);
+        let (bad, removable) = rest.split_at_mut(BITMAP_LEN);
+
+        let mut summary = I2cPostSummary {
+            devices: NUM_DEVICES as u16,
+            ..Default::default()
+        };
+
+        for index in 0..NUM_DEVICES {
+            let (byte, bit) = (index / 8, 1 << (index % 8));
+
+            if REMOVABLE[index] {
+                removable[byte] |= bit;
+            }
+
+            match i2c_config::validation::validate(task, index) {
+                Ok(I2cValidation::RawReadOk) => {
+                    present[byte] |= bit;
+                    summary.present += 1;
+                }
+                Ok(I2cValidation::Good) => {
+                    validated[byte] |= bit;
+                    summary.validated += 1;
+                }
+                Ok(I2cValidation::Bad) => {
+                    ringbuf_entry!(Trace::PostBad(index));
+                    bad[byte] |= bit;
+                    summary.bad += 1;
+                }
+                Err(code) => {
+                    ringbuf_entry!(Trace::PostAbsent(index, code));
+                    codes[summary.absent as usize] = code as u8;
+                    summary.absent += 1;
+                    if REMOVABLE[index] {
+                        summary.absent_removable += 1;
+                    }
+                }
+            }
+        }
+
+        summary.len = (4 * BITMAP_LEN) as u16 + summary.absent;
+        summary.timestamp = sys_get_timer().now;
+        ringbuf_entry!(Trace::PostDone {
+            bad: summary.bad,
+            absent: summary.absent
+        });
+
+        self.post = Some(Post { summary, report });
+        summary
+    }
+}
+
 impl idl::InOrderValidateImpl for ServerImpl {
     fn validate_i2c(
         &mut self,
         _: &RecvMessage,
         index: u32,
     ) -> Result<ValidateOk, RequestError<ValidateError>> {
-        use i2c_config::validation::I2cValidation;
-
         let index = index as usize;
         ringbuf_entry!(Trace::Validate(index));
 
@@ -51,11 +130,40 @@ impl idl::InOrderValidateImpl for ServerImpl {
             },
         }
     }
+
+    fn run_i2c_post(
+        &mut self,
+        _: &RecvMessage,
+    ) -> Result<I2cPostSummary, RequestError<ValidateError>> {
+        Ok(self.run_post())
+    }
+
+    fn i2c_post_report(
+        &mut self,
+        _: &RecvMessage,
+        report: Leased<W, [u8]>,
+    ) -> Result<I2cPostSummary, RequestError<ValidateError>> {
+        let post = self.post.as_ref().ok_or(ValidateError::PostNotRun)?;
+        let len = post.summary.len as usize;
+        if report.len() < len {
+            return Err(ValidateError::BadLease.into());
+        }
+        report
+            .write_range(0..len, &post.report[..len])
+            .map_err(|()| RequestError::went_away())?;
+        Ok(post.summary)
+    }
 }
 
 #[export_name = "main"]
 fn main() -> ! {
-    let mut server = ServerImpl;
+    let mut server = ServerImpl { post: None };
+
+    // Optionally sweep over every device at boot, so that the board's health
+    // can be read all at once.
+    #[cfg(feature = "boot-post")]
+    server.run_post();
+
     let mut buffer = [0; idl::INCOMING_SIZE];
 
     loop {
@@ -64,7 +172,7 @@ fn main() -> ! {
 }
 
 mod idl {
-    use super::{ValidateError, ValidateOk};
+    use super::{I2cPostSummary, ValidateError, ValidateOk};
 
     include!(concat!(env!("OUT_DIR"), "/server_stub.rs"));
 }
@@ -53,6 +53,12 @@ task_config::task_config! {
     tup: &'static [(u32, bool)],
 }
 
+// Likewise for `patchable_task_config!`, with `test_patchable_task_config`
+task_config::patchable_task_config! {
+    patched: u32,
+    gains: [f32; 2],
+}
+
 // Actual list of functions with their names.
 test_cases! {
     test_send,
@@ -95,6 +101,7 @@ test_cases! {
     test_timer_notify,
     test_timer_notify_past,
     test_task_config,
+    test_patchable_task_config,
     test_task_status,
     test_task_fault_injection,
     test_refresh_task_id_basic,
@@ -1265,6 +1272,15 @@ fn test_task_config() {
     assert_eq!(TASK_CONFIG.tup, [(1, true), (2, true), (3, false)]);
 }
 
+fn test_patchable_task_config() {
+    // These values are also hard-coded in `app.toml` (and aren't patched by
+    // the test build), so this tests that they were correctly encoded into
+    // the config blob and decoded from it.
+    let config = PatchableConfig::read();
+    assert_eq!(config.patched, 7);
+    assert_eq!(config.gains, [1.5, -0.25]);
+}
+
 fn test_task_status() {
     let mut id: usize = 0;
     let assist = assist_task_id();